@@ -0,0 +1,134 @@
+// Benchmarks of handling a MIDI message, from the parsed message to the events written to the
+// virtual devices, which are thrown away here so only midkb's own work is measured.
+// Run with `cargo bench`.
+
+use std::hint::black_box;
+use std::io;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use midi_msg::{Channel, ChannelVoiceMsg, ControlChange, MidiMsg};
+use midkb::config::Config;
+use midkb::output::Output;
+use midkb::MidiInputHandler;
+
+/// An output doing nothing with the events
+struct NullOutput;
+
+impl Output for NullOutput {
+    fn press(&mut self, _code: u16) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn release(&mut self, _code: u16) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_mouse(&mut self, _x: i32, _y: i32) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn scroll_x(&mut self, _value: i32) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn scroll_y(&mut self, _value: i32) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_axis(&mut self, _axis: u16, _value: i32) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+const CONFIG: &str = r#"
+    [notes]
+    36 = 30
+    37 = 48
+    38 = [29, 46]
+
+    [cc.1]
+    bind_mode = "Keyboard"
+    counter_clockwise = 105
+    clockwise = 106
+
+    [cc.2]
+    bind_mode = "Mouse"
+    counter_clockwise = "-x"
+    clockwise = "x"
+"#;
+
+fn handler() -> MidiInputHandler {
+    let config: Config = toml::from_str(CONFIG).unwrap();
+    MidiInputHandler::new(Box::new(NullOutput), config)
+}
+
+fn voice(msg: ChannelVoiceMsg) -> MidiMsg {
+    MidiMsg::ChannelVoice {
+        channel: Channel::Ch1,
+        msg,
+    }
+}
+
+fn note(note: u8, on: bool) -> MidiMsg {
+    voice(match on {
+        true => ChannelVoiceMsg::NoteOn {
+            note,
+            velocity: 100,
+        },
+        false => ChannelVoiceMsg::NoteOff { note, velocity: 0 },
+    })
+}
+
+fn cc(control: u8, value: u8) -> MidiMsg {
+    voice(ChannelVoiceMsg::ControlChange {
+        control: ControlChange::CC { control, value },
+    })
+}
+
+fn bench_handle_midi_msg(c: &mut Criterion) {
+    let mut group = c.benchmark_group("handle_midi_msg");
+
+    let mut notes = handler();
+    group.bench_function("note on and off", |b| {
+        b.iter(|| {
+            notes.handle_midi_msg(black_box(note(36, true)));
+            notes.handle_midi_msg(black_box(note(36, false)));
+        })
+    });
+
+    let mut chords = handler();
+    group.bench_function("several keys", |b| {
+        b.iter(|| {
+            chords.handle_midi_msg(black_box(note(38, true)));
+            chords.handle_midi_msg(black_box(note(38, false)));
+        })
+    });
+
+    let mut unbound = handler();
+    group.bench_function("unbound note", |b| {
+        b.iter(|| unbound.handle_midi_msg(black_box(note(100, true))))
+    });
+
+    // The value keeps going up, wrapping around, so every message moves the knob
+    let mut knobs = handler();
+    let mut value = 0;
+    group.bench_function("knob", |b| {
+        b.iter(|| {
+            value = (value + 1) % 128;
+            knobs.handle_midi_msg(black_box(cc(1, value)));
+        })
+    });
+
+    let mut mouse = handler();
+    group.bench_function("mouse knob", |b| {
+        b.iter(|| {
+            value = (value + 1) % 128;
+            mouse.handle_midi_msg(black_box(cc(2, value)));
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_handle_midi_msg);
+criterion_main!(benches);
@@ -0,0 +1,567 @@
+// midkb-gui: a desktop frontend for midkb, running the same library as the command line.
+// Pick the MIDI device from a list, watch its messages and the actions they trigger as they
+// come in, switch profiles, and bind controls by dragging keys onto them, into the base
+// bindings or a profile. Saving writes the bindings into the config file, keeping its
+// comments and formatting, and applies them straight away.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::Parser;
+use eframe::egui;
+use midkb::config::{CCTarget, Config};
+use midkb::config_edit::{self, Control};
+use midkb::map::{key_name, MappingTable, Row};
+use midkb::session::{MidiEvent, Session};
+use toml_edit::DocumentMut;
+
+/// How many messages the live view keeps
+const LIVE_EVENTS: usize = 200;
+
+/// How many triggered actions the live view shows
+const LIVE_ACTIONS: usize = 20;
+
+/// How often new MIDI messages are picked up while nothing else redraws the window
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    /// Config file to edit and run
+    #[arg(short, long, default_value = "config.toml")]
+    config: PathBuf,
+}
+
+/// A change made in the bindings list, applied once it's drawn
+enum Edit {
+    /// A key dropped on a control, with the slot it was dropped on: the only one for a note,
+    /// counter clockwise or clockwise for a CC
+    Drop(Control, usize, u16),
+    Unbind(Control),
+}
+
+struct App {
+    config_path: PathBuf,
+    /// The config file, edited in place
+    doc: DocumentMut,
+    /// The config file as loaded, with the base bindings and every profile as edited so far
+    config: Config,
+    /// The bindings being edited, with their profile applied
+    rows: Vec<Row>,
+    shown: Config,
+    /// Which profile the editor binds into, or the base bindings with `None`
+    profile: Option<String>,
+    new_profile: String,
+    /// Keys dropped on one direction of a CC, waiting for the other
+    pending_cc: HashMap<u8, [Option<u16>; 2]>,
+    /// Controls pressed on the controller that aren't bound yet, listed to drop keys on
+    touched: Vec<Control>,
+    last_control: Option<Control>,
+    key_filter: String,
+
+    session: Option<Session>,
+    ports: Vec<String>,
+    live: VecDeque<MidiEvent>,
+
+    status: String,
+    unsaved: bool,
+}
+
+impl App {
+    fn new(config_path: PathBuf) -> Result<Self, String> {
+        let text = std::fs::read_to_string(&config_path)
+            .map_err(|e| format!("failed to read config file {config_path:?}: {e}"))?;
+        let doc: DocumentMut = text
+            .parse()
+            .map_err(|e| format!("invalid config file {config_path:?}: {e}"))?;
+        let mut config = Config::load(&config_path).map_err(|e| match e.hint() {
+            Some(hint) => format!("{e}\n\n{hint}"),
+            None => e.to_string(),
+        })?;
+
+        let (session, status) = match Session::start(config.clone()) {
+            Ok(session) => (Some(session), String::new()),
+            Err(e) => {
+                let status = match e.hint() {
+                    Some(hint) => format!("{e}. {hint}"),
+                    None => e.to_string(),
+                };
+                (None, status)
+            }
+        };
+
+        // Edit the bindings as written, without `default_profile` applied on top
+        config.switch_profile(None)?;
+
+        let mut app = Self {
+            config_path,
+            doc,
+            shown: config.clone(),
+            config,
+            rows: Vec::new(),
+            profile: None,
+            new_profile: String::new(),
+            pending_cc: HashMap::new(),
+            touched: Vec::new(),
+            last_control: None,
+            key_filter: String::new(),
+            session,
+            ports: Session::ports(),
+            live: VecDeque::with_capacity(LIVE_EVENTS),
+            status,
+            unsaved: false,
+        };
+        app.refresh_rows();
+
+        // Start on the configured device, if it's plugged in
+        let device = app.config.midi_device.clone();
+        let port = app
+            .ports
+            .iter()
+            .find(|port| !device.is_empty() && port.contains(&device))
+            .cloned();
+        if let Some(port) = port {
+            app.connect(&port);
+        }
+
+        Ok(app)
+    }
+
+    fn refresh_rows(&mut self) {
+        let mut shown = self.config.clone();
+        if let Some(profile) = &self.profile {
+            if let Err(message) = shown.apply_profile(profile) {
+                self.status = message;
+            }
+        }
+        self.rows = MappingTable::new(&shown).rows;
+        self.shown = shown;
+    }
+
+    fn connect(&mut self, port: &str) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        self.status = match session.connect(port) {
+            true => format!("Connected to {port}"),
+            false => format!("Couldn't connect to {port}"),
+        };
+    }
+
+    /// The keys a CC is bound to in the bindings being edited, counter clockwise first
+    fn cc_keys(&self, cc: u8) -> [Option<u16>; 2] {
+        let key = |target: &Option<CCTarget>| match target {
+            Some(CCTarget::Key(code)) => Some(*code),
+            _ => None,
+        };
+        match self.shown.cc.get_dir_config(cc) {
+            Some(binding) => [key(&binding.counter_clockwise), key(&binding.clockwise)],
+            None => [None, None],
+        }
+    }
+
+    fn apply(&mut self, edit: Edit) {
+        match edit {
+            Edit::Drop(control @ Control::Note(_), _, code) => self.rebind(control, &[code]),
+            Edit::Drop(control @ Control::Cc(cc), slot, code) => {
+                let mut keys = self
+                    .pending_cc
+                    .remove(&cc)
+                    .unwrap_or_else(|| self.cc_keys(cc));
+                keys[slot] = Some(code);
+                match keys {
+                    [Some(counter_clockwise), Some(clockwise)] => {
+                        self.rebind(control, &[counter_clockwise, clockwise])
+                    }
+                    _ => {
+                        self.pending_cc.insert(cc, keys);
+                        let direction = match slot {
+                            0 => "clockwise",
+                            _ => "counter clockwise",
+                        };
+                        self.status = format!("Now drop the key for turning cc {cc} {direction}");
+                    }
+                }
+            }
+            Edit::Unbind(control) => {
+                config_edit::unbind(&mut self.doc, self.profile.as_deref(), control);
+                self.reload_binding(control);
+                self.status = format!("Unbound {}", control.source());
+            }
+        }
+    }
+
+    fn rebind(&mut self, control: Control, keys: &[u16]) {
+        config_edit::bind(&mut self.doc, self.profile.as_deref(), control, keys);
+        if self.reload_binding(control) {
+            self.touched.retain(|touched| *touched != control);
+            let keys: Vec<_> = keys.iter().map(|code| key_name(*code)).collect();
+            self.status = format!("Bound {} to {}", control.source(), keys.join(" / "));
+        }
+    }
+
+    /// Pick up a binding changed in the config file, returning whether it loaded
+    fn reload_binding(&mut self, control: Control) -> bool {
+        let profile = self.profile.as_deref();
+        if let Err(message) =
+            config_edit::reload_binding(&mut self.config, &self.doc, profile, control)
+        {
+            self.status = format!("Couldn't load the new binding: {message}");
+            return false;
+        }
+        self.unsaved = true;
+        self.refresh_rows();
+        true
+    }
+
+    fn add_profile(&mut self) {
+        let name = self.new_profile.trim().to_string();
+        if name.is_empty() {
+            return;
+        }
+        if !config_edit::add_profile(&mut self.doc, &name) {
+            self.status = format!("There's already a profile called {name}");
+            return;
+        }
+
+        self.config.profiles.entry(name.clone()).or_default();
+        self.status = format!("Added profile {name}");
+        self.profile = Some(name);
+        self.new_profile.clear();
+        self.unsaved = true;
+        self.refresh_rows();
+    }
+
+    fn remove_profile(&mut self, name: &str) {
+        config_edit::remove_profile(&mut self.doc, name);
+        self.config.profiles.remove(name);
+        self.profile = None;
+        self.status = format!("Removed profile {name}");
+        self.unsaved = true;
+        self.refresh_rows();
+    }
+
+    fn save(&mut self) {
+        if let Err(e) = config_edit::save(&self.doc, &self.config_path) {
+            self.status = format!("Failed to save to {}: {e}", self.config_path.display());
+            return;
+        }
+        self.unsaved = false;
+
+        match Config::load(&self.config_path) {
+            Ok(config) => {
+                if let Some(session) = &self.session {
+                    session.handler().reload(config);
+                }
+                self.status = format!("Saved to {}", self.config_path.display());
+            }
+            Err(e) => self.status = format!("Saved, but the config doesn't load: {e}"),
+        }
+    }
+
+    /// Pick up the messages received since the last frame
+    fn poll(&mut self) {
+        let Some(session) = &self.session else {
+            return;
+        };
+
+        for event in session.events() {
+            if let Some(control) = event.control {
+                self.last_control = Some(control);
+                let bound = self
+                    .rows
+                    .iter()
+                    .any(|row| Control::from_source(&row.source) == Some(control));
+                if !bound && !self.touched.contains(&control) {
+                    self.touched.push(control);
+                }
+            }
+
+            if self.live.len() == LIVE_EVENTS {
+                self.live.pop_front();
+            }
+            self.live.push_back(event);
+        }
+    }
+
+    fn device_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let Some(session) = &mut self.session else {
+                ui.label("Not running, so bindings can only be edited");
+                return;
+            };
+
+            let current = session.port().unwrap_or("Not connected").to_string();
+            let mut picked = None;
+            egui::ComboBox::from_label("MIDI device")
+                .selected_text(&current)
+                .show_ui(ui, |ui| {
+                    for port in &self.ports {
+                        if ui.selectable_label(*port == current, port).clicked() {
+                            picked = Some(port.clone());
+                        }
+                    }
+                });
+            if ui.button("Refresh").clicked() {
+                self.ports = Session::ports();
+            }
+            if session.port().is_some() && ui.button("Disconnect").clicked() {
+                session.disconnect();
+            }
+
+            ui.separator();
+
+            let status = session.handler().status();
+            let mut bypassed = status.bypassed;
+            if ui.checkbox(&mut bypassed, "Bypass").changed() {
+                session.handler().set_bypass(bypassed);
+            }
+
+            // Switches the running bindings, unlike the profile edited below
+            let mut names: Vec<_> = self.config.profiles.keys().cloned().collect();
+            names.sort_unstable();
+            let mut switch = None;
+            egui::ComboBox::from_label("Active profile")
+                .selected_text(status.profile.as_deref().unwrap_or("None"))
+                .show_ui(ui, |ui| {
+                    if ui
+                        .selectable_label(status.profile.is_none(), "None")
+                        .clicked()
+                    {
+                        switch = Some(None);
+                    }
+                    for name in names {
+                        let active = status.profile.as_ref() == Some(&name);
+                        if ui.selectable_label(active, &name).clicked() {
+                            switch = Some(Some(name));
+                        }
+                    }
+                });
+            if let Some(name) = switch {
+                if let Err(message) = session.handler().switch_profile(name.as_deref()) {
+                    self.status = message;
+                }
+            }
+
+            if let Some(port) = picked {
+                self.connect(&port);
+            }
+        });
+    }
+
+    fn key_palette(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Keys");
+        ui.label("Drag a key onto a binding");
+        ui.text_edit_singleline(&mut self.key_filter);
+
+        let filter = self.key_filter.trim().to_ascii_uppercase();
+        egui::ScrollArea::vertical().id_salt("keys").show(ui, |ui| {
+            for name in midkb::keycodes::names().filter(|name| name.contains(&filter)) {
+                let Some(code) = midkb::keycodes::from_name(name) else {
+                    continue;
+                };
+                ui.dnd_drag_source(egui::Id::new(("key", code)), code, |ui| {
+                    ui.label(key_name(code));
+                });
+            }
+        });
+    }
+
+    fn live_view(&self, ui: &mut egui::Ui) {
+        ui.columns(2, |columns| {
+            columns[0].heading("MIDI in");
+            egui::ScrollArea::vertical()
+                .id_salt("midi")
+                .stick_to_bottom(true)
+                .show(&mut columns[0], |ui| {
+                    for event in &self.live {
+                        ui.monospace(&event.line);
+                    }
+                });
+
+            columns[1].heading("Actions");
+            let Some(session) = &self.session else {
+                return;
+            };
+            let recent = session.handler().status().recent_events;
+            for entry in recent.iter().rev().take(LIVE_ACTIONS) {
+                columns[1].monospace(format!(
+                    "{} {} ch {}  {}",
+                    entry.event, entry.number, entry.channel, entry.action
+                ));
+            }
+        });
+    }
+
+    fn profile_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let mut names: Vec<_> = self.config.profiles.keys().cloned().collect();
+            names.sort_unstable();
+
+            let mut edit = self.profile.clone();
+            egui::ComboBox::from_label("Editing")
+                .selected_text(self.profile.as_deref().unwrap_or("Base bindings"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut edit, None, "Base bindings");
+                    for name in names {
+                        ui.selectable_value(&mut edit, Some(name.clone()), name);
+                    }
+                });
+            if edit != self.profile {
+                self.profile = edit;
+                self.pending_cc.clear();
+                self.refresh_rows();
+            }
+
+            if let Some(profile) = self.profile.clone() {
+                if ui.button("Remove profile").clicked() {
+                    self.remove_profile(&profile);
+                }
+            }
+
+            ui.separator();
+            ui.text_edit_singleline(&mut self.new_profile);
+            if ui.button("Add profile").clicked() {
+                self.add_profile();
+            }
+
+            ui.separator();
+            let save = egui::Button::new(if self.unsaved { "Save *" } else { "Save" });
+            if ui.add_enabled(self.unsaved, save).clicked() {
+                self.save();
+            }
+        });
+    }
+
+    fn bindings(&mut self, ui: &mut egui::Ui) {
+        let mut edits = Vec::new();
+
+        egui::ScrollArea::vertical()
+            .id_salt("bindings")
+            .show(ui, |ui| {
+                egui::Grid::new("bindings")
+                    .striped(true)
+                    .num_columns(4)
+                    .show(ui, |ui| {
+                        for control in &self.touched {
+                            self.binding_row(ui, *control, "not bound yet", &mut edits);
+                        }
+                        for row in &self.rows {
+                            match Control::from_source(&row.source) {
+                                Some(control) => {
+                                    self.binding_row(ui, control, &row.action, &mut edits)
+                                }
+                                // Pitch bend, transport and the like are edited in the file
+                                None => {
+                                    ui.label(&row.source);
+                                    ui.label(&row.action);
+                                    ui.end_row();
+                                }
+                            }
+                        }
+                    });
+            });
+
+        for edit in edits {
+            self.apply(edit);
+        }
+    }
+
+    fn binding_row(
+        &self,
+        ui: &mut egui::Ui,
+        control: Control,
+        action: &str,
+        edits: &mut Vec<Edit>,
+    ) {
+        let source = control.source();
+        match self.last_control == Some(control) {
+            true => ui.strong(source),
+            false => ui.label(source),
+        };
+        ui.label(action);
+
+        let slots = match control {
+            Control::Note(_) => vec![None],
+            Control::Cc(cc) => {
+                let keys = self.pending_cc.get(&cc).copied();
+                keys.unwrap_or_else(|| self.cc_keys(cc)).to_vec()
+            }
+        };
+        ui.horizontal(|ui| {
+            for (slot, key) in slots.into_iter().enumerate() {
+                let text = match (control, slot, key) {
+                    (_, _, Some(code)) => key_name(code),
+                    (Control::Note(_), ..) => "drop a key".to_string(),
+                    (Control::Cc(_), 0, None) => "counter clockwise".to_string(),
+                    (Control::Cc(_), _, None) => "clockwise".to_string(),
+                };
+                let (_, dropped) =
+                    ui.dnd_drop_zone::<u16, _>(egui::Frame::group(ui.style()), |ui| {
+                        ui.label(text);
+                    });
+                if let Some(code) = dropped {
+                    edits.push(Edit::Drop(control, slot, *code));
+                }
+            }
+        });
+
+        if ui.small_button("Unbind").clicked() {
+            edits.push(Edit::Unbind(control));
+        }
+        ui.end_row();
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll();
+
+        egui::TopBottomPanel::top("device").show(ctx, |ui| self.device_bar(ui));
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| ui.label(&self.status));
+        egui::TopBottomPanel::bottom("live")
+            .resizable(true)
+            .default_height(180.0)
+            .show(ctx, |ui| self.live_view(ui));
+        egui::SidePanel::left("keys").show(ctx, |ui| self.key_palette(ui));
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.profile_bar(ui);
+            ui.separator();
+            self.bindings(ui);
+        });
+
+        ctx.request_repaint_after(POLL_INTERVAL);
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string()))
+        .init();
+
+    let app = match App::new(cli.config) {
+        Ok(app) => app,
+        Err(message) => {
+            eprintln!("{message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("midkb")
+            .with_inner_size([960.0, 720.0]),
+        ..Default::default()
+    };
+    match eframe::run_native("midkb", options, Box::new(|_| Ok(Box::new(app)))) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,249 @@
+// Bluetooth LE MIDI input.
+// BLE-MIDI controllers expose a single characteristic that notifies with packets of
+// timestamped MIDI messages. This scans for the configured device, subscribes to it,
+// and keeps scanning so it reconnects when the device wakes up after going to sleep.
+
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+
+use btleplug::api::{Central, CentralEvent, Manager as _, Peripheral as _, ScanFilter};
+use btleplug::platform::{Adapter, Manager, Peripheral, PeripheralId};
+use futures_util::StreamExt;
+use tracing::{debug, info, warn};
+use uuid::{uuid, Uuid};
+
+use crate::config::BleConfig;
+use crate::input::{Connection, MidiSource};
+use crate::parser::data_len;
+use crate::worker::MidiSender;
+use crate::MidiInputHandler;
+
+const MIDI_SERVICE: Uuid = uuid!("03b80e5a-ede8-4b33-a751-6ce34ec4c700");
+const MIDI_CHARACTERISTIC: Uuid = uuid!("7772e5db-3868-4112-a1a9-f2669d106bf3");
+
+/// Turns BLE-MIDI packets back into raw MIDI bytes. A SysEx message can go on over several
+/// packets, so whether one is in progress is kept from one packet to the next
+#[derive(Default)]
+struct PacketDecoder {
+    sysex: bool,
+}
+
+impl PacketDecoder {
+    /// Strip the header and timestamp bytes out of a packet, leaving the raw MIDI bytes.
+    /// Every message is preceded by a timestamp byte, except running status data following
+    /// the previous message and SysEx data, whose F7 gets a timestamp of its own
+    fn decode(&mut self, packet: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(packet.len());
+        let mut running_status = None;
+        let mut expect_timestamp = true;
+        // Skip the header byte
+        let mut i = 1;
+
+        while i < packet.len() {
+            let byte = packet[i];
+
+            // SysEx data is copied through until the F7, interrupted only by real-time messages
+            if self.sysex && byte & 0x80 == 0 {
+                bytes.push(byte);
+                i += 1;
+                continue;
+            }
+
+            let len = if byte & 0x80 != 0 {
+                if expect_timestamp {
+                    expect_timestamp = false;
+                    i += 1;
+                    continue;
+                }
+
+                match byte {
+                    0xF0 => {
+                        self.sysex = true;
+                        running_status = None;
+                    }
+                    0xF1..=0xF7 => {
+                        self.sysex = false;
+                        running_status = None;
+                    }
+                    0xF8..=0xFF => {}
+                    _ => {
+                        self.sysex = false;
+                        running_status = Some(byte);
+                    }
+                }
+                1 + data_len(byte)
+            } else {
+                match running_status {
+                    Some(status) => data_len(status),
+                    None => {
+                        i += 1;
+                        continue;
+                    }
+                }
+            };
+
+            let end = (i + len).min(packet.len());
+            bytes.extend(&packet[i..end]);
+            i = end;
+            expect_timestamp = true;
+        }
+
+        bytes
+    }
+}
+
+/// Find the configured device among the discovered peripherals
+async fn matches(adapter: &Adapter, id: &PeripheralId, device: &str) -> Option<Peripheral> {
+    let peripheral = adapter.peripheral(id).await.ok()?;
+    let name = peripheral.properties().await.ok()??.local_name?;
+
+    name.contains(device).then_some(peripheral)
+}
+
+/// Connect to a BLE-MIDI peripheral and queue its notifications for the worker
+/// until it disconnects
+async fn connect(peripheral: Peripheral, sender: MidiSender) -> btleplug::Result<()> {
+    peripheral.connect().await?;
+    peripheral.discover_services().await?;
+
+    let Some(characteristic) = peripheral
+        .characteristics()
+        .into_iter()
+        .find(|c| c.uuid == MIDI_CHARACTERISTIC)
+    else {
+        // Not marked as connected, so it's tried again the next time it's seen
+        let _ = peripheral.disconnect().await;
+        return Err(btleplug::Error::Other(
+            "the device has no BLE-MIDI characteristic".into(),
+        ));
+    };
+
+    peripheral.subscribe(&characteristic).await?;
+    let mut notifications = peripheral.notifications().await?;
+
+    info!(id = ?peripheral.id(), "BLE-MIDI device connected");
+
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let mut decoder = PacketDecoder::default();
+
+        while let Some(notification) = notifications.next().await {
+            if notification.uuid != MIDI_CHARACTERISTIC {
+                continue;
+            }
+
+            let bytes = decoder.decode(&notification.value);
+            sender.send(started.elapsed().as_micros() as u64, &bytes);
+        }
+    });
+
+    Ok(())
+}
+
+/// The configured BLE-MIDI device, connected to whenever it shows up
+pub struct BleSource {
+    pub config: BleConfig,
+    /// Lets go of everything when the device disconnects
+    pub handler: Arc<Mutex<MidiInputHandler>>,
+}
+
+impl MidiSource for BleSource {
+    fn connect(&self, sender: MidiSender) -> Option<Box<dyn Connection>> {
+        let listener = listen(self.config.clone(), sender, self.handler.clone());
+        Some(Box::new(tokio::spawn(listener)))
+    }
+}
+
+/// Scan for the configured BLE-MIDI device, connecting to it whenever it shows up
+async fn listen(config: BleConfig, sender: MidiSender, handler: Arc<Mutex<MidiInputHandler>>) {
+    if let Err(e) = scan(&config, sender, handler).await {
+        warn!(%e, "Stopped listening for BLE-MIDI devices");
+    }
+}
+
+async fn scan(
+    config: &BleConfig,
+    sender: MidiSender,
+    handler: Arc<Mutex<MidiInputHandler>>,
+) -> btleplug::Result<()> {
+    let manager = Manager::new().await?;
+    let Some(adapter) = manager.adapters().await?.into_iter().next() else {
+        warn!("No Bluetooth adapter found");
+        return Ok(());
+    };
+
+    let mut events = adapter.events().await?;
+    adapter
+        .start_scan(ScanFilter {
+            services: vec![MIDI_SERVICE],
+        })
+        .await?;
+
+    info!(device = ?config.device, "Scanning for BLE-MIDI devices");
+
+    let mut connected: Option<PeripheralId> = None;
+
+    while let Some(event) = events.next().await {
+        match event {
+            // Sleeping devices start advertising again when they wake up
+            CentralEvent::DeviceDiscovered(id) | CentralEvent::DeviceUpdated(id)
+                if connected.is_none() =>
+            {
+                let Some(peripheral) = matches(&adapter, &id, &config.device).await else {
+                    continue;
+                };
+
+                match connect(peripheral, sender.new_source()).await {
+                    Ok(()) => connected = Some(id),
+                    Err(e) => debug!(%e, "Failed to connect to BLE-MIDI device, retrying"),
+                }
+            }
+            CentralEvent::DeviceDisconnected(id) if connected.as_ref() == Some(&id) => {
+                info!("BLE-MIDI device disconnected, waiting for it to come back");
+                connected = None;
+                handler
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .release_all();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_packet() {
+        // Header, timestamp, note on, running status note on, timestamp, control change
+        let packet = [0x80, 0x80, 0x90, 60, 100, 62, 100, 0x81, 0xB0, 1, 64];
+        assert_eq!(
+            PacketDecoder::default().decode(&packet),
+            vec![0x90, 60, 100, 62, 100, 0xB0, 1, 64]
+        );
+
+        // An MMC Play SysEx, with a clock in the middle and a timestamp before the F7
+        let packet = [
+            0x80, 0x80, 0xF0, 0x7F, 0x7F, 0x06, 0x81, 0xF8, 0x02, 0x82, 0xF7,
+        ];
+        assert_eq!(
+            PacketDecoder::default().decode(&packet),
+            vec![0xF0, 0x7F, 0x7F, 0x06, 0xF8, 0x02, 0xF7]
+        );
+
+        // A SysEx going on in the next packet, whose data has no timestamp before it
+        let mut decoder = PacketDecoder::default();
+        assert_eq!(
+            decoder.decode(&[0x80, 0x80, 0xF0, 0x7F, 0x7F]),
+            vec![0xF0, 0x7F, 0x7F]
+        );
+        assert_eq!(
+            decoder.decode(&[0x80, 0x06, 0x02, 0x81, 0xF7, 0x82, 0x90, 60, 100]),
+            vec![0x06, 0x02, 0xF7, 0x90, 60, 100]
+        );
+    }
+}
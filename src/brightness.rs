@@ -0,0 +1,136 @@
+// Screen brightness from a fader or encoder, for desktops without brightness keys.
+// The backlight is set through sysfs when midkb may write to it, and through logind otherwise,
+// which lets the user on the active session set it without any udev rules.
+// Like volume changes, levels go to a thread that only applies the latest one.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use tracing::{debug, warn};
+
+/// Where backlight devices show up
+const BACKLIGHT_DIR: &str = "/sys/class/backlight";
+
+/// Queues brightness levels for the thread applying them
+pub struct Backlight {
+    levels: Sender<(Option<String>, u8)>,
+}
+
+impl Backlight {
+    /// Start the thread applying levels
+    pub fn spawn() -> Self {
+        let (levels, receiver) = channel();
+        std::thread::spawn(move || apply_levels(receiver));
+        Self { levels }
+    }
+
+    /// Set a backlight, the first one if `device` is `None`, to a percentage of its range
+    pub fn set(&self, device: Option<&str>, percent: u8) {
+        let _ = self.levels.send((device.map(str::to_string), percent));
+    }
+}
+
+/// Where a control's value puts the brightness, in percent from `min` to `max`
+pub fn percent(value: u8, min: u8, max: u8) -> u8 {
+    let (min, max) = (min.min(100) as u32, max.min(100) as u32);
+    (min + (value as u32 * max.saturating_sub(min) + 63) / 127) as u8
+}
+
+/// A percentage as a raw level of a backlight going up to `max_brightness`
+fn raw_level(percent: u8, max_brightness: u32) -> u32 {
+    ((percent as u64 * max_brightness as u64 + 50) / 100) as u32
+}
+
+fn apply_levels(receiver: Receiver<(Option<String>, u8)>) {
+    while let Ok(first) = receiver.recv() {
+        let levels: HashMap<_, _> = std::iter::once(first).chain(receiver.try_iter()).collect();
+
+        for (device, percent) in levels {
+            if let Err(e) = apply(device.as_deref(), percent) {
+                warn!(%e, ?device, "Failed to set the brightness");
+            }
+        }
+    }
+}
+
+fn apply(device: Option<&str>, percent: u8) -> Result<(), String> {
+    let dir = match device {
+        Some(device) => Path::new(BACKLIGHT_DIR).join(device),
+        None => first_backlight().ok_or("there's no backlight in /sys/class/backlight")?,
+    };
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let max_brightness = std::fs::read_to_string(dir.join("max_brightness"))
+        .map_err(|e| format!("can't read {name}'s max_brightness: {e}"))?;
+    let max_brightness: u32 = max_brightness
+        .trim()
+        .parse()
+        .map_err(|_| format!("{name}'s max_brightness isn't a number"))?;
+
+    let level = raw_level(percent, max_brightness);
+    debug!(name, percent, level, "Setting brightness");
+
+    match std::fs::write(dir.join("brightness"), level.to_string()) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => logind(&name, level),
+        Err(e) => Err(format!("can't write {name}'s brightness: {e}")),
+    }
+}
+
+/// The first backlight by name, which on most laptops is the only one
+fn first_backlight() -> Option<PathBuf> {
+    let mut devices: Vec<_> = std::fs::read_dir(BACKLIGHT_DIR)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    devices.sort();
+    devices.into_iter().next()
+}
+
+/// Set the brightness through logind's session object, which needs no write access to sysfs
+fn logind(name: &str, level: u32) -> Result<(), String> {
+    let output = Command::new("busctl")
+        .args([
+            "call",
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1/session/auto",
+            "org.freedesktop.login1.Session",
+            "SetBrightness",
+            "ssu",
+            "backlight",
+            name,
+            &level.to_string(),
+        ])
+        .output()
+        .map_err(|e| format!("can't run busctl to ask logind: {e}"))?;
+
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(format!(
+            "logind refused: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(percent(0, 5, 100), 5);
+        assert_eq!(percent(127, 5, 100), 100);
+        assert_eq!(percent(64, 0, 100), 50);
+        assert_eq!(percent(127, 20, 80), 80);
+
+        assert_eq!(raw_level(50, 1000), 500);
+        assert_eq!(raw_level(100, 19393), 19393);
+        assert_eq!(raw_level(1, 7), 0);
+    }
+}
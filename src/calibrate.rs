@@ -0,0 +1,250 @@
+// `midkb calibrate`: walk through the configured CCs, having each one moved from end to end
+// (and left at its center, for controls resting there) to measure the range it really sends.
+// The ranges are saved to `calibration.toml` next to the config file, and values are stretched
+// over the full 0 to 127 before they're mapped, since cheap faders rarely reach either end.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use midi_msg::{ChannelVoiceMsg, MidiMsg};
+
+use crate::config::{CCValueMode, CalibrationConfig, Config, RestPosition};
+use crate::error::{Error, Result};
+use crate::input::{self, InputTarget};
+use crate::monitor::cc_name;
+use crate::picker::prompt;
+
+/// The file calibration is saved to, next to the config file
+const CALIBRATION_FILE: &str = "calibration.toml";
+
+/// The values a control sent since it was last reset
+#[derive(Debug, Clone, Copy)]
+struct Seen {
+    min: u8,
+    max: u8,
+    last: u8,
+}
+
+type SeenValues = Arc<Mutex<HashMap<u8, Seen>>>;
+
+/// The CCs to calibrate, and whether each rests at its center.
+/// Relative controls send steps instead of positions, so they have no range to measure
+fn controls(config: &Config) -> BTreeMap<u8, bool> {
+    let mut controls = BTreeMap::new();
+
+    for (cc, dir_config) in &config.cc.cc {
+        let Ok(cc) = cc.trim().parse::<u8>() else {
+            continue;
+        };
+        if dir_config.mode == CCValueMode::Relative {
+            continue;
+        }
+
+        let centered = dir_config
+            .gamepad
+            .as_ref()
+            .is_some_and(|gamepad| gamepad.rest == Some(RestPosition::Center));
+        controls.insert(cc, centered);
+    }
+
+    // XY pads are joysticks springing back to the middle
+    for pad in config.xy_pads.values() {
+        controls.insert(pad.x, true);
+        controls.insert(pad.y, true);
+    }
+
+    controls
+}
+
+/// Ask for the controls to be moved, saving the ranges they sent
+pub fn run(config: Config, config_path: &Path) -> Result<()> {
+    let controls = controls(&config);
+    if controls.is_empty() {
+        println!("No CCs to calibrate in {}", config_path.display());
+        return Ok(());
+    }
+
+    let seen = SeenValues::default();
+    let recorded = seen.clone();
+    let sender = crate::worker::spawn_with(move |_, _, bytes, parser| {
+        for msg in parser.feed(bytes) {
+            if let MidiMsg::ChannelVoice {
+                msg: ChannelVoiceMsg::ControlChange { control },
+                ..
+            } = msg
+            {
+                let value = control.value();
+                let mut seen = recorded.lock().unwrap();
+                let seen = seen.entry(control.control()).or_insert(Seen {
+                    min: value,
+                    max: value,
+                    last: value,
+                });
+                seen.min = seen.min.min(value);
+                seen.max = seen.max.max(value);
+                seen.last = value;
+            }
+        }
+    });
+
+    let connection =
+        input::connect(&InputTarget::from_config(&config), sender).ok_or(Error::NoInput)?;
+
+    let mut calibration = config.calibration.controls.clone();
+    for (cc, centered) in controls {
+        match cc_name(cc) {
+            Some(name) => println!("\ncc {cc} ({name})"),
+            None => println!("\ncc {cc}"),
+        }
+
+        seen.lock().unwrap().remove(&cc);
+        prompt("  Move it all the way to one end and the other, then press Enter ");
+        let Some(range) = seen.lock().unwrap().get(&cc).copied() else {
+            println!("  Nothing came from cc {cc}, skipping it");
+            continue;
+        };
+        if range.min == range.max {
+            println!("  cc {cc} only sent {}, skipping it", range.min);
+            continue;
+        }
+
+        let center = centered.then(|| {
+            prompt("  Let it go back to the center, then press Enter ");
+            seen.lock().unwrap()[&cc].last
+        });
+
+        println!(
+            "  Sends {} to {}{}",
+            range.min,
+            range.max,
+            center.map_or(String::new(), |center| format!(", resting at {center}"))
+        );
+        calibration.insert(
+            cc.to_string(),
+            CalibrationConfig {
+                min: range.min,
+                max: range.max,
+                center,
+            },
+        );
+    }
+
+    connection.close();
+    save(&calibration, config_path)
+}
+
+/// Write the calibration file, and include it from the config file if it isn't yet
+fn save(calibration: &HashMap<String, CalibrationConfig>, config_path: &Path) -> Result<()> {
+    let path = config_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(CALIBRATION_FILE);
+    std::fs::write(&path, calibration_file(calibration)).map_err(|source| {
+        Error::SaveCalibration {
+            path: path.clone(),
+            source,
+        }
+    })?;
+    println!("\nSaved the calibration to {}", path.display());
+
+    let config = std::fs::read_to_string(config_path).unwrap_or_default();
+    if config.contains(CALIBRATION_FILE) {
+        return Ok(());
+    }
+
+    match include_calibration(&config) {
+        Some(updated) => {
+            std::fs::write(config_path, updated).map_err(|source| Error::SaveCalibration {
+                path: config_path.to_path_buf(),
+                source,
+            })?;
+            println!("Included it from {}", config_path.display());
+        }
+        None => println!(
+            "Add \"{CALIBRATION_FILE}\" to the `include` list of {} to use it",
+            config_path.display()
+        ),
+    }
+
+    Ok(())
+}
+
+fn calibration_file(calibration: &HashMap<String, CalibrationConfig>) -> String {
+    let numbered: BTreeMap<u8, &CalibrationConfig> = calibration
+        .iter()
+        .filter_map(|(cc, calibration)| Some((cc.trim().parse().ok()?, calibration)))
+        .collect();
+
+    let mut file = "# Ranges measured by `midkb calibrate`\n".to_string();
+    for (cc, calibration) in numbered {
+        file.push_str(&format!(
+            "\n[calibration.{cc}]\nmin = {}\nmax = {}\n",
+            calibration.min, calibration.max
+        ));
+        if let Some(center) = calibration.center {
+            file.push_str(&format!("center = {center}\n"));
+        }
+    }
+    file
+}
+
+/// Add an `include` of the calibration file to a config without one, or `None` if it already
+/// includes other files, since rewriting that list could lose its formatting and comments
+fn include_calibration(config: &str) -> Option<String> {
+    let has_include = config.lines().any(|line| {
+        line.strip_prefix("include")
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    });
+    if has_include {
+        return None;
+    }
+
+    // Top level keys have to come before any table
+    Some(format!("include = [\"{CALIBRATION_FILE}\"]\n{config}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibration_file() {
+        let calibration = HashMap::from([
+            (
+                "21".to_string(),
+                CalibrationConfig {
+                    min: 1,
+                    max: 126,
+                    center: Some(63),
+                },
+            ),
+            (
+                "7".to_string(),
+                CalibrationConfig {
+                    min: 3,
+                    max: 124,
+                    center: None,
+                },
+            ),
+        ]);
+
+        let file = calibration_file(&calibration);
+        assert_eq!(
+            file,
+            "# Ranges measured by `midkb calibrate`\n\
+             \n[calibration.7]\nmin = 3\nmax = 124\n\
+             \n[calibration.21]\nmin = 1\nmax = 126\ncenter = 63\n"
+        );
+
+        // It reads back as the same calibration
+        let config: Config = toml::from_str(&format!("[cc]\n[notes]\n{file}")).unwrap();
+        assert_eq!(config.calibration.controls, calibration);
+
+        assert_eq!(
+            include_calibration("midi_device = \"x\"\n").unwrap(),
+            "include = [\"calibration.toml\"]\nmidi_device = \"x\"\n"
+        );
+        assert_eq!(include_calibration("include = [\"pads.toml\"]\n"), None);
+    }
+}
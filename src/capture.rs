@@ -0,0 +1,85 @@
+// Capture of the MIDI messages midkb couldn't parse, one JSON object per line with the raw
+// bytes and when they arrived, so unsupported messages from exotic hardware can be attached
+// to a bug report and supported later.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::warn;
+
+/// A message that failed to parse, as written to the capture file
+#[derive(serde::Serialize, Debug)]
+struct CapturedMessage {
+    /// Milliseconds since the unix epoch
+    timestamp_ms: u64,
+    /// When the input received it, in the input's own clock
+    time: u64,
+    /// The raw bytes, in hex
+    bytes: String,
+}
+
+#[derive(Debug)]
+pub struct CaptureLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl CaptureLog {
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+        })
+    }
+
+    /// Append a message, logging instead of failing if it can't be written
+    pub fn record(&mut self, time: u64, bytes: &[u8]) {
+        let message = CapturedMessage {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_millis() as u64),
+            time,
+            bytes: hex(bytes),
+        };
+
+        let mut line = serde_json::to_string(&message).expect("captured messages always serialize");
+        line.push('\n');
+
+        if let Err(e) = self.file.write_all(line.as_bytes()) {
+            warn!(?e, path = ?self.path, "Failed to write unparsed MIDI message capture");
+        }
+    }
+}
+
+/// Bytes as space separated hex, like MIDI monitors show them
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture() {
+        let path = std::env::temp_dir().join(format!("midkb-capture-{}.jsonl", std::process::id()));
+
+        let mut log = CaptureLog::open(&path).unwrap();
+        log.record(1200, &[0xF4]);
+        log.record(1300, &[0xF0, 0x7E, 0x7F, 0xF7]);
+
+        let captured = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = captured.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains(r#""time":1200,"bytes":"F4""#));
+        assert!(lines[1].contains(r#""bytes":"F0 7E 7F F7""#));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+// Command line arguments
+
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::config::Config;
+use crate::error::Result;
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Config file to load, defaults to `config.toml` in the working directory
+    /// (or the user/system config path with `--daemon`)
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Start from a bundled config for a common controller, with the config file merged on top.
+    /// Without a config file, the preset is used as it is
+    #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(
+        crate::presets::PRESETS.iter().map(|(name, _)| *name)
+    ))]
+    pub preset: Option<String>,
+
+    /// Use this MIDI device instead of the config's `midi_device`
+    #[arg(short, long)]
+    pub device: Option<String>,
+
+    /// Run as a systemd service: notify systemd when ready and log in a journald friendly format
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Periodically report how long events take from MIDI receipt to the uinput write
+    #[arg(long)]
+    pub measure_latency: bool,
+
+    /// Log output format, `json` emits one structured event per line
+    #[arg(long, value_enum, default_value_t)]
+    pub log_format: LogFormat,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Print a JSON Schema of the config file, for editors to validate and autocomplete configs with
+    Schema,
+    /// Print the bindings of the config as a table, with keys bound more than once and
+    /// bindings that can never be triggered
+    Map {
+        /// Show the bindings with this profile applied instead of `default_profile`
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Connect to the MIDI device and print every message it sends, without mapping anything.
+    /// Works without a config file with `--device`
+    Monitor,
+    /// Run MIDI events typed on stdin, like `noteon 60 100` or `cc 1 64`, through the config
+    /// and print the key presses and mouse movement they cause instead of injecting them
+    Simulate,
+    /// Move each configured CC from end to end to measure the range it really sends, saved to
+    /// calibration.toml next to the config so its values are stretched over the full range
+    Calibrate,
+    /// Full screen editor for the bindings: select one, press a pad or turn a control and then
+    /// press a key to rebind it, and save the changes into the config file
+    Edit,
+    /// Render the bindings as a document to print and keep next to the controller
+    Export {
+        #[arg(long, value_enum, default_value_t)]
+        format: crate::export::ExportFormat,
+        /// Show the bindings with this profile applied instead of `default_profile`
+        #[arg(long)]
+        profile: Option<String>,
+        /// Also lay the pads and controls out like on this controller, defaults to the
+        /// controller of `--preset`
+        #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(
+            crate::export::TEMPLATES
+        ))]
+        template: Option<String>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum LogFormat {
+    /// Human readable lines
+    #[default]
+    Text,
+    /// One JSON object per event, for jq or log pipelines
+    Json,
+}
+
+impl Cli {
+    /// Load the config file, applying overrides from the command line
+    pub fn load_config(&self, path: &Path) -> Result<Config> {
+        let mut config = match &self.preset {
+            Some(preset) => {
+                let overrides = (self.config.is_some() || path.exists()).then_some(path);
+                Config::load_preset(preset, overrides)?
+            }
+            None => Config::load(path)?,
+        };
+
+        if let Some(device) = &self.device {
+            config.midi_device = device.clone();
+        }
+
+        Ok(config)
+    }
+
+    /// The config file to load, following the lookup order for the current mode
+    pub fn config_path(&self) -> PathBuf {
+        if let Some(path) = &self.config {
+            return path.clone();
+        }
+
+        if !self.daemon {
+            return PathBuf::from("config.toml");
+        }
+
+        let user_config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|dir| dir.join("midkb/config.toml"));
+
+        match user_config {
+            Some(path) if path.exists() => path,
+            _ => PathBuf::from("/etc/midkb/config.toml"),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+// Commands for the Wayland compositor, sent straight over its IPC socket like `swaymsg` and
+// `hyprctl` do, so a pad can switch workspaces or move focus without midkb pressing the
+// compositor's keybinding and hoping nothing else grabs it first.
+//
+//   sway (and i3)  $SWAYSOCK (or $I3SOCK), commands like `workspace 3` or `focus left`
+//   Hyprland       $XDG_RUNTIME_DIR/hypr/$HYPRLAND_INSTANCE_SIGNATURE/.socket.sock,
+//                  commands like `dispatch workspace 3` or `dispatch movefocus l`
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, warn};
+
+/// Magic string starting every i3 IPC message
+const I3_MAGIC: &[u8] = b"i3-ipc";
+
+/// i3 IPC message type running a command
+const I3_RUN_COMMAND: u32 = 0;
+
+/// The compositor's IPC socket, and which protocol it speaks
+#[derive(Debug, PartialEq)]
+enum Socket {
+    Sway(PathBuf),
+    Hyprland(PathBuf),
+}
+
+/// Send a command to the compositor, without waiting for it to be carried out
+pub fn send(command: String) {
+    std::thread::spawn(move || {
+        let result = match find_socket() {
+            Some(Socket::Sway(path)) => sway(&path, &command),
+            Some(Socket::Hyprland(path)) => hyprland(&path, &command),
+            None => Err("no sway or Hyprland session found".to_string()),
+        };
+
+        match result {
+            Ok(()) => debug!(command, "Compositor command done"),
+            Err(e) => warn!(%e, command, "Compositor command failed"),
+        }
+    });
+}
+
+fn find_socket() -> Option<Socket> {
+    if let Some(path) = std::env::var_os("SWAYSOCK").or_else(|| std::env::var_os("I3SOCK")) {
+        return Some(Socket::Sway(path.into()));
+    }
+
+    let signature = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+    // Hyprland moved its sockets from /tmp to the runtime directory in 0.40
+    let dirs = [
+        std::env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("hypr")),
+        Some(PathBuf::from("/tmp/hypr")),
+    ];
+    dirs.into_iter()
+        .flatten()
+        .map(|dir| dir.join(&signature).join(".socket.sock"))
+        .find(|path| path.exists())
+        .map(Socket::Hyprland)
+}
+
+fn sway(path: &Path, command: &str) -> Result<(), String> {
+    let mut stream = UnixStream::connect(path).map_err(|e| format!("can't connect: {e}"))?;
+    stream
+        .write_all(&i3_message(I3_RUN_COMMAND, command))
+        .map_err(|e| format!("can't send: {e}"))?;
+
+    let mut header = [0; 14];
+    stream
+        .read_exact(&mut header)
+        .map_err(|e| format!("no reply: {e}"))?;
+    let length = u32::from_ne_bytes(header[6..10].try_into().expect("4 bytes"));
+    let mut reply = vec![0; length as usize];
+    stream
+        .read_exact(&mut reply)
+        .map_err(|e| format!("no reply: {e}"))?;
+
+    check_sway_reply(&reply)
+}
+
+/// An i3 IPC message: the magic string, the payload's length and the message type in native
+/// byte order, then the payload
+fn i3_message(kind: u32, payload: &str) -> Vec<u8> {
+    let mut message = I3_MAGIC.to_vec();
+    message.extend((payload.len() as u32).to_ne_bytes());
+    message.extend(kind.to_ne_bytes());
+    message.extend(payload.as_bytes());
+    message
+}
+
+/// sway replies with one result per command, which are separated by `;` or `,`
+fn check_sway_reply(reply: &[u8]) -> Result<(), String> {
+    let results: Vec<serde_json::Value> =
+        serde_json::from_slice(reply).map_err(|_| "unexpected reply".to_string())?;
+
+    match results.iter().find(|result| result["success"] != true) {
+        Some(failed) => Err(failed["error"]
+            .as_str()
+            .unwrap_or("the command failed")
+            .to_string()),
+        None => Ok(()),
+    }
+}
+
+fn hyprland(path: &Path, command: &str) -> Result<(), String> {
+    let mut stream = UnixStream::connect(path).map_err(|e| format!("can't connect: {e}"))?;
+    stream
+        .write_all(command.as_bytes())
+        .map_err(|e| format!("can't send: {e}"))?;
+
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .map_err(|e| format!("no reply: {e}"))?;
+
+    match reply.trim() {
+        "ok" => Ok(()),
+        error => Err(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sway_ipc() {
+        let message = i3_message(I3_RUN_COMMAND, "workspace 3");
+        assert_eq!(&message[..6], b"i3-ipc");
+        assert_eq!(&message[6..10], 11u32.to_ne_bytes());
+        assert_eq!(&message[10..14], 0u32.to_ne_bytes());
+        assert_eq!(&message[14..], b"workspace 3");
+
+        assert_eq!(check_sway_reply(br#"[{"success":true}]"#), Ok(()));
+        assert_eq!(
+            check_sway_reply(
+                br#"[{"success":true},{"success":false,"parse_error":true,"error":"Unknown command"}]"#
+            ),
+            Err("Unknown command".to_string())
+        );
+    }
+}
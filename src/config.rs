@@ -1,69 +1,2593 @@
 
-#[derive(serde::Deserialize, Debug, Default)]
+use crate::layout::Layout;
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone)]
 pub struct Config {
+    /// Version of the config format. Older configs are upgraded as they're loaded
+    #[serde(default = "default_version")]
+    pub version: i64,
+
     pub cc: CCConfig,
     pub notes: NoteBinding,
     
     /// The string to search for in the midi device port
     /// e.g. "28:0" for the port containing "28:0" in the name
+    #[serde(default)]
     pub midi_device: String,
+
+    /// How `midi_device` is matched against the port names
+    #[serde(default)]
+    pub device_match: DeviceMatch,
+
+    /// What a NoteOn with velocity 0 means, unless the active profile says otherwise
+    #[serde(default)]
+    pub zero_velocity: ZeroVelocity,
+
+    /// Which MIDI system to read `midi_device` from
+    #[serde(default)]
+    pub backend: Backend,
+
+    /// The keyboard layout configured on the desktop, used to type text and `char` actions
+    #[serde(default)]
+    pub layout: Layout,
+
+    /// How to enter characters that can't be typed with the layout's keys
+    #[serde(default)]
+    pub unicode_input: UnicodeInput,
+
+    /// MPE mode, for controllers that play each note on its own channel
+    pub mpe: Option<MpeConfig>,
+
+    /// Treat messages on one channel as if they came on another, by channel (1-16), before
+    /// they're looked up in the bindings, e.g. `{ 10 = 1 }`
+    #[serde(default)]
+    pub channel_map: ChannelMap,
+
+    /// Only act on notes played on these channels (1-16), all of them if empty.
+    /// A note binding's own `channel` takes precedence
+    #[serde(default)]
+    pub note_channels: Vec<u8>,
+
+    /// Force-release a note's keys if no NoteOff arrives after this long,
+    /// unless the note sets its own `max_hold_ms`
+    pub max_hold_ms: Option<u64>,
+
+    /// Release every held key and button once no MIDI has arrived for this long
+    pub idle_release_ms: Option<u64>,
+
+    /// Hold off releasing keys while a sustain pedal is down
+    pub sustain: Option<SustainConfig>,
+
+    /// Take where the controls are when the controller connects, instead of acting on the
+    /// first value of every CC
+    pub snapshot: Option<SnapshotConfig>,
+
+    /// Log every triggered action to a file
+    pub history: Option<HistoryConfig>,
+
+    /// Append MIDI messages that fail to parse to a file, with their raw bytes, for reporting
+    /// messages from hardware midkb doesn't support yet
+    pub capture: Option<CaptureConfig>,
+
+    /// Count the notes and controls played that have no binding, and list them on exit
+    #[serde(default)]
+    pub report_unbound: bool,
+
+    /// Name of a virtual MIDI output port to pass every unbound message through to
+    pub thru: Option<String>,
+
+    /// Name of a virtual MIDI input port to create, for sequencers and scripts to send
+    /// messages to alongside the device
+    pub virtual_input: Option<String>,
+
+    /// Forward incoming messages to other MIDI ports
+    #[serde(default)]
+    pub routes: std::collections::HashMap<String, RouteConfig>,
+
+    /// Also accept MIDI over the network with RTP-MIDI
+    pub rtp_midi: Option<RtpMidiConfig>,
+
+    /// Also accept MIDI from WebSocket clients, like browser control surfaces
+    pub websocket: Option<WebSocketConfig>,
+
+    /// Also connect to a Bluetooth LE MIDI device
+    pub ble: Option<BleConfig>,
+
+    /// Serve a small HTTP API for checking on and controlling midkb remotely
+    pub http: Option<HttpConfig>,
+
+    /// Serve a web page showing live events and the bindings, with an editor for the config file.
+    /// Needs midkb built with the `web-ui` feature
+    pub web_ui: Option<WebUiConfig>,
+
+    /// Publish triggered actions to an MQTT broker and take commands from it
+    pub mqtt: Option<MqttConfig>,
+
+    /// Control OBS Studio through obs-websocket, for the `obs_*` actions
+    pub obs: Option<ObsConfig>,
+
+    /// Desktop notifications for switching profiles, layers and banks, and for `notify` actions
+    pub notifications: Option<NotificationsConfig>,
+
+    /// Play a click when a binding fires, and tones when something is toggled
+    pub sound: Option<SoundConfig>,
+
+    /// Profile to apply on top of `[notes]` and `[cc]` on startup
+    pub default_profile: Option<String>,
+
+    /// Variations on the bindings, each overriding only the notes and CCs it lists
+    #[serde(default)]
+    pub profiles: std::collections::HashMap<String, ProfileConfig>,
+
+    /// Actions for the transport messages dedicated transport buttons send
+    #[serde(default)]
+    pub transport: TransportConfig,
+
+    /// Names of the virtual devices midkb creates
+    #[serde(default)]
+    pub devices: DevicesConfig,
+
+    /// Pairs of CCs acting as a joystick that moves the mouse
+    #[serde(default)]
+    pub xy_pads: std::collections::HashMap<String, XyPadConfig>,
+
+    /// Banks of pads, stepped through with the `bank_up` and `bank_down` actions
+    pub banks: Option<BankConfig>,
+
+    /// Light each bound pad in a color showing what its binding is doing
+    pub feedback: Option<FeedbackConfig>,
+
+    /// Feed pitch bend to an axis of a virtual gamepad, or hold keys while it's bent
+    pub pitch_bend: Option<PitchBendConfig>,
+
+    /// The range each CC really sends, by CC number, as measured by `midkb calibrate`
+    #[serde(default)]
+    pub calibration: Calibration,
+
+    /// The profile currently applied, if any
+    #[serde(skip)]
+    pub active_profile: Option<String>,
+
+    /// `[notes]` and `[cc]` as written, before any profile was applied
+    #[serde(skip)]
+    base_bindings: Option<(NoteBinding, CCConfig)>,
 }
 
-#[derive(serde::Deserialize, Debug, Default)]
-/// Mode to bind the CC controls to
-pub enum CCBindMode {
-    /// Press a key on the keyboard everytime the CC is moved
-    #[default]
+fn default_version() -> i64 {
+    1
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone)]
+#[serde(from = "ChannelMapRepr")]
+pub struct ChannelMap {
+    pub channels: std::collections::HashMap<String, u8>,
+    // The channel each channel (1-16) is treated as, 0 if it isn't remapped, so remapping
+    // every message doesn't allocate
+    by_channel: [u8; 16],
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(transparent)]
+struct ChannelMapRepr(std::collections::HashMap<String, u8>);
+
+impl From<ChannelMapRepr> for ChannelMap {
+    fn from(ChannelMapRepr(channels): ChannelMapRepr) -> Self {
+        let mut by_channel = [0; 16];
+        for (from, to) in &channels {
+            if let Ok(from @ 1..=16) = from.trim().parse::<u8>() {
+                by_channel[from as usize - 1] = *to;
+            }
+        }
+        Self {
+            channels,
+            by_channel,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, PartialEq)]
+/// Bindings for MIDI Start, Stop, Continue and Song Select messages. Each triggers its action once,
+/// releasing anything it pressed right away
+pub struct TransportConfig {
+    pub start: Option<Action>,
+    pub stop: Option<Action>,
+    #[serde(rename = "continue")]
+    pub resume: Option<Action>,
+    /// Song Select, keyed by song number
+    #[serde(default)]
+    pub songs: std::collections::HashMap<String, Action>,
+    /// MIDI Machine Control commands
+    #[serde(default)]
+    pub mmc: MmcConfig,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, PartialEq)]
+/// Bindings for MIDI Machine Control commands, which some controllers' transport buttons send as SysEx
+pub struct MmcConfig {
+    pub play: Option<Action>,
+    pub stop: Option<Action>,
+    pub pause: Option<Action>,
+    pub record: Option<Action>,
+    pub record_exit: Option<Action>,
+    pub fast_forward: Option<Action>,
+    pub rewind: Option<Action>,
+    pub locate: Option<Action>,
+}
+
+impl MmcConfig {
+    /// The action bound to a command, by its field name
+    pub fn get(&self, command: &str) -> Option<&Action> {
+        match command {
+            "play" => self.play.as_ref(),
+            "stop" => self.stop.as_ref(),
+            "pause" => self.pause.as_ref(),
+            "record" => self.record.as_ref(),
+            "record_exit" => self.record_exit.as_ref(),
+            "fast_forward" => self.fast_forward.as_ref(),
+            "rewind" => self.rewind.as_ref(),
+            "locate" => self.locate.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl TransportConfig {
+    pub fn get_song(&self, song: u8) -> Option<&Action> {
+        self.songs.get(&song.to_string())
+    }
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone)]
+/// A variation on the base `[notes]` and `[cc]`, overriding only the bindings it lists
+pub struct ProfileConfig {
+    /// Another profile to build on instead of the base bindings
+    pub inherits: Option<String>,
+    /// What a NoteOn with velocity 0 means while the profile is active
+    pub zero_velocity: Option<ZeroVelocity>,
+    #[serde(default)]
+    pub notes: NoteBinding,
+    #[serde(default)]
+    pub cc: CCConfig,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// The virtual devices events are injected through, as they show up to the desktop
+pub struct DevicesConfig {
+    #[serde(default = "default_keyboard_name")]
+    pub keyboard_name: String,
+    #[serde(default = "default_mouse_name")]
+    pub mouse_name: String,
+    /// Only created once something is sent to it
+    #[serde(default = "default_gamepad_name")]
+    pub gamepad_name: String,
+    /// More devices, by the name bindings pick them with in their `device` field
+    #[serde(default)]
+    pub extra: std::collections::HashMap<String, ExtraDeviceConfig>,
+}
+
+impl Default for DevicesConfig {
+    fn default() -> Self {
+        Self {
+            keyboard_name: default_keyboard_name(),
+            mouse_name: default_mouse_name(),
+            gamepad_name: default_gamepad_name(),
+            extra: std::collections::HashMap::new(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// A virtual device on top of the usual keyboard, mouse and gamepad
+pub struct ExtraDeviceConfig {
+    pub kind: DeviceKind,
+    /// The name it shows up with, the table's name by default
+    pub name: Option<String>,
+}
+
+/// Which events a virtual device takes
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceKind {
+    /// Keys
     Keyboard,
-    /// Move the mouse everytime the CC is moved
+    /// Mouse buttons, movement and scrolling
     Mouse,
-    /// Toggle like a switch, similar to NoteOn/NoteOff
-    Toggle,
+    /// Gamepad buttons and axes
+    Gamepad,
+}
+
+fn default_keyboard_name() -> String {
+    "midkb keyboard".to_string()
+}
+
+fn default_mouse_name() -> String {
+    "midkb mouse".to_string()
+}
+
+fn default_gamepad_name() -> String {
+    "midkb gamepad".to_string()
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Two CCs acting as the axes of an XY pad or joystick nub. The cursor keeps moving
+/// while the pad is held off center, faster the further it's pushed
+pub struct XyPadConfig {
+    /// CC number of the horizontal axis
+    pub x: u8,
+    /// CC number of the vertical axis
+    pub y: u8,
+    /// How far from the center (64) an axis can rest without moving the cursor
+    #[serde(default = "default_xy_dead_zone")]
+    pub dead_zone: u8,
+    #[serde(default)]
+    pub invert_x: bool,
+    #[serde(default)]
+    pub invert_y: bool,
+    /// Multiplier for the cursor speed. At 1.0 full deflection moves about 1000 pixels a second
+    #[serde(default = "default_xy_speed")]
+    pub speed: f32,
+}
+
+fn default_xy_dead_zone() -> u8 {
+    4
+}
+
+fn default_xy_speed() -> f32 {
+    1.0
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Banks multiply what a pad grid can reach: in bank `n`, a pad triggers the binding of
+/// its note plus `n * offset`, or each bank switches to its own profile
+pub struct BankConfig {
+    /// How many banks to step through when offsetting notes
+    #[serde(default = "default_bank_count")]
+    pub count: u8,
+    /// How far a pad's note moves per bank
+    #[serde(default)]
+    pub offset: u8,
+    /// First and last note of the pads that are banked. Other notes, like the
+    /// bank buttons themselves, always trigger their own binding
+    pub pads: Option<[u8; 2]>,
+    /// One profile per bank, switched to instead of offsetting notes
+    #[serde(default)]
+    pub profiles: Vec<String>,
+    /// Go from the last bank back to the first and the other way around
+    #[serde(default)]
+    pub wrap: bool,
+    /// Light up a note on the controller for the current bank
+    pub feedback: Option<BankFeedbackConfig>,
+}
+
+impl BankConfig {
+    /// How many banks there are, which is never none
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u8 {
+        match self.profiles.len() {
+            0 => self.count.max(1),
+            profiles => profiles.min(u8::MAX as usize) as u8,
+        }
+    }
+
+    /// The note whose binding a pad triggers in a bank
+    pub fn note(&self, note: u8, bank: u8) -> u8 {
+        let banked = match self.pads {
+            Some([first, last]) => (first..=last).contains(&note),
+            None => true,
+        };
+
+        match banked && self.profiles.is_empty() {
+            true => note
+                .saturating_add(bank.saturating_mul(self.offset))
+                .min(127),
+            false => note,
+        }
+    }
+
+    /// The bank `step` banks away from `bank`
+    pub fn step(&self, bank: u8, step: i8) -> u8 {
+        let len = self.len() as i16;
+        let bank = bank as i16 + step as i16;
+
+        match self.wrap {
+            true => bank.rem_euclid(len) as u8,
+            false => bank.clamp(0, len - 1) as u8,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone)]
+#[serde(from = "CalibrationRepr")]
+pub struct Calibration {
+    pub controls: std::collections::HashMap<String, CalibrationConfig>,
+    // `controls` by CC number, so calibrating every control change doesn't allocate
+    by_number: Vec<Option<CalibrationConfig>>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(transparent)]
+struct CalibrationRepr(std::collections::HashMap<String, CalibrationConfig>);
+
+impl From<CalibrationRepr> for Calibration {
+    fn from(CalibrationRepr(controls): CalibrationRepr) -> Self {
+        Self {
+            by_number: by_number(&controls),
+            controls,
+        }
+    }
+}
+
+impl Calibration {
+    pub fn get(&self, cc: u8) -> Option<&CalibrationConfig> {
+        self.by_number.get(cc as usize)?.as_ref()
+    }
+}
+
+/// The values a control actually reaches, stretched to the full 0 to 127 before it's mapped,
+/// since cheap faders rarely get all the way to either end
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq)]
+pub struct CalibrationConfig {
+    pub min: u8,
+    pub max: u8,
+    /// Where a control resting in the middle sits, mapped to 64
+    pub center: Option<u8>,
+}
+
+impl CalibrationConfig {
+    pub fn normalize(&self, value: u8) -> u8 {
+        let stretch = |(from_low, from_high): (u8, u8), (to_low, to_high): (u8, u8)| {
+            if from_high <= from_low {
+                return to_low;
+            }
+            let offset = value.clamp(from_low, from_high) - from_low;
+            let scaled = offset as f32 * (to_high - to_low) as f32 / (from_high - from_low) as f32;
+            to_low + scaled.round() as u8
+        };
+
+        match self.center {
+            Some(center) if value <= center => stretch((self.min, center), (0, 64)),
+            Some(center) => stretch((center, self.max), (64, 127)),
+            None => stretch((self.min, self.max), (0, 127)),
+        }
+    }
+}
+
+/// What the pitch wheel drives
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PitchBendConfig {
+    Gamepad(GamepadAxisConfig),
+    Keys(PitchBendKeysConfig),
+}
+
+impl PitchBendConfig {
+    /// Extra device from `[devices.extra]` the pitch wheel's events go to
+    pub fn device(&self) -> &Option<String> {
+        match self {
+            Self::Gamepad(axis) => &axis.device,
+            Self::Keys(keys) => &keys.device,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Actions held while the pitch wheel is bent up or down
+pub struct PitchBendKeysConfig {
+    pub up: Option<Action>,
+    pub down: Option<Action>,
+    /// How far from the center, out of 8192, the wheel has to be bent before anything is held,
+    /// since its spring never brings it back to exactly the center
+    #[serde(default = "default_pitch_bend_dead_zone")]
+    pub dead_zone: u16,
+    /// Let go when the wheel springs back into the dead zone. When off, the action stays held
+    /// until the wheel is bent the other way
+    #[serde(default = "default_release_at_center")]
+    pub release_at_center: bool,
+    pub device: Option<String>,
+}
+
+fn default_pitch_bend_dead_zone() -> u16 {
+    512
+}
+
+fn default_release_at_center() -> bool {
+    true
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// A control moving an axis of the virtual gamepad
+pub struct GamepadAxisConfig {
+    pub axis: GamepadAxis,
+    /// The lowest and highest value of the control that move the axis all the way,
+    /// 0 to 127 for CCs and 0 to 16383 for pitch bend by default
+    pub range: Option<[u16; 2]>,
+    /// How far the control can move from where it rests without moving the axis
+    #[serde(default)]
+    pub dead_zone: u16,
+    /// Response curve exponent: above 1.0 gives finer control near the resting position,
+    /// below 1.0 near the ends
+    #[serde(default = "default_gamepad_curve")]
+    pub curve: f32,
+    #[serde(default)]
+    pub invert: bool,
+    /// Where the control sits when left alone: the bottom for faders, which maps to one end
+    /// of the axis, or the center for pitch bend and joysticks, which maps to the middle
+    pub rest: Option<RestPosition>,
+    /// Extra gamepad from `[devices.extra]` to move the axis of, for pitch bend
+    pub device: Option<String>,
+}
+
+fn default_gamepad_curve() -> f32 {
+    1.0
+}
+
+/// An absolute axis of the virtual gamepad
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GamepadAxis {
+    /// Left stick, horizontal
+    X,
+    /// Left stick, vertical
+    Y,
+    /// Left trigger on most gamepads
+    Z,
+    /// Right stick, horizontal
+    Rx,
+    /// Right stick, vertical
+    Ry,
+    /// Right trigger on most gamepads
+    Rz,
+    Throttle,
+    Rudder,
+    Wheel,
+    Gas,
+    Brake,
+}
+
+impl GamepadAxis {
+    /// The axis' evdev code
+    pub fn code(self) -> u16 {
+        use evdev::AbsoluteAxisCode as Abs;
+
+        let code = match self {
+            Self::X => Abs::ABS_X,
+            Self::Y => Abs::ABS_Y,
+            Self::Z => Abs::ABS_Z,
+            Self::Rx => Abs::ABS_RX,
+            Self::Ry => Abs::ABS_RY,
+            Self::Rz => Abs::ABS_RZ,
+            Self::Throttle => Abs::ABS_THROTTLE,
+            Self::Rudder => Abs::ABS_RUDDER,
+            Self::Wheel => Abs::ABS_WHEEL,
+            Self::Gas => Abs::ABS_GAS,
+            Self::Brake => Abs::ABS_BRAKE,
+        };
+        code.0
+    }
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RestPosition {
+    Bottom,
+    Center,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// A control setting a sink's or an app's volume
+pub struct VolumeConfig {
+    #[serde(flatten)]
+    pub target: VolumeTarget,
+    /// The volume at the top of the control's range, in percent. Above 100 amplifies
+    #[serde(default = "default_volume_max")]
+    pub max: u16,
+}
+
+fn default_volume_max() -> u16 {
+    100
+}
+
+impl Default for VolumeConfig {
+    fn default() -> Self {
+        Self {
+            target: VolumeTarget::default(),
+            max: default_volume_max(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, PartialEq, Eq, Hash)]
+/// Whose volume to change: a sink by name, every stream of an app, or the default sink
+pub struct VolumeTarget {
+    /// Name of a sink, as listed by `pactl list short sinks`
+    pub sink: Option<String>,
+    /// Name of an app playing sound, like `Firefox` or `spotify`, as listed by
+    /// `pactl list sink-inputs` under `application.name` or `application.process.binary`
+    pub app: Option<String>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// A control setting the screen's brightness
+pub struct BrightnessConfig {
+    /// Name of the backlight in /sys/class/backlight, like `intel_backlight`, the first one by default
+    pub device: Option<String>,
+    /// The brightness at the bottom of the control's range, in percent. Above 0 by default,
+    /// so the screen never goes completely dark
+    #[serde(default = "default_brightness_min")]
+    pub min: u8,
+    /// The brightness at the top of the control's range, in percent
+    #[serde(default = "default_brightness_max")]
+    pub max: u8,
+}
+
+fn default_brightness_min() -> u8 {
+    5
+}
+
+fn default_brightness_max() -> u8 {
+    100
+}
+
+impl Default for BrightnessConfig {
+    fn default() -> Self {
+        Self {
+            device: None,
+            min: default_brightness_min(),
+            max: default_brightness_max(),
+        }
+    }
+}
+
+fn default_bank_count() -> u8 {
+    2
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Pad LEDs lit by the state of each pad's binding, for pad controllers with LEDs
+pub struct FeedbackConfig {
+    /// Substring of the name of the MIDI output port leading back to the controller
+    pub port: String,
+    /// How the controller takes colors
+    #[serde(default)]
+    pub protocol: LedProtocol,
+    /// Channel to send on with the `velocity` protocol, from 1 to 16
+    #[serde(default = "default_feedback_channel")]
+    pub channel: u8,
+    /// Colors for every pad, the protocol's defaults otherwise
+    #[serde(flatten)]
+    pub colors: PadColors,
+    /// Colors for single pads, by note, replacing the ones for every pad
+    #[serde(default)]
+    pub notes: std::collections::HashMap<String, PadColors>,
+}
+
+/// How a controller's pad LEDs take colors
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LedProtocol {
+    /// A NoteOn whose velocity picks the color, like the original APC mini and most other pads
+    #[default]
+    Velocity,
+    /// Launchpad Mini MK3 in programmer mode: palette colors as velocities, RGB through SysEx
+    LaunchpadMiniMk3,
+    /// Launchpad X in programmer mode
+    LaunchpadX,
+    /// Launchpad Pro MK3 in programmer mode
+    LaunchpadProMk3,
+    /// APC mini mk2: palette colors as velocities at full brightness, RGB through SysEx
+    ApcMiniMk2,
+}
+
+/// A pad color: a palette index, sent as the velocity, or `[red, green, blue]` from 0 to 255
+/// for controllers taking RGB colors
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum LedColor {
+    Palette(u8),
+    Rgb([u8; 3]),
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, PartialEq)]
+/// The color of a pad in each state of its binding
+pub struct PadColors {
+    /// Nothing going on
+    pub idle: Option<LedColor>,
+    /// Held down
+    pub pressed: Option<LedColor>,
+    /// Toggled on: a toggled layer that's active, an armed sticky modifier, a held `click`
+    /// button or `bypass` while bypassed
+    pub latched: Option<LedColor>,
+    /// Only triggered because its `when` layer is active
+    pub layer: Option<LedColor>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Notes to light up on the controller, one per bank, for controllers whose pads
+/// have LEDs driven by incoming NoteOn messages
+pub struct BankFeedbackConfig {
+    /// Substring of the name of the MIDI output port leading back to the controller
+    pub port: String,
+    /// The note of each bank's LED, in bank order
+    pub notes: Vec<u8>,
+    /// Channel to send on, from 1 to 16
+    #[serde(default = "default_feedback_channel")]
+    pub channel: u8,
+    /// Velocity lighting the current bank's LED, which picks the color on many controllers
+    #[serde(default = "default_feedback_velocity")]
+    pub velocity: u8,
+}
+
+fn default_feedback_channel() -> u8 {
+    1
+}
+
+fn default_feedback_velocity() -> u8 {
+    127
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Bluetooth LE MIDI input, needs midkb to be built with the `ble` feature
+pub struct BleConfig {
+    /// Substring of the device's advertised name
+    pub device: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// MQTT event publishing and commands
+pub struct MqttConfig {
+    /// Host name or address of the broker
+    pub broker: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    /// Prefix of the topics: actions go to `<topic>/events`, the status to `<topic>/status`,
+    /// and commands are read from `<topic>/command`
+    #[serde(default = "default_mqtt_topic")]
+    pub topic: String,
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_client_id() -> String {
+    "midkb".to_string()
+}
+
+fn default_mqtt_topic() -> String {
+    "midkb".to_string()
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Where OBS Studio's WebSocket server is, as set up under Tools > WebSocket Server Settings
+pub struct ObsConfig {
+    #[serde(default = "default_obs_host")]
+    pub host: String,
+    #[serde(default = "default_obs_port")]
+    pub port: u16,
+    /// The server password, if authentication is turned on
+    pub password: Option<String>,
+}
+
+fn default_obs_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_obs_port() -> u16 {
+    4455
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// What to show desktop notifications for, confirming changes that don't show on screen
+pub struct NotificationsConfig {
+    /// Notify when the profile changes
+    #[serde(default)]
+    pub profiles: bool,
+    /// Notify when a layer is toggled on or off
+    #[serde(default)]
+    pub layers: bool,
+    /// Notify when the bank changes
+    #[serde(default)]
+    pub banks: bool,
+    /// How long notifications stay up
+    #[serde(default = "default_notification_timeout_ms")]
+    pub timeout_ms: u32,
 }
 
-#[derive(serde::Deserialize, Debug, Default)]
-pub struct CCDirectionConfig {
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            profiles: false,
+            layers: false,
+            banks: false,
+            timeout_ms: default_notification_timeout_ms(),
+        }
+    }
+}
+
+fn default_notification_timeout_ms() -> u32 {
+    1500
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Audible feedback, for controllers without LEDs
+pub struct SoundConfig {
+    /// Loudness, from 0 to 1
+    #[serde(default = "default_sound_volume")]
+    pub volume: f32,
+    /// Also click on every event from a CC, not just on notes and transport buttons
+    #[serde(default)]
+    pub cc: bool,
+}
+
+fn default_sound_volume() -> f32 {
+    0.5
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// HTTP control API
+pub struct HttpConfig {
+    /// Address to serve the API on
+    pub listen: std::net::SocketAddr,
+    /// Only answer requests with an `Authorization: Bearer <token>` header carrying this token
+    pub token: Option<String>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Web UI for configuring midkb from a browser
+pub struct WebUiConfig {
+    /// Address to serve the page on, which has to be a loopback address
+    pub listen: std::net::SocketAddr,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// WebSocket MIDI input
+pub struct WebSocketConfig {
+    /// Address to accept WebSocket connections on
+    pub listen: std::net::SocketAddr,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Network MIDI input, as sent by macOS/iOS network sessions and rtpMIDI
+pub struct RtpMidiConfig {
+    /// Address of the session control port, the data port is the one after it
+    pub listen: std::net::SocketAddr,
+    /// Name shown to peers when they connect
+    #[serde(default = "default_rtp_midi_name")]
+    pub name: String,
+}
+
+fn default_rtp_midi_name() -> String {
+    "midkb".to_string()
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Forwards incoming messages to another MIDI port, transforming them on the way
+pub struct RouteConfig {
+    /// Substring of the output port's name, like `midi_device`
+    pub port: String,
+    /// Only forward messages on these channels (1-16), all of them if empty
+    #[serde(default)]
+    pub channels: Vec<u8>,
+    /// Move every forwarded message to this channel (1-16)
+    pub channel: Option<u8>,
+    /// Semitones to shift notes by, dropping notes pushed out of range
+    #[serde(default)]
+    pub transpose: i8,
+    /// CC numbers to renumber, e.g. `{ 1 = 74 }`
+    #[serde(default)]
+    pub cc: std::collections::HashMap<String, u8>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// A sustain pedal for keybinds: while it's down, notes let go of their keys only once it lifts
+pub struct SustainConfig {
+    /// CC number of the pedal, down from a value of 64
+    #[serde(default = "default_sustain_cc")]
+    pub cc: u8,
+}
+
+fn default_sustain_cc() -> u8 {
+    64
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Where and how much action history to keep
+pub struct HistoryConfig {
+    /// The JSON lines file to append to
+    pub path: std::path::PathBuf,
+    /// Rotate the file once it grows past this size, 0 to never rotate
+    #[serde(default = "default_history_size_kb")]
+    pub max_size_kb: u64,
+    /// How many rotated files to keep around
+    #[serde(default = "default_history_keep")]
+    pub keep: usize,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// How to learn where the controls are when the controller connects. The first value of
+/// every CC only sets where its control is, so the first touch doesn't move anything
+pub struct SnapshotConfig {
+    /// A message asking the controller to send the position of all its controls
+    pub request: Option<SnapshotRequest>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// A message to send to the controller as it connects
+pub struct SnapshotRequest {
+    /// Substring of the name of the MIDI output port leading back to the controller
+    pub port: String,
+    /// The raw bytes, like a SysEx message from the controller's programmer's reference
+    pub message: Vec<u8>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// Where to capture MIDI messages that fail to parse
+pub struct CaptureConfig {
+    /// The JSON lines file to append to
+    pub path: std::path::PathBuf,
+}
+
+fn default_history_size_kb() -> u64 {
+    1024
+}
+
+fn default_history_keep() -> usize {
+    3
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// What a NoteOn with velocity 0 means
+pub enum ZeroVelocity {
+    /// A NoteOff, as the MIDI spec has it and most controllers send it
+    #[default]
+    NoteOff,
+    /// A press of its own, for controllers with a distinct soft press
+    Press,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// How to pick the MIDI port out of the available ones
+pub enum DeviceMatch {
+    /// The first port whose name contains `midi_device`
+    #[default]
+    Substring,
+    /// The port named exactly `midi_device`
+    Exact,
+    /// The first port whose name matches the `midi_device` regex
+    Regex,
+    /// The port at the `midi_device` position in the list of ports, starting at 0
+    Index,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// MIDI input backend
+pub enum Backend {
+    /// ALSA sequencer ports, where most USB controllers show up
+    #[default]
+    Alsa,
+    /// JACK MIDI ports, needs midkb to be built with the `jack` feature
+    Jack,
+    /// Play a file of `midkb simulate` commands named by `midi_device`, once, as if it came
+    /// from a controller
+    Replay,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, Copy, PartialEq)]
+/// Which end of the channel range an MPE zone occupies
+pub enum MpeZone {
+    /// Master channel 1, member channels counting up from 2
+    #[default]
+    Lower,
+    /// Master channel 16, member channels counting down from 15
+    Upper,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone)]
+/// Where a note's per-note expression is routed.
+/// Axes are mouse axes, `x`, `y`, `-x` or `-y`
+pub struct MpeRoute {
+    /// Mouse axis moved by the note's pitch bend
+    pub pitch_bend: Option<MouseAxis>,
+    /// Mouse axis moved by the note's pressure
+    pub pressure: Option<MouseAxis>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone)]
+pub struct MpeConfig {
+    #[serde(default)]
+    pub zone: MpeZone,
+
+    /// Number of member channels in the zone
+    #[serde(default = "default_member_channels")]
+    pub member_channels: u8,
+
+    /// Routing used for every note without its own entry in `notes`
+    #[serde(flatten)]
+    pub default_route: MpeRoute,
+
+    /// Multiplier for the mouse movement. At 1.0 a full pitch bend moves 128 pixels,
+    /// and full pressure moves 127 pixels
+    #[serde(default = "default_mpe_sensitivity")]
+    pub sensitivity: f32,
+
+    /// How far from the center, out of 8192, a note's pitch bend has to go before it moves the
+    /// mouse, so a finger resting on a key doesn't make the cursor drift
+    #[serde(default)]
+    pub pitch_bend_dead_zone: u16,
+
+    /// Per-note routing, keyed by MIDI note
+    #[serde(default)]
+    pub notes: MpeNotes,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone)]
+#[serde(from = "MpeNotesRepr")]
+pub struct MpeNotes {
+    pub notes: std::collections::HashMap<String, MpeRoute>,
+    // `notes` by number, so routing every note's expression doesn't allocate
+    by_number: Vec<Option<MpeRoute>>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(transparent)]
+struct MpeNotesRepr(std::collections::HashMap<String, MpeRoute>);
+
+impl From<MpeNotesRepr> for MpeNotes {
+    fn from(MpeNotesRepr(notes): MpeNotesRepr) -> Self {
+        Self {
+            by_number: by_number(&notes),
+            notes,
+        }
+    }
+}
+
+fn default_member_channels() -> u8 {
+    15
+}
+
+fn default_mpe_sensitivity() -> f32 {
+    1.0
+}
+
+impl MpeConfig {
+    /// Whether a channel (1-16) is one of the zone's member channels
+    pub fn is_member(&self, channel: u8) -> bool {
+        let members = self.member_channels.clamp(1, 15);
+
+        match self.zone {
+            MpeZone::Lower => (2..=1 + members).contains(&channel),
+            MpeZone::Upper => (16 - members..=15).contains(&channel),
+        }
+    }
+
+    pub fn route(&self, note: u8) -> &MpeRoute {
+        self.notes
+            .by_number
+            .get(note as usize)
+            .and_then(Option::as_ref)
+            .unwrap_or(&self.default_route)
+    }
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// Method used to enter arbitrary unicode characters
+pub enum UnicodeInput {
+    /// Ctrl+Shift+U, the hex code point, then space.
+    /// Understood by IBus and GTK applications
+    #[default]
+    CtrlShiftU,
+    /// Don't try to enter characters the layout can't type
+    Disabled,
+}
+
+/// How a control's value is read
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CCValueMode {
+    /// The value is where the control is, and moves are worked out from the last value
+    #[default]
+    Absolute,
+    /// The value is how many steps an endless encoder turned since its last message,
+    /// counting from `center`
+    Relative,
+}
+
+/// How far Mouse mode moves the cursor for how many steps the control moved in one event
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseCurve {
+    /// In proportion to the steps
+    Linear,
+    /// With the square of the steps, slower than linear for small moves and faster for large ones
+    Exponential,
+    /// Interpolated between `curve_points`
+    Custom,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone)]
+/// Mode to bind the CC controls to
+pub enum CCBindMode {
+    /// Press a key on the keyboard everytime the CC is moved
+    #[default]
+    Keyboard,
+    /// Move the mouse everytime the CC is moved
+    Mouse,
+    /// Toggle like a switch, similar to NoteOn/NoteOff
+    Toggle,
+    /// Release every held key and button when the control sends a non-zero value
+    Panic,
+    /// Run the `run` shell command every time the CC is moved, with `{value}` and
+    /// the other placeholders filled in
+    Command,
+    /// Move an axis of a virtual gamepad to wherever the control is, as set up in `gamepad`,
+    /// for analog triggers and throttles
+    Gamepad,
+    /// Split the control's range into equal zones, one per entry in `zones`, holding the key
+    /// of the zone the control is in and releasing it when the control leaves the zone
+    Zones,
+    /// Step to the next bank of pads when the control sends a non-zero value
+    BankUp,
+    /// Step to the previous bank of pads when the control sends a non-zero value
+    BankDown,
+    /// Set the volume of the sink or app in `volume` to wherever the control is,
+    /// the default sink if there's no `volume`
+    Volume,
+    /// Set the screen's backlight to wherever the control is, within the range in `brightness`
+    Brightness,
+}
+
+/// A mouse axis, written as `x`, `y`, `-x` or `-y`
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String")]
+pub enum MouseAxis {
+    X,
+    NegX,
+    Y,
+    NegY,
+}
+
+impl MouseAxis {
+    /// Mouse movement for moving `amount` pixels along this axis
+    pub fn motion(self, amount: i32) -> (i32, i32) {
+        match self {
+            Self::X => (amount, 0),
+            Self::NegX => (-amount, 0),
+            Self::Y => (0, amount),
+            Self::NegY => (0, -amount),
+        }
+    }
+}
+
+impl TryFrom<String> for MouseAxis {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.trim() {
+            "x" => Ok(Self::X),
+            "-x" => Ok(Self::NegX),
+            "y" => Ok(Self::Y),
+            "-y" => Ok(Self::NegY),
+            _ => Err(format!("unknown mouse axis `{s}`, expected x, y, -x or -y")),
+        }
+    }
+}
+
+impl schemars::JsonSchema for MouseAxis {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "MouseAxis".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "A mouse axis",
+            "type": "string",
+            "enum": ["x", "-x", "y", "-y"],
+        })
+    }
+}
+
+/// A scroll wheel, written as `wheel` (scrolling up), `-wheel`, `hwheel` (scrolling right) or `-hwheel`
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String")]
+pub enum ScrollAxis {
+    Wheel,
+    NegWheel,
+    HWheel,
+    NegHWheel,
+}
+
+impl ScrollAxis {
+    /// Horizontal and vertical wheel movement for scrolling `amount` along this wheel
+    pub fn motion(self, amount: i32) -> (i32, i32) {
+        match self {
+            Self::HWheel => (amount, 0),
+            Self::NegHWheel => (-amount, 0),
+            Self::Wheel => (0, amount),
+            Self::NegWheel => (0, -amount),
+        }
+    }
+}
+
+impl TryFrom<String> for ScrollAxis {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.trim() {
+            "wheel" => Ok(Self::Wheel),
+            "-wheel" => Ok(Self::NegWheel),
+            "hwheel" => Ok(Self::HWheel),
+            "-hwheel" => Ok(Self::NegHWheel),
+            _ => Err(format!(
+                "unknown wheel `{s}`, expected wheel, hwheel, -wheel or -hwheel"
+            )),
+        }
+    }
+}
+
+impl schemars::JsonSchema for ScrollAxis {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "ScrollAxis".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "A scroll wheel",
+            "type": "string",
+            "enum": ["wheel", "-wheel", "hwheel", "-hwheel"],
+        })
+    }
+}
+
+/// A keycode, written as a number, a hex string like "0x1E" or a key name like "KEY_A"
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "KeyCodeRepr")]
+pub struct KeyCode(pub u16);
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum KeyCodeRepr {
+    Code(u16),
+    Name(String),
+}
+
+impl TryFrom<KeyCodeRepr> for KeyCode {
+    type Error = String;
+
+    fn try_from(repr: KeyCodeRepr) -> Result<Self, Self::Error> {
+        match repr {
+            KeyCodeRepr::Code(code) => Ok(Self(code)),
+            KeyCodeRepr::Name(name) => crate::keycodes::parse(&name)
+                .map(Self)
+                .ok_or_else(|| format!("`{name}` is neither a keycode nor a key name")),
+        }
+    }
+}
+
+impl schemars::JsonSchema for KeyCode {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "KeyCode".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "A keycode, in decimal or in hex like \"0x1E\", or a key name",
+            "anyOf": [
+                { "type": "integer", "minimum": 0, "maximum": 65535 },
+                { "type": "string", "enum": crate::keycodes::names().collect::<Vec<_>>() },
+                // Key names are also accepted in lowercase and without `KEY_`
+                { "type": "string", "pattern": "^\\s*[A-Za-z0-9_]+\\s*$" },
+            ],
+        })
+    }
+}
+
+/// A key action, written as a bare keycode or key name, or as `{ type = "key", code = ... }`
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(untagged)]
+enum KeyRepr {
+    Code(KeyCode),
+    Tagged {
+        #[serde(rename = "type")]
+        kind: KeyTag,
+        code: KeyCode,
+    },
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum KeyTag {
+    Key,
+}
+
+fn deserialize_key<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    match serde::Deserialize::deserialize(deserializer)? {
+        KeyRepr::Code(code) => Ok(code.0),
+        KeyRepr::Tagged {
+            kind: KeyTag::Key,
+            code,
+        } => Ok(code.0),
+    }
+}
+
+/// A keycode field written as a number, a hex string or a key name, kept as the number
+fn deserialize_keycode<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    serde::Deserialize::deserialize(deserializer).map(|KeyCode(code)| code)
+}
+
+/// What turning a CC one way does: tap a key, move the mouse along an axis, scroll a wheel,
+/// or trigger any other action
+#[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "CCTargetRepr")]
+pub enum CCTarget {
+    Key(u16),
+    Axis(MouseAxis),
+    Scroll(ScrollAxis),
+    Action(Box<Action>),
+}
+
+/// A CC target written as a tagged action, or in the older bare forms:
+/// a keycode, or a string with a key name, an axis or a wheel
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum CCTargetRepr {
+    Code(u16),
+    Name(String),
+    Motion(MotionRepr),
+    Action(Action),
+}
+
+/// `{ type = "mouse_move", axis = ... }` or `{ type = "scroll", axis = ... }`, moving along
+/// the axis in the direction the control turned
+#[derive(serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MotionRepr {
+    MouseMove { axis: MouseAxis },
+    Scroll { axis: ScrollAxis },
+}
+
+impl TryFrom<CCTargetRepr> for CCTarget {
+    type Error = String;
+
+    fn try_from(repr: CCTargetRepr) -> Result<Self, Self::Error> {
+        let s = match repr {
+            CCTargetRepr::Code(code) => return Ok(Self::Key(code)),
+            CCTargetRepr::Motion(MotionRepr::MouseMove { axis }) => return Ok(Self::Axis(axis)),
+            CCTargetRepr::Motion(MotionRepr::Scroll { axis }) => return Ok(Self::Scroll(axis)),
+            CCTargetRepr::Action(Action::Key(code)) => return Ok(Self::Key(code)),
+            CCTargetRepr::Action(action) => return Ok(Self::Action(Box::new(action))),
+            CCTargetRepr::Name(s) => s,
+        };
+
+        if let Ok(axis) = MouseAxis::try_from(s.clone()) {
+            return Ok(Self::Axis(axis));
+        }
+        if let Ok(wheel) = ScrollAxis::try_from(s.clone()) {
+            return Ok(Self::Scroll(wheel));
+        }
+
+        KeyCode::try_from(KeyCodeRepr::Name(s.clone()))
+            .map(|code| Self::Key(code.0))
+            .map_err(|_| {
+                format!("`{s}` is neither a keycode, a key name, a mouse axis, nor a wheel")
+            })
+    }
+}
+
+impl schemars::JsonSchema for CCTarget {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "CCTarget".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "An action or key in Keyboard and Toggle mode, \
+                a mouse axis or wheel in Mouse mode",
+            "anyOf": [
+                generator.subschema_for::<MouseAxis>(),
+                generator.subschema_for::<ScrollAxis>(),
+                generator.subschema_for::<KeyCode>(),
+                {
+                    "type": "object",
+                    "properties": {
+                        "type": { "enum": ["mouse_move"] },
+                        "axis": generator.subschema_for::<MouseAxis>(),
+                    },
+                    "required": ["type", "axis"],
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "type": { "enum": ["scroll"] },
+                        "axis": generator.subschema_for::<ScrollAxis>(),
+                    },
+                    "required": ["type", "axis"],
+                },
+                generator.subschema_for::<Action>(),
+            ],
+        })
+    }
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone)]
+pub struct CCDirectionConfig {
+
+    pub bind_mode: CCBindMode,
+
+    /// How the control's value is read: as where it is, or as how far it turned
+    #[serde(default)]
+    pub mode: CCValueMode,
+
+    /// The value a relative control sends for not moving, with higher values turning
+    /// clockwise and lower ones counter-clockwise. Values wrap around, so with a center of 0,
+    /// 1 is a step clockwise and 127 a step counter-clockwise
+    #[serde(default = "default_relative_center")]
+    pub center: u8,
+
+    // both counter_clockwise can be either an action, like a keycode (a number or a key name like "KEY_A"),
+    // or a mouse axis (x, y, -x, -y) or wheel (wheel, hwheel, -wheel, -hwheel), depending on the bind mode
+    pub counter_clockwise: Option<CCTarget>,
+    pub clockwise: Option<CCTarget>,
+
+    /// Shell command for Command mode
+    pub run: Option<String>,
+
+    /// Scroll wheels in fractions of a notch, scaled by how far the control moved,
+    /// instead of a whole notch per event. Smoother in apps with pixel-precise scrolling
+    #[serde(default)]
+    pub hi_res: bool,
+
+    /// Ignore changes smaller than this many steps from the last accepted value,
+    /// for faders that jitter by ±1
+    #[serde(default)]
+    pub min_delta: u8,
+
+    /// Ignore changes turning back against the direction the control last moved in, unless
+    /// they go further than this from the last accepted value. Unlike `min_delta`, moves
+    /// carrying on in the same direction still count from the first step, so this stops a
+    /// worn fader from flapping between directions without making it coarser
+    #[serde(default)]
+    pub jitter: u8,
+
+    /// Coalesce changes arriving within this many milliseconds of the last accepted one
+    pub debounce_ms: Option<u64>,
+
+    /// In Toggle mode, ignore switching on within this many milliseconds of the last time it
+    /// fired, so a destructive action can't be triggered twice by accident
+    pub cooldown_ms: Option<u64>,
+
+    /// Treat jumps further than this as an endless encoder wrapping around between 127 and 0,
+    /// moving the short way around instead of all the way back
+    pub wrap_threshold: Option<u8>,
+
+    /// Keep producing events when the control resends 0 or 127, counter-clockwise at 0 and
+    /// clockwise at 127, for controls that repeat their end value while turned past it.
+    /// Otherwise repeated values are ignored
+    #[serde(default)]
+    pub pegged_repeat: bool,
+
+    /// Swap the directions, for encoders that report backwards
+    #[serde(default)]
+    pub invert: bool,
+
+    /// How many steps the control has to move in one direction for each event,
+    /// for controllers with a very fine resolution
+    #[serde(default = "default_steps_per_event")]
+    pub steps_per_event: u8,
+
+    /// Smoothing factor for Mouse mode, from 0.0 (none) to just below 1.0 (very smooth).
+    /// Movement is accumulated and drained gradually so the cursor glides instead of stepping
+    pub smoothing: Option<f32>,
+
+    /// How far Mouse mode moves the cursor for how far the control moved.
+    /// Without one, every event moves it the same distance
+    pub curve: Option<ResponseCurve>,
+
+    /// The `[steps, pixels]` points of a custom `curve`, by steps, with the distance in between
+    /// interpolated. Moves beyond the last point go as far as it does
+    #[serde(default)]
+    pub curve_points: Vec<(u8, u32)>,
+
+    /// Ignore the control after it jumps away from the value the mapping last saw
+    /// (e.g. after switching profiles or reconnecting), until it crosses that value again
+    #[serde(default)]
+    pub soft_takeover: bool,
+
+    /// In Keyboard mode, emit more key taps per event when the encoder is spun quickly,
+    /// based on how far and how fast the value moved
+    #[serde(default)]
+    pub repeat_acceleration: bool,
+
+    /// Upper bound on key taps per event with `repeat_acceleration`
+    #[serde(default = "default_max_repeat")]
+    pub max_repeat: u8,
+
+    /// Keys or actions for Zones mode, one for each equal slice of the control's range,
+    /// from the bottom up
+    #[serde(default)]
+    pub zones: Vec<CCTarget>,
+
+    /// The gamepad axis Gamepad mode moves
+    pub gamepad: Option<GamepadAxisConfig>,
+
+    /// The sink or app Volume mode sets the volume of
+    pub volume: Option<VolumeConfig>,
+
+    /// The backlight Brightness mode sets, and its range
+    pub brightness: Option<BrightnessConfig>,
+
+    /// Extra device from `[devices.extra]` to send the control's events to
+    pub device: Option<String>,
+}
+
+fn default_relative_center() -> u8 {
+    64
+}
+
+fn default_max_repeat() -> u8 {
+    8
+}
+
+fn default_steps_per_event() -> u8 {
+    1
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone)]
+#[serde(from = "CCConfigRepr")]
+pub struct CCConfig {
+    pub cc: std::collections::HashMap<String, CCDirectionConfig>,
+    // `cc` by number, so looking up a control on every event doesn't allocate. Shared, so the
+    // handler can hold on to a control's config while it acts on it
+    by_number: Vec<Option<std::sync::Arc<CCDirectionConfig>>>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+struct CCConfigRepr {
+    // would be a toml of the form:
+    // [cc]
+    // <cc_number> = [counter_clockwise, clockwise]
+    // 1 = [60, 70]
+    #[serde(flatten)]
+    cc: std::collections::HashMap<String, CCDirectionConfig>,
+}
+
+impl From<CCConfigRepr> for CCConfig {
+    fn from(repr: CCConfigRepr) -> Self {
+        Self {
+            by_number: by_number(&repr.cc),
+            cc: repr.cc,
+        }
+    }
+}
+
+/// Bindings keyed by their number in the config, indexed by that number. Keys that aren't a
+/// number from 0 to 127 are left out, `map` reports them
+fn by_number<T: Clone, U: From<T> + Clone>(
+    bindings: &std::collections::HashMap<String, T>,
+) -> Vec<Option<U>> {
+    let mut table = vec![None; 128];
+    for (key, binding) in bindings {
+        if let Ok(number @ 0..=127) = key.trim().parse::<u8>() {
+            table[number as usize] = Some(binding.clone().into());
+        }
+    }
+    table
+}
+
+/// Two keys naming the same number, like `37` and `037`, which `by_number` can't tell apart
+fn duplicate_number<T>(bindings: &std::collections::HashMap<String, T>) -> Option<(&str, &str)> {
+    let mut seen = [None; 128];
+    for key in bindings.keys() {
+        if let Ok(number @ 0..=127) = key.trim().parse::<u8>() {
+            if let Some(other) = seen[number as usize].replace(key.as_str()) {
+                let mut pair = [other, key.as_str()];
+                pair.sort_unstable();
+                return Some((pair[0], pair[1]));
+            }
+        }
+    }
+    None
+}
+
+/// An action that can be bound to a note
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    /// Arm a modifier key (e.g. Shift) that is applied only to the next key emitted,
+    /// then automatically released, like sticky keys.
+    /// Triggering it again while armed disarms it.
+    Sticky {
+        #[serde(deserialize_with = "deserialize_keycode")]
+        #[schemars(with = "KeyCode")]
+        code: u16,
+    },
+    /// Force-release every key and button currently held by the virtual device
+    Panic,
+    /// Stop or resume injecting events, to play the controller as an instrument for a while.
+    /// While bypassed, everything but this pad is passed through to the thru port
+    Bypass,
+    /// Type out a literal string, resolving each character through the configured layout
+    Type { text: String },
+    /// Hold the key typing a character on the configured layout, with Shift or AltGr
+    /// if it needs them, so the binding does the same on QWERTY, QWERTZ and AZERTY
+    Char { char: char },
+    /// Type a string through the unicode input method, for emoji and other
+    /// characters no key on the layout produces
+    Unicode { text: String },
+    /// Run a shell command with `sh -c`, without waiting for it to finish.
+    /// `{note}`, `{velocity}` and `{channel}` are replaced with the triggering note's
+    Command { run: String },
+    /// Send a command to sway or Hyprland over its IPC socket, like `workspace 3` for sway
+    /// or `dispatch workspace 3` for Hyprland, with the same placeholders as `command`
+    Compositor { command: String },
+    /// Show a desktop notification, with the same placeholders as `command`, to confirm that
+    /// the pad registered
+    Notify { text: String },
+    /// Switch OBS to a scene
+    ObsScene { scene: String },
+    /// Show or hide a source in an OBS scene, the one on air by default
+    ObsSource {
+        source: String,
+        scene: Option<String>,
+    },
+    /// Start, stop or toggle recording in OBS
+    ObsRecord {
+        #[serde(default)]
+        command: ObsRecordCommand,
+    },
+    /// Mute or unmute a sink or app, the default sink by default
+    Volume {
+        #[serde(flatten)]
+        target: VolumeTarget,
+    },
+    /// Activate a named layer while the note is held, or flip it on/off with `toggle`.
+    /// Layers are checked by `when` conditions
+    Layer {
+        name: String,
+        #[serde(default)]
+        toggle: bool,
+    },
+    /// Hold a named modifier while the note is held, without pressing anything.
+    /// Only notes listing the modifier in their `with` table act differently while it's held
+    Modifier { name: String },
+    /// Step to the next bank of pads
+    BankUp,
+    /// Step to the previous bank of pads
+    BankDown,
+    /// Hold a mouse button while the note is held, and move the cursor with the `x` and `y` CCs
+    /// in the meantime, to click and drag from the controller. Those CCs skip their own
+    /// bindings while dragging
+    Drag {
+        #[serde(default)]
+        button: MouseButton,
+        x: Option<u8>,
+        y: Option<u8>,
+        /// Pixels moved per step of the CC
+        #[serde(default = "default_drag_speed")]
+        speed: i32,
+    },
+    /// Move the mouse once by a relative amount, e.g. over to the next monitor
+    MouseMove {
+        #[serde(default)]
+        dx: i32,
+        #[serde(default)]
+        dy: i32,
+        /// Scale the movement by how hard the pad is hit, the velocity over 127 raised to this
+        /// exponent: 1.0 is linear, above 1.0 keeps soft hits short for fine nudges
+        velocity_curve: Option<f32>,
+    },
+    /// Click a mouse button, `count` times in a row for double or triple clicks, or hold it
+    /// down or let go of it depending on `mode`
+    Click {
+        #[serde(default)]
+        button: MouseButton,
+        #[serde(default = "default_click_count")]
+        count: u8,
+        #[serde(default)]
+        mode: ClickMode,
+    },
+    /// A bare keycode or key name, or `{ type = "key", code = ... }` with either,
+    /// held down for as long as the note is held
+    #[serde(untagged, deserialize_with = "deserialize_key")]
+    #[schemars(with = "KeyRepr")]
+    Key(u16),
+    /// A list of actions, all triggered together in order
+    #[serde(untagged)]
+    Multi(Vec<Action>),
+    /// An action only triggered when a condition on midkb's state holds
+    #[serde(untagged)]
+    When { when: Condition, then: Box<Action> },
+}
+
+/// What an `obs_record` action does
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ObsRecordCommand {
+    #[default]
+    Toggle,
+    Start,
+    Stop,
+}
+
+fn default_drag_speed() -> i32 {
+    10
+}
+
+fn default_click_count() -> u8 {
+    1
+}
+
+/// What a `click` action does with its button
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickMode {
+    /// Press and release it, `count` times
+    #[default]
+    Click,
+    /// Click it twice, `count` times
+    DoubleClick,
+    /// Press it and keep it held after the note is released, until a `release` lets go of it,
+    /// to start a drag from one pad and drop from another
+    Hold,
+    /// Let go of a button held by `hold`
+    Release,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseButton {
+    #[default]
+    Left,
+    Right,
+    Middle,
+}
+
+impl MouseButton {
+    /// The button's evdev code
+    pub fn code(self) -> u16 {
+        match self {
+            Self::Left => mouse_keyboard_input::BTN_LEFT,
+            Self::Right => mouse_keyboard_input::BTN_RIGHT,
+            Self::Middle => mouse_keyboard_input::BTN_MIDDLE,
+        }
+    }
+}
+
+/// A condition on midkb's internal state, as written in a `when` field:
+/// `<layer>` for an active layer, `key:<name or code>` for a key midkb is holding down,
+/// either of them prefixed with `!` to negate it
+#[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub struct Condition {
+    pub negate: bool,
+    pub kind: ConditionKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConditionKind {
+    Layer(String),
+    Key(u16),
+}
+
+impl TryFrom<String> for Condition {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let (negate, rest) = match s.trim().strip_prefix('!') {
+            Some(rest) => (true, rest.trim()),
+            None => (false, s.trim()),
+        };
+
+        let kind = match rest.split_once(':') {
+            Some(("key", key)) => {
+                let code = crate::keycodes::parse(key)
+                    .ok_or_else(|| format!("unknown key `{key}` in condition `{s}`"))?;
+                ConditionKind::Key(code)
+            }
+            Some(("layer", layer)) => ConditionKind::Layer(layer.to_string()),
+            Some((kind, _)) => return Err(format!("unknown condition kind `{kind}` in `{s}`")),
+            None if rest.is_empty() => return Err("empty condition".to_string()),
+            None => ConditionKind::Layer(rest.to_string()),
+        };
+
+        Ok(Self { negate, kind })
+    }
+}
+
+impl schemars::JsonSchema for Condition {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Condition".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "`<layer>` or `layer:<layer>` for an active layer, `key:<name or code>` \
+                for a held key, prefixed with `!` to negate it",
+            "type": "string",
+            "pattern": "^\\s*!?\\s*\\S+$",
+        })
+    }
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone)]
+#[serde(try_from = "NoteBindingRepr")]
+pub struct NoteBinding {
+    /// Notes by number, including the ones `grid` binds
+    pub notes: std::collections::HashMap<String, NoteConfig>,
+    // `notes` by number, so looking up a note on every event doesn't allocate
+    by_number: Vec<Option<NoteConfig>>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+struct NoteBindingRepr {
+    /// A grid of pads bound to a block of keys in one go. Notes bound on their own win
+    grid: Option<GridConfig>,
+    #[serde(flatten)]
+    notes: std::collections::HashMap<String, NoteConfig>,
+}
+
+impl TryFrom<NoteBindingRepr> for NoteBinding {
+    type Error = String;
+
+    fn try_from(repr: NoteBindingRepr) -> Result<Self, Self::Error> {
+        let mut notes = repr.notes;
+        if let Some(grid) = repr.grid {
+            for (note, key) in grid.keys()? {
+                notes
+                    .entry(note.to_string())
+                    .or_insert_with(|| NoteConfigRepr::Action(Action::Key(key)).into());
+            }
+        }
+
+        Ok(Self {
+            by_number: by_number(&notes),
+            notes,
+        })
+    }
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+/// A rectangular grid of pads, with `start_note` at the bottom left and notes going up to
+/// the right, then a row up. The top left pad gets the top left key of the block, and so on
+pub struct GridConfig {
+    pub start_note: u8,
+    pub rows: u8,
+    pub cols: u8,
+    /// How far apart the notes of two rows are, `cols` by default. Launchpads in
+    /// programmer mode number their rows 10 apart
+    pub row_step: Option<u8>,
+    pub keys: GridKeys,
+}
+
+/// A block of keys to lay a pad grid onto
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GridKeys {
+    /// The numeric keypad, 4 rows of 4 from KP7 KP8 KP9 KPSLASH to KP0 KPDOT KPENTER KPPLUS
+    Numpad,
+    /// The letter rows, 4 rows of 10 from the number row to Z X C V B N M , . /
+    Qwerty,
+    /// F13 to F24, 3 rows of 4, which nothing else uses so they're free for shortcuts
+    F13F24,
+}
+
+impl GridKeys {
+    /// The block's keys, rows from the top
+    fn rows(&self) -> &'static [&'static [u16]] {
+        use mouse_keyboard_input::*;
+
+        match self {
+            GridKeys::Numpad => &[
+                &[KEY_KP7, KEY_KP8, KEY_KP9, KEY_KPSLASH],
+                &[KEY_KP4, KEY_KP5, KEY_KP6, KEY_KPASTERISK],
+                &[KEY_KP1, KEY_KP2, KEY_KP3, KEY_KPMINUS],
+                &[KEY_KP0, KEY_KPDOT, KEY_KPENTER, KEY_KPPLUS],
+            ],
+            // KEY_10 is the 0 key
+            GridKeys::Qwerty => &[
+                &[
+                    KEY_1, KEY_2, KEY_3, KEY_4, KEY_5, KEY_6, KEY_7, KEY_8, KEY_9, KEY_10,
+                ],
+                &[
+                    KEY_Q, KEY_W, KEY_E, KEY_R, KEY_T, KEY_Y, KEY_U, KEY_I, KEY_O, KEY_P,
+                ],
+                &[
+                    KEY_A,
+                    KEY_S,
+                    KEY_D,
+                    KEY_F,
+                    KEY_G,
+                    KEY_H,
+                    KEY_J,
+                    KEY_K,
+                    KEY_L,
+                    KEY_SEMICOLON,
+                ],
+                &[
+                    KEY_Z, KEY_X, KEY_C, KEY_V, KEY_B, KEY_N, KEY_M, KEY_COMMA, KEY_DOT, KEY_SLASH,
+                ],
+            ],
+            GridKeys::F13F24 => &[
+                &[KEY_F13, KEY_F14, KEY_F15, KEY_F16],
+                &[KEY_F17, KEY_F18, KEY_F19, KEY_F20],
+                &[KEY_F21, KEY_F22, KEY_F23, KEY_F24],
+            ],
+        }
+    }
+}
+
+impl GridConfig {
+    /// Every pad's note and the key it's bound to
+    pub fn keys(&self) -> Result<Vec<(u8, u16)>, String> {
+        let block = self.keys.rows();
+        let (rows, cols) = (self.rows as usize, self.cols as usize);
+        if rows > block.len() || cols > block[0].len() {
+            return Err(format!(
+                "a grid of {rows} by {cols} pads doesn't fit on {:?}, which has {} rows of {} keys",
+                self.keys,
+                block.len(),
+                block[0].len()
+            ));
+        }
+
+        let row_step = self.row_step.unwrap_or(self.cols) as usize;
+        let mut keys = Vec::new();
+        for row in 0..rows {
+            // Rows count up from the bottom of the grid, but down from the top of the block
+            let block_row = &block[rows - 1 - row][..cols];
+
+            for (col, key) in block_row.iter().enumerate() {
+                let note = self.start_note as usize + row * row_step + col;
+                if note > 127 {
+                    return Err(format!("the grid's notes go past 127, to {note}"));
+                }
+
+                keys.push((note as u8, *key));
+            }
+        }
+
+        Ok(keys)
+    }
+}
+
+impl NoteBinding {
+    pub fn get(&self, note: u8) -> Option<&NoteConfig> {
+        self.by_number.get(note as usize)?.as_ref()
+    }
+
+    /// Bind the notes another set of bindings binds, replacing any binding they have here
+    pub fn extend(&mut self, other: &NoteBinding) {
+        self.notes.extend(other.notes.clone());
+        self.by_number = by_number(&self.notes);
+    }
+
+    /// Unbind a note
+    pub fn remove(&mut self, note: u8) {
+        self.notes.retain(|key, _| key.trim().parse() != Ok(note));
+        self.by_number = by_number(&self.notes);
+    }
+
+    pub fn get_action(&self, note: u8) -> Option<&Action> {
+        self.get(note).map(|binding| &binding.action)
+    }
+}
+
+/// What a note is bound to, either written as a bare action or as
+/// `{ action = ..., min_hold_ms = ... }` to add timing options
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Clone, PartialEq)]
+#[serde(from = "NoteConfigRepr")]
+pub struct NoteConfig {
+    pub action: Action,
+    /// Keep the keys held at least this long, even if the pad bounces
+    /// and sends NoteOff right away
+    pub min_hold_ms: Option<u64>,
+    /// Force-release the keys if no NoteOff arrives after this long
+    pub max_hold_ms: Option<u64>,
+    /// Release the keys exactly this long after the press, whenever the NoteOff arrives
+    pub tap_ms: Option<u64>,
+    /// Let go of and press the keys again this many times a second while the note is held
+    pub turbo_hz: Option<f32>,
+    /// Ignore presses within this long of the last one that fired, so a destructive action
+    /// can't be triggered twice by accident
+    pub cooldown_ms: Option<u64>,
+    /// Actions replacing `action` while a modifier note is held, by modifier name
+    pub with: std::collections::HashMap<String, Action>,
+    /// Extra device from `[devices.extra]` to send the note's events to
+    pub device: Option<String>,
+    /// Only trigger on notes played on this channel (1-16), instead of `note_channels`
+    pub channel: Option<u8>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(untagged)]
+enum NoteConfigRepr {
+    Options {
+        action: Action,
+        min_hold_ms: Option<u64>,
+        max_hold_ms: Option<u64>,
+        tap_ms: Option<u64>,
+        turbo_hz: Option<f32>,
+        cooldown_ms: Option<u64>,
+        #[serde(default)]
+        with: std::collections::HashMap<String, Action>,
+        device: Option<String>,
+        channel: Option<u8>,
+    },
+    Action(Action),
+}
+
+impl From<NoteConfigRepr> for NoteConfig {
+    fn from(repr: NoteConfigRepr) -> Self {
+        match repr {
+            NoteConfigRepr::Options {
+                action,
+                min_hold_ms,
+                max_hold_ms,
+                tap_ms,
+                turbo_hz,
+                cooldown_ms,
+                with,
+                device,
+                channel,
+            } => Self {
+                action,
+                min_hold_ms,
+                max_hold_ms,
+                tap_ms,
+                turbo_hz,
+                cooldown_ms,
+                with,
+                device,
+                channel,
+            },
+            NoteConfigRepr::Action(action) => Self {
+                action,
+                min_hold_ms: None,
+                max_hold_ms: None,
+                tap_ms: None,
+                turbo_hz: None,
+                cooldown_ms: None,
+                with: std::collections::HashMap::new(),
+                device: None,
+                channel: None,
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Read and parse a config file, along with the files it includes
+    pub fn load(path: &std::path::Path) -> crate::error::Result<Self> {
+        let (config_file, table) = read_table(path)?;
+
+        // Parse a single file straight from its text when possible, so errors point at the line
+        let config = match config_file {
+            Some(config_file) if !table.contains_key("include") => toml::from_str(&config_file),
+            _ => {
+                let table = merge_includes(path, table, &mut Vec::new())?;
+                toml::Value::Table(table).try_into()
+            }
+        };
+
+        Self::from_parsed(path, config)
+    }
+
+    /// A JSON Schema describing the config file
+    pub fn json_schema() -> String {
+        let schema = schemars::schema_for!(Config);
+        serde_json::to_string_pretty(&schema).expect("schemas always serialize")
+    }
+
+    /// Load a bundled preset, with the config file at `overrides` merged on top if there is one
+    pub fn load_preset(
+        name: &str,
+        overrides: Option<&std::path::Path>,
+    ) -> crate::error::Result<Self> {
+        let preset_path = std::path::PathBuf::from(format!("presets/{name}.toml"));
+        let preset =
+            crate::presets::get(name).ok_or_else(|| crate::error::Error::InvalidConfig {
+                path: preset_path.clone(),
+                message: format!("there's no bundled preset called `{name}`"),
+            })?;
+
+        let mut table: toml::Table =
+            toml::from_str(preset).expect("bundled presets are valid TOML");
+        crate::migrate::migrate(&mut table).expect("bundled presets have a valid version");
+        if let Some(path) = overrides {
+            let (_, overrides) = read_table(path)?;
+            merge_tables(
+                &mut table,
+                merge_includes(path, overrides, &mut Vec::new())?,
+            );
+        }
+
+        Self::from_parsed(
+            overrides.unwrap_or(&preset_path),
+            toml::Value::Table(table).try_into(),
+        )
+    }
+
+    fn from_parsed(
+        path: &std::path::Path,
+        config: Result<Self, toml::de::Error>,
+    ) -> crate::error::Result<Self> {
+        let mut config = config.map_err(|source| crate::error::Error::ParseConfig {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        config
+            .resolve()
+            .map_err(|message| crate::error::Error::InvalidConfig {
+                path: path.to_path_buf(),
+                message,
+            })?;
+
+        Ok(config)
+    }
+
+    /// Apply the default profile and check the resulting bindings
+    fn resolve(&mut self) -> Result<(), String> {
+        if self.version > crate::migrate::CURRENT_VERSION {
+            return Err(format!(
+                "the config is written for version {} of the config format, but this midkb only \
+                 understands up to version {}, update midkb to use it",
+                self.version,
+                crate::migrate::CURRENT_VERSION
+            ));
+        }
+
+        if let Some(banks) = &self.banks {
+            if let Some(name) = banks
+                .profiles
+                .iter()
+                .find(|name| !self.profiles.contains_key(*name))
+            {
+                return Err(format!("banks.profiles lists unknown profile `{name}`"));
+            }
+        }
+
+        self.check_devices()?;
+        self.check_channels()?;
+        self.check_numbers()?;
+
+        if self.layout == Layout::Auto {
+            self.layout = Layout::detect();
+        }
+
+        // Every profile has to resolve, not only the one switched to on startup, so a broken one
+        // is caught now rather than when it's switched to
+        let mut profiles: Vec<String> = self.profiles.keys().cloned().collect();
+        profiles.sort_unstable();
+        for name in profiles {
+            self.switch_profile(Some(&name))
+                .map_err(|e| format!("profile `{name}`: {e}"))?;
+        }
+
+        let profile = self.default_profile.clone();
+        self.switch_profile(profile.as_deref())
+    }
+
+    /// Check every binding's `device` is one of the extra devices
+    fn check_devices(&self) -> Result<(), String> {
+        let bindings = std::iter::once(("", &self.notes, &self.cc)).chain(
+            self.profiles
+                .iter()
+                .map(|(name, profile)| (name.as_str(), &profile.notes, &profile.cc)),
+        );
+
+        let mut devices = Vec::new();
+        for (profile, notes, cc) in bindings {
+            let prefix = match profile {
+                "" => String::new(),
+                profile => format!("profiles.{profile}."),
+            };
+
+            for (note, binding) in &notes.notes {
+                devices.push((format!("{prefix}notes.{note}"), &binding.device));
+            }
+            for (control, dir_config) in &cc.cc {
+                devices.push((format!("{prefix}cc.{control}"), &dir_config.device));
+            }
+        }
+        if let Some(pitch_bend) = &self.pitch_bend {
+            devices.push(("pitch_bend".to_string(), pitch_bend.device()));
+        }
+
+        for (binding, device) in devices {
+            if let Some(device) = device {
+                if !self.devices.extra.contains_key(device) {
+                    return Err(format!(
+                        "{binding} uses device `{device}`, which isn't in [devices.extra]"
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check every channel remapped and note channel filter is a channel from 1 to 16
+    fn check_channels(&self) -> Result<(), String> {
+        let valid = |channel: &u8| (1..=16).contains(channel);
+        let mut mapped = Vec::new();
+        for (from, to) in &self.channel_map.channels {
+            let channel = from.trim().parse().ok().filter(valid);
+            if channel.is_none() || !valid(to) {
+                return Err(format!(
+                    "channel_map maps channel {from} to {to}, channels go from 1 to 16"
+                ));
+            }
+            if mapped.contains(&channel) {
+                return Err(format!("channel_map maps channel {from} more than once"));
+            }
+            mapped.push(channel);
+        }
+        if let Some(channel) = self.note_channels.iter().find(|channel| !valid(channel)) {
+            return Err(format!(
+                "note_channels lists channel {channel}, channels go from 1 to 16"
+            ));
+        }
+
+        let profiles = self
+            .profiles
+            .iter()
+            .map(|(name, profile)| (format!("profiles.{name}."), &profile.notes));
+        for (prefix, notes) in std::iter::once((String::new(), &self.notes)).chain(profiles) {
+            for (note, binding) in &notes.notes {
+                if let Some(channel) = binding.channel.filter(|channel| !valid(channel)) {
+                    return Err(format!(
+                        "{prefix}notes.{note} is on channel {channel}, channels go from 1 to 16"
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check no two keys of a section name the same note or control, like `37` and `037`
+    fn check_numbers(&self) -> Result<(), String> {
+        let profiles = self
+            .profiles
+            .iter()
+            .map(|(name, profile)| (format!("profiles.{name}."), &profile.notes, &profile.cc));
+        let sections = std::iter::once((String::new(), &self.notes, &self.cc))
+            .chain(profiles)
+            .flat_map(|(prefix, notes, cc)| {
+                [
+                    (format!("{prefix}notes"), duplicate_number(&notes.notes)),
+                    (format!("{prefix}cc"), duplicate_number(&cc.cc)),
+                ]
+            })
+            .chain([
+                (
+                    "calibration".to_string(),
+                    duplicate_number(&self.calibration.controls),
+                ),
+                (
+                    "mpe.notes".to_string(),
+                    self.mpe
+                        .as_ref()
+                        .and_then(|mpe| duplicate_number(&mpe.notes.notes)),
+                ),
+            ]);
+
+        for (section, duplicate) in sections {
+            if let Some((first, second)) = duplicate {
+                return Err(format!(
+                    "{section}.{first} and {section}.{second} are the same number, \
+                     only one of them can be used"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// What a NoteOn with velocity 0 means, set by the active profile, the profiles it
+    /// inherits from, or else the config
+    pub fn zero_velocity(&self) -> ZeroVelocity {
+        let mut next = self.active_profile.as_deref();
+        while let Some(profile) = next.and_then(|name| self.profiles.get(name)) {
+            if let Some(zero_velocity) = profile.zero_velocity {
+                return zero_velocity;
+            }
+            next = profile.inherits.as_deref();
+        }
+
+        self.zero_velocity
+    }
+
+    /// The channel (1-16) a message's channel is treated as, going by `channel_map`
+    pub fn map_channel(&self, channel: u8) -> u8 {
+        let mapped = channel
+            .checked_sub(1)
+            .and_then(|index| self.channel_map.by_channel.get(index as usize));
+        match mapped {
+            Some(&mapped) if mapped != 0 => mapped,
+            _ => channel,
+        }
+    }
+
+    /// Replace the current profile with another, or go back to plain `[notes]` and `[cc]`
+    /// with `None`. The bindings are left alone if the profile doesn't resolve
+    pub fn switch_profile(&mut self, name: Option<&str>) -> Result<(), String> {
+        let (notes, cc) = self
+            .base_bindings
+            .get_or_insert_with(|| (self.notes.clone(), self.cc.clone()))
+            .clone();
+        let previous = (
+            std::mem::replace(&mut self.notes, notes),
+            std::mem::replace(&mut self.cc, cc),
+        );
+
+        let applied = match name {
+            Some(name) => self.apply_profile(name),
+            None => Ok(()),
+        }
+        .and_then(|_| self.cc.validate());
+
+        match applied {
+            Ok(()) => {
+                self.active_profile = name.map(str::to_string);
+                Ok(())
+            }
+            Err(e) => {
+                (self.notes, self.cc) = previous;
+                Err(e)
+            }
+        }
+    }
+
+    /// Apply a profile's bindings on top of the current ones, after those of every
+    /// profile it inherits from
+    pub fn apply_profile(&mut self, name: &str) -> Result<(), String> {
+        let mut chain = Vec::new();
+        let mut next = Some(name);
+
+        while let Some(name) = next {
+            if chain.contains(&name) {
+                return Err(format!("profile `{name}` inherits from itself"));
+            }
+
+            let profile = self
+                .profiles
+                .get(name)
+                .ok_or_else(|| format!("unknown profile `{name}`"))?;
+            chain.push(name);
+            next = profile.inherits.as_deref();
+        }
+
+        for name in chain.into_iter().rev() {
+            let profile = &self.profiles[name];
+            self.notes.extend(&profile.notes);
+            self.cc.extend(&profile.cc);
+        }
+
+        Ok(())
+    }
+}
+
+/// Read a config file and parse it as a TOML table, upgraded to the current format.
+/// The file's text is returned too, unless upgrading changed the table
+fn read_table(path: &std::path::Path) -> crate::error::Result<(Option<String>, toml::Table)> {
+    let text = std::fs::read_to_string(path).map_err(|source| crate::error::Error::ReadConfig {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let mut table = toml::from_str(&text).map_err(|source| crate::error::Error::ParseConfig {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let changes = crate::migrate::migrate(&mut table).map_err(|message| {
+        crate::error::Error::InvalidConfig {
+            path: path.to_path_buf(),
+            message,
+        }
+    })?;
+
+    if changes.is_empty() {
+        return Ok((Some(text), table));
+    }
+
+    tracing::warn!(
+        ?path,
+        "Upgraded the config from an older format while loading it. Update it as below and set \
+         `version = {}` to stop seeing this",
+        crate::migrate::CURRENT_VERSION
+    );
+    for change in &changes {
+        tracing::info!(?path, "{change}");
+    }
+
+    Ok((None, table))
+}
+
+/// Merge the files listed in a config's `include` underneath it, recursively.
+/// Paths are relative to the including file, and later files override earlier ones
+fn merge_includes(
+    path: &std::path::Path,
+    mut table: toml::Table,
+    seen: &mut Vec<std::path::PathBuf>,
+) -> crate::error::Result<toml::Table> {
+    let invalid = |message: &str| crate::error::Error::InvalidConfig {
+        path: path.to_path_buf(),
+        message: message.to_string(),
+    };
+
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if seen.contains(&canonical) {
+        return Err(invalid("the file ends up including itself"));
+    }
+    seen.push(canonical);
+
+    let includes = match table.remove("include") {
+        None => Vec::new(),
+        Some(toml::Value::Array(includes)) => includes,
+        Some(_) => return Err(invalid("`include` must be a list of file paths")),
+    };
 
-    pub bind_mode: CCBindMode,
+    let mut merged = toml::Table::new();
+    for include in includes {
+        let include = include
+            .as_str()
+            .ok_or_else(|| invalid("`include` must be a list of file paths"))?;
+        let include_path = path
+            .parent()
+            .unwrap_or(std::path::Path::new("."))
+            .join(include);
 
-    // both counter_clockwise can be either a keycode (see keycode crate for the codes, must be a u16)
-    // or a mouse axis (x, y)
-    #[serde(serialize_with = "serde_with::rust::display_fromstr")]
-    pub counter_clockwise: Option<String>,
-    #[serde(serialize_with = "serde_with::rust::display_fromstr")]
-    pub clockwise: Option<String>,
-}
+        let (_, included) = read_table(&include_path)?;
+        merge_tables(&mut merged, merge_includes(&include_path, included, seen)?);
+    }
 
-#[derive(serde::Deserialize, Debug, Default)]
-pub struct CCConfig {
-    // would be a toml of the form:
-    // [cc]
-    // <cc_number> = [counter_clockwise, clockwise]
-    // 1 = [60, 70]
-    #[serde(flatten)]
-    pub cc: std::collections::HashMap<String, CCDirectionConfig>,
-}
-#[derive(serde::Deserialize, Debug, Default)]
-pub struct NoteBinding {
-    #[serde(flatten)]
-    pub notes: std::collections::HashMap<String, u16>,
+    merge_tables(&mut merged, table);
+    seen.pop();
+
+    Ok(merged)
 }
 
-impl NoteBinding {
-    pub fn get_key(&self, note: u8) -> Option<u16> {
-        self.notes.get(&note.to_string()).copied()
+/// Merge one TOML table into another, merging tables they both have and replacing anything else
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => {
+                merge_tables(base, overlay)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
     }
 }
 
 impl CCConfig {
     pub fn get_dir_config(&self, cc: u8) -> Option<&CCDirectionConfig> {
-        self.cc.get(&cc.to_string())
+        self.by_number.get(cc as usize)?.as_deref()
+    }
+
+    /// A control's config, shared rather than borrowed from the bindings
+    pub fn shared_dir_config(&self, cc: u8) -> Option<std::sync::Arc<CCDirectionConfig>> {
+        self.by_number.get(cc as usize)?.clone()
+    }
+
+    /// Bind the controls another set of bindings binds, replacing any binding they have here
+    pub fn extend(&mut self, other: &CCConfig) {
+        self.cc.extend(other.cc.clone());
+        self.by_number = by_number(&self.cc);
+    }
+
+    /// Unbind a control
+    pub fn remove(&mut self, cc: u8) {
+        self.cc.retain(|key, _| key.trim().parse() != Ok(cc));
+        self.by_number = by_number(&self.cc);
+    }
+
+    /// Check every control's targets fit its bind mode: keys and actions for `Keyboard`
+    /// and `Toggle`, axes and wheels for `Mouse`
+    pub fn validate(&self) -> Result<(), String> {
+        for (cc, dir_config) in &self.cc {
+            if matches!(dir_config.bind_mode, CCBindMode::Command) && dir_config.run.is_none() {
+                return Err(format!("cc.{cc} is in Command mode, but has no `run` command"));
+            }
+
+            if matches!(dir_config.bind_mode, CCBindMode::Gamepad) && dir_config.gamepad.is_none() {
+                return Err(format!("cc.{cc} is in Gamepad mode, but has no `gamepad` axis"));
+            }
+
+            match (dir_config.curve, dir_config.curve_points.is_empty()) {
+                (Some(ResponseCurve::Custom), true) => {
+                    return Err(format!("cc.{cc} has a custom curve, but no `curve_points`"));
+                }
+                (Some(ResponseCurve::Custom), false) => {
+                    let ascending = dir_config
+                        .curve_points
+                        .windows(2)
+                        .all(|pair| pair[0].0 < pair[1].0);
+                    if !ascending {
+                        return Err(format!(
+                            "cc.{cc}.curve_points must go up in steps, one point for each"
+                        ));
+                    }
+                }
+                (_, false) => {
+                    return Err(format!(
+                        "cc.{cc} has `curve_points`, but they're only used with curve = \"custom\""
+                    ));
+                }
+                (_, true) => {}
+            }
+
+            if matches!(dir_config.bind_mode, CCBindMode::Zones) {
+                if dir_config.zones.is_empty() || dir_config.zones.len() > 128 {
+                    return Err(format!(
+                        "cc.{cc} is in Zones mode, but doesn't list from 1 to 128 `zones`"
+                    ));
+                }
+
+                let not_a_key = dir_config
+                    .zones
+                    .iter()
+                    .any(|zone| !matches!(zone, CCTarget::Key(_) | CCTarget::Action(_)));
+                if not_a_key {
+                    return Err(format!("cc.{cc}.zones must all be keys or actions"));
+                }
+            }
+
+            let targets = [
+                ("counter_clockwise", &dir_config.counter_clockwise),
+                ("clockwise", &dir_config.clockwise),
+            ];
+
+            for (field, target) in targets {
+                let valid = match (&dir_config.bind_mode, target) {
+                    (_, None)
+                    | (
+                        CCBindMode::Panic
+                        | CCBindMode::Command
+                        | CCBindMode::Zones
+                        | CCBindMode::Gamepad
+                        | CCBindMode::Volume
+                        | CCBindMode::Brightness
+                        | CCBindMode::BankUp
+                        | CCBindMode::BankDown,
+                        _,
+                    ) => true,
+                    (CCBindMode::Keyboard | CCBindMode::Toggle, Some(target)) => {
+                        matches!(target, CCTarget::Key(_) | CCTarget::Action(_))
+                    }
+                    (CCBindMode::Mouse, Some(target)) => {
+                        matches!(target, CCTarget::Axis(_) | CCTarget::Scroll(_))
+                    }
+                };
+
+                if !valid {
+                    return Err(format!(
+                        "cc.{cc}.{field} doesn't fit bind_mode {:?}: use a key or action for Keyboard and Toggle, an axis or wheel for Mouse",
+                        dir_config.bind_mode
+                    ));
+                }
+            }
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mouse_keyboard_input::{KEY_F16, KEY_F17, KEY_KP1, KEY_KP9};
 
     #[test]
     fn test_deserialize_config() {
@@ -82,4 +2606,751 @@ mod tests {
         let config: Config = toml::from_str(config).unwrap();
         println!("{:#?}", config);
     }
+
+    #[test]
+    fn test_example_config() {
+        let config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        assert_eq!(config.midi_device, "28:0");
+    }
+
+    #[test]
+    fn test_deserialize_sticky_action() {
+        let config = r#"
+            midi_device = "28:0"
+            [cc]
+            [notes]
+            60 = 12
+            61 = { type = "sticky", code = 42 }
+            62 = { type = "bypass" }
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(config.notes.get_action(60), Some(&Action::Key(12)));
+        assert_eq!(config.notes.get_action(61), Some(&Action::Sticky { code: 42 }));
+        assert_eq!(config.notes.get_action(62), Some(&Action::Bypass));
+    }
+
+    #[test]
+    fn test_deserialize_note_options() {
+        let config = r#"
+            midi_device = "28:0"
+            [cc]
+            [notes]
+            60 = 12
+            61 = { action = 13, min_hold_ms = 20, max_hold_ms = 5000 }
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(config.notes.get(60).unwrap().min_hold_ms, None);
+        assert_eq!(config.notes.get(61).unwrap().min_hold_ms, Some(20));
+        assert_eq!(config.notes.get(61).unwrap().max_hold_ms, Some(5000));
+        assert_eq!(config.notes.get_action(61), Some(&Action::Key(13)));
+    }
+
+    #[test]
+    fn test_deserialize_multi_action() {
+        let config = r#"
+            midi_device = "28:0"
+            [cc]
+            [notes]
+            60 = [12, { type = "command", run = "true" }]
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(
+            config.notes.get_action(60),
+            Some(&Action::Multi(vec![
+                Action::Key(12),
+                Action::Command {
+                    run: "true".to_string()
+                }
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_deserialize_conditions() {
+        let config = r#"
+            midi_device = "28:0"
+            [cc]
+            [notes]
+            60 = [{ when = "fx", then = 33 }, { when = "!key:LEFTSHIFT", then = 32 }]
+            61 = { type = "layer", name = "fx" }
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(
+            config.notes.get_action(60),
+            Some(&Action::Multi(vec![
+                Action::When {
+                    when: Condition {
+                        negate: false,
+                        kind: ConditionKind::Layer("fx".to_string())
+                    },
+                    then: Box::new(Action::Key(33))
+                },
+                Action::When {
+                    when: Condition {
+                        negate: true,
+                        kind: ConditionKind::Key(42)
+                    },
+                    then: Box::new(Action::Key(32))
+                },
+            ]))
+        );
+
+        assert!(Condition::try_from("key:NOPE".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_mpe() {
+        let config = r#"
+            midi_device = "28:0"
+            [cc]
+            [notes]
+            [mpe]
+            pitch_bend = "x"
+            [mpe.notes.60]
+            pressure = "-y"
+            [mpe.notes." 62"]
+            pitch_bend = "y"
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        let mpe = config.mpe.unwrap();
+        assert!(mpe.is_member(2) && !mpe.is_member(1));
+        assert_eq!(mpe.route(61).pitch_bend, Some(MouseAxis::X));
+        assert_eq!(mpe.route(60).pressure, Some(MouseAxis::NegY));
+        assert_eq!(mpe.route(62).pitch_bend, Some(MouseAxis::Y));
+    }
+
+    #[test]
+    fn test_deserialize_drag_action() {
+        let config = r#"
+            [cc]
+            [notes]
+            60 = { type = "drag", x = 21, y = 22 }
+            61 = { type = "drag", button = "right", x = 21, speed = 4 }
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(
+            config.notes.get_action(60),
+            Some(&Action::Drag {
+                button: MouseButton::Left,
+                x: Some(21),
+                y: Some(22),
+                speed: 10,
+            })
+        );
+        assert_eq!(
+            config.notes.get_action(61),
+            Some(&Action::Drag {
+                button: MouseButton::Right,
+                x: Some(21),
+                y: None,
+                speed: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn test_calibration() {
+        let config = r#"
+            [cc]
+            [notes]
+            [calibration.7]
+            min = 4
+            max = 120
+            [calibration.21]
+            min = 2
+            max = 125
+            center = 60
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(
+            config.calibration.get(21),
+            config.calibration.controls.get("21")
+        );
+        assert_eq!(config.calibration.get(1), None);
+
+        let fader = config.calibration.controls["7"];
+        assert_eq!(fader.normalize(4), 0);
+        assert_eq!(fader.normalize(0), 0);
+        assert_eq!(fader.normalize(62), 64);
+        assert_eq!(fader.normalize(120), 127);
+        assert_eq!(fader.normalize(127), 127);
+
+        // Each side of the center is stretched on its own
+        let knob = config.calibration.controls["21"];
+        assert_eq!(knob.normalize(2), 0);
+        assert_eq!(knob.normalize(60), 64);
+        assert_eq!(knob.normalize(31), 32);
+        assert_eq!(knob.normalize(125), 127);
+    }
+
+    #[test]
+    fn test_deserialize_mouse_actions() {
+        let config = r#"
+            [cc]
+            [notes]
+            60 = { type = "mouse_move", dx = 1920 }
+            65 = { type = "mouse_move", dy = -40, velocity_curve = 2.0 }
+            61 = { type = "click", count = 2 }
+            62 = { type = "click", button = "middle" }
+            63 = { type = "click", mode = "double_click" }
+            64 = { type = "click", button = "right", mode = "hold" }
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(
+            config.notes.get_action(60),
+            Some(&Action::MouseMove {
+                dx: 1920,
+                dy: 0,
+                velocity_curve: None
+            })
+        );
+        assert_eq!(
+            config.notes.get_action(65),
+            Some(&Action::MouseMove {
+                dx: 0,
+                dy: -40,
+                velocity_curve: Some(2.0)
+            })
+        );
+        assert_eq!(
+            config.notes.get_action(61),
+            Some(&Action::Click {
+                button: MouseButton::Left,
+                count: 2,
+                mode: ClickMode::Click,
+            })
+        );
+        assert_eq!(
+            config.notes.get_action(62),
+            Some(&Action::Click {
+                button: MouseButton::Middle,
+                count: 1,
+                mode: ClickMode::Click,
+            })
+        );
+        assert_eq!(
+            config.notes.get_action(63),
+            Some(&Action::Click {
+                button: MouseButton::Left,
+                count: 1,
+                mode: ClickMode::DoubleClick,
+            })
+        );
+        assert_eq!(
+            config.notes.get_action(64),
+            Some(&Action::Click {
+                button: MouseButton::Right,
+                count: 1,
+                mode: ClickMode::Hold,
+            })
+        );
+    }
+
+    #[test]
+    fn test_profile_inheritance() {
+        let config = r#"
+            default_profile = "photoshop"
+            [cc.1]
+            bind_mode = "Keyboard"
+            clockwise = "60"
+            [notes]
+            60 = 12
+            61 = 13
+            [profiles.editing.notes]
+            61 = 14
+            62 = 15
+            [profiles.photoshop]
+            inherits = "editing"
+            [profiles.photoshop.notes]
+            62 = 16
+            [profiles.photoshop.cc.1]
+            bind_mode = "Mouse"
+            clockwise = "x"
+        "#;
+
+        let mut config: Config = toml::from_str(config).unwrap();
+        config.resolve().unwrap();
+        assert_eq!(config.notes.get_action(60), Some(&Action::Key(12)));
+        assert_eq!(config.notes.get_action(61), Some(&Action::Key(14)));
+        assert_eq!(config.notes.get_action(62), Some(&Action::Key(16)));
+        assert_eq!(
+            config.cc.get_dir_config(1).unwrap().clockwise,
+            Some(CCTarget::Axis(MouseAxis::X))
+        );
+
+        let looping = r#"
+            [cc]
+            [notes]
+            [profiles.a]
+            inherits = "b"
+            [profiles.b]
+            inherits = "a"
+        "#;
+        let mut config: Config = toml::from_str(looping).unwrap();
+        assert!(config.apply_profile("a").is_err());
+        assert!(config.apply_profile("missing").is_err());
+        assert!(config.resolve().is_err());
+
+        // Profiles that aren't switched to on startup are checked too
+        let broken = r#"
+            [cc]
+            [notes]
+            [profiles.editing.cc.1]
+            bind_mode = "Command"
+        "#;
+        let mut config: Config = toml::from_str(broken).unwrap();
+        let error = config.resolve().unwrap_err();
+        assert!(error.starts_with("profile `editing`: cc.1"), "{error}");
+    }
+
+    #[test]
+    fn test_deserialize_volume() {
+        let config = r#"
+            [notes]
+            60 = { type = "volume" }
+            61 = { type = "volume", app = "spotify" }
+            [cc.7]
+            bind_mode = "Volume"
+            volume = { sink = "alsa_output.usb", max = 150 }
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(
+            config.notes.get_action(60),
+            Some(&Action::Volume {
+                target: VolumeTarget::default()
+            })
+        );
+        assert_eq!(
+            config.notes.get_action(61),
+            Some(&Action::Volume {
+                target: VolumeTarget {
+                    sink: None,
+                    app: Some("spotify".to_string()),
+                }
+            })
+        );
+
+        let volume = config.cc.get_dir_config(7).unwrap().volume.clone().unwrap();
+        assert_eq!(volume.target.sink.as_deref(), Some("alsa_output.usb"));
+        assert_eq!(volume.max, 150);
+    }
+
+    #[test]
+    fn test_lookup_by_number() {
+        let config = r#"
+            [cc.7]
+            bind_mode = "Keyboard"
+            clockwise = 30
+            [notes]
+            36 = 30
+            "037" = 31
+            200 = 32
+            kick = 33
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(config.notes.get_action(36), Some(&Action::Key(30)));
+        assert_eq!(config.notes.get_action(37), Some(&Action::Key(31)));
+        assert_eq!(config.notes.get_action(200), None);
+        assert_eq!(config.notes.get_action(38), None);
+        assert!(config.cc.get_dir_config(7).is_some());
+        assert!(config.cc.get_dir_config(8).is_none());
+
+        // Two keys for the same number can't both be looked up
+        let config = "[cc]\n[notes]\n[profiles.game.notes]\n37 = 30\n\"037\" = 31\n";
+        let mut config: Config = toml::from_str(config).unwrap();
+        let error = config.resolve().unwrap_err();
+        assert!(
+            error.contains("profiles.game.notes.037 and profiles.game.notes.37"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_deserialize_notifications() {
+        let config = r#"
+            [notifications]
+            layers = true
+            [cc]
+            [notes]
+            60 = [32, { type = "notify", text = "Mic muted" }]
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(
+            config.notifications,
+            Some(NotificationsConfig {
+                layers: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            config.notes.get_action(60),
+            Some(&Action::Multi(vec![
+                Action::Key(32),
+                Action::Notify {
+                    text: "Mic muted".to_string()
+                }
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_note_grid() {
+        let config = r#"
+            [cc]
+            [notes]
+            grid = { start_note = 36, rows = 3, cols = 3, keys = "numpad" }
+            37 = { type = "panic" }
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(config.notes.notes.len(), 9);
+        // The bottom left pad gets the bottom left key of a 3 by 3 block
+        assert_eq!(config.notes.get_action(36), Some(&Action::Key(KEY_KP1)));
+        assert_eq!(config.notes.get_action(37), Some(&Action::Panic));
+        assert_eq!(config.notes.get_action(44), Some(&Action::Key(KEY_KP9)));
+
+        let launchpad = GridConfig {
+            start_note: 11,
+            rows: 2,
+            cols: 4,
+            row_step: Some(10),
+            keys: GridKeys::F13F24,
+        };
+        let keys = launchpad.keys().unwrap();
+        assert_eq!(keys[0], (11, KEY_F17));
+        assert_eq!(keys[7], (24, KEY_F16));
+
+        let too_wide = GridConfig {
+            cols: 5,
+            ..launchpad.clone()
+        };
+        assert!(too_wide.keys().is_err());
+        let too_high = GridConfig {
+            start_note: 120,
+            ..launchpad
+        };
+        assert!(too_high.keys().is_err());
+    }
+
+    #[test]
+    fn test_extra_devices() {
+        let config = r#"
+            [devices.extra.joystick]
+            kind = "gamepad"
+            name = "midkb flight stick"
+            [notes]
+            60 = { action = { type = "key", code = "BTN_SOUTH" }, device = "joystick" }
+            [cc.1]
+            bind_mode = "Gamepad"
+            gamepad = { axis = "throttle" }
+            device = "joystick"
+        "#;
+
+        let mut config: Config = toml::from_str(config).unwrap();
+        config.resolve().unwrap();
+        assert_eq!(config.devices.extra["joystick"].kind, DeviceKind::Gamepad);
+        assert_eq!(
+            config.notes.get(60).unwrap().device.as_deref(),
+            Some("joystick")
+        );
+
+        let missing = r#"
+            [cc]
+            [notes]
+            [profiles.game.cc.1]
+            bind_mode = "Toggle"
+            clockwise = "KEY_W"
+            device = "joystick"
+        "#;
+        let mut config: Config = toml::from_str(missing).unwrap();
+        let error = config.resolve().unwrap_err();
+        assert!(error.contains("profiles.game.cc.1"), "{error}");
+    }
+
+    #[test]
+    fn test_note_channels() {
+        let mut config: Config = toml::from_str("note_channels = [0]\n[cc]\n[notes]").unwrap();
+        let error = config.resolve().unwrap_err();
+        assert!(error.contains("note_channels lists channel 0"), "{error}");
+
+        let config = r#"
+            [cc]
+            [notes]
+            [profiles.game.notes]
+            60 = { action = 30, channel = 17 }
+        "#;
+        let mut config: Config = toml::from_str(config).unwrap();
+        let error = config.resolve().unwrap_err();
+        assert!(error.contains("profiles.game.notes.60"), "{error}");
+
+        let mut config: Config = toml::from_str("channel_map = { 10 = 1 }\n[cc]\n[notes]").unwrap();
+        config.resolve().unwrap();
+        assert_eq!((config.map_channel(10), config.map_channel(2)), (1, 2));
+
+        let mut config: Config = toml::from_str("channel_map = { 1 = 0 }\n[cc]\n[notes]").unwrap();
+        assert!(config.resolve().is_err());
+
+        // Channels are numbers however they're written
+        let config = "channel_map = { \" 10\" = 1, \"03\" = 4 }\n[cc]\n[notes]";
+        let mut config: Config = toml::from_str(config).unwrap();
+        config.resolve().unwrap();
+        assert_eq!((config.map_channel(10), config.map_channel(3)), (1, 4));
+
+        let config = "channel_map = { 10 = 1, \"010\" = 2 }\n[cc]\n[notes]";
+        let mut config: Config = toml::from_str(config).unwrap();
+        let error = config.resolve().unwrap_err();
+        assert!(error.contains("more than once"), "{error}");
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let schema: serde_json::Value = serde_json::from_str(&Config::json_schema()).unwrap();
+        let definitions = &schema["$defs"];
+
+        assert!(schema["properties"]["notes"].is_object());
+        assert!(definitions["CCBindMode"].to_string().contains("Keyboard"));
+        assert!(definitions["Action"].to_string().contains("sticky"));
+        assert!(definitions["KeyCode"].to_string().contains("KEY_LEFTSHIFT"));
+        assert!(definitions["CCTarget"].to_string().contains("mouse_move"));
+    }
+
+    #[test]
+    fn test_deserialize_typed_actions() {
+        let config = r#"
+            [notes]
+            60 = { type = "key", code = "KEY_A" }
+            61 = { type = "key", code = 31 }
+            62 = 32
+            63 = { type = "key", code = "0x20" }
+            64 = "0x1E"
+            65 = "KEY_A"
+            66 = { type = "sticky", code = "KEY_LEFTSHIFT" }
+            67 = { type = "sticky", code = "0x2A" }
+            68 = ["leftctrl", "0x2E"]
+            [cc.1]
+            bind_mode = "Keyboard"
+            counter_clockwise = { type = "key", code = "volumedown" }
+            clockwise = { type = "command", run = "true" }
+            [cc.2]
+            bind_mode = "Mouse"
+            counter_clockwise = { type = "mouse_move", axis = "-x" }
+            clockwise = { type = "scroll", axis = "wheel" }
+            [cc.3]
+            bind_mode = "Toggle"
+            clockwise = 57
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(config.notes.get_action(60), Some(&Action::Key(30)));
+        assert_eq!(config.notes.get_action(61), Some(&Action::Key(31)));
+        assert_eq!(config.notes.get_action(62), Some(&Action::Key(32)));
+        assert_eq!(config.notes.get_action(63), Some(&Action::Key(32)));
+        assert_eq!(config.notes.get_action(64), Some(&Action::Key(30)));
+        assert_eq!(config.notes.get_action(65), Some(&Action::Key(30)));
+        assert_eq!(
+            config.notes.get_action(66),
+            Some(&Action::Sticky { code: 42 })
+        );
+        assert_eq!(
+            config.notes.get_action(67),
+            Some(&Action::Sticky { code: 42 })
+        );
+        assert_eq!(
+            config.notes.get_action(68),
+            Some(&Action::Multi(vec![Action::Key(29), Action::Key(46)]))
+        );
+
+        let cc1 = config.cc.get_dir_config(1).unwrap();
+        assert_eq!(cc1.counter_clockwise, Some(CCTarget::Key(114)));
+        assert!(matches!(
+            cc1.clockwise.as_ref().unwrap(),
+            CCTarget::Action(action) if matches!(**action, Action::Command { .. })
+        ));
+        let cc2 = config.cc.get_dir_config(2).unwrap();
+        assert_eq!(cc2.counter_clockwise, Some(CCTarget::Axis(MouseAxis::NegX)));
+        assert_eq!(cc2.clockwise, Some(CCTarget::Scroll(ScrollAxis::Wheel)));
+        let cc3 = config.cc.get_dir_config(3).unwrap();
+        assert_eq!(cc3.clockwise, Some(CCTarget::Key(57)));
+        assert!(config.cc.validate().is_ok());
+
+        let unknown_key = r#"
+            [cc]
+            [notes]
+            60 = { type = "key", code = "KEY_NOPE" }
+        "#;
+        assert!(toml::from_str::<Config>(unknown_key).is_err());
+
+        let action_on_axis = r#"
+            [notes]
+            [cc.1]
+            bind_mode = "Mouse"
+            clockwise = { type = "command", run = "true" }
+        "#;
+        let config: Config = toml::from_str(action_on_axis).unwrap();
+        assert!(config.cc.validate().is_err());
+    }
+
+    #[test]
+    fn test_switch_profile() {
+        let config = r#"
+            default_profile = "editing"
+            [cc]
+            [notes]
+            60 = 12
+            [profiles.editing.notes]
+            60 = 14
+            61 = 15
+        "#;
+
+        let mut config: Config = toml::from_str(config).unwrap();
+        config.resolve().unwrap();
+        assert_eq!(config.active_profile.as_deref(), Some("editing"));
+        assert_eq!(config.notes.get_action(61), Some(&Action::Key(15)));
+
+        assert!(config.switch_profile(Some("missing")).is_err());
+        assert_eq!(config.active_profile.as_deref(), Some("editing"));
+        assert_eq!(config.notes.get_action(60), Some(&Action::Key(14)));
+
+        config.switch_profile(None).unwrap();
+        assert_eq!(config.active_profile, None);
+        assert_eq!(config.notes.get_action(60), Some(&Action::Key(12)));
+        assert_eq!(config.notes.get_action(61), None);
+    }
+
+    #[test]
+    fn test_config_includes() {
+        let dir = std::env::temp_dir().join(format!("midkb-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("parts")).unwrap();
+
+        std::fs::write(
+            dir.join("config.toml"),
+            r#"
+                include = ["parts/pads.toml", "parts/encoders.toml"]
+                midi_device = "Launchkey"
+                [notes]
+                61 = 14
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("parts/pads.toml"),
+            "include = [\"shared.toml\"]\n[notes]\n60 = 12\n61 = 13\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("parts/shared.toml"), "midi_device = \"28:0\"\n").unwrap();
+        std::fs::write(
+            dir.join("parts/encoders.toml"),
+            "[cc.1]\nbind_mode = \"Mouse\"\nclockwise = \"x\"\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&dir.join("config.toml")).unwrap();
+        assert_eq!(config.midi_device, "Launchkey");
+        assert_eq!(config.notes.get_action(60), Some(&Action::Key(12)));
+        assert_eq!(config.notes.get_action(61), Some(&Action::Key(14)));
+        assert!(config.cc.get_dir_config(1).is_some());
+
+        std::fs::write(dir.join("parts/shared.toml"), "include = [\"pads.toml\"]\n").unwrap();
+        assert!(Config::load(&dir.join("config.toml")).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_deserialize_transport() {
+        let config = r#"
+            [cc]
+            [notes]
+            [transport]
+            start = 57
+            continue = { type = "command", run = "playerctl play" }
+            [transport.songs]
+            3 = 4
+            [transport.mmc]
+            record = 19
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        assert_eq!(config.transport.start, Some(Action::Key(57)));
+        assert_eq!(config.transport.stop, None);
+        assert!(matches!(config.transport.resume, Some(Action::Command { .. })));
+        assert_eq!(config.transport.get_song(3), Some(&Action::Key(4)));
+        assert_eq!(config.transport.mmc.get("record"), Some(&Action::Key(19)));
+        assert_eq!(config.transport.mmc.get("play"), None);
+    }
+
+    #[test]
+    fn test_deserialize_cc_targets() {
+        let config = r#"
+            midi_device = "28:0"
+            [cc.1]
+            bind_mode = "Keyboard"
+            counter_clockwise = "60"
+            clockwise = "KEY_VOLUMEUP"
+            [cc.2]
+            bind_mode = "Mouse"
+            counter_clockwise = "-x"
+            clockwise = "x"
+            [cc.3]
+            bind_mode = "Mouse"
+            counter_clockwise = "-wheel"
+            clockwise = "wheel"
+            hi_res = true
+            [notes]
+        "#;
+
+        let config: Config = toml::from_str(config).unwrap();
+        let cc1 = config.cc.get_dir_config(1).unwrap();
+        assert_eq!(cc1.counter_clockwise, Some(CCTarget::Key(60)));
+        assert_eq!(cc1.clockwise, Some(CCTarget::Key(115)));
+        let cc2 = config.cc.get_dir_config(2).unwrap();
+        assert_eq!(cc2.counter_clockwise, Some(CCTarget::Axis(MouseAxis::NegX)));
+        let cc3 = config.cc.get_dir_config(3).unwrap();
+        assert_eq!(cc3.clockwise, Some(CCTarget::Scroll(ScrollAxis::Wheel)));
+        assert!(cc3.hi_res);
+        assert!(config.cc.validate().is_ok());
+
+        let typo = r#"
+            [cc.1]
+            bind_mode = "Keyboard"
+            clockwise = "6O"
+            [notes]
+        "#;
+        assert!(toml::from_str::<Config>(typo).is_err());
+
+        let mismatched = r#"
+            [cc.1]
+            bind_mode = "Mouse"
+            clockwise = "60"
+            [notes]
+        "#;
+        let config: Config = toml::from_str(mismatched).unwrap();
+        assert!(config.cc.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_curve() {
+        let validate = |curve: &str| {
+            let config =
+                format!("[notes]\n[cc.1]\nbind_mode = \"Mouse\"\nclockwise = \"x\"\n{curve}");
+            toml::from_str::<Config>(&config).unwrap().cc.validate()
+        };
+
+        assert!(validate("curve = \"exponential\"").is_ok());
+        assert!(validate("curve = \"custom\"\ncurve_points = [[1, 2], [8, 80]]").is_ok());
+        assert!(validate("curve = \"custom\"").is_err());
+        assert!(validate("curve = \"custom\"\ncurve_points = [[8, 80], [1, 2]]").is_err());
+        assert!(validate("curve = \"linear\"\ncurve_points = [[1, 2]]").is_err());
+    }
 }
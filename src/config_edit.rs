@@ -0,0 +1,305 @@
+// Rebinding controls in the config file, keeping its comments and formatting.
+// Shared by `midkb edit` and the desktop frontend, which both edit the file through a
+// `DocumentMut` and update their loaded config to match without reading the file again.
+
+use std::path::Path;
+
+use toml_edit::{DocumentMut, Item, Value};
+
+use crate::config::{CCConfig, Config, NoteBinding};
+use crate::map::key_name;
+
+/// A pad or control on the controller
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    Note(u8),
+    Cc(u8),
+}
+
+impl Control {
+    /// The control a row of the mapping table binds, if it's a plain note or CC
+    pub fn from_source(source: &str) -> Option<Self> {
+        if let Some(note) = source.strip_prefix("note ") {
+            return note.parse().ok().filter(|note| *note < 128).map(Self::Note);
+        }
+        let cc = source.strip_prefix("cc ")?;
+        cc.parse().ok().filter(|cc| *cc < 128).map(Self::Cc)
+    }
+
+    /// How the mapping table lists the control
+    pub fn source(self) -> String {
+        match self {
+            Self::Note(note) => format!("note {note}"),
+            Self::Cc(cc) => format!("cc {cc}"),
+        }
+    }
+
+    /// How many keys binding it takes, one per direction for CCs
+    pub fn key_count(self) -> usize {
+        match self {
+            Self::Note(_) => 1,
+            Self::Cc(_) => 2,
+        }
+    }
+
+    /// The section of a config file or profile the control is bound in, and its key there
+    fn entry(self) -> (&'static str, String) {
+        match self {
+            Self::Note(note) => ("notes", note.to_string()),
+            Self::Cc(cc) => ("cc", cc.to_string()),
+        }
+    }
+}
+
+/// A key code as a config value, with its name in a comment where TOML allows one
+fn key_value(code: u16, comment: bool) -> Item {
+    let value = Value::from(code as i64);
+    match comment {
+        true => Item::Value(value.decorated(" ", format!("   # {}", key_name(code)))),
+        false => Item::Value(value),
+    }
+}
+
+/// A table in the document, added as an implicit table if it isn't there yet
+fn table_entry<'a>(
+    parent: &'a mut dyn toml_edit::TableLike,
+    key: &str,
+) -> Option<&'a mut dyn toml_edit::TableLike> {
+    parent
+        .entry(key)
+        .or_insert_with(|| {
+            let mut table = toml_edit::Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_like_mut()
+}
+
+/// Bind a control to keys in the config file, one key for a note, and the counter clockwise
+/// and clockwise keys for a CC. With a profile, the binding goes into the profile's section.
+/// Other options of the binding are kept
+pub fn bind(doc: &mut DocumentMut, profile: Option<&str>, control: Control, keys: &[u16]) {
+    let (section, key) = control.entry();
+
+    let root = doc.as_table_mut() as &mut dyn toml_edit::TableLike;
+    let parent = match profile {
+        Some(profile) => {
+            table_entry(root, "profiles").and_then(|profiles| table_entry(profiles, profile))
+        }
+        None => Some(root),
+    };
+    let Some(section) = parent.and_then(|parent| table_entry(parent, section)) else {
+        return;
+    };
+
+    match control {
+        Control::Note(_) => match section.get_mut(&key) {
+            // A `[notes.60]` table, or `60 = { action = ..., tap_ms = ... }`
+            Some(Item::Table(table)) => {
+                table.insert("action", key_value(keys[0], true));
+            }
+            Some(Item::Value(Value::InlineTable(table))) => {
+                table.insert("action", Value::from(keys[0] as i64));
+            }
+            _ => {
+                section.insert(&key, key_value(keys[0], true));
+            }
+        },
+        Control::Cc(_) => {
+            let entry = section.entry(&key).or_insert(toml_edit::table());
+            if !entry.is_table_like() {
+                *entry = toml_edit::table();
+            }
+            let inline = entry.is_inline_table();
+            let table = entry.as_table_like_mut().expect("just made it a table");
+            table.insert("bind_mode", toml_edit::value("Keyboard"));
+            table.insert("counter_clockwise", key_value(keys[0], !inline));
+            table.insert("clockwise", key_value(keys[1], !inline));
+        }
+    }
+}
+
+/// Remove a control's binding from the config file, or from a profile's section
+pub fn unbind(doc: &mut DocumentMut, profile: Option<&str>, control: Control) {
+    let (section, key) = control.entry();
+    let parent = match profile {
+        Some(profile) => doc
+            .get_mut("profiles")
+            .and_then(|profiles| profiles.get_mut(profile)),
+        None => Some(doc.as_item_mut()),
+    };
+
+    if let Some(section) = parent
+        .and_then(|parent| parent.get_mut(section))
+        .and_then(Item::as_table_like_mut)
+    {
+        section.remove(&key);
+    }
+}
+
+/// Update the loaded config with a control's binding in the config file, so the mapping table
+/// shows it without reading the file again. A binding removed from the file is removed too
+pub fn reload_binding(
+    config: &mut Config,
+    doc: &DocumentMut,
+    profile: Option<&str>,
+    control: Control,
+) -> Result<(), String> {
+    let (section, key) = control.entry();
+    let parent = match profile {
+        Some(profile) => doc
+            .get("profiles")
+            .and_then(|profiles| profiles.get(profile)),
+        None => Some(doc.as_item()),
+    };
+    let binding = parent
+        .and_then(|parent| parent.get(section))
+        .and_then(|section| section.get(&key));
+
+    let (notes, cc) = match profile {
+        Some(profile) => {
+            let profile = config.profiles.entry(profile.to_string()).or_default();
+            (&mut profile.notes, &mut profile.cc)
+        }
+        None => (&mut config.notes, &mut config.cc),
+    };
+
+    let Some(binding) = binding else {
+        match control {
+            Control::Note(note) => notes.remove(note),
+            Control::Cc(cc_number) => cc.remove(cc_number),
+        }
+        return Ok(());
+    };
+
+    let mut snippet = DocumentMut::new();
+    snippet.insert(&key, binding.clone());
+    let snippet = snippet.to_string();
+
+    match control {
+        Control::Note(_) => {
+            let binding: NoteBinding = toml::from_str(&snippet).map_err(|e| e.to_string())?;
+            notes.extend(&binding);
+        }
+        Control::Cc(_) => {
+            let binding: CCConfig = toml::from_str(&snippet).map_err(|e| e.to_string())?;
+            cc.extend(&binding);
+        }
+    }
+    Ok(())
+}
+
+/// Add an empty `[profiles.<name>]` section, returning whether there wasn't one already
+pub fn add_profile(doc: &mut DocumentMut, name: &str) -> bool {
+    let root = doc.as_table_mut() as &mut dyn toml_edit::TableLike;
+    let Some(profiles) = table_entry(root, "profiles") else {
+        return false;
+    };
+    if profiles.contains_key(name) {
+        return false;
+    }
+    profiles.insert(name, toml_edit::table());
+    true
+}
+
+/// Remove a profile's section with all of its bindings
+pub fn remove_profile(doc: &mut DocumentMut, name: &str) {
+    if let Some(profiles) = doc.get_mut("profiles").and_then(Item::as_table_like_mut) {
+        profiles.remove(name);
+    }
+}
+
+/// Write the edited config file. It's written next to the old one and renamed over it,
+/// so a crash halfway can't leave it truncated
+pub fn save(doc: &DocumentMut, path: &Path) -> std::io::Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".new");
+    let new_path = path.with_file_name(name);
+
+    std::fs::write(&new_path, doc.to_string())?;
+    std::fs::rename(&new_path, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&new_path);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mouse_keyboard_input::*;
+
+    #[test]
+    fn test_bind() {
+        let text = "# pads\n[notes]\n36 = 30 # A\n37 = { action = 48, tap_ms = 50 }\n\n\
+                    [cc.21]\nbind_mode = \"Mouse\"\njitter = 2\ncounter_clockwise = \"-x\"\n\
+                    clockwise = \"x\"\n";
+        let mut doc: DocumentMut = text.parse().unwrap();
+        let mut config: Config = toml::from_str(text).unwrap();
+
+        bind(&mut doc, None, Control::Note(36), &[KEY_W]);
+        bind(&mut doc, None, Control::Note(37), &[KEY_E]);
+        bind(&mut doc, None, Control::Note(38), &[KEY_R]);
+        bind(&mut doc, None, Control::Cc(21), &[KEY_LEFT, KEY_RIGHT]);
+        assert_eq!(
+            doc.to_string(),
+            "# pads\n[notes]\n36 = 17   # W\n37 = { action = 18, tap_ms = 50 }\n38 = 19   # R\n\n\
+             [cc.21]\nbind_mode = \"Keyboard\"\njitter = 2\ncounter_clockwise = 105   # LEFT\n\
+             clockwise = 106   # RIGHT\n"
+        );
+
+        for control in [Control::Note(37), Control::Note(38), Control::Cc(21)] {
+            reload_binding(&mut config, &doc, None, control).unwrap();
+        }
+        let binding = config.notes.get(37).unwrap();
+        assert_eq!(binding.action, crate::config::Action::Key(KEY_E));
+        assert_eq!(binding.tap_ms, Some(50));
+        assert!(config.notes.get(38).is_some());
+        assert_eq!(config.cc.get_dir_config(21).unwrap().jitter, 2);
+
+        // Binding into a config without the section adds it
+        let mut doc = DocumentMut::new();
+        bind(&mut doc, None, Control::Cc(1), &[KEY_A, KEY_B]);
+        assert_eq!(
+            doc.to_string(),
+            "[cc.1]\nbind_mode = \"Keyboard\"\ncounter_clockwise = 30   # A\nclockwise = 48   # B\n"
+        );
+    }
+
+    #[test]
+    fn test_profiles() {
+        let text = "[cc]\n[notes]\n36 = 30 # A\n";
+        let mut doc: DocumentMut = text.parse().unwrap();
+        let mut config: Config = toml::from_str(text).unwrap();
+
+        assert!(add_profile(&mut doc, "editing"));
+        assert!(!add_profile(&mut doc, "editing"));
+        bind(&mut doc, Some("editing"), Control::Note(36), &[KEY_Z]);
+        bind(&mut doc, Some("game"), Control::Note(37), &[KEY_X]);
+        assert_eq!(
+            doc.to_string(),
+            "[cc]\n[notes]\n36 = 30 # A\n\n[profiles.editing]\n\n[profiles.editing.notes]\n\
+             36 = 44   # Z\n\n[profiles.game.notes]\n37 = 45   # X\n"
+        );
+
+        reload_binding(&mut config, &doc, Some("editing"), Control::Note(36)).unwrap();
+        assert_eq!(
+            config.notes.get(36).unwrap().action,
+            crate::config::Action::Key(KEY_A)
+        );
+        config.switch_profile(Some("editing")).unwrap();
+        assert_eq!(
+            config.notes.get(36).unwrap().action,
+            crate::config::Action::Key(KEY_Z)
+        );
+
+        unbind(&mut doc, Some("editing"), Control::Note(36));
+        reload_binding(&mut config, &doc, Some("editing"), Control::Note(36)).unwrap();
+        assert!(config.profiles["editing"].notes.get(36).is_none());
+
+        remove_profile(&mut doc, "game");
+        assert_eq!(
+            doc.to_string(),
+            "[cc]\n[notes]\n36 = 30 # A\n\n[profiles.editing]\n"
+        );
+    }
+}
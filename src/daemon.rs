@@ -0,0 +1,33 @@
+// systemd service support.
+// With `Type=notify`, systemd waits for the service to say it's ready over the socket
+// in `$NOTIFY_SOCKET` before considering it started. This speaks that protocol directly
+// instead of pulling in libsystemd.
+
+use std::os::unix::net::UnixDatagram;
+
+use tracing::{trace, warn};
+
+/// Send a state update like `READY=1` to systemd, doing nothing when not run by systemd
+pub fn notify(state: &str) {
+    let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+
+    let result = UnixDatagram::unbound().and_then(|socket| {
+        let path = socket_path.as_encoded_bytes();
+
+        // Paths starting with @ are in the abstract namespace
+        if let Some(name) = path.strip_prefix(b"@") {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &addr)
+        } else {
+            socket.send_to(state.as_bytes(), &socket_path)
+        }
+    });
+
+    match result {
+        Ok(_) => trace!(?state, "Notified systemd"),
+        Err(e) => warn!(?e, ?state, "Failed to notify systemd"),
+    }
+}
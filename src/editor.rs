@@ -0,0 +1,433 @@
+// `midkb edit`: a full screen editor for the bindings of the config file. Pick a binding, press
+// the pad or turn the control to bind on the controller, then press the key it should type.
+// The list shows the new binding straight away, and saving writes it into the config file,
+// keeping its comments and formatting. A running midkb picks it up on SIGHUP.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Sender};
+
+use midi_msg::{ChannelVoiceMsg, MidiMsg};
+use toml_edit::DocumentMut;
+
+use crate::config::Config;
+use crate::config_edit::{self, bind, reload_binding, Control};
+use crate::error::{Error, Result};
+use crate::input::{self, InputTarget};
+use crate::layout::Layout;
+use crate::map::{key_name, MappingTable, Row};
+
+/// A key pressed in the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TermKey {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    F(u8),
+}
+
+/// The keys in what the terminal sent for one read
+fn parse_keys(bytes: &[u8]) -> Vec<TermKey> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+    let mut keys = Vec::new();
+
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '\r' | '\n' => TermKey::Enter,
+            '\t' => TermKey::Tab,
+            '\x7f' | '\x08' => TermKey::Backspace,
+            '\x1b' => match chars.peek() {
+                Some('[' | 'O') => {
+                    chars.next();
+                    // F5 and up are sent as a number ending in `~`
+                    let mut number = String::new();
+                    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                        number.push(digit);
+                    }
+                    match chars.next() {
+                        Some('A') => TermKey::Up,
+                        Some('B') => TermKey::Down,
+                        Some('C') => TermKey::Right,
+                        Some('D') => TermKey::Left,
+                        Some(c @ 'P'..='S') => TermKey::F(c as u8 - b'P' + 1),
+                        Some('~') => match number.as_str() {
+                            "15" => TermKey::F(5),
+                            "17" => TermKey::F(6),
+                            "18" => TermKey::F(7),
+                            "19" => TermKey::F(8),
+                            "20" => TermKey::F(9),
+                            "21" => TermKey::F(10),
+                            "23" => TermKey::F(11),
+                            "24" => TermKey::F(12),
+                            _ => continue,
+                        },
+                        _ => continue,
+                    }
+                }
+                _ => TermKey::Escape,
+            },
+            c => TermKey::Char(c),
+        };
+        keys.push(key);
+    }
+
+    keys
+}
+
+/// The key code a terminal key is typed with, ignoring Shift and AltGr
+fn key_code(key: TermKey, layout: Layout) -> Option<u16> {
+    use mouse_keyboard_input::*;
+
+    let code = match key {
+        TermKey::Char(c) => layout.resolve(c)?.code,
+        TermKey::Enter => KEY_ENTER,
+        TermKey::Tab => KEY_TAB,
+        TermKey::Backspace => KEY_BACKSPACE,
+        TermKey::Up => KEY_UP,
+        TermKey::Down => KEY_DOWN,
+        TermKey::Left => KEY_LEFT,
+        TermKey::Right => KEY_RIGHT,
+        TermKey::F(n) => crate::keycodes::from_name(&format!("F{n}"))?,
+        // Escape cancels instead
+        TermKey::Escape => return None,
+    };
+    Some(code)
+}
+
+/// Something to react to
+enum Event {
+    Key(TermKey),
+    Control(Control),
+}
+
+/// What the editor is waiting for
+#[derive(Debug, PartialEq)]
+enum Mode {
+    /// Moving through the bindings
+    Browse,
+    /// A control to bind, with the selected binding's own as the default
+    Control(Option<Control>),
+    /// The keys to bind a control to, with the ones pressed so far
+    Keys(Control, Vec<u16>),
+}
+
+/// The terminal in raw mode on the alternate screen, put back as it was when dropped
+struct Screen {
+    saved: String,
+}
+
+impl Screen {
+    fn enter() -> std::io::Result<Self> {
+        let saved = stty(&["-g"])?.trim().to_string();
+        stty(&["raw", "-echo"])?;
+        // The alternate screen, with the cursor hidden
+        print!("\x1b[?1049h\x1b[?25l");
+        std::io::stdout().flush()?;
+        Ok(Self { saved })
+    }
+
+    /// The number of rows and columns of the terminal
+    fn size(&self) -> (usize, usize) {
+        let size = stty(&["size"]).unwrap_or_default();
+        let mut numbers = size.split_whitespace().map(|n| n.parse().ok());
+        match (numbers.next().flatten(), numbers.next().flatten()) {
+            (Some(rows), Some(cols)) => (rows, cols),
+            _ => (24, 80),
+        }
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Run `stty` on the terminal midkb is running in
+fn stty(args: &[&str]) -> std::io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+struct Editor {
+    doc: DocumentMut,
+    config: Config,
+    rows: Vec<Row>,
+    selected: usize,
+    /// The first row on screen
+    scroll: usize,
+    mode: Mode,
+    status: String,
+    unsaved: bool,
+}
+
+impl Editor {
+    /// Handle an event, returning whether to quit
+    fn handle(&mut self, event: Event, config_path: &Path) -> bool {
+        let mode = std::mem::replace(&mut self.mode, Mode::Browse);
+        self.mode = match (mode, event) {
+            (Mode::Browse, Event::Key(key)) => return self.browse(key, config_path),
+            (Mode::Browse, Event::Control(_)) => Mode::Browse,
+
+            (Mode::Control(_), Event::Key(TermKey::Escape)) => {
+                self.status = "Cancelled".to_string();
+                Mode::Browse
+            }
+            (Mode::Control(Some(control)), Event::Key(TermKey::Enter))
+            | (Mode::Control(_), Event::Control(control)) => Mode::Keys(control, Vec::new()),
+            (mode @ Mode::Control(_), Event::Key(_)) => mode,
+
+            (Mode::Keys(..), Event::Key(TermKey::Escape)) => {
+                self.status = "Cancelled".to_string();
+                Mode::Browse
+            }
+            (Mode::Keys(control, mut keys), Event::Key(key)) => {
+                match key_code(key, self.config.layout) {
+                    Some(code) => keys.push(code),
+                    None => self.status = "That key can't be bound from here".to_string(),
+                }
+                if keys.len() < control.key_count() {
+                    Mode::Keys(control, keys)
+                } else {
+                    self.rebind(control, &keys);
+                    Mode::Browse
+                }
+            }
+            (mode @ Mode::Keys(..), Event::Control(_)) => mode,
+        };
+        false
+    }
+
+    fn browse(&mut self, key: TermKey, config_path: &Path) -> bool {
+        match key {
+            TermKey::Up | TermKey::Char('k') => self.selected = self.selected.saturating_sub(1),
+            TermKey::Down | TermKey::Char('j') => {
+                self.selected = (self.selected + 1).min(self.rows.len().saturating_sub(1));
+            }
+            TermKey::Enter => {
+                let control = self
+                    .rows
+                    .get(self.selected)
+                    .and_then(|row| Control::from_source(&row.source));
+                self.mode = Mode::Control(control);
+            }
+            TermKey::Char('s') => self.save(config_path),
+            TermKey::Char('q') if self.unsaved => {
+                self.status = "There are unsaved changes, press s to save them, or Q to quit \
+                               without saving"
+                    .to_string();
+            }
+            // Ctrl+C
+            TermKey::Char('q' | 'Q' | '\x03') => return true,
+            _ => {}
+        }
+        false
+    }
+
+    fn rebind(&mut self, control: Control, keys: &[u16]) {
+        bind(&mut self.doc, None, control, keys);
+        if let Err(message) = reload_binding(&mut self.config, &self.doc, None, control) {
+            self.status = format!("Couldn't load the new binding: {message}");
+            return;
+        }
+
+        self.rows = MappingTable::new(&self.config).rows;
+        let source = control.source();
+        if let Some(index) = self.rows.iter().position(|row| row.source == source) {
+            self.selected = index;
+        }
+        let keys: Vec<_> = keys.iter().map(|code| key_name(*code)).collect();
+        self.status = format!("Bound {source} to {}", keys.join(" / "));
+        self.unsaved = true;
+    }
+
+    fn save(&mut self, config_path: &Path) {
+        match config_edit::save(&self.doc, config_path) {
+            Ok(()) => {
+                self.status = format!("Saved to {}", config_path.display());
+                self.unsaved = false;
+            }
+            Err(e) => self.status = format!("Failed to save to {}: {e}", config_path.display()),
+        }
+    }
+
+    fn draw(&mut self, (height, width): (usize, usize), config_path: &Path) {
+        let mut lines = vec![
+            format!(
+                "midkb edit: {}{}",
+                config_path.display(),
+                if self.unsaved { " (unsaved)" } else { "" }
+            ),
+            match &self.mode {
+                Mode::Browse => "Up/Down select  Enter rebind  s save  q quit".to_string(),
+                Mode::Control(Some(control)) => format!(
+                    "Press a pad or turn a control to bind, or Enter to rebind {}. Esc cancels",
+                    control.source()
+                ),
+                Mode::Control(None) => {
+                    "Press a pad or turn a control to bind. Esc cancels".to_string()
+                }
+                Mode::Keys(control, keys) => match (control, keys.len()) {
+                    (Control::Note(_), _) => format!("Press the key for {}", control.source()),
+                    (Control::Cc(_), 0) => format!(
+                        "Press the key for turning {} counter clockwise",
+                        control.source()
+                    ),
+                    (Control::Cc(_), _) => {
+                        format!("Press the key for turning {} clockwise", control.source())
+                    }
+                },
+            },
+            String::new(),
+        ];
+
+        // Keep the selected row on screen, under the header and above the status line
+        let visible = height.saturating_sub(lines.len() + 2).max(1);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + visible {
+            self.scroll = self.selected + 1 - visible;
+        }
+
+        let source_width = self.rows.iter().map(|row| row.source.len()).max();
+        for (i, row) in self.rows.iter().enumerate().skip(self.scroll).take(visible) {
+            let marker = if i == self.selected { '>' } else { ' ' };
+            lines.push(format!(
+                "{marker} {:<width$}  {}",
+                row.source,
+                row.action,
+                width = source_width.unwrap_or(0)
+            ));
+        }
+        if self.rows.is_empty() {
+            lines.push("  No bindings yet, press Enter to add one".to_string());
+        }
+
+        let mut screen = "\x1b[H\x1b[2J".to_string();
+        for line in &lines {
+            screen.extend(line.chars().take(width));
+            screen.push_str("\r\n");
+        }
+        // The status line at the bottom
+        screen.push_str(&format!("\x1b[{height};1H"));
+        screen.extend(self.status.chars().take(width));
+
+        print!("{screen}");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Send the keys typed in the terminal as events
+fn read_terminal(events: Sender<Event>) {
+    let mut stdin = std::io::stdin();
+    let mut buf = [0; 64];
+    while let Ok(read @ 1..) = stdin.read(&mut buf) {
+        for key in parse_keys(&buf[..read]) {
+            if events.send(Event::Key(key)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+pub fn run(config: Config, config_path: &Path) -> Result<()> {
+    let text = std::fs::read_to_string(config_path).map_err(|source| Error::ReadConfig {
+        path: config_path.to_path_buf(),
+        source,
+    })?;
+    let doc: DocumentMut = text.parse().map_err(|e| Error::InvalidConfig {
+        path: config_path.to_path_buf(),
+        message: format!("{e}"),
+    })?;
+
+    let (events, received) = channel();
+    let controls = events.clone();
+    let sender = crate::worker::spawn_with(move |_, _, bytes, parser| {
+        for msg in parser.feed(bytes) {
+            let control = match msg {
+                MidiMsg::ChannelVoice {
+                    msg: ChannelVoiceMsg::NoteOn { note, velocity },
+                    ..
+                } if velocity > 0 => Control::Note(note),
+                MidiMsg::ChannelVoice {
+                    msg: ChannelVoiceMsg::ControlChange { control },
+                    ..
+                } => Control::Cc(control.control()),
+                _ => continue,
+            };
+            let _ = controls.send(Event::Control(control));
+        }
+    });
+    let connection =
+        input::connect(&InputTarget::from_config(&config), sender).ok_or(Error::NoInput)?;
+
+    let screen = Screen::enter().map_err(Error::Terminal)?;
+    std::thread::spawn(move || read_terminal(events));
+
+    let mut editor = Editor {
+        doc,
+        rows: MappingTable::new(&config).rows,
+        config,
+        selected: 0,
+        scroll: 0,
+        mode: Mode::Browse,
+        status: String::new(),
+        unsaved: false,
+    };
+    editor.draw(screen.size(), config_path);
+    for event in received {
+        if editor.handle(event, config_path) {
+            break;
+        }
+        editor.draw(screen.size(), config_path);
+    }
+
+    drop(screen);
+    connection.close();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mouse_keyboard_input::*;
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(
+            parse_keys(b"a\r\x1b[A\x1b[B\x1bOP\x1b[15~\x1b\x7f"),
+            [
+                TermKey::Char('a'),
+                TermKey::Enter,
+                TermKey::Up,
+                TermKey::Down,
+                TermKey::F(1),
+                TermKey::F(5),
+                TermKey::Escape,
+                TermKey::Backspace,
+            ]
+        );
+        assert_eq!(key_code(TermKey::Char('W'), Layout::Us), Some(KEY_W));
+        assert_eq!(key_code(TermKey::F(5), Layout::Us), Some(KEY_F5));
+        assert_eq!(key_code(TermKey::Escape, Layout::Us), None);
+    }
+}
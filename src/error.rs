@@ -0,0 +1,76 @@
+// Errors that stop midkb from starting, with hints on how to fix them
+
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to read config file {path:?}: {source}")]
+    ReadConfig {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("invalid config file {path:?}: {source}")]
+    ParseConfig {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("invalid config file {path:?}: {message}")]
+    InvalidConfig { path: PathBuf, message: String },
+
+    #[error("failed to save calibration to {path:?}: {source}")]
+    SaveCalibration {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("failed to create the virtual keyboard and mouse: {0}")]
+    VirtualDevice(std::io::Error),
+
+    #[error("failed to set up the terminal: {0}")]
+    Terminal(std::io::Error),
+
+    #[error("failed to listen for signals: {0}")]
+    Signal(std::io::Error),
+
+    #[cfg(feature = "tokio")]
+    #[error("failed to start the async runtime: {0}")]
+    Runtime(std::io::Error),
+
+    #[error("no MIDI device found, and can't wait for one to be plugged in")]
+    NoInput,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// What the user can do about the error, if there's anything obvious
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::ReadConfig { source, .. } if source.kind() == std::io::ErrorKind::NotFound => {
+                Some("Copy the example config.toml from the repository, or pass one with --config")
+            }
+            Self::VirtualDevice(e) => {
+                match e.kind() {
+                    std::io::ErrorKind::PermissionDenied => Some(
+                        "midkb needs write access to /dev/uinput to create the virtual devices.\n\
+                         Allow the input group to use it with a udev rule, then join the group and log in again:\n\n\
+                         \x20 echo 'KERNEL==\"uinput\", GROUP=\"input\", MODE=\"0660\", OPTIONS+=\"static_node=uinput\"' \\\n\
+                         \x20   | sudo tee /etc/udev/rules.d/99-uinput.rules\n\
+                         \x20 sudo udevadm control --reload-rules && sudo udevadm trigger\n\
+                         \x20 sudo usermod -aG input $USER",
+                    ),
+                    std::io::ErrorKind::NotFound => {
+                        Some("The uinput kernel module isn't loaded, load it with `sudo modprobe uinput`")
+                    }
+                    _ => None,
+                }
+            }
+            Self::NoInput => Some(
+                "Plug the device in before starting midkb, or check midi_device and device_match in the config",
+            ),
+            _ => None,
+        }
+    }
+}
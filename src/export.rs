@@ -0,0 +1,259 @@
+// `midkb export`: the bindings as a document to print and keep next to the controller, in
+// Markdown or HTML. For controllers with a bundled preset, the pads and controls can also be
+// drawn where they are on the controller, each labelled with what it does.
+
+use std::fmt::Write;
+
+use clap::ValueEnum;
+
+use crate::map::{MappingTable, Row};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExportFormat {
+    /// A web page, printing each table on its own
+    #[default]
+    Html,
+    /// Markdown, with the tables as GitHub flavored Markdown tables
+    Md,
+}
+
+/// The controllers the bindings can be laid out on, named like their presets
+pub const TEMPLATES: &[&str] = &["apc-mini", "launchpad-mini", "mpd218", "nanokontrol2"];
+
+/// A controller's controls as they're laid out on it, in rows from the top.
+/// Each is written like the mapping table lists it, e.g. `note 11` or `cc 91`
+fn template(name: &str) -> Option<Vec<Vec<String>>> {
+    let notes = |notes: std::ops::RangeInclusive<u8>| -> Vec<String> {
+        notes.map(|note| format!("note {note}")).collect()
+    };
+    let ccs = |ccs: &mut dyn Iterator<Item = u8>| -> Vec<String> {
+        ccs.map(|cc| format!("cc {cc}")).collect()
+    };
+
+    let rows = match name {
+        // Notes in the grid go up by 10 a row, from 11 at the bottom left, with the buttons on
+        // the right sending CCs that end in 9
+        "launchpad-mini" => std::iter::once(ccs(&mut (91..=98)))
+            .chain((1..=8).rev().map(|row| {
+                let mut cells = notes(row * 10 + 1..=row * 10 + 8);
+                cells.push(format!("cc {}", row * 10 + 9));
+                cells
+            }))
+            .collect(),
+        // Numbered as on the mk2
+        "apc-mini" => (0..8)
+            .rev()
+            .map(|row| {
+                let mut cells = notes(row * 8..=row * 8 + 7);
+                cells.push(format!("note {}", 112 + 7 - row));
+                cells
+            })
+            .chain([notes(100..=107), ccs(&mut (48..=56))])
+            .collect(),
+        "mpd218" => std::iter::once(ccs(&mut [3, 9, 12, 13, 14, 15].into_iter()))
+            .chain((0..4).rev().map(|row| notes(36 + row * 4..=39 + row * 4)))
+            .collect(),
+        "nanokontrol2" => vec![
+            // Track, cycle and marker buttons, then rewind, fast forward, stop, play and record
+            ccs(&mut [58, 59, 46, 60, 61, 62, 43, 44, 42, 41, 45].into_iter()),
+            ccs(&mut (16..=23)),
+            ccs(&mut (32..=39)),
+            ccs(&mut (48..=55)),
+            ccs(&mut (64..=71)),
+            ccs(&mut (0..=7)),
+        ],
+        _ => return None,
+    };
+    Some(rows)
+}
+
+/// The binding of a control on the layout, if it has one
+fn binding<'a>(table: &'a MappingTable, source: &str) -> Option<&'a Row> {
+    table.rows.iter().find(|row| row.source == source)
+}
+
+/// What a control is labelled with on the layout: the keys it presses, or else what it does
+fn label(row: &Row) -> String {
+    match row.keys.is_empty() {
+        true => row.action.clone(),
+        false => row.keys.join(" + "),
+    }
+}
+
+/// Render the bindings, laid out on a controller when `layout` names one of `TEMPLATES`
+pub fn export(
+    table: &MappingTable,
+    title: &str,
+    format: ExportFormat,
+    layout: Option<&str>,
+) -> String {
+    let layout = layout.and_then(template);
+    match format {
+        ExportFormat::Html => html(table, title, layout.as_deref()),
+        ExportFormat::Md => markdown(table, title, layout.as_deref()),
+    }
+}
+
+fn markdown(table: &MappingTable, title: &str, layout: Option<&[Vec<String>]>) -> String {
+    let cell = |text: &str| text.replace('|', "\\|");
+    let mut doc = format!("# {}\n", cell(title));
+
+    if let Some(layout) = layout {
+        let columns = layout.iter().map(Vec::len).max().unwrap_or(0);
+        doc.push_str(&format!(
+            "\n|{}\n|{}\n",
+            " |".repeat(columns),
+            "---|".repeat(columns)
+        ));
+        for controls in layout {
+            doc.push('|');
+            for source in controls {
+                match binding(table, source) {
+                    Some(row) => {
+                        let _ = write!(doc, " {}<br>**{}** |", source, cell(&label(row)));
+                    }
+                    None => {
+                        let _ = write!(doc, " {source} |");
+                    }
+                }
+            }
+            doc.push_str(&" |".repeat(columns - controls.len()));
+            doc.push('\n');
+        }
+    }
+
+    doc.push_str("\n| Note/CC | Channel | Action | Keys |\n|---|---|---|---|\n");
+    for row in &table.rows {
+        let _ = writeln!(
+            doc,
+            "| {} | {} | {} | {} |",
+            cell(&row.source),
+            cell(&row.channel),
+            cell(&row.action),
+            cell(&row.keys.join(" + "))
+        );
+    }
+    doc
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html(table: &MappingTable, title: &str, layout: Option<&[Vec<String>]>) -> String {
+    let mut doc = format!(
+        "<!doctype html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title}</title>\n<style>\n\
+         body {{ font-family: sans-serif; }}\n\
+         table {{ border-collapse: collapse; margin-bottom: 2em; page-break-inside: avoid; }}\n\
+         td, th {{ border: 1px solid #888; padding: 0.2em 0.5em; text-align: left; }}\n\
+         .layout td {{ height: 4em; text-align: center; vertical-align: middle; width: 5em; }}\n\
+         .layout small {{ color: #666; display: block; }}\n\
+         .layout .unbound {{ color: #aaa; }}\n\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n",
+        title = escape(title)
+    );
+
+    if let Some(layout) = layout {
+        doc.push_str("<table class=\"layout\">\n");
+        for controls in layout {
+            doc.push_str("<tr>");
+            for source in controls {
+                match binding(table, source) {
+                    Some(row) => {
+                        let _ = write!(
+                            doc,
+                            "<td title=\"{}\"><small>{}</small>{}</td>",
+                            escape(&row.action),
+                            escape(source),
+                            escape(&label(row))
+                        );
+                    }
+                    None => {
+                        let _ = write!(doc, "<td class=\"unbound\"><small>{source}</small></td>");
+                    }
+                }
+            }
+            doc.push_str("</tr>\n");
+        }
+        doc.push_str("</table>\n");
+    }
+
+    doc.push_str(
+        "<table>\n<tr><th>Note/CC</th><th>Channel</th><th>Action</th><th>Keys</th></tr>\n",
+    );
+    for row in &table.rows {
+        let _ = writeln!(
+            doc,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&row.source),
+            escape(&row.channel),
+            escape(&row.action),
+            escape(&row.keys.join(" + "))
+        );
+    }
+    doc.push_str("</table>\n</body>\n</html>\n");
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn table() -> MappingTable {
+        let config: Config = toml::from_str(
+            r#"
+                [notes]
+                36 = 44
+                37 = { type = "command", run = "echo a | b" }
+                [cc]
+            "#,
+        )
+        .unwrap();
+        MappingTable::new(&config)
+    }
+
+    #[test]
+    fn test_templates() {
+        for name in TEMPLATES {
+            assert!(template(name).is_some(), "{name}");
+        }
+
+        let launchpad = template("launchpad-mini").unwrap();
+        assert_eq!(launchpad[0][0], "cc 91");
+        assert_eq!(launchpad[1][0], "note 81");
+        assert_eq!(launchpad[8][..2], ["note 11", "note 12"]);
+        assert_eq!(launchpad[8][8], "cc 19");
+
+        let mpd218 = template("mpd218").unwrap();
+        assert_eq!(mpd218[4], ["note 36", "note 37", "note 38", "note 39"]);
+    }
+
+    #[test]
+    fn test_export() {
+        let table = table();
+
+        let md = export(
+            &table,
+            "midkb: config.toml",
+            ExportFormat::Md,
+            Some("mpd218"),
+        );
+        assert!(
+            md.starts_with("# midkb: config.toml\n\n| | | | | | |\n|---|---|---|---|---|---|\n")
+        );
+        assert!(md.contains(
+            "| note 36<br>**Z** | note 37<br>**run `echo a \\| b`** | note 38 | note 39 | | |\n"
+        ));
+        assert!(md.contains("| note 36 | any | press Z | Z |\n"));
+
+        let html = export(&table, "midkb: <test>", ExportFormat::Html, None);
+        assert!(html.contains("<h1>midkb: &lt;test&gt;</h1>"));
+        assert!(!html.contains("class=\"layout\""));
+        assert!(html.contains("<tr><td>note 36</td><td>any</td><td>press Z</td><td>Z</td></tr>"));
+    }
+}
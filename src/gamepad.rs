@@ -0,0 +1,113 @@
+// Gamepad axes fed straight from a CC or pitch bend, so faders and pitch wheels work as analog
+// triggers, throttles and sticks instead of being cut up into key presses.
+
+use crate::config::{GamepadAxisConfig, RestPosition};
+
+/// The range of every axis of the virtual gamepad
+pub const AXIS_MIN: i32 = -32767;
+pub const AXIS_MAX: i32 = 32767;
+
+/// Highest value of a CC
+pub const CC_MAX: u16 = 127;
+
+/// Highest value of a pitch bend, whose center is 8192
+pub const PITCH_BEND_MAX: u16 = 16383;
+
+/// Where an axis goes for a control's value, from 0 to `max`
+pub fn position(config: &GamepadAxisConfig, value: u16, max: u16, rest: RestPosition) -> i32 {
+    let [low, high] = config.range.unwrap_or([0, max]);
+    let (low, high) = (low.min(high) as f32, low.max(high) as f32);
+    let span = (high - low).max(1.0);
+
+    let dead_zone = config.dead_zone as f32 / span;
+
+    let travel = match config.rest.unwrap_or(rest) {
+        RestPosition::Bottom => {
+            let travel = ((value as f32 - low) / span).clamp(0.0, 1.0);
+            let travel = if config.invert { 1.0 - travel } else { travel };
+            shape(travel, dead_zone, config.curve)
+        }
+        RestPosition::Center => {
+            // From -1 to 1 around the center, which is rounded up like pitch bend's 8192 and a
+            // centered CC's 64, so a control at rest puts the axis right in the middle.
+            // Each side moves the axis all the way, though the top side is a step shorter
+            let center = ((low + high) / 2.0).ceil();
+            let offset = value as f32 - center;
+            let side = if offset > 0.0 {
+                high - center
+            } else {
+                center - low
+            };
+            let offset = (offset / side.max(1.0)).clamp(-1.0, 1.0);
+            let offset = if config.invert { -offset } else { offset };
+            let shaped = shape(offset.abs(), dead_zone * 2.0, config.curve);
+            (offset.signum() * shaped + 1.0) / 2.0
+        }
+    };
+
+    AXIS_MIN + (travel * (AXIS_MAX - AXIS_MIN) as f32).round() as i32
+}
+
+/// Apply the dead zone and curve to a distance from the resting position, from 0 to 1
+fn shape(distance: f32, dead_zone: f32, curve: f32) -> f32 {
+    if distance <= dead_zone {
+        return 0.0;
+    }
+
+    ((distance - dead_zone) / (1.0 - dead_zone).max(f32::EPSILON))
+        .clamp(0.0, 1.0)
+        .powf(curve)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GamepadAxis;
+
+    fn axis() -> GamepadAxisConfig {
+        GamepadAxisConfig {
+            axis: GamepadAxis::Gas,
+            range: None,
+            dead_zone: 0,
+            curve: 1.0,
+            invert: false,
+            rest: None,
+            device: None,
+        }
+    }
+
+    #[test]
+    fn test_position() {
+        let fader = axis();
+        let bottom = RestPosition::Bottom;
+        assert_eq!(position(&fader, 0, CC_MAX, bottom), AXIS_MIN);
+        assert_eq!(position(&fader, 127, CC_MAX, bottom), AXIS_MAX);
+
+        let trimmed = GamepadAxisConfig {
+            range: Some([10, 110]),
+            dead_zone: 5,
+            invert: true,
+            ..axis()
+        };
+        assert_eq!(position(&trimmed, 120, CC_MAX, bottom), AXIS_MIN);
+        assert_eq!(position(&trimmed, 107, CC_MAX, bottom), AXIS_MIN);
+        assert_eq!(position(&trimmed, 0, CC_MAX, bottom), AXIS_MAX);
+
+        let curved = GamepadAxisConfig {
+            curve: 2.0,
+            ..axis()
+        };
+        assert!(position(&curved, 64, CC_MAX, bottom) < position(&fader, 64, CC_MAX, bottom));
+
+        // Pitch bend rests in the middle of the axis
+        let wheel = GamepadAxisConfig {
+            dead_zone: 100,
+            ..axis()
+        };
+        let center = RestPosition::Center;
+        assert_eq!(position(&wheel, 8192, PITCH_BEND_MAX, center), 0);
+        assert_eq!(position(&wheel, 8250, PITCH_BEND_MAX, center), 0);
+        assert_eq!(position(&wheel, 0, PITCH_BEND_MAX, center), AXIS_MIN);
+        assert_eq!(position(&wheel, 16383, PITCH_BEND_MAX, center), AXIS_MAX);
+    }
+}
@@ -0,0 +1,157 @@
+// Append-only history of triggered actions, one JSON object per line,
+// so what midkb did during a session can be audited afterwards.
+// The file is rotated once it grows past the configured size.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::warn;
+
+use crate::config::HistoryConfig;
+
+/// A triggered action, as written to the history file
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct HistoryEntry {
+    /// Milliseconds since the unix epoch
+    pub timestamp_ms: u64,
+    /// The kind of MIDI event, e.g. `note_on` or `cc`
+    pub event: &'static str,
+    pub channel: u8,
+    /// Note or controller number
+    pub number: u8,
+    /// Velocity or controller value
+    pub value: u8,
+    /// The binding the event resolved to
+    pub action: String,
+}
+
+impl HistoryEntry {
+    /// An action triggered just now
+    pub fn new(event: &'static str, channel: u8, number: u8, value: u8, action: String) -> Self {
+        Self {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_millis() as u64),
+            event,
+            channel,
+            number,
+            value,
+            action,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct HistoryLog {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    keep: usize,
+}
+
+fn open_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl HistoryLog {
+    pub fn open(config: &HistoryConfig) -> std::io::Result<Self> {
+        let file = open_append(&config.path)?;
+
+        Ok(Self {
+            path: config.path.clone(),
+            written: file.metadata()?.len(),
+            file,
+            max_bytes: config.max_size_kb * 1024,
+            keep: config.keep,
+        })
+    }
+
+    /// Append an action to the history, logging instead of failing if it can't be written
+    pub fn record(&mut self, entry: &HistoryEntry) {
+        let mut line = serde_json::to_string(entry).expect("history entries always serialize");
+        line.push('\n');
+
+        if let Err(e) = self.write_line(&line) {
+            warn!(?e, path = ?self.path, "Failed to write action history");
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.max_bytes > 0 && self.written + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shift `history.jsonl` to `history.jsonl.1`, `.1` to `.2` and so on,
+    /// dropping the oldest, then start a fresh file
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let rotated = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{n}"));
+            PathBuf::from(name)
+        };
+
+        if self.keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                let from = rotated(n);
+                if from.exists() {
+                    std::fs::rename(from, rotated(n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated(1))?;
+        }
+
+        self.file = open_append(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_rotation() {
+        let dir = std::env::temp_dir().join(format!("midkb-history-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.jsonl");
+
+        let mut log = HistoryLog::open(&HistoryConfig {
+            path: path.clone(),
+            max_size_kb: 1,
+            keep: 2,
+        })
+        .unwrap();
+
+        for _ in 0..50 {
+            log.record(&HistoryEntry::new(
+                "note_on",
+                1,
+                60,
+                100,
+                "Key(30)".to_string(),
+            ));
+        }
+
+        let current = std::fs::read_to_string(&path).unwrap();
+        assert!(current.len() <= 1024);
+        assert!(current
+            .lines()
+            .all(|line| line.contains(r#""event":"note_on""#)));
+        assert!(dir.join("history.jsonl.1").exists());
+        assert!(dir.join("history.jsonl.2").exists());
+        assert!(!dir.join("history.jsonl.3").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,63 @@
+// MIDI device hotplug detection.
+// The ALSA sequencer announces every port that appears or goes away on its system
+// announce port, so subscribing to it tells us the instant a controller is plugged
+// in or out without polling the port list.
+
+use alsa::seq::{Addr, EventType, PortCap, PortSubscribe, PortType, Seq};
+use alsa::Direction;
+use tracing::{trace, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HotplugEvent {
+    PortAdded,
+    PortRemoved,
+}
+
+/// Start watching for ports being added and removed, passing each change to `on_event`
+/// from a background thread
+pub fn watch<F>(mut on_event: F) -> alsa::Result<()>
+where
+    F: FnMut(HotplugEvent) + Send + 'static,
+{
+    let seq = Seq::open(None, Some(Direction::Capture), false)?;
+    seq.set_client_name(c"midkb-hotplug")?;
+
+    let port = seq.create_simple_port(
+        c"announce",
+        PortCap::WRITE | PortCap::NO_EXPORT,
+        PortType::APPLICATION,
+    )?;
+
+    let subscription = PortSubscribe::empty()?;
+    subscription.set_sender(Addr::system_announce());
+    subscription.set_dest(Addr {
+        client: seq.client_id()?,
+        port,
+    });
+    seq.subscribe_port(&subscription)?;
+
+    std::thread::spawn(move || {
+        let mut input = seq.input();
+
+        loop {
+            let event = match input.event_input() {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!(%e, "Stopped watching for MIDI devices");
+                    return;
+                }
+            };
+
+            let hotplug = match event.get_type() {
+                EventType::PortStart => HotplugEvent::PortAdded,
+                EventType::PortExit => HotplugEvent::PortRemoved,
+                _ => continue,
+            };
+
+            trace!(?hotplug, "MIDI port announcement");
+            on_event(hotplug);
+        }
+    });
+
+    Ok(())
+}
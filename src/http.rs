@@ -0,0 +1,252 @@
+// HTTP control API.
+// A tiny HTTP/1.1 server so a Stream Deck, a script or a phone's browser can see what
+// midkb is doing and steer it: every endpoint answers with the current status as JSON.
+//
+//   GET    /status          the status, changing nothing
+//   POST   /pause           stop turning MIDI into input, like the bypass toggle
+//   POST   /resume          start again
+//   POST   /profile/<name>  switch to a profile, its name percent-encoded
+//   DELETE /profile         go back to plain `[notes]` and `[cc]`
+//
+// Browsers let any page send a request to a local server, so requests naming another host or
+// coming from another site's page are turned away, and a token can be required on top.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use crate::config::HttpConfig;
+use crate::request::{self, ReadError, Request};
+use crate::MidiInputHandler;
+
+/// Serve the API until midkb exits
+pub async fn listen(config: HttpConfig, handler: Arc<Mutex<MidiInputHandler>>) {
+    let listener = match TcpListener::bind(config.listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!(%e, listen = ?config.listen, "Failed to listen for HTTP API requests");
+            return;
+        }
+    };
+
+    info!(listen = ?config.listen, "Serving the HTTP API");
+    handler
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .keep_recent_events();
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, from)) => {
+                tokio::spawn(serve(stream, from, config.clone(), handler.clone()));
+            }
+            Err(e) => warn!(%e, "Failed to accept HTTP API client"),
+        }
+    }
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    from: SocketAddr,
+    config: HttpConfig,
+    handler: Arc<Mutex<MidiInputHandler>>,
+) {
+    // No endpoint takes a body
+    let (status, body) = match request::read(&mut stream, 0).await {
+        Ok(request) => {
+            debug!(
+                method = request.method,
+                path = request.path,
+                ?from,
+                "HTTP API request"
+            );
+            match check(&config, &request) {
+                Ok(()) => {
+                    let mut handler = handler.lock().unwrap_or_else(PoisonError::into_inner);
+                    respond(&mut handler, &request.method, &request.path)
+                }
+                Err((status, message)) => {
+                    debug!(?from, message, "Turning away HTTP API request");
+                    (status, error_body(message))
+                }
+            }
+        }
+        Err(ReadError::TooLarge) => ("413 Payload Too Large", error_body("request too large")),
+        Err(e) => {
+            debug!(%e, ?from, "Failed to read HTTP request");
+            return;
+        }
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    if let Err(e) = stream.write_all(response.as_bytes()).await {
+        debug!(%e, ?from, "Failed to send HTTP response");
+    }
+}
+
+/// Whether a request may use the API, or the status and message to turn it away with
+fn check(config: &HttpConfig, request: &Request) -> Result<(), (&'static str, &'static str)> {
+    // Pages from other sites could reach the API through DNS rebinding, or post to it directly
+    let host = request.header("host").unwrap_or_default();
+    if !names_listen_address(host, config.listen) {
+        return Err((
+            "403 Forbidden",
+            "the HTTP API only answers to its own address",
+        ));
+    }
+    if let Some(origin) = request.header("origin") {
+        let same_site = origin
+            .strip_prefix("http://")
+            .is_some_and(|origin| names_listen_address(origin, config.listen));
+        if !same_site {
+            return Err((
+                "403 Forbidden",
+                "the HTTP API doesn't answer other sites' pages",
+            ));
+        }
+    }
+
+    if let Some(token) = &config.token {
+        let given = request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        if !same_secret(given.trim(), token) {
+            return Err(("401 Unauthorized", "a valid token is needed"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a Host header or the host of an Origin names the address the API is served on.
+/// A DNS name could point anywhere, so `localhost` is the only name taken
+fn names_listen_address(host: &str, listen: SocketAddr) -> bool {
+    // Split off the port, keeping IPv6 addresses in their brackets whole
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => (name, port.parse().ok()),
+        _ => (host, Some(80)),
+    };
+    if port != Some(listen.port()) {
+        return false;
+    }
+
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    let ip = listen.ip();
+    if name.eq_ignore_ascii_case("localhost") {
+        return ip.is_loopback() || ip.is_unspecified();
+    }
+
+    name.parse::<IpAddr>().is_ok_and(|named| {
+        named == ip || ip.is_unspecified() || (named.is_loopback() && ip.is_loopback())
+    })
+}
+
+/// Compare a token without giving away through timing how much of it was right
+fn same_secret(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Carry out a request, returning the HTTP status line and JSON body to answer with
+fn respond(handler: &mut MidiInputHandler, method: &str, path: &str) -> (&'static str, String) {
+    let result = match (method, path.trim_end_matches('/')) {
+        ("GET", "/status") => Ok(()),
+        ("POST", "/pause") => {
+            handler.set_bypass(true);
+            Ok(())
+        }
+        ("POST", "/resume") => {
+            handler.set_bypass(false);
+            Ok(())
+        }
+        ("DELETE", "/profile") => handler.switch_profile(None),
+        ("POST", path) if path.starts_with("/profile/") => {
+            match request::percent_decode(&path["/profile/".len()..]) {
+                Some(name) => handler.switch_profile(Some(&name)),
+                None => Err("the profile name isn't valid percent-encoded UTF-8".to_string()),
+            }
+        }
+        _ => return ("404 Not Found", error_body("no such endpoint")),
+    };
+
+    match result {
+        Ok(()) => (
+            "200 OK",
+            serde_json::to_string(&handler.status()).expect("statuses always serialize"),
+        ),
+        Err(e) => ("400 Bad Request", error_body(&e)),
+    }
+}
+
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_listen_address() {
+        let local = "127.0.0.1:8766".parse().unwrap();
+        assert!(names_listen_address("127.0.0.1:8766", local));
+        assert!(names_listen_address("localhost:8766", local));
+        assert!(!names_listen_address("localhost:8767", local));
+        assert!(!names_listen_address("evil.example:8766", local));
+        assert!(!names_listen_address("192.168.1.2:8766", local));
+
+        // Served to the network, clients reach it by one of the machine's addresses
+        let everywhere = "0.0.0.0:8766".parse().unwrap();
+        assert!(names_listen_address("192.168.1.2:8766", everywhere));
+        assert!(!names_listen_address("midkb.local:8766", everywhere));
+
+        let v6 = "[::1]:80".parse().unwrap();
+        assert!(names_listen_address("[::1]", v6));
+        assert!(names_listen_address("localhost", v6));
+    }
+
+    #[test]
+    fn test_switch_profile() {
+        let config = r#"
+            [cc]
+            [notes]
+            60 = 30
+            [profiles."my game".notes]
+            60 = 31
+        "#;
+        let config = toml::from_str(config).unwrap();
+        let output = crate::output::RecordingOutput::default();
+        let mut handler = MidiInputHandler::new(Box::new(output), config);
+
+        // Profile names are percent-encoded in the path
+        let (status, _) = respond(&mut handler, "POST", "/profile/my%20game");
+        assert_eq!(status, "200 OK");
+        assert_eq!(handler.config().active_profile.as_deref(), Some("my game"));
+
+        let (status, _) = respond(&mut handler, "POST", "/profile/my%2game");
+        assert_eq!(status, "400 Bad Request");
+        let (status, _) = respond(&mut handler, "POST", "/profile/%+1");
+        assert_eq!(status, "400 Bad Request");
+        let (status, _) = respond(&mut handler, "POST", "/profile/%FF");
+        assert_eq!(status, "400 Bad Request");
+    }
+
+    #[test]
+    fn test_same_secret() {
+        assert!(same_secret("hunter2", "hunter2"));
+        assert!(!same_secret("hunter3", "hunter2"));
+        assert!(!same_secret("hunter", "hunter2"));
+    }
+}
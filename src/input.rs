@@ -0,0 +1,294 @@
+// Where MIDI comes from. Every input is a `MidiSource`: the configured device, through ALSA,
+// JACK or a replayed file as `backend` picks, and the inputs coming in alongside it, the
+// virtual port and the network and Bluetooth listeners. Each one queues its raw bytes for the
+// worker until its connection is closed, so adding an input doesn't touch the main loop.
+
+use std::sync::{Arc, Mutex};
+
+use midir::os::unix::VirtualInput;
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiInputPort};
+use tracing::{error, info};
+
+use crate::config::{Backend, Config, DeviceMatch};
+use crate::worker::MidiSender;
+use crate::MidiInputHandler;
+
+/// Somewhere MIDI comes from
+pub trait MidiSource {
+    /// Start queueing the source's messages for the worker.
+    /// Returns `None` if it can't be reached right now
+    fn connect(&self, sender: MidiSender) -> Option<Box<dyn Connection>>;
+
+    /// Whether the source is there to connect to, checked when a MIDI device is unplugged
+    fn is_present(&self) -> bool {
+        true
+    }
+}
+
+/// An open connection to a source, which stops feeding the worker once closed
+pub trait Connection {
+    fn close(self: Box<Self>);
+}
+
+impl Connection for MidiInputConnection<()> {
+    fn close(self: Box<Self>) {
+        MidiInputConnection::close(*self);
+    }
+}
+
+/// Network and Bluetooth inputs run as tasks on the runtime until they're aborted
+#[cfg(feature = "tokio")]
+impl Connection for tokio::task::JoinHandle<()> {
+    fn close(self: Box<Self>) {
+        self.abort();
+    }
+}
+
+/// Which MIDI port to connect to, and how to find it
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputTarget {
+    pub backend: Backend,
+    pub device: String,
+    pub device_match: DeviceMatch,
+}
+
+impl InputTarget {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            backend: config.backend,
+            device: config.midi_device.clone(),
+            device_match: config.device_match,
+        }
+    }
+
+    /// The index of the first port name matching the configured device
+    pub fn find<S: AsRef<str>>(&self, names: &[S]) -> Option<usize> {
+        let names = names.iter().map(AsRef::as_ref);
+
+        if self.device.is_empty() {
+            return None;
+        }
+
+        match self.device_match {
+            DeviceMatch::Substring => names
+                .into_iter()
+                .position(|name| name.contains(&self.device)),
+            DeviceMatch::Exact => names.into_iter().position(|name| name == self.device),
+            DeviceMatch::Regex => match regex::Regex::new(&self.device) {
+                Ok(regex) => names.into_iter().position(|name| regex.is_match(name)),
+                Err(e) => {
+                    error!(%e, "Invalid midi_device regex");
+                    None
+                }
+            },
+            DeviceMatch::Index => match self.device.trim().parse::<usize>() {
+                Ok(index) => (index < names.len()).then_some(index),
+                Err(_) => {
+                    error!(device = ?self.device, "midi_device must be a port number with device_match = \"index\"");
+                    None
+                }
+            },
+        }
+    }
+}
+
+fn new_input() -> Option<MidiInput> {
+    MidiInput::new("midir reading input")
+        .inspect_err(|e| error!(%e, "Failed to open the MIDI sequencer"))
+        .ok()
+}
+
+/// Names of every MIDI input port
+pub fn port_names() -> Vec<String> {
+    new_input().map_or_else(Vec::new, |input| {
+        input
+            .ports()
+            .iter()
+            .map(|p| input.port_name(p).unwrap_or_default())
+            .collect()
+    })
+}
+
+/// The first input port matching the target
+fn find_port(input: &MidiInput, target: &InputTarget) -> Option<MidiInputPort> {
+    let ports = input.ports();
+    let names: Vec<String> = ports
+        .iter()
+        .map(|p| input.port_name(p).unwrap_or_default())
+        .collect();
+
+    target.find(&names).map(|index| ports[index].clone())
+}
+
+/// The source the target's backend reads its device from
+pub fn source(target: &InputTarget) -> Box<dyn MidiSource> {
+    match target.backend {
+        Backend::Alsa => Box::new(AlsaPort(target.clone())),
+        #[cfg(feature = "jack")]
+        Backend::Jack => Box::new(crate::jack_input::JackPort(target.clone())),
+        #[cfg(not(feature = "jack"))]
+        Backend::Jack => Box::new(NoJack),
+        Backend::Replay => Box::new(crate::replay::Replay::new(&target.device)),
+    }
+}
+
+/// Connect to the device matching the target, queueing its messages for the worker.
+/// Returns `None` if no such device is plugged in
+pub fn connect(target: &InputTarget, sender: MidiSender) -> Option<Box<dyn Connection>> {
+    source(target).connect(sender)
+}
+
+/// The sources the config has come in alongside the device
+pub fn extra_sources(
+    config: &Config,
+    handler: &Arc<Mutex<MidiInputHandler>>,
+) -> Vec<Box<dyn MidiSource>> {
+    let mut sources: Vec<Box<dyn MidiSource>> = Vec::new();
+
+    if let Some(name) = &config.virtual_input {
+        sources.push(Box::new(VirtualPort(name.clone())));
+    }
+
+    #[cfg(feature = "rtp-midi")]
+    if let Some(rtp_midi) = &config.rtp_midi {
+        sources.push(Box::new(rtp_midi.clone()));
+    }
+    #[cfg(not(feature = "rtp-midi"))]
+    if config.rtp_midi.is_some() {
+        error!("midkb was built without RTP-MIDI support, rebuild it with `--features rtp-midi`");
+    }
+
+    #[cfg(feature = "websocket")]
+    if let Some(websocket) = &config.websocket {
+        sources.push(Box::new(websocket.clone()));
+    }
+    #[cfg(not(feature = "websocket"))]
+    if config.websocket.is_some() {
+        error!(
+            "midkb was built without WebSocket MIDI support, rebuild it with `--features websocket`"
+        );
+    }
+
+    #[cfg(feature = "ble")]
+    if let Some(ble) = &config.ble {
+        sources.push(Box::new(crate::ble::BleSource {
+            config: ble.clone(),
+            handler: handler.clone(),
+        }));
+    }
+    #[cfg(not(feature = "ble"))]
+    if config.ble.is_some() {
+        error!("midkb was built without Bluetooth MIDI support, rebuild it with `--features ble`");
+    }
+    // Only Bluetooth needs the handler, to let go of everything when the device disconnects
+    #[cfg(not(feature = "ble"))]
+    let _ = handler;
+
+    sources
+}
+
+/// An ALSA sequencer port, found by name
+pub struct AlsaPort(pub InputTarget);
+
+impl MidiSource for AlsaPort {
+    fn connect(&self, sender: MidiSender) -> Option<Box<dyn Connection>> {
+        connect_alsa(&self.0, sender).map(|connection| Box::new(connection) as _)
+    }
+
+    fn is_present(&self) -> bool {
+        new_input().is_some_and(|input| find_port(&input, &self.0).is_some())
+    }
+}
+
+/// A virtual input port other MIDI clients can send messages to, with the given name
+pub struct VirtualPort(pub String);
+
+impl MidiSource for VirtualPort {
+    fn connect(&self, sender: MidiSender) -> Option<Box<dyn Connection>> {
+        let name = &self.0;
+        let mut mid_input = new_input()?;
+        mid_input.ignore(Ignore::Time);
+
+        info!(?name, "Creating virtual MIDI input port");
+
+        mid_input
+            .create_virtual(name, move |time, midimsg, _| sender.send(time, midimsg), ())
+            .inspect_err(|e| error!(%e, ?name, "Failed to create virtual MIDI input port"))
+            .ok()
+            .map(|connection| Box::new(connection) as _)
+    }
+}
+
+/// Stands in for JACK ports when midkb is built without them
+#[cfg(not(feature = "jack"))]
+struct NoJack;
+
+#[cfg(not(feature = "jack"))]
+impl MidiSource for NoJack {
+    fn connect(&self, _sender: MidiSender) -> Option<Box<dyn Connection>> {
+        error!("midkb was built without JACK support, rebuild it with `--features jack`");
+        None
+    }
+}
+
+fn connect_alsa(target: &InputTarget, sender: MidiSender) -> Option<MidiInputConnection<()>> {
+    let mut mid_input = new_input()?;
+
+    // SysEx carries MMC transport commands
+    mid_input.ignore(Ignore::Time);
+
+    info!("Available input ports:");
+    for (i, p) in mid_input.ports().iter().enumerate() {
+        info!("{}: {}", i, mid_input.port_name(p).unwrap_or_default());
+    }
+
+    let Some(in_port) = find_port(&mid_input, target) else {
+        error!(device = ?target.device, "No input port found");
+        return None;
+    };
+
+    info!("Opening connection");
+
+    mid_input
+        .connect(
+            &in_port,
+            "midkb-bind",
+            move |time, midimsg, _| sender.send(time, midimsg),
+            (),
+        )
+        .inspect_err(|e| error!(%e, "Failed to connect to the MIDI device"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_match() {
+        let names = [
+            "Midi Through:Midi Through Port-0 14:0",
+            "Launchkey Mini MK3:Launchkey Mini MK3 MIDI 1 28:0",
+            "Launchkey Mini MK3:Launchkey Mini MK3 MIDI 2 28:1",
+        ];
+
+        let target = |device: &str, device_match| InputTarget {
+            backend: Backend::Alsa,
+            device: device.to_string(),
+            device_match,
+        };
+
+        assert_eq!(
+            target("Launchkey", DeviceMatch::Substring).find(&names),
+            Some(1)
+        );
+        assert_eq!(target("Launchkey", DeviceMatch::Exact).find(&names), None);
+        assert_eq!(target(names[2], DeviceMatch::Exact).find(&names), Some(2));
+        assert_eq!(
+            target(r"MIDI 2 \d+:1$", DeviceMatch::Regex).find(&names),
+            Some(2)
+        );
+        assert_eq!(target("0", DeviceMatch::Index).find(&names), Some(0));
+        assert_eq!(target("3", DeviceMatch::Index).find(&names), None);
+    }
+}
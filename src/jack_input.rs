@@ -0,0 +1,83 @@
+// JACK MIDI input backend.
+// In pro-audio setups the controller is often owned by JACK and invisible to raw
+// ALSA clients, so this registers a JACK MIDI input port and connects the
+// configured device's port to it instead.
+
+use jack::contrib::ClosureProcessHandler;
+use jack::{AsyncClient, Client, ClientOptions, Control, MidiIn, PortFlags, ProcessScope};
+use tracing::{error, info};
+
+use crate::input::{Connection, InputTarget, MidiSource};
+use crate::worker::MidiSender;
+
+type ProcessCallback = Box<dyn FnMut(&Client, &ProcessScope) -> Control + Send>;
+
+type JackConnection = AsyncClient<(), ClosureProcessHandler<(), ProcessCallback>>;
+
+/// A JACK MIDI output port, found by name.
+/// JACK keeps its own connections, so the port is always assumed to be there
+pub struct JackPort(pub InputTarget);
+
+impl MidiSource for JackPort {
+    fn connect(&self, sender: MidiSender) -> Option<Box<dyn Connection>> {
+        connect(&self.0, sender).map(|client| Box::new(client) as _)
+    }
+}
+
+impl Connection for JackConnection {
+    fn close(self: Box<Self>) {
+        if let Err(e) = self.deactivate() {
+            error!(%e, "Failed to close JACK client");
+        }
+    }
+}
+
+/// Connect the first JACK MIDI output port matching the target to midkb
+fn connect(target: &InputTarget, sender: MidiSender) -> Option<JackConnection> {
+    let (client, _status) = Client::new("midkb", ClientOptions::NO_START_SERVER)
+        .inspect_err(|e| error!(%e, "Failed to connect to the JACK server"))
+        .ok()?;
+
+    let sources = client.ports(None, Some("midi"), PortFlags::IS_OUTPUT);
+
+    info!("Available JACK MIDI ports:");
+    for (i, port) in sources.iter().enumerate() {
+        info!("{}: {}", i, port);
+    }
+
+    let Some(source) = target.find(&sources).map(|index| sources[index].clone()) else {
+        error!(device = ?target.device, "No JACK MIDI port found");
+        return None;
+    };
+
+    let port = client
+        .register_port("midi_in", MidiIn::default())
+        .inspect_err(|e| error!(%e, "Failed to register JACK MIDI port"))
+        .ok()?;
+    let port_name = port.name().ok()?;
+
+    let process: ProcessCallback = Box::new(move |_, ps| {
+        for event in port.iter(ps) {
+            sender.send(event.time as u64, event.bytes);
+        }
+
+        Control::Continue
+    });
+
+    let active = client
+        .activate_async((), ClosureProcessHandler::new(process))
+        .inspect_err(|e| error!(%e, "Failed to activate JACK client"))
+        .ok()?;
+
+    info!(?source, "Opening JACK connection");
+
+    if let Err(e) = active
+        .as_client()
+        .connect_ports_by_name(&source, &port_name)
+    {
+        error!(%e, ?source, "Failed to connect JACK MIDI port");
+        return None;
+    }
+
+    Some(active)
+}
@@ -0,0 +1,63 @@
+// Latency measurement for `--measure-latency`.
+// Collects how long each bound MIDI event took from being received to its uinput
+// writes completing, and summarizes them into percentiles for periodic reports.
+
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+pub struct LatencyStats {
+    samples: Vec<Duration>,
+}
+
+/// Percentiles of the latencies recorded since the last report
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencyReport {
+    pub count: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, latency: Duration) {
+        self.samples.push(latency);
+    }
+
+    /// Summarize the recorded latencies and start over, `None` if there were none
+    pub fn report(&mut self) -> Option<LatencyReport> {
+        let mut samples = std::mem::take(&mut self.samples);
+        samples.sort_unstable();
+
+        let max = *samples.last()?;
+        let percentile = |p: usize| samples[(samples.len() - 1) * p / 100];
+
+        Some(LatencyReport {
+            count: samples.len(),
+            p50: percentile(50),
+            p95: percentile(95),
+            max,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_report() {
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.report(), None);
+
+        for micros in 1..=100 {
+            stats.record(Duration::from_micros(micros));
+        }
+
+        let report = stats.report().unwrap();
+        assert_eq!(report.count, 100);
+        assert_eq!(report.p50, Duration::from_micros(50));
+        assert_eq!(report.p95, Duration::from_micros(95));
+        assert_eq!(report.max, Duration::from_micros(100));
+        assert_eq!(stats.report(), None);
+    }
+}
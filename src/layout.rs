@@ -0,0 +1,366 @@
+// Character to keycode tables for typing text on the virtual keyboard.
+// The virtual device only emits scancodes, so what a scancode types depends on
+// the keyboard layout the desktop has configured. These tables map characters
+// back to the scancode (and modifiers) that produce them on each layout.
+// With `layout = "auto"`, the layout is picked from the system's XKB settings as the config loads.
+
+use mouse_keyboard_input::*;
+use tracing::{info, warn};
+
+/// Keyboard layout the desktop is using, used to resolve characters to keycodes
+#[derive(serde::Deserialize, schemars::JsonSchema, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// US QWERTY
+    #[default]
+    Us,
+    /// German QWERTZ
+    De,
+    /// French AZERTY
+    Fr,
+    /// Whichever of the above XKB is set up with, from `XKB_DEFAULT_LAYOUT` or the
+    /// system keyboard settings written by `localectl`
+    Auto,
+}
+
+/// A keycode and the modifiers that need to be held to type a character
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyStroke {
+    pub code: u16,
+    pub shift: bool,
+    pub altgr: bool,
+}
+
+impl KeyStroke {
+    const fn plain(code: u16) -> Self {
+        Self {
+            code,
+            shift: false,
+            altgr: false,
+        }
+    }
+
+    const fn shifted(code: u16) -> Self {
+        Self {
+            code,
+            shift: true,
+            altgr: false,
+        }
+    }
+
+    const fn altgr(code: u16) -> Self {
+        Self {
+            code,
+            shift: false,
+            altgr: true,
+        }
+    }
+
+    /// The modifier keys to hold while pressing `code`
+    pub fn modifiers(&self) -> impl Iterator<Item = u16> {
+        let shift = self.shift.then_some(KEY_LEFTSHIFT);
+        let altgr = self.altgr.then_some(KEY_RIGHTALT);
+        shift.into_iter().chain(altgr)
+    }
+}
+
+// mouse-keyboard-input names the 0 key after its position in the row
+const KEY_0: u16 = KEY_10;
+
+const QWERTY_LETTERS: [u16; 26] = [
+    KEY_A, KEY_B, KEY_C, KEY_D, KEY_E, KEY_F, KEY_G, KEY_H, KEY_I, KEY_J, KEY_K, KEY_L, KEY_M,
+    KEY_N, KEY_O, KEY_P, KEY_Q, KEY_R, KEY_S, KEY_T, KEY_U, KEY_V, KEY_W, KEY_X, KEY_Y, KEY_Z,
+];
+
+const DIGITS: [u16; 10] = [
+    KEY_0, KEY_1, KEY_2, KEY_3, KEY_4, KEY_5, KEY_6, KEY_7, KEY_8, KEY_9,
+];
+
+impl Layout {
+    /// Resolve a character to the keystroke that types it on this layout,
+    /// or `None` if the layout can't type it directly
+    pub fn resolve(&self, c: char) -> Option<KeyStroke> {
+        // Whitespace is the same everywhere
+        match c {
+            ' ' => return Some(KeyStroke::plain(KEY_SPACE)),
+            '\n' => return Some(KeyStroke::plain(KEY_ENTER)),
+            '\t' => return Some(KeyStroke::plain(KEY_TAB)),
+            _ => {}
+        }
+
+        match self {
+            // Replaced by the detected layout when the config loads
+            Layout::Us | Layout::Auto => resolve_us(c),
+            Layout::De => resolve_de(c),
+            Layout::Fr => resolve_fr(c),
+        }
+    }
+
+    /// The layout XKB is set up with, or US if it can't be found or isn't supported
+    pub fn detect() -> Layout {
+        let configured = std::env::var("XKB_DEFAULT_LAYOUT")
+            .ok()
+            .or_else(|| setting("/etc/default/keyboard", "XKBLAYOUT"))
+            .or_else(|| {
+                setting(
+                    "/etc/X11/xorg.conf.d/00-keyboard.conf",
+                    "Option \"XkbLayout\"",
+                )
+            });
+
+        let Some(name) = configured else {
+            warn!("Couldn't find the XKB layout, typing for a US layout");
+            return Layout::Us;
+        };
+
+        match Layout::from_xkb(&name) {
+            Some(layout) => {
+                info!(xkb = name, ?layout, "Detected keyboard layout");
+                layout
+            }
+            None => {
+                warn!(xkb = name, "Unsupported XKB layout, typing for a US layout");
+                Layout::Us
+            }
+        }
+    }
+
+    /// The layout for an XKB layout list like `de` or `fr,us`, going by the first one
+    fn from_xkb(name: &str) -> Option<Layout> {
+        match name.split(',').next()?.trim() {
+            "us" => Some(Layout::Us),
+            "de" => Some(Layout::De),
+            "fr" => Some(Layout::Fr),
+            _ => None,
+        }
+    }
+}
+
+/// The value of a setting in a config file, like `XKBLAYOUT="de"`
+fn setting(path: &str, key: &str) -> Option<String> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    contents.lines().find_map(|line| {
+        let value = line.trim().strip_prefix(key)?;
+        let value = value
+            .trim_start_matches([' ', '\t', '='])
+            .trim()
+            .trim_matches('"');
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
+fn letter(c: char, map: impl Fn(char) -> char) -> Option<KeyStroke> {
+    if !c.is_ascii_alphabetic() {
+        return None;
+    }
+
+    let index = (map(c.to_ascii_lowercase()) as u8 - b'a') as usize;
+    let code = QWERTY_LETTERS[index];
+
+    if c.is_ascii_uppercase() {
+        Some(KeyStroke::shifted(code))
+    } else {
+        Some(KeyStroke::plain(code))
+    }
+}
+
+fn resolve_us(c: char) -> Option<KeyStroke> {
+    if let Some(stroke) = letter(c, |c| c) {
+        return Some(stroke);
+    }
+
+    if let Some(d) = c.to_digit(10) {
+        return Some(KeyStroke::plain(DIGITS[d as usize]));
+    }
+
+    let stroke = match c {
+        '!' => KeyStroke::shifted(KEY_1),
+        '@' => KeyStroke::shifted(KEY_2),
+        '#' => KeyStroke::shifted(KEY_3),
+        '$' => KeyStroke::shifted(KEY_4),
+        '%' => KeyStroke::shifted(KEY_5),
+        '^' => KeyStroke::shifted(KEY_6),
+        '&' => KeyStroke::shifted(KEY_7),
+        '*' => KeyStroke::shifted(KEY_8),
+        '(' => KeyStroke::shifted(KEY_9),
+        ')' => KeyStroke::shifted(KEY_0),
+        '-' => KeyStroke::plain(KEY_MINUS),
+        '_' => KeyStroke::shifted(KEY_MINUS),
+        '=' => KeyStroke::plain(KEY_EQUAL),
+        '+' => KeyStroke::shifted(KEY_EQUAL),
+        '[' => KeyStroke::plain(KEY_LEFTBRACE),
+        '{' => KeyStroke::shifted(KEY_LEFTBRACE),
+        ']' => KeyStroke::plain(KEY_RIGHTBRACE),
+        '}' => KeyStroke::shifted(KEY_RIGHTBRACE),
+        ';' => KeyStroke::plain(KEY_SEMICOLON),
+        ':' => KeyStroke::shifted(KEY_SEMICOLON),
+        '\'' => KeyStroke::plain(KEY_APOSTROPHE),
+        '"' => KeyStroke::shifted(KEY_APOSTROPHE),
+        '`' => KeyStroke::plain(KEY_GRAVE),
+        '~' => KeyStroke::shifted(KEY_GRAVE),
+        '\\' => KeyStroke::plain(KEY_BACKSLASH),
+        '|' => KeyStroke::shifted(KEY_BACKSLASH),
+        ',' => KeyStroke::plain(KEY_COMMA),
+        '<' => KeyStroke::shifted(KEY_COMMA),
+        '.' => KeyStroke::plain(KEY_DOT),
+        '>' => KeyStroke::shifted(KEY_DOT),
+        '/' => KeyStroke::plain(KEY_SLASH),
+        '?' => KeyStroke::shifted(KEY_SLASH),
+        _ => return None,
+    };
+
+    Some(stroke)
+}
+
+fn resolve_de(c: char) -> Option<KeyStroke> {
+    // Y and Z are swapped on QWERTZ
+    let swap_yz = |c| match c {
+        'y' => 'z',
+        'z' => 'y',
+        c => c,
+    };
+
+    if let Some(stroke) = letter(c, swap_yz) {
+        return Some(stroke);
+    }
+
+    if let Some(d) = c.to_digit(10) {
+        return Some(KeyStroke::plain(DIGITS[d as usize]));
+    }
+
+    let stroke = match c {
+        '!' => KeyStroke::shifted(KEY_1),
+        '"' => KeyStroke::shifted(KEY_2),
+        '§' => KeyStroke::shifted(KEY_3),
+        '$' => KeyStroke::shifted(KEY_4),
+        '%' => KeyStroke::shifted(KEY_5),
+        '&' => KeyStroke::shifted(KEY_6),
+        '/' => KeyStroke::shifted(KEY_7),
+        '(' => KeyStroke::shifted(KEY_8),
+        ')' => KeyStroke::shifted(KEY_9),
+        '=' => KeyStroke::shifted(KEY_0),
+        'ß' => KeyStroke::plain(KEY_MINUS),
+        '?' => KeyStroke::shifted(KEY_MINUS),
+        'ü' => KeyStroke::plain(KEY_LEFTBRACE),
+        'Ü' => KeyStroke::shifted(KEY_LEFTBRACE),
+        '+' => KeyStroke::plain(KEY_RIGHTBRACE),
+        '*' => KeyStroke::shifted(KEY_RIGHTBRACE),
+        'ö' => KeyStroke::plain(KEY_SEMICOLON),
+        'Ö' => KeyStroke::shifted(KEY_SEMICOLON),
+        'ä' => KeyStroke::plain(KEY_APOSTROPHE),
+        'Ä' => KeyStroke::shifted(KEY_APOSTROPHE),
+        '#' => KeyStroke::plain(KEY_BACKSLASH),
+        '\'' => KeyStroke::shifted(KEY_BACKSLASH),
+        ',' => KeyStroke::plain(KEY_COMMA),
+        ';' => KeyStroke::shifted(KEY_COMMA),
+        '.' => KeyStroke::plain(KEY_DOT),
+        ':' => KeyStroke::shifted(KEY_DOT),
+        '-' => KeyStroke::plain(KEY_SLASH),
+        '_' => KeyStroke::shifted(KEY_SLASH),
+        '<' => KeyStroke::plain(KEY_102ND),
+        '>' => KeyStroke::shifted(KEY_102ND),
+        '@' => KeyStroke::altgr(KEY_Q),
+        '€' => KeyStroke::altgr(KEY_E),
+        '{' => KeyStroke::altgr(KEY_7),
+        '[' => KeyStroke::altgr(KEY_8),
+        ']' => KeyStroke::altgr(KEY_9),
+        '}' => KeyStroke::altgr(KEY_0),
+        '\\' => KeyStroke::altgr(KEY_MINUS),
+        '~' => KeyStroke::altgr(KEY_RIGHTBRACE),
+        '|' => KeyStroke::altgr(KEY_102ND),
+        _ => return None,
+    };
+
+    Some(stroke)
+}
+
+fn resolve_fr(c: char) -> Option<KeyStroke> {
+    // A/Q and Z/W are swapped on AZERTY, and M sits where QWERTY has the semicolon
+    let swap = |c| match c {
+        'a' => 'q',
+        'q' => 'a',
+        'z' => 'w',
+        'w' => 'z',
+        c => c,
+    };
+
+    match c {
+        'm' => return Some(KeyStroke::plain(KEY_SEMICOLON)),
+        'M' => return Some(KeyStroke::shifted(KEY_SEMICOLON)),
+        _ => {}
+    }
+
+    if let Some(stroke) = letter(c, swap) {
+        return Some(stroke);
+    }
+
+    // Digits need shift on AZERTY
+    if let Some(d) = c.to_digit(10) {
+        return Some(KeyStroke::shifted(DIGITS[d as usize]));
+    }
+
+    let stroke = match c {
+        '&' => KeyStroke::plain(KEY_1),
+        'é' => KeyStroke::plain(KEY_2),
+        '"' => KeyStroke::plain(KEY_3),
+        '\'' => KeyStroke::plain(KEY_4),
+        '(' => KeyStroke::plain(KEY_5),
+        '-' => KeyStroke::plain(KEY_6),
+        'è' => KeyStroke::plain(KEY_7),
+        '_' => KeyStroke::plain(KEY_8),
+        'ç' => KeyStroke::plain(KEY_9),
+        'à' => KeyStroke::plain(KEY_0),
+        ')' => KeyStroke::plain(KEY_MINUS),
+        '°' => KeyStroke::shifted(KEY_MINUS),
+        '=' => KeyStroke::plain(KEY_EQUAL),
+        '+' => KeyStroke::shifted(KEY_EQUAL),
+        '$' => KeyStroke::plain(KEY_RIGHTBRACE),
+        '£' => KeyStroke::shifted(KEY_RIGHTBRACE),
+        'ù' => KeyStroke::plain(KEY_APOSTROPHE),
+        '%' => KeyStroke::shifted(KEY_APOSTROPHE),
+        '*' => KeyStroke::plain(KEY_BACKSLASH),
+        'µ' => KeyStroke::shifted(KEY_BACKSLASH),
+        ',' => KeyStroke::plain(KEY_M),
+        '?' => KeyStroke::shifted(KEY_M),
+        ';' => KeyStroke::plain(KEY_COMMA),
+        '.' => KeyStroke::shifted(KEY_COMMA),
+        ':' => KeyStroke::plain(KEY_DOT),
+        '/' => KeyStroke::shifted(KEY_DOT),
+        '!' => KeyStroke::plain(KEY_SLASH),
+        '§' => KeyStroke::shifted(KEY_SLASH),
+        '<' => KeyStroke::plain(KEY_102ND),
+        '>' => KeyStroke::shifted(KEY_102ND),
+        '#' => KeyStroke::altgr(KEY_3),
+        '{' => KeyStroke::altgr(KEY_4),
+        '[' => KeyStroke::altgr(KEY_5),
+        '|' => KeyStroke::altgr(KEY_6),
+        '\\' => KeyStroke::altgr(KEY_8),
+        '@' => KeyStroke::altgr(KEY_0),
+        ']' => KeyStroke::altgr(KEY_MINUS),
+        '}' => KeyStroke::altgr(KEY_EQUAL),
+        '€' => KeyStroke::altgr(KEY_E),
+        _ => return None,
+    };
+
+    Some(stroke)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_layouts() {
+        assert_eq!(Layout::Us.resolve('a'), Some(KeyStroke::plain(KEY_A)));
+        assert_eq!(Layout::Us.resolve('?'), Some(KeyStroke::shifted(KEY_SLASH)));
+        assert_eq!(Layout::De.resolve('z'), Some(KeyStroke::plain(KEY_Y)));
+        assert_eq!(Layout::De.resolve('@'), Some(KeyStroke::altgr(KEY_Q)));
+        assert_eq!(Layout::Fr.resolve('A'), Some(KeyStroke::shifted(KEY_Q)));
+        assert_eq!(Layout::Fr.resolve('1'), Some(KeyStroke::shifted(KEY_1)));
+        assert_eq!(Layout::Us.resolve('é'), None);
+
+        assert_eq!(Layout::from_xkb("de"), Some(Layout::De));
+        assert_eq!(Layout::from_xkb("fr,us"), Some(Layout::Fr));
+        assert_eq!(Layout::from_xkb("dvorak"), None);
+    }
+}
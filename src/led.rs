@@ -0,0 +1,241 @@
+// LED feedback for pad controllers: every bound pad is lit in a color showing what its binding
+// is doing, whether it's idle, held down, latched on, or live because its layer is active.
+// Colors are sent the way the controller takes them: a palette index as a NoteOn's velocity,
+// or an RGB color in the SysEx of Launchpads and the APC mini mk2.
+
+use std::collections::HashMap;
+
+use midir::MidiOutputConnection;
+use tracing::{debug, warn};
+
+use crate::config::{FeedbackConfig, LedColor, LedProtocol, PadColors};
+use crate::midi_output;
+
+/// What a pad's binding is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PadState {
+    Idle,
+    Layer,
+    Latched,
+    Pressed,
+}
+
+/// The color lighting a pad in a state, from its own colors, the ones for every pad, or the
+/// protocol's defaults
+pub fn color(config: &FeedbackConfig, note: u8, state: PadState) -> LedColor {
+    let pick = |colors: &PadColors| match state {
+        PadState::Idle => colors.idle,
+        PadState::Layer => colors.layer,
+        PadState::Latched => colors.latched,
+        PadState::Pressed => colors.pressed,
+    };
+
+    config
+        .notes
+        .get(&note.to_string())
+        .and_then(pick)
+        .or_else(|| pick(&config.colors))
+        .unwrap_or_else(|| default_color(config.protocol, state))
+}
+
+fn default_color(protocol: LedProtocol, state: PadState) -> LedColor {
+    let velocity = match (protocol, state) {
+        (LedProtocol::Velocity, PadState::Idle) => 0,
+        (LedProtocol::Velocity, _) => 127,
+        // Launchpads and the APC mini mk2 share a palette: dim white, white, green and blue
+        (_, PadState::Idle) => 1,
+        (_, PadState::Pressed) => 3,
+        (_, PadState::Latched) => 21,
+        (_, PadState::Layer) => 45,
+    };
+    LedColor::Palette(velocity)
+}
+
+/// The message lighting a pad in a color
+pub fn message(config: &FeedbackConfig, note: u8, color: LedColor) -> Vec<u8> {
+    let launchpad = |device: u8, [r, g, b]: [u8; 3]| {
+        // Lighting type 3 takes RGB from 0 to 127
+        vec![
+            0xF0,
+            0x00,
+            0x20,
+            0x29,
+            0x02,
+            device,
+            0x03,
+            0x03,
+            note,
+            r >> 1,
+            g >> 1,
+            b >> 1,
+            0xF7,
+        ]
+    };
+
+    match (config.protocol, color) {
+        (LedProtocol::Velocity, LedColor::Palette(velocity)) => {
+            vec![
+                0x90 | (config.channel.clamp(1, 16) - 1),
+                note,
+                velocity & 0x7F,
+            ]
+        }
+        // The closest a velocity can get to a color is on or off
+        (LedProtocol::Velocity, LedColor::Rgb(rgb)) => {
+            let velocity = if rgb == [0, 0, 0] { 0 } else { 127 };
+            vec![0x90 | (config.channel.clamp(1, 16) - 1), note, velocity]
+        }
+        // Channel 1 is a static color
+        (
+            LedProtocol::LaunchpadMiniMk3 | LedProtocol::LaunchpadX | LedProtocol::LaunchpadProMk3,
+            LedColor::Palette(velocity),
+        ) => vec![0x90, note, velocity & 0x7F],
+        // Channel 7 is full brightness
+        (LedProtocol::ApcMiniMk2, LedColor::Palette(velocity)) => {
+            vec![0x96, note, velocity & 0x7F]
+        }
+        (LedProtocol::LaunchpadMiniMk3, LedColor::Rgb(rgb)) => launchpad(0x0D, rgb),
+        (LedProtocol::LaunchpadX, LedColor::Rgb(rgb)) => launchpad(0x0C, rgb),
+        (LedProtocol::LaunchpadProMk3, LedColor::Rgb(rgb)) => launchpad(0x0E, rgb),
+        // A range of pads from `note` to `note`, with each component split over two bytes
+        (LedProtocol::ApcMiniMk2, LedColor::Rgb([r, g, b])) => vec![
+            0xF0,
+            0x47,
+            0x7F,
+            0x4F,
+            0x24,
+            0x00,
+            0x08,
+            note,
+            note,
+            r >> 7,
+            r & 0x7F,
+            g >> 7,
+            g & 0x7F,
+            b >> 7,
+            b & 0x7F,
+            0xF7,
+        ],
+    }
+}
+
+/// The message getting a controller ready for its pads to be lit, if it needs one
+fn setup_message(protocol: LedProtocol) -> Option<Vec<u8>> {
+    // Launchpads only let every pad be lit freely in programmer mode
+    let device = match protocol {
+        LedProtocol::LaunchpadMiniMk3 => 0x0D,
+        LedProtocol::LaunchpadX => 0x0C,
+        LedProtocol::LaunchpadProMk3 => 0x0E,
+        LedProtocol::Velocity | LedProtocol::ApcMiniMk2 => return None,
+    };
+    Some(vec![0xF0, 0x00, 0x20, 0x29, 0x02, device, 0x0E, 0x01, 0xF7])
+}
+
+/// The connection to the controller's LEDs, and the color each pad was last lit in
+pub struct Leds {
+    connection: MidiOutputConnection,
+    lit: HashMap<u8, LedColor>,
+}
+
+impl Leds {
+    pub fn open(config: &FeedbackConfig) -> Option<Self> {
+        let mut leds = Self {
+            connection: midi_output::connect(&config.port)?,
+            lit: HashMap::new(),
+        };
+        leds.reset(config);
+        Some(leds)
+    }
+
+    /// Set the controller up again and forget what's lit, for when it reconnects
+    pub fn reset(&mut self, config: &FeedbackConfig) {
+        self.lit.clear();
+        if let Some(message) = setup_message(config.protocol) {
+            debug!(protocol = ?config.protocol, "Setting the controller up for LED feedback");
+            self.send(&message);
+        }
+    }
+
+    /// Light the pads whose color changed, turning off the ones no longer bound
+    pub fn update(&mut self, config: &FeedbackConfig, colors: &HashMap<u8, LedColor>) {
+        let off: Vec<u8> = self
+            .lit
+            .keys()
+            .filter(|note| !colors.contains_key(note))
+            .copied()
+            .collect();
+        for note in off {
+            self.lit.remove(&note);
+            self.send(&message(config, note, LedColor::Palette(0)));
+        }
+
+        for (&note, &color) in colors {
+            if self.lit.insert(note, color) != Some(color) {
+                self.send(&message(config, note, color));
+            }
+        }
+    }
+
+    fn send(&mut self, message: &[u8]) {
+        if let Err(e) = self.connection.send(message) {
+            warn!(%e, "Failed to send LED feedback");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feedback(config: &str) -> FeedbackConfig {
+        toml::from_str(config).unwrap()
+    }
+
+    #[test]
+    fn test_colors() {
+        let config = feedback(
+            r#"
+                port = "Launchpad"
+                protocol = "launchpad_mini_mk3"
+                pressed = [255, 0, 0]
+                [notes.11]
+                idle = 5
+            "#,
+        );
+
+        assert_eq!(color(&config, 11, PadState::Idle), LedColor::Palette(5));
+        assert_eq!(
+            color(&config, 11, PadState::Pressed),
+            LedColor::Rgb([255, 0, 0])
+        );
+        assert_eq!(color(&config, 12, PadState::Idle), LedColor::Palette(1));
+        assert_eq!(color(&config, 12, PadState::Latched), LedColor::Palette(21));
+
+        assert_eq!(message(&config, 11, LedColor::Palette(21)), [0x90, 11, 21]);
+        assert_eq!(
+            message(&config, 11, LedColor::Rgb([255, 0, 64])),
+            [0xF0, 0x00, 0x20, 0x29, 0x02, 0x0D, 0x03, 0x03, 11, 127, 0, 32, 0xF7]
+        );
+    }
+
+    #[test]
+    fn test_protocols() {
+        let apc = feedback("port = \"APC\"\nprotocol = \"apc_mini_mk2\"");
+        assert_eq!(message(&apc, 7, LedColor::Palette(5)), [0x96, 7, 5]);
+        assert_eq!(
+            message(&apc, 7, LedColor::Rgb([255, 128, 0])),
+            [0xF0, 0x47, 0x7F, 0x4F, 0x24, 0x00, 0x08, 7, 7, 1, 127, 1, 0, 0, 0, 0xF7]
+        );
+
+        let velocity = feedback("port = \"nanoPAD\"\nchannel = 10");
+        assert_eq!(color(&velocity, 36, PadState::Idle), LedColor::Palette(0));
+        assert_eq!(
+            message(&velocity, 36, LedColor::Palette(127)),
+            [0x99, 36, 127]
+        );
+        assert_eq!(
+            message(&velocity, 36, LedColor::Rgb([0, 9, 0])),
+            [0x99, 36, 127]
+        );
+    }
+}
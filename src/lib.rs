@@ -0,0 +1,4017 @@
+// midkb binds MIDI controllers to keyboard keys, the mouse and gamepad axes on Linux.
+// `MidiInputHandler` applies the bindings of a `Config` to each message from the controller,
+// acting on the virtual devices through an `Output`, and `main` is the `midkb` command line
+// built around it. Other frontends run the same handling through `session`.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::IsTerminal;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+#[cfg(feature = "ble")]
+mod ble;
+mod brightness;
+mod calibrate;
+mod capture;
+mod cli;
+mod compositor;
+pub mod config;
+pub mod config_edit;
+mod daemon;
+mod editor;
+mod error;
+mod export;
+mod gamepad;
+mod history;
+mod hotplug;
+#[cfg(feature = "http")]
+mod http;
+mod input;
+#[cfg(feature = "jack")]
+mod jack_input;
+pub mod keycodes;
+mod latency;
+mod layout;
+mod led;
+#[cfg(all(test, feature = "loopback-tests"))]
+mod loopback_tests;
+pub mod map;
+mod midi_output;
+mod migrate;
+mod mmc;
+mod monitor;
+mod mpe;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod notify;
+#[cfg(feature = "obs")]
+mod obs;
+pub mod output;
+mod parser;
+mod picker;
+#[cfg(feature = "pipewire")]
+mod pipewire_volume;
+mod presets;
+mod replay;
+#[cfg(any(feature = "http", feature = "web-ui"))]
+mod request;
+mod routes;
+#[cfg(feature = "rtp-midi")]
+mod rtp_midi;
+pub mod session;
+mod signals;
+mod simulate;
+mod smoothing;
+mod sound;
+mod template;
+mod timer_wheel;
+mod volume;
+#[cfg(feature = "web-ui")]
+mod web_ui;
+#[cfg(feature = "websocket")]
+mod websocket;
+mod worker;
+mod xy_pad;
+use capture::CaptureLog;
+use clap::Parser;
+use cli::{Cli, LogFormat};
+use config::{Action, Backend, Condition, ConditionKind, Config, UnicodeInput, ZeroVelocity};
+use history::{HistoryEntry, HistoryLog};
+use hotplug::HotplugEvent;
+use input::InputTarget;
+use latency::LatencyStats;
+use layout::KeyStroke;
+use midi_msg::{
+    Channel, ChannelModeMsg, ChannelVoiceMsg, ControlChange, MidiMsg, SystemCommonMsg,
+    SystemRealTimeMsg,
+};
+use midir::MidiOutputConnection;
+use mouse_keyboard_input::{KEY_LEFTCTRL, KEY_LEFTSHIFT, KEY_SPACE, KEY_U};
+use mpe::MpeState;
+use output::{Output, VirtualOutput, WHEEL_NOTCH};
+use parser::MidiParser;
+use routes::Router;
+use smoothing::SmoothedMotion;
+use template::EventVars;
+use timer_wheel::TimerWheel;
+use tracing::{debug, error, info, trace, warn};
+use xy_pad::XyPadState;
+
+/// How often the handler's background tick runs
+const TICK_INTERVAL: Duration = Duration::from_millis(8);
+
+/// How often `--measure-latency` reports its percentiles
+const LATENCY_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Wheel units scrolled per step of a `hi_res` control's value
+const HI_RES_WHEEL_STEP: i32 = 15;
+
+/// Note number actions from one-shot messages like transport are pressed under,
+/// outside the range of real MIDI notes, so they can be released right away
+const ONE_SHOT_NOTE: u8 = 128;
+
+/// Note numbers the pitch wheel's `up` and `down` actions are held under while it's bent
+const PITCH_BEND_UP_NOTE: u8 = 129;
+const PITCH_BEND_DOWN_NOTE: u8 = 130;
+
+/// How many triggered actions the HTTP API reports
+const RECENT_EVENTS: usize = 32;
+
+/// How far a soft-takeover control may jump from the stored value before it's ignored
+const SOFT_TAKEOVER_THRESHOLD: u8 = 5;
+
+/// Jumps arriving this soon after an accepted value are treated as fast movement, not a takeover
+const SOFT_TAKEOVER_WINDOW: Duration = Duration::from_millis(50);
+
+/// Changes arriving faster than this after the previous one count as a fast spin
+const REPEAT_ACCEL_WINDOW: Duration = Duration::from_millis(50);
+
+/// How long a device that sends Active Sensing can go quiet before it's taken as disconnected,
+/// as the MIDI spec has it
+const ACTIVE_SENSING_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// What midkb is doing, as served by the HTTP API and published over MQTT
+#[derive(serde::Serialize, Debug)]
+pub struct Status {
+    /// The profile applied on top of `[notes]` and `[cc]`, if any
+    pub profile: Option<String>,
+    /// Whether MIDI is currently passed through instead of handled
+    pub bypassed: bool,
+    /// The current bank of pads, counting from 0
+    pub bank: u8,
+    /// Key and button codes held down on the virtual devices
+    pub held_keys: Vec<u16>,
+    pub active_layers: Vec<String>,
+    /// How many MIDI messages have failed to parse since midkb started
+    pub parse_failures: u64,
+    /// The last few triggered actions, oldest first
+    pub recent_events: Vec<HistoryEntry>,
+}
+
+/// Something the handler does later, on the tick it's due
+#[derive(Debug, PartialEq)]
+enum Timer {
+    /// Release a note with a `tap_ms`
+    TapRelease(u8),
+    /// Let go of or press again the keys of a held `turbo_hz` note
+    Turbo(u8),
+}
+
+#[derive(Debug)]
+pub enum CCDirection {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// An accepted movement of a CC
+#[derive(Debug)]
+pub struct CCMove {
+    pub direction: CCDirection,
+    /// How far the value moved since the last accepted one
+    pub delta: u8,
+    /// Time since the last accepted value, if there was one
+    pub elapsed: Option<Duration>,
+}
+
+impl CCMove {
+    /// How many key taps to emit for this move: one per move when turned slowly,
+    /// scaling up with the value delta and speed when spun quickly
+    pub fn repeat_count(&self, max: u8) -> u8 {
+        let speed = match self.elapsed {
+            Some(elapsed) if elapsed < REPEAT_ACCEL_WINDOW => {
+                REPEAT_ACCEL_WINDOW.as_secs_f32() / elapsed.as_secs_f32().max(0.001)
+            }
+            _ => 1.0,
+        };
+
+        ((self.delta as f32 * speed).round() as u8).clamp(1, max.max(1))
+    }
+}
+
+/// How far Mouse mode moves the cursor for an event, without a curve and for a step of a linear one
+const MOUSE_STEP: i32 = 10;
+
+/// How many pixels Mouse mode moves the cursor for a move of `delta` steps, along the control's
+/// response curve
+fn mouse_distance(cc_config: &config::CCDirectionConfig, delta: u8) -> i32 {
+    let delta = delta as i32;
+    match cc_config.curve {
+        None => MOUSE_STEP,
+        Some(config::ResponseCurve::Linear) => MOUSE_STEP * delta,
+        Some(config::ResponseCurve::Exponential) => (MOUSE_STEP * delta * delta / 2).max(1),
+        Some(config::ResponseCurve::Custom) => {
+            // Interpolate between the points around the delta, starting from nothing at 0 steps
+            let mut below = (0, 0);
+            for &(steps, pixels) in &cc_config.curve_points {
+                let (steps, pixels) = (steps as i32, pixels as i32);
+                if delta <= steps {
+                    let (from_steps, from_pixels) = below;
+                    return from_pixels
+                        + (pixels - from_pixels) * (delta - from_steps)
+                            / (steps - from_steps).max(1);
+                }
+                below = (steps, pixels);
+            }
+            below.1
+        }
+    }
+}
+
+/// The steps a relative control turned, from the value it sent and the value it sends for
+/// not moving. Each side of `center` covers half the range, wrapping around at 0 and 127
+fn relative_step(value: u8, center: u8) -> i32 {
+    (value as i32 - center as i32 + 64).rem_euclid(128) - 64
+}
+
+/// Turn a mouse axis from the config (`x`, `y`, `-x`, `-y`) and an amount into a movement
+fn axis_motion(axis: Option<config::MouseAxis>, amount: i32) -> (i32, i32) {
+    axis.map_or((0, 0), |axis| axis.motion(amount))
+}
+
+/// Spawn a shell command in the background, reaping it once it exits
+fn run_command(run: &str) {
+    info!(?run, "Running command");
+
+    match std::process::Command::new("sh").arg("-c").arg(run).spawn() {
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => error!(?e, ?run, "Failed to run command"),
+    }
+}
+
+pub struct MidiInputHandler {
+    device: Box<dyn Output>,
+    config: config::Config,
+
+    // A map for determining the direction of CC messages
+    // Should contain the CC number as the key and the velocity as value, if not exists it will be created and set
+    // to the last known value
+    cc_map: HashMap<u8, u8>,
+
+    // When each CC last produced an event, for debouncing and soft-takeover
+    cc_last_event: HashMap<u8, Instant>,
+
+    // The last value each CC physically sent, even if it wasn't accepted
+    cc_physical: HashMap<u8, u8>,
+
+    // Steps each CC moved that haven't added up to `steps_per_event` yet, signed by direction
+    cc_steps: HashMap<u8, i32>,
+
+    // The direction each CC last moved in, 1 or -1, for its `jitter` threshold
+    cc_last_direction: HashMap<u8, i32>,
+
+    // The zone each Zones mode control is in, whose key is held
+    cc_zones: HashMap<u8, usize>,
+
+    // Whether events are being sent to a binding's extra device
+    routed: bool,
+
+    // Pending mouse movement from smoothed CCs, drained on every tick
+    mouse_smoothing: HashMap<u8, SmoothedMotion>,
+
+    // Notes held on MPE member channels
+    mpe: MpeState,
+
+    // Current position of each XY pad, by name
+    xy_pads: HashMap<String, XyPadState>,
+
+    // Drags in progress, by the note holding them: the x and y CCs, and the speed
+    drags: HashMap<u8, (Option<u8>, Option<u8>, i32)>,
+
+    // Layers currently active, checked by `when` conditions
+    active_layers: HashSet<String>,
+
+    // Layers activated by each held note, deactivated again on NoteOff
+    layer_note_map: HashMap<u8, Vec<String>>,
+
+    // Modifiers held by notes, with the note holding each, in the order they were pressed
+    held_modifiers: Vec<(u8, String)>,
+
+    // Modifiers armed by sticky actions, applied to the next key press only
+    pending_modifiers: Vec<u16>,
+
+    // Which of the pitch wheel's actions is held, by the note it's held under
+    pitch_bend_held: Option<u8>,
+
+    // The keys pressed by each held note, so NoteOff releases exactly what NoteOn pressed
+    key_note_map: HashMap<u8, Vec<u16>>,
+
+    // Every key and button currently held down on the virtual device
+    held_keys: HashSet<u16>,
+
+    // When each held note was pressed
+    note_pressed_at: HashMap<u8, Instant>,
+
+    // Notes released before their `min_hold_ms`, and when to actually release them
+    pending_releases: HashMap<u8, Instant>,
+
+    // Taps and turbo cycles waiting for their time
+    timers: TimerWheel<Timer>,
+
+    // Held `turbo_hz` notes, with half their period and whether their keys are let go right now
+    turbo: HashMap<u8, (Duration, bool)>,
+
+    // When each binding with a `cooldown_ms` last fired, by note and by CC
+    note_fired_at: HashMap<u8, Instant>,
+    cc_fired_at: HashMap<u8, Instant>,
+
+    // How often each unbound note and CC was played, for `report_unbound`
+    unbound: BTreeMap<(&'static str, u8), u32>,
+
+    // When the last message came, for `idle_release_ms`
+    last_message: Instant,
+
+    // When the last message came, once the device has sent Active Sensing, and where to report
+    // it going quiet
+    last_sensed: Option<Instant>,
+    sensing_lost: Option<Box<dyn FnMut() + Send>>,
+
+    // Whether the sustain pedal is down, and the notes released meanwhile that still hold their keys
+    sustain_down: bool,
+    sustained: HashSet<u8>,
+
+    // The event currently being handled, for command placeholders
+    event: EventVars,
+
+    // Where triggered actions are logged, if enabled
+    history: Option<HistoryLog>,
+
+    // Where messages that fail to parse are captured, if enabled
+    capture: Option<CaptureLog>,
+
+    // How many messages have failed to parse
+    parse_failures: u64,
+
+    // The last few triggered actions, newest last, for the HTTP API
+    recent_events: VecDeque<HistoryEntry>,
+
+    // Whether to keep `recent_events`, once something shows them
+    keep_recent_events: bool,
+
+    // Where every triggered action is sent as it happens, like the MQTT publisher
+    #[cfg(feature = "mqtt")]
+    event_subscribers: Vec<tokio::sync::mpsc::UnboundedSender<HistoryEntry>>,
+
+    // Where `obs_*` actions are queued for the OBS connection, if there is one
+    #[cfg(feature = "obs")]
+    obs: Option<tokio::sync::mpsc::UnboundedSender<Action>>,
+
+    // Plays sound feedback, started the first time a sound is played
+    speaker: Option<sound::Speaker>,
+
+    // Applies volume changes, started the first time one is made
+    mixer: Option<volume::Mixer>,
+
+    // Applies brightness levels, started the first time one is set
+    backlight: Option<brightness::Backlight>,
+
+    // Virtual port unbound messages are passed through to, if enabled
+    thru: Option<MidiOutputConnection>,
+
+    // Output ports of the configured routes
+    router: Router,
+
+    // Latencies of handled events, when measuring them
+    latency: Option<LatencyStats>,
+
+    // Whether injecting events is paused, passing everything through instead
+    bypassed: bool,
+
+    // The current bank of pads, counting from 0
+    bank: u8,
+
+    // Output port the current bank is shown on, if enabled
+    bank_feedback: Option<MidiOutputConnection>,
+
+    // Pad LEDs lit with the state of their bindings, if enabled
+    leds: Option<led::Leds>,
+}
+
+fn open_history(config: &Config) -> Option<HistoryLog> {
+    let history = config.history.as_ref()?;
+
+    HistoryLog::open(history)
+        .inspect_err(|e| error!(?e, path = ?history.path, "Failed to open action history"))
+        .ok()
+}
+
+fn open_capture(config: &Config) -> Option<CaptureLog> {
+    let capture = config.capture.as_ref()?;
+
+    CaptureLog::open(&capture.path)
+        .inspect_err(
+            |e| error!(?e, path = ?capture.path, "Failed to open the unparsed message capture"),
+        )
+        .ok()
+}
+
+fn open_bank_feedback(config: &Config) -> Option<MidiOutputConnection> {
+    let feedback = config.banks.as_ref()?.feedback.as_ref()?;
+    midi_output::connect(&feedback.port)
+}
+
+/// The bank to start in: the one whose profile is applied, or the first
+fn initial_bank(config: &Config) -> u8 {
+    config
+        .banks
+        .as_ref()
+        .and_then(|banks| {
+            let profile = config.active_profile.as_ref()?;
+            banks.profiles.iter().position(|name| name == profile)
+        })
+        .unwrap_or_default() as u8
+}
+
+impl MidiInputHandler {
+    pub fn new(device: Box<dyn Output>, config: Config) -> Self {
+        let mut handler = Self {
+            history: open_history(&config),
+            capture: open_capture(&config),
+            parse_failures: 0,
+            recent_events: VecDeque::with_capacity(RECENT_EVENTS),
+            keep_recent_events: false,
+            #[cfg(feature = "mqtt")]
+            event_subscribers: Vec::new(),
+            #[cfg(feature = "obs")]
+            obs: None,
+            speaker: None,
+            mixer: None,
+            backlight: None,
+            thru: config.thru.as_deref().and_then(midi_output::open_virtual),
+            router: Router::new(&config.routes),
+            latency: None,
+            bypassed: false,
+            bank: initial_bank(&config),
+            bank_feedback: open_bank_feedback(&config),
+            leds: config.feedback.as_ref().and_then(led::Leds::open),
+            config,
+            device,
+            cc_map: HashMap::new(),
+            cc_last_event: HashMap::new(),
+            cc_physical: HashMap::new(),
+            cc_steps: HashMap::new(),
+            cc_last_direction: HashMap::new(),
+            cc_zones: HashMap::new(),
+            routed: false,
+            mouse_smoothing: HashMap::new(),
+            mpe: MpeState::default(),
+            xy_pads: HashMap::new(),
+            drags: HashMap::new(),
+            active_layers: HashSet::new(),
+            layer_note_map: HashMap::new(),
+            held_modifiers: Vec::new(),
+            pending_modifiers: Vec::new(),
+            pitch_bend_held: None,
+            key_note_map: HashMap::new(),
+            held_keys: HashSet::new(),
+            note_pressed_at: HashMap::new(),
+            pending_releases: HashMap::new(),
+            timers: TimerWheel::new(TICK_INTERVAL),
+            turbo: HashMap::new(),
+            note_fired_at: HashMap::new(),
+            cc_fired_at: HashMap::new(),
+            unbound: BTreeMap::new(),
+            last_message: Instant::now(),
+            last_sensed: None,
+            sensing_lost: None,
+            sustain_down: false,
+            event: EventVars::default(),
+            sustained: HashSet::new(),
+        };
+
+        handler.show_bank();
+        handler
+    }
+
+    /// Press a key, wrapping it in any pending sticky modifiers
+    fn press_key(&mut self, key: u16) {
+        let modifiers = std::mem::take(&mut self.pending_modifiers);
+
+        for modifier in &modifiers {
+            let _ = self.device.press(*modifier);
+        }
+
+        let _ = self.device.press(key);
+        self.held_keys.insert(key);
+
+        for modifier in modifiers.iter().rev() {
+            trace!(?modifier, "Releasing sticky modifier");
+            let _ = self.device.release(*modifier);
+        }
+    }
+
+    /// Press and immediately release a key
+    fn tap_key(&mut self, key: u16) {
+        self.press_key(key);
+        self.release_key(key);
+    }
+
+    fn release_key(&mut self, key: u16) {
+        let _ = self.device.release(key);
+        self.held_keys.remove(&key);
+    }
+
+    /// Get ready for a controller that just connected: its controls may have moved while it
+    /// was away, so with `[snapshot]` their next values only tell where they are now
+    pub fn connected(&mut self) {
+        self.last_sensed = None;
+
+        if let (Some(leds), Some(feedback)) = (&mut self.leds, &self.config.feedback) {
+            leds.reset(feedback);
+        }
+
+        let Some(snapshot) = &self.config.snapshot else {
+            return;
+        };
+
+        self.cc_map.clear();
+        self.cc_physical.clear();
+        self.cc_last_event.clear();
+        self.cc_last_direction.clear();
+        self.cc_steps.clear();
+
+        if let Some(request) = &snapshot.request {
+            let Some(mut connection) = midi_output::connect(&request.port) else {
+                return;
+            };
+            match connection.send(&request.message) {
+                Ok(()) => debug!(port = request.port, "Asked the controller for a snapshot"),
+                Err(e) => warn!(%e, port = request.port, "Failed to send the snapshot request"),
+            }
+        }
+    }
+
+    /// Force-release every key and button currently held, and forget all held note state
+    pub fn release_all(&mut self) {
+        if !self.held_keys.is_empty() {
+            info!(keys = ?self.held_keys, "Releasing all held keys");
+        }
+
+        for key in self.held_keys.drain() {
+            let _ = self.device.release(key);
+        }
+
+        self.key_note_map.clear();
+        self.pitch_bend_held = None;
+        self.note_pressed_at.clear();
+        self.pending_releases.clear();
+        self.timers.clear();
+        self.turbo.clear();
+        self.sustained.clear();
+        self.held_modifiers.clear();
+        self.pending_modifiers.clear();
+        self.cc_zones.clear();
+        self.drags.clear();
+        self.mpe.clear();
+
+        // Momentary layers go away with the notes holding them
+        for layer in self.layer_note_map.drain().flat_map(|(_, layers)| layers) {
+            self.active_layers.remove(&layer);
+        }
+    }
+
+    /// Forget everything the controller did, for when it or a DAW resets it: let go of everything
+    /// held, turn latched layers off, and start over tracking where the CCs are
+    pub fn reset(&mut self) {
+        self.release_all();
+        self.active_layers.clear();
+        self.sustain_down = false;
+        self.cc_map.clear();
+        self.cc_last_event.clear();
+        self.cc_physical.clear();
+        self.cc_steps.clear();
+        self.cc_last_direction.clear();
+        self.mouse_smoothing.clear();
+        self.xy_pads.clear();
+    }
+
+    /// Log a triggered action to the history file and the recent events
+    fn record_event(
+        &mut self,
+        event: &'static str,
+        channel: u8,
+        number: u8,
+        value: u8,
+        action: impl FnOnce() -> String,
+    ) {
+        if event != "cc" || self.config.sound.as_ref().is_some_and(|sound| sound.cc) {
+            self.play(sound::Sound::Click);
+        }
+
+        #[cfg(feature = "mqtt")]
+        let subscribed = !self.event_subscribers.is_empty();
+        #[cfg(not(feature = "mqtt"))]
+        let subscribed = false;
+        // Describing the action is only worth it when something reads it
+        if self.history.is_none() && !subscribed && !self.keep_recent_events {
+            return;
+        }
+
+        let entry = HistoryEntry::new(event, channel, number, value, action());
+
+        if let Some(history) = &mut self.history {
+            history.record(&entry);
+        }
+
+        #[cfg(feature = "mqtt")]
+        self.event_subscribers
+            .retain(|subscriber| subscriber.send(entry.clone()).is_ok());
+
+        if self.keep_recent_events {
+            if self.recent_events.len() == RECENT_EVENTS {
+                self.recent_events.pop_front();
+            }
+            self.recent_events.push_back(entry);
+        }
+    }
+
+    /// Play a sound if sound feedback is turned on
+    fn play(&mut self, sound: sound::Sound) {
+        let Some(config) = &self.config.sound else {
+            return;
+        };
+
+        let volume = config.volume;
+        self.speaker
+            .get_or_insert_with(sound::Speaker::spawn)
+            .play(sound, volume);
+    }
+
+    /// Play the sound for something being toggled on or off
+    fn play_toggle(&mut self, on: bool) {
+        self.play(if on {
+            sound::Sound::On
+        } else {
+            sound::Sound::Off
+        });
+    }
+
+    /// Count a message that failed to parse, and capture it if enabled
+    fn record_unparsed(&mut self, time: u64, bytes: &[u8]) {
+        self.parse_failures += 1;
+
+        if let Some(capture) = &mut self.capture {
+            capture.record(time, bytes);
+        }
+    }
+
+    /// Keep the last few triggered actions from now on, for `status` to list
+    pub fn keep_recent_events(&mut self) {
+        self.keep_recent_events = true;
+    }
+
+    /// Receive every action triggered from now on
+    #[cfg(feature = "mqtt")]
+    pub fn subscribe_events(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<HistoryEntry> {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        self.event_subscribers.push(tx);
+        rx
+    }
+
+    /// Receive the `obs_*` actions triggered from now on, to send to OBS
+    #[cfg(feature = "obs")]
+    pub fn obs_actions(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<Action> {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        self.obs = Some(tx);
+        rx
+    }
+
+    /// Queue an `obs_*` action for the OBS connection
+    #[cfg(feature = "obs")]
+    fn send_obs(&self, action: &Action) {
+        match &self.obs {
+            Some(obs) => {
+                let _ = obs.send(action.clone());
+            }
+            None => warn!(
+                ?action,
+                "OBS action triggered, but there's no [obs] section"
+            ),
+        }
+    }
+
+    #[cfg(not(feature = "obs"))]
+    fn send_obs(&self, action: &Action) {
+        warn!(
+            ?action,
+            "OBS action triggered, but midkb was built without OBS support"
+        );
+    }
+
+    /// Call `on_lost` whenever a device sending Active Sensing goes quiet
+    pub fn on_sensing_lost(&mut self, on_lost: impl FnMut() + Send + 'static) {
+        self.sensing_lost = Some(Box::new(on_lost));
+    }
+
+    /// Note a message arriving, starting to watch for silence once Active Sensing shows up
+    fn received(&mut self, msg: &MidiMsg) {
+        self.last_message = Instant::now();
+
+        let active_sensing = matches!(
+            msg,
+            MidiMsg::SystemRealTime {
+                msg: SystemRealTimeMsg::ActiveSensing
+            }
+        );
+        if active_sensing && self.last_sensed.is_none() {
+            debug!("The MIDI device sends Active Sensing, watching for it to stop");
+        }
+
+        if active_sensing || self.last_sensed.is_some() {
+            self.last_sensed = Some(Instant::now());
+        }
+    }
+
+    /// The config in use, with the active profile applied
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// A snapshot of what midkb is doing, for the HTTP API and MQTT
+    pub fn status(&self) -> Status {
+        let mut held_keys: Vec<u16> = self.held_keys.iter().copied().collect();
+        held_keys.sort_unstable();
+        let mut active_layers: Vec<String> = self.active_layers.iter().cloned().collect();
+        active_layers.sort_unstable();
+
+        Status {
+            profile: self.config.active_profile.clone(),
+            bypassed: self.bypassed,
+            bank: self.bank,
+            held_keys,
+            active_layers,
+            parse_failures: self.parse_failures,
+            recent_events: self.recent_events.iter().cloned().collect(),
+        }
+    }
+
+    /// Log everything held and the state bindings depend on, for SIGUSR2
+    pub fn dump_state(&self) {
+        let status = self.status();
+
+        let held_keys: Vec<String> = status
+            .held_keys
+            .iter()
+            .map(|&code| keycodes::name(code).map_or_else(|| code.to_string(), str::to_string))
+            .collect();
+        let mut held_notes: Vec<u8> = self.key_note_map.keys().copied().collect();
+        held_notes.sort_unstable();
+        let mut cc_values: Vec<(u8, u8)> = self
+            .cc_physical
+            .iter()
+            .map(|(&cc, &value)| (cc, value))
+            .collect();
+        cc_values.sort_unstable();
+        let cc_values: Vec<String> = cc_values
+            .into_iter()
+            .map(|(cc, value)| format!("{cc}={value}"))
+            .collect();
+
+        info!(
+            profile = ?status.profile,
+            layers = ?status.active_layers,
+            bank = status.bank,
+            bypassed = status.bypassed,
+            sustain = self.sustain_down,
+            ?held_keys,
+            ?held_notes,
+            ?cc_values,
+            parse_failures = status.parse_failures,
+            "Current state"
+        );
+    }
+
+    /// Switch to another profile, or back to the plain bindings with `None`,
+    /// releasing everything held under the old one
+    pub fn switch_profile(&mut self, name: Option<&str>) -> Result<(), String> {
+        self.apply_profile(name)?;
+
+        if self.notifications().profiles {
+            self.notify(&format!("Profile: {}", name.unwrap_or("none")));
+        }
+        Ok(())
+    }
+
+    fn apply_profile(&mut self, name: Option<&str>) -> Result<(), String> {
+        self.config.switch_profile(name)?;
+
+        self.release_all();
+        self.mouse_smoothing.clear();
+        self.cc_steps.clear();
+
+        info!(profile = ?name, "Switched profile");
+        Ok(())
+    }
+
+    fn notifications(&self) -> config::NotificationsConfig {
+        self.config.notifications.clone().unwrap_or_default()
+    }
+
+    fn notify(&self, text: &str) {
+        notify::show(text, self.notifications().timeout_ms);
+    }
+
+    /// Pause or resume injecting events, letting go of everything held when pausing
+    pub fn set_bypass(&mut self, bypassed: bool) {
+        if self.bypassed != bypassed {
+            self.toggle_bypass();
+        }
+    }
+
+    /// Move `step` banks up or down, switching to the new bank's profile if it has one
+    fn step_bank(&mut self, step: i8) {
+        let Some(banks) = &self.config.banks else {
+            warn!("Bank action triggered, but no [banks] are configured");
+            return;
+        };
+
+        let bank = banks.step(self.bank, step);
+        if bank == self.bank {
+            return;
+        }
+
+        let profile = banks.profiles.get(bank as usize).cloned();
+        if let Some(profile) = &profile {
+            if let Err(e) = self.apply_profile(Some(profile)) {
+                warn!(%e, bank, "Failed to switch to the bank's profile");
+                return;
+            }
+        }
+
+        self.bank = bank;
+        info!(bank, "Switched bank");
+        self.show_bank();
+
+        if self.notifications().banks {
+            let text = match profile {
+                Some(profile) => format!("Bank {}: {profile}", bank + 1),
+                None => format!("Bank {}", bank + 1),
+            };
+            self.notify(&text);
+        }
+    }
+
+    /// Light up the current bank's LED on the controller and turn the others off
+    fn show_bank(&mut self) {
+        let Some(connection) = &mut self.bank_feedback else {
+            return;
+        };
+        let Some(feedback) = self.config.banks.as_ref().and_then(|b| b.feedback.as_ref()) else {
+            return;
+        };
+
+        let status = 0x90 | (feedback.channel.clamp(1, 16) - 1);
+        for (bank, &note) in feedback.notes.iter().enumerate() {
+            let velocity = match bank == self.bank as usize {
+                true => feedback.velocity,
+                false => 0,
+            };
+
+            if let Err(e) = connection.send(&[status, note, velocity]) {
+                warn!(%e, "Failed to send bank feedback");
+            }
+        }
+    }
+
+    /// The note whose binding a pad triggers in the current bank
+    fn banked_note(&self, note: u8) -> u8 {
+        self.config
+            .banks
+            .as_ref()
+            .map_or(note, |banks| banks.note(note, self.bank))
+    }
+
+    pub fn toggle_bypass(&mut self) {
+        self.bypassed = !self.bypassed;
+
+        if self.bypassed {
+            self.release_all();
+            self.mouse_smoothing.clear();
+            self.xy_pads.clear();
+        }
+
+        info!(bypassed = self.bypassed, "Bypass toggled");
+        self.play_toggle(!self.bypassed);
+    }
+
+    /// The transport binding for a Start, Stop, Continue, Song Select or MMC message, and its name for logs
+    fn transport_action(&self, msg: &MidiMsg) -> Option<(&'static str, u8, &Action)> {
+        let transport = &self.config.transport;
+
+        match msg {
+            MidiMsg::SystemRealTime { msg } => {
+                let (name, action) = match msg {
+                    SystemRealTimeMsg::Start => ("start", &transport.start),
+                    SystemRealTimeMsg::Stop => ("stop", &transport.stop),
+                    SystemRealTimeMsg::Continue => ("continue", &transport.resume),
+                    _ => return None,
+                };
+                Some((name, 0, action.as_ref()?))
+            }
+            MidiMsg::SystemCommon {
+                msg: SystemCommonMsg::SongSelect(song),
+            } => Some(("song_select", *song, transport.get_song(*song)?)),
+            MidiMsg::SystemExclusive { .. } => {
+                let command = mmc::command_name(msg)?;
+                Some((command, 0, transport.mmc.get(command)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Run an action triggered by a one-shot message, releasing whatever it pressed right away
+    fn fire_action(&mut self, action: &Action) {
+        self.note_on_action(ONE_SHOT_NOTE, action);
+        self.release_note(ONE_SHOT_NOTE);
+    }
+
+    /// Hold the pitch wheel's `up` or `down` action while it's bent past the dead zone, letting
+    /// go when it springs back or, if it doesn't release at the center, when bent the other way
+    fn pitch_bend_keys(&mut self, channel: u8, bend: u16, keys: &config::PitchBendKeysConfig) {
+        let offset = bend as i32 - mpe::PITCH_BEND_CENTER as i32;
+        let bent = if offset > keys.dead_zone as i32 {
+            Some(PITCH_BEND_UP_NOTE)
+        } else if offset < -(keys.dead_zone as i32) {
+            Some(PITCH_BEND_DOWN_NOTE)
+        } else {
+            None
+        };
+
+        let hold = match bent {
+            None if !keys.release_at_center => self.pitch_bend_held,
+            bent => bent,
+        };
+        if hold == self.pitch_bend_held {
+            return;
+        }
+
+        if let Some(held) = self.pitch_bend_held.take() {
+            trace!(bend, "Pitch wheel back, releasing");
+            self.release_note(held);
+        }
+        self.pitch_bend_held = hold;
+
+        let action = match hold {
+            Some(PITCH_BEND_UP_NOTE) => keys.up.as_ref(),
+            Some(_) => keys.down.as_ref(),
+            None => None,
+        };
+        if let (Some(note), Some(action)) = (hold, action) {
+            debug!(channel, bend, ?action, "Pitch wheel bent");
+            self.record_event("pitch_bend", channel, 0, (bend >> 7) as u8, || {
+                format!("{action:?}")
+            });
+            self.route(keys.device.as_deref());
+            self.note_on_action(note, action);
+            self.route(None);
+        }
+    }
+
+    /// A control change with its value stretched over the range `midkb calibrate` measured
+    fn calibrated(&self, control: ControlChange) -> ControlChange {
+        match self.config.calibration.get(control.control()) {
+            Some(calibration) => ControlChange::CC {
+                control: control.control(),
+                value: calibration.normalize(control.value()),
+            },
+            None => control,
+        }
+    }
+
+    /// Whether a message presses a pad bound to `bypass`, the only thing handled while bypassed
+    fn is_bypass_toggle(&self, msg: &MidiMsg) -> bool {
+        matches!(
+            msg,
+            MidiMsg::ChannelVoice {
+                msg: ChannelVoiceMsg::NoteOn { note, velocity },
+                ..
+            } if *velocity > 0
+                && self.config.notes.get_action(self.banked_note(*note)) == Some(&Action::Bypass)
+        )
+    }
+
+    /// Swap in a new config, letting go of everything pressed under the old one
+    pub fn reload(&mut self, config: Config) {
+        self.release_all();
+        self.mouse_smoothing.clear();
+        self.xy_pads.clear();
+        self.cc_steps.clear();
+        self.note_fired_at.clear();
+        self.cc_fired_at.clear();
+        self.history = open_history(&config);
+        self.capture = open_capture(&config);
+
+        if config.thru != self.config.thru {
+            self.thru = config.thru.as_deref().and_then(midi_output::open_virtual);
+        }
+
+        if config.banks != self.config.banks {
+            self.bank_feedback = open_bank_feedback(&config);
+        }
+        self.bank = initial_bank(&config);
+
+        if config.feedback != self.config.feedback {
+            // Close the old connection first, in case it's to the same port
+            self.leds = None;
+            self.leds = config.feedback.as_ref().and_then(led::Leds::open);
+        }
+
+        if config.routes != self.config.routes {
+            // Close the old connections first, in case a route reconnects to the same port
+            self.router = Router::default();
+            self.router = Router::new(&config.routes);
+        }
+
+        self.config = config;
+        self.show_bank();
+    }
+
+    /// Click a single keystroke, holding its modifiers around it
+    fn click_stroke(&mut self, stroke: KeyStroke) {
+        for modifier in stroke.modifiers() {
+            let _ = self.device.press(modifier);
+        }
+
+        let _ = self.device.press(stroke.code);
+        let _ = self.device.release(stroke.code);
+
+        for modifier in stroke.modifiers() {
+            let _ = self.device.release(modifier);
+        }
+    }
+
+    /// Type out a string by clicking the keys that produce each character,
+    /// falling back to unicode input for characters the layout doesn't have
+    fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            match self.config.layout.resolve(c) {
+                Some(stroke) => self.click_stroke(stroke),
+                None if self.config.unicode_input != UnicodeInput::Disabled => {
+                    self.type_unicode_char(c)
+                }
+                None => {
+                    warn!(?c, layout = ?self.config.layout, "Character can't be typed on this layout, skipping");
+                }
+            }
+        }
+    }
+
+    /// Type a string entirely through the unicode input sequence
+    fn type_unicode(&mut self, text: &str) {
+        if self.config.unicode_input == UnicodeInput::Disabled {
+            warn!(?text, "Unicode input is disabled, not typing");
+            return;
+        }
+
+        for c in text.chars() {
+            self.type_unicode_char(c);
+        }
+    }
+
+    /// Enter a single character by its code point using the IBus/GTK
+    /// Ctrl+Shift+U sequence: the hex digits followed by space to commit
+    fn type_unicode_char(&mut self, c: char) {
+        trace!(?c, "Typing unicode character");
+
+        let _ = self.device.press(KEY_LEFTCTRL);
+        let _ = self.device.press(KEY_LEFTSHIFT);
+        let _ = self.device.press(KEY_U);
+        let _ = self.device.release(KEY_U);
+        let _ = self.device.release(KEY_LEFTSHIFT);
+        let _ = self.device.release(KEY_LEFTCTRL);
+
+        for digit in format!("{:x}", c as u32).chars() {
+            // Hex digits are on every supported layout
+            if let Some(stroke) = self.config.layout.resolve(digit) {
+                self.click_stroke(stroke);
+            }
+        }
+
+        let _ = self.device.press(KEY_SPACE);
+        let _ = self.device.release(KEY_SPACE);
+    }
+
+    fn toggle_sticky(&mut self, code: u16) {
+        if let Some(pos) = self.pending_modifiers.iter().position(|m| *m == code) {
+            trace!(?code, "Sticky modifier disarmed");
+            self.pending_modifiers.remove(pos);
+            self.play_toggle(false);
+        } else {
+            trace!(?code, "Sticky modifier armed");
+            self.pending_modifiers.push(code);
+            self.play_toggle(true);
+        }
+    }
+
+    /// Periodic work that isn't driven by an incoming MIDI message
+    pub fn tick(&mut self) {
+        let (mut dx, mut dy) = (0, 0);
+
+        // Smoothed motion of controls sending it to an extra mouse is moved separately
+        let mut routed = Vec::new();
+        for (cc, motion) in &mut self.mouse_smoothing {
+            let (x, y) = motion.drain();
+            match self
+                .config
+                .cc
+                .get_dir_config(*cc)
+                .and_then(|c| c.device.as_ref())
+            {
+                Some(device) if x != 0 || y != 0 => routed.push((device.clone(), x, y)),
+                _ => {
+                    dx += x;
+                    dy += y;
+                }
+            }
+        }
+
+        for (device, x, y) in routed {
+            self.route(Some(&device));
+            let _ = self.device.move_mouse(x, y);
+            self.route(None);
+        }
+
+        for (name, state) in &mut self.xy_pads {
+            if let Some(pad) = self.config.xy_pads.get(name) {
+                let (x, y) = state.motion(pad);
+                dx += x;
+                dy += y;
+            }
+        }
+
+        if dx != 0 || dy != 0 {
+            let _ = self.device.move_mouse(dx, dy);
+        }
+
+        let now = Instant::now();
+        let due: Vec<u8> = self
+            .pending_releases
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(note, _)| *note)
+            .collect();
+
+        for note in due {
+            self.pending_releases.remove(&note);
+            self.release_note(note);
+        }
+
+        for timer in self.timers.expire(now) {
+            match timer {
+                Timer::TapRelease(note) => {
+                    trace!(?note, "Tap over, releasing");
+                    self.release_note(note);
+                }
+                Timer::Turbo(note) => self.turbo_cycle(note, now),
+            }
+        }
+
+        // Notes whose NoteOff never arrived, e.g. lost or the device was unplugged mid-press
+        let stuck: Vec<u8> = self
+            .note_pressed_at
+            .iter()
+            .filter(|(note, pressed_at)| {
+                let max_hold = self
+                    .config
+                    .notes
+                    .get(**note)
+                    .and_then(|binding| binding.max_hold_ms)
+                    .or(self.config.max_hold_ms);
+
+                !self.pending_releases.contains_key(note)
+                    && !self.timers.is_scheduled(&Timer::TapRelease(**note))
+                    && max_hold.is_some_and(|max_hold| {
+                        now.duration_since(**pressed_at) >= Duration::from_millis(max_hold)
+                    })
+            })
+            .map(|(note, _)| *note)
+            .collect();
+
+        for note in stuck {
+            warn!(
+                ?note,
+                "Note held past max_hold_ms without a NoteOff, releasing it"
+            );
+            self.release_note(note);
+        }
+
+        let idle_release = self.config.idle_release_ms.map(Duration::from_millis);
+        if !self.held_keys.is_empty()
+            && idle_release.is_some_and(|idle| now.duration_since(self.last_message) >= idle)
+        {
+            error!(
+                keys = ?self.held_keys,
+                idle_release_ms = self.config.idle_release_ms,
+                "Keys held with no MIDI for idle_release_ms, releasing everything"
+            );
+            self.release_all();
+        }
+
+        // A dead cable doesn't unplug the port, but it does stop the Active Sensing
+        if self
+            .last_sensed
+            .is_some_and(|at| now.duration_since(at) > ACTIVE_SENSING_TIMEOUT)
+        {
+            warn!("The MIDI device stopped sending Active Sensing, releasing everything");
+            self.last_sensed = None;
+            self.release_all();
+            if let Some(sensing_lost) = &mut self.sensing_lost {
+                sensing_lost();
+            }
+        }
+
+        self.update_leds();
+    }
+
+    /// Light the pads whose binding's state changed
+    fn update_leds(&mut self) {
+        if self.leds.is_none() {
+            return;
+        }
+        let Some(feedback) = &self.config.feedback else {
+            return;
+        };
+
+        let colors = self.pad_colors(feedback);
+        if let Some(leds) = &mut self.leds {
+            leds.update(feedback, &colors);
+        }
+    }
+
+    /// The color of every pad bound in the current bank
+    fn pad_colors(&self, feedback: &config::FeedbackConfig) -> HashMap<u8, config::LedColor> {
+        (0..128)
+            .filter_map(|pad| {
+                let binding = self.config.notes.get(self.banked_note(pad))?;
+                let state =
+                    if self.note_pressed_at.contains_key(&pad) || self.sustained.contains(&pad) {
+                        led::PadState::Pressed
+                    } else if self.is_latched(&binding.action) {
+                        led::PadState::Latched
+                    } else if self.is_layer_live(&binding.action) {
+                        led::PadState::Layer
+                    } else {
+                        led::PadState::Idle
+                    };
+                Some((pad, led::color(feedback, pad, state)))
+            })
+            .collect()
+    }
+
+    /// Whether an action toggled something that's on right now
+    fn is_latched(&self, action: &Action) -> bool {
+        match action {
+            Action::Layer { name, toggle: true } => self.active_layers.contains(name),
+            Action::Sticky { code } => self.pending_modifiers.contains(code),
+            Action::Bypass => self.bypassed,
+            Action::Click {
+                button,
+                mode: config::ClickMode::Hold,
+                ..
+            } => self.held_keys.contains(&button.code()),
+            Action::Multi(actions) => actions.iter().any(|action| self.is_latched(action)),
+            Action::When { then, .. } => self.is_latched(then),
+            _ => false,
+        }
+    }
+
+    /// Whether an action is only triggered because its `when` layer is active, and it is
+    fn is_layer_live(&self, action: &Action) -> bool {
+        match action {
+            Action::When { when, then } => {
+                let on_layer = matches!(when.kind, ConditionKind::Layer(_)) && !when.negate;
+                (on_layer && self.check_condition(when)) || self.is_layer_live(then)
+            }
+            Action::Multi(actions) => actions.iter().any(|action| self.is_layer_live(action)),
+            _ => false,
+        }
+    }
+
+    /// Let go of a held turbo note's keys, or press them again if they're let go
+    fn turbo_cycle(&mut self, note: u8, now: Instant) {
+        let Some((half_period, released)) = self.turbo.get_mut(&note) else {
+            return;
+        };
+        *released = !*released;
+        let (half_period, released) = (*half_period, *released);
+
+        let keys = self.key_note_map.get(&note).cloned().unwrap_or_default();
+        if released {
+            for key in keys.into_iter().rev() {
+                self.release_key(key);
+            }
+        } else {
+            for key in keys {
+                self.press_key(key);
+            }
+        }
+
+        self.timers.schedule(now + half_period, Timer::Turbo(note));
+    }
+
+    /// Release everything a note pressed: its keys, in reverse order, and its momentary layers
+    fn release_note(&mut self, note: u8) {
+        self.note_pressed_at.remove(&note);
+        self.drags.remove(&note);
+
+        if let Some((_, released)) = self.turbo.remove(&note) {
+            self.timers.cancel(&Timer::Turbo(note));
+            // Its keys are already up
+            if released {
+                self.key_note_map.remove(&note);
+            }
+        }
+
+        if let Some(keys) = self.key_note_map.remove(&note) {
+            for key in keys.into_iter().rev() {
+                self.release_key(key);
+            }
+        }
+
+        for layer in self.layer_note_map.remove(&note).unwrap_or_default() {
+            trace!(?layer, "Layer deactivated");
+            self.active_layers.remove(&layer);
+        }
+
+        self.held_modifiers.retain(|(held_by, _)| *held_by != note);
+    }
+
+    /// The action a note triggers, or its `with` action for the last held modifier it lists
+    fn note_action(&self, note: u8) -> Option<Action> {
+        let binding = self.config.notes.get(self.banked_note(note))?;
+        let modified = self
+            .held_modifiers
+            .iter()
+            .rev()
+            .find_map(|(_, modifier)| binding.with.get(modifier));
+
+        Some(modified.unwrap_or(&binding.action).clone())
+    }
+
+    /// Move a Zones mode control into the zone its value falls in, releasing the key
+    /// of the zone it left
+    fn enter_zone(&mut self, channel: u8, control: ControlChange, zones: &[config::CCTarget]) {
+        let cc = control.control();
+        let zone = control.value() as usize * zones.len() / 128;
+
+        let previous = self.cc_zones.insert(cc, zone);
+        if previous == Some(zone) {
+            return;
+        }
+
+        trace!(?cc, ?previous, zone, "Control entered zone");
+
+        if let Some(config::CCTarget::Key(key)) = previous.and_then(|previous| zones.get(previous))
+        {
+            self.release_key(*key);
+        }
+
+        match &zones[zone] {
+            config::CCTarget::Key(key) => self.press_key(*key),
+            // Other actions can't be held, so they fire once when entering the zone
+            config::CCTarget::Action(action) => {
+                self.event = EventVars::for_cc(channel, control);
+                self.fire_action(action);
+            }
+            _ => {}
+        }
+    }
+
+    /// Work out which way and how far a CC moved, from how far a relative control says it
+    /// turned, or else since the last accepted value. Returns `None` if it didn't move enough
+    /// for an event
+    fn handle_cc(&mut self, cc: ControlChange) -> Option<CCMove> {
+        let val = cc.value();
+        let cc = cc.control();
+        let now = Instant::now();
+
+        let relative = self
+            .config
+            .cc
+            .get_dir_config(cc)
+            .filter(|c| c.mode == config::CCValueMode::Relative)
+            .map(|c| c.center);
+
+        let (step, last_event) = match relative {
+            Some(center) => {
+                let step = relative_step(val, center);
+                if step == 0 {
+                    trace!(?cc, ?val, "Relative CC didn't turn, ignoring");
+                    return None;
+                }
+                (step, self.cc_last_event.insert(cc, now))
+            }
+            None => self.absolute_step(cc, val, now)?,
+        };
+
+        let cc_config = self.config.cc.get_dir_config(cc);
+        let mut delta = step.unsigned_abs() as u8;
+
+        // Fine controllers only produce an event every `steps_per_event` steps in the same direction
+        let steps_per_event = cc_config.map_or(1, |c| c.steps_per_event.max(1)) as i32;
+        if steps_per_event > 1 {
+            let steps = self.cc_steps.entry(cc).or_insert(0);
+
+            // Turning back starts counting again
+            if steps.signum() != step.signum() {
+                *steps = 0;
+            }
+            *steps += step;
+
+            let events = *steps / steps_per_event;
+            if events == 0 {
+                trace!(
+                    ?cc,
+                    ?val,
+                    steps = *steps,
+                    "CC below steps_per_event, accumulating"
+                );
+                return None;
+            }
+
+            *steps -= events * steps_per_event;
+            delta = events.unsigned_abs() as u8;
+        }
+
+        // if value is less than last known value, we are turning counter-clockwise
+        // AKA, left key
+        let clockwise = (step > 0) != cc_config.is_some_and(|c| c.invert);
+        let direction = if clockwise {
+            CCDirection::Clockwise
+        } else {
+            CCDirection::CounterClockwise
+        };
+
+        Some(CCMove {
+            direction,
+            delta,
+            elapsed: last_event.map(|t| now.duration_since(t)),
+        })
+    }
+
+    /// The steps an absolute control moved since the last accepted value, and when that was.
+    /// Returns `None` if the change was filtered out by `min_delta`, `jitter` or `debounce_ms`,
+    /// in which case the last accepted value is kept so the change accumulates
+    fn absolute_step(&mut self, cc: u8, val: u8, now: Instant) -> Option<(i32, Option<Instant>)> {
+        let cc_config = self.config.cc.get_dir_config(cc);
+
+        let Some(&last) = self.cc_map.get(&cc) else {
+            trace!(?cc, ?val, "New CC value mapped");
+            self.cc_map.insert(cc, val);
+            self.cc_physical.insert(cc, val);
+            self.cc_last_event.insert(cc, now);
+
+            // Without a snapshot, there's nothing to compare the first value with
+            if self.config.snapshot.is_some() {
+                return None;
+            }
+            return Some((1, None));
+        };
+
+        let last_event = self.cc_last_event.get(&cc).copied();
+
+        // The physical position of the control, which may differ from the last accepted value
+        // while soft-takeover is waiting for it to pick up
+        let physical = self.cc_physical.insert(cc, val);
+
+        if cc_config.is_some_and(|c| c.soft_takeover)
+            && last.abs_diff(val) > SOFT_TAKEOVER_THRESHOLD
+        {
+            // A big jump is only a real move if it follows closely on one we accepted,
+            // or if the control crossed over the stored value on its way
+            let recent = last_event.is_some_and(|t| now.duration_since(t) < SOFT_TAKEOVER_WINDOW);
+            let crossed =
+                physical.is_some_and(|p| (p < last && val >= last) || (p > last && val <= last));
+
+            if !recent && !crossed {
+                trace!(
+                    ?cc,
+                    ?val,
+                    ?last,
+                    "Soft-takeover waiting for control to pick up"
+                );
+                return None;
+            }
+        }
+
+        // Endless encoders sending absolute values wrap from 127 to 0 and back,
+        // so a jump further than `wrap_threshold` is really the short way around
+        let mut step = val as i32 - last as i32;
+        if let Some(threshold) = cc_config.and_then(|c| c.wrap_threshold) {
+            if step.abs() > threshold as i32 {
+                trace!(?cc, ?val, ?last, "CC wrapped around");
+                step -= 128 * step.signum();
+            }
+        }
+
+        if step == 0 {
+            // A control pegged at either end can keep resending the same value as it's pushed further,
+            // which only means anything if it's configured to repeat there
+            let pegged = cc_config.is_some_and(|c| c.pegged_repeat) && (val == 0 || val == 127);
+            if !pegged {
+                trace!(?cc, ?val, "CC value unchanged, ignoring");
+                return None;
+            }
+
+            step = if val == 0 { -1 } else { 1 };
+        } else {
+            let min_delta = cc_config.map_or(0, |c| c.min_delta);
+            if step.unsigned_abs() < min_delta as u32 {
+                trace!(?cc, ?val, ?last, "CC change below min_delta, ignoring");
+                return None;
+            }
+
+            let jitter = cc_config.map_or(0, |c| c.jitter);
+            let turned_back = self
+                .cc_last_direction
+                .get(&cc)
+                .is_some_and(|&direction| direction != step.signum());
+            if turned_back && step.unsigned_abs() <= jitter as u32 {
+                trace!(?cc, ?val, ?last, "CC turned back within jitter, ignoring");
+                return None;
+            }
+        }
+
+        if let (Some(debounce_ms), Some(last_event)) =
+            (cc_config.and_then(|c| c.debounce_ms), last_event)
+        {
+            if now.duration_since(last_event) < Duration::from_millis(debounce_ms) {
+                trace!(?cc, ?val, "CC change within debounce window, coalescing");
+                return None;
+            }
+        }
+
+        self.cc_map.insert(cc, val);
+        self.cc_last_event.insert(cc, now);
+        self.cc_last_direction.insert(cc, step.signum());
+
+        Some((step, last_event))
+    }
+
+    /// Track the sustain pedal, releasing the notes it held once it lifts.
+    /// Returns true if the CC is the pedal
+    fn handle_sustain(&mut self, cc: ControlChange) -> bool {
+        if self.config.sustain.as_ref().map(|s| s.cc) != Some(cc.control()) {
+            return false;
+        }
+
+        let down = cc.value() >= 64;
+        if down != self.sustain_down {
+            trace!(down, "Sustain pedal");
+            self.sustain_down = down;
+
+            if !down {
+                for note in std::mem::take(&mut self.sustained) {
+                    self.release_note(note);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Move the cursor with a CC that's driving a drag in progress. Returns true if it was consumed
+    fn handle_drag(&mut self, cc: ControlChange) -> bool {
+        let (control, value) = (cc.control(), cc.value());
+
+        let Some((dx, dy)) = self.drags.values().find_map(|(x, y, speed)| {
+            let (x, y) = (*x == Some(control), *y == Some(control));
+            (x || y).then_some((if x { *speed } else { 0 }, if y { *speed } else { 0 }))
+        }) else {
+            return false;
+        };
+
+        // Keep the CC's regular tracking in step, so its own binding doesn't see
+        // the whole drag as one jump once the drag is over
+        let last = self.cc_physical.insert(control, value);
+        self.cc_map.insert(control, value);
+
+        if let Some(last) = last {
+            let delta = value as i32 - last as i32;
+            trace!(cc = control, delta, "Dragging");
+            let _ = self.device.move_mouse(dx * delta, dy * delta);
+        }
+
+        true
+    }
+
+    /// Turn the horizontal and vertical scroll wheels, in high-resolution wheel units
+    fn scroll(&mut self, (h, v): (i32, i32)) {
+        if h != 0 {
+            let _ = self.device.scroll_x(h);
+        }
+        if v != 0 {
+            let _ = self.device.scroll_y(v);
+        }
+    }
+
+    /// Feed a CC to the XY pad using it, if any. Returns true if it was consumed
+    fn handle_xy_pad(&mut self, cc: ControlChange) -> bool {
+        let (control, value) = (cc.control(), cc.value());
+
+        let Some((name, pad)) = self
+            .config
+            .xy_pads
+            .iter()
+            .find(|(_, pad)| pad.x == control || pad.y == control)
+        else {
+            return false;
+        };
+
+        trace!(pad = ?name, cc = control, value, "XY pad moved");
+
+        self.xy_pads
+            .entry(name.clone())
+            .or_default()
+            .update(pad, control, value)
+    }
+
+    /// Send the following events to a binding's extra device, if it has one,
+    /// or back to the usual devices with `None`
+    fn route(&mut self, device: Option<&str>) {
+        if device.is_some() || self.routed {
+            self.routed = device.is_some();
+            self.device.route(device);
+        }
+    }
+
+    /// Run the action bound to a note that was just pressed
+    fn note_on_action(&mut self, note: u8, action: &Action) {
+        match action {
+            Action::Key(key) => {
+                self.press_key(*key);
+                self.key_note_map.entry(note).or_default().push(*key);
+            }
+            Action::Sticky { code } => self.toggle_sticky(*code),
+            Action::Panic => self.release_all(),
+            Action::Bypass => self.toggle_bypass(),
+            Action::Type { text } => self.type_text(text),
+            Action::Char { char } => match self.config.layout.resolve(*char) {
+                Some(stroke) => {
+                    for key in stroke.modifiers().chain([stroke.code]) {
+                        self.press_key(key);
+                        self.key_note_map.entry(note).or_default().push(key);
+                    }
+                }
+                None => {
+                    warn!(?char, layout = ?self.config.layout, "Character can't be typed on this layout");
+                }
+            },
+            Action::Unicode { text } => self.type_unicode(text),
+            Action::Command { run } => run_command(&self.event.expand(run)),
+            Action::Compositor { command } => compositor::send(self.event.expand(command)),
+            Action::Notify { text } => self.notify(&self.event.expand(text)),
+            Action::Volume { target } => self.change_volume(target, volume::Change::ToggleMute),
+            Action::ObsScene { .. } | Action::ObsSource { .. } | Action::ObsRecord { .. } => {
+                self.send_obs(action)
+            }
+            Action::Layer { name, toggle: true } => {
+                if !self.active_layers.remove(name) {
+                    self.active_layers.insert(name.clone());
+                }
+                let active = self.active_layers.contains(name);
+                info!(layer = ?name, active, "Layer toggled");
+                self.play_toggle(active);
+
+                if self.notifications().layers {
+                    let state = if active { "on" } else { "off" };
+                    self.notify(&format!("Layer {name} {state}"));
+                }
+            }
+            Action::Layer {
+                name,
+                toggle: false,
+            } => {
+                trace!(layer = ?name, "Layer activated");
+                self.active_layers.insert(name.clone());
+                self.layer_note_map
+                    .entry(note)
+                    .or_default()
+                    .push(name.clone());
+            }
+            Action::Modifier { name } => {
+                trace!(modifier = ?name, "Modifier held");
+                self.held_modifiers.push((note, name.clone()));
+            }
+            Action::BankUp => self.step_bank(1),
+            Action::BankDown => self.step_bank(-1),
+            Action::Drag {
+                button,
+                x,
+                y,
+                speed,
+            } => {
+                trace!(?button, ?x, ?y, "Drag started");
+                self.press_key(button.code());
+                self.key_note_map
+                    .entry(note)
+                    .or_default()
+                    .push(button.code());
+                self.drags.insert(note, (*x, *y, *speed));
+            }
+            Action::MouseMove {
+                dx,
+                dy,
+                velocity_curve,
+            } => {
+                let scale = velocity_curve
+                    .zip(self.event.velocity)
+                    .map_or(1.0, |(curve, velocity)| {
+                        (velocity as f32 / 127.0).powf(curve)
+                    });
+                let scaled = |distance: i32| (distance as f32 * scale).round() as i32;
+                let _ = self.device.move_mouse(scaled(*dx), scaled(*dy));
+            }
+            Action::Click {
+                button,
+                count,
+                mode,
+            } => match mode {
+                config::ClickMode::Click | config::ClickMode::DoubleClick => {
+                    let per_count = if *mode == config::ClickMode::DoubleClick {
+                        2
+                    } else {
+                        1
+                    };
+                    for _ in 0..*count as u16 * per_count {
+                        let _ = self.device.click(button.code());
+                    }
+                }
+                // Not tied to the note, so it stays down after NoteOff
+                config::ClickMode::Hold => {
+                    trace!(?button, "Mouse button held");
+                    self.press_key(button.code());
+                }
+                config::ClickMode::Release => {
+                    trace!(?button, "Mouse button released");
+                    self.release_key(button.code());
+                }
+            },
+            Action::Multi(actions) => {
+                for action in actions {
+                    self.note_on_action(note, action);
+                }
+            }
+            Action::When { when, then } => {
+                if self.check_condition(when) {
+                    self.note_on_action(note, then);
+                }
+            }
+        }
+    }
+
+    fn change_volume(&mut self, target: &config::VolumeTarget, change: volume::Change) {
+        trace!(?target, ?change, "Volume change");
+        self.mixer
+            .get_or_insert_with(volume::Mixer::spawn)
+            .change(target, change);
+    }
+
+    /// Whether a `when` condition currently holds
+    fn check_condition(&self, condition: &Condition) -> bool {
+        let holds = match &condition.kind {
+            ConditionKind::Layer(layer) => self.active_layers.contains(layer),
+            ConditionKind::Key(key) => self.held_keys.contains(key),
+        };
+
+        holds != condition.negate
+    }
+
+    /// Track notes on MPE member channels and route their per-note expression.
+    /// Returns true if the message was fully consumed
+    fn handle_mpe(&mut self, channel: u8, msg: ChannelVoiceMsg) -> bool {
+        let Some(mpe) = self.config.mpe.as_ref() else {
+            return false;
+        };
+
+        if !mpe.is_member(channel) {
+            return false;
+        }
+
+        let (note, delta, axis, scale) = match msg {
+            ChannelVoiceMsg::NoteOn { note, .. } => {
+                self.mpe.note_on(channel, note);
+                return false;
+            }
+            ChannelVoiceMsg::NoteOff { .. } => {
+                self.mpe.note_off(channel);
+                return false;
+            }
+            ChannelVoiceMsg::PitchBend { bend } => {
+                let Some((note, delta)) =
+                    self.mpe.pitch_bend(channel, bend, mpe.pitch_bend_dead_zone)
+                else {
+                    return true;
+                };
+                (note, delta, &mpe.route(note).pitch_bend, 1.0 / 64.0)
+            }
+            ChannelVoiceMsg::ChannelPressure { pressure } => {
+                let Some((note, delta)) = self.mpe.pressure(channel, pressure) else {
+                    return true;
+                };
+                (note, delta, &mpe.route(note).pressure, 1.0)
+            }
+            _ => return false,
+        };
+
+        let amount = (delta as f32 * scale * mpe.sensitivity).round() as i32;
+        let (dx, dy) = axis_motion(*axis, amount);
+
+        trace!(?channel, ?note, ?delta, ?dx, ?dy, "MPE expression");
+
+        if dx != 0 || dy != 0 {
+            let _ = self.device.move_mouse(dx, dy);
+        }
+
+        true
+    }
+
+    /// Move a message to the channel `channel_map` treats its channel as
+    fn remap_channel(&self, msg: MidiMsg) -> MidiMsg {
+        let remap = |channel: Channel| {
+            let mapped = self.config.map_channel(channel as u8 + 1);
+            Channel::from_u8(mapped - 1)
+        };
+
+        match msg {
+            MidiMsg::ChannelVoice { channel, msg } => MidiMsg::ChannelVoice {
+                channel: remap(channel),
+                msg,
+            },
+            MidiMsg::ChannelMode { channel, msg } => MidiMsg::ChannelMode {
+                channel: remap(channel),
+                msg,
+            },
+            msg => msg,
+        }
+    }
+
+    /// Turn a NoteOn with velocity 0 into the NoteOff it stands for, unless `zero_velocity`
+    /// makes it a press of its own
+    fn zero_velocity_note_off(&self, msg: MidiMsg) -> MidiMsg {
+        match msg {
+            MidiMsg::ChannelVoice {
+                channel,
+                msg: ChannelVoiceMsg::NoteOn { note, velocity: 0 },
+            } if self.config.zero_velocity() == ZeroVelocity::NoteOff => MidiMsg::ChannelVoice {
+                channel,
+                msg: ChannelVoiceMsg::NoteOff { note, velocity: 0 },
+            },
+            msg => msg,
+        }
+    }
+
+    /// Whether a note played on a channel (1-16) reaches its binding, going by the binding's
+    /// own `channel` or else `note_channels`
+    fn note_channel_allowed(&self, note: u8, channel: u8) -> bool {
+        let only = self
+            .config
+            .notes
+            .get(self.banked_note(note))
+            .and_then(|binding| binding.channel);
+
+        match only {
+            Some(only) => only == channel,
+            None => {
+                self.config.note_channels.is_empty() || self.config.note_channels.contains(&channel)
+            }
+        }
+    }
+
+    /// Count a note or control the controller sent that has no binding
+    fn note_unbound(&mut self, msg: &MidiMsg) {
+        if !self.config.report_unbound || self.bypassed {
+            return;
+        }
+
+        let control = match msg {
+            MidiMsg::ChannelVoice {
+                msg: ChannelVoiceMsg::NoteOn { note, velocity },
+                ..
+            } if *velocity > 0 => ("note", *note),
+            MidiMsg::ChannelVoice {
+                msg: ChannelVoiceMsg::ControlChange { control },
+                ..
+            } => ("cc", control.control()),
+            _ => return,
+        };
+        *self.unbound.entry(control).or_default() += 1;
+    }
+
+    /// Log the notes and controls played without a binding, with `report_unbound`
+    pub fn report_unbound(&self) {
+        if self.unbound.is_empty() {
+            return;
+        }
+
+        let unbound: Vec<String> = self
+            .unbound
+            .iter()
+            .map(|((kind, number), count)| format!("{kind} {number} ({count}x)"))
+            .collect();
+        warn!(
+            unbound = unbound.join(", "),
+            "Played notes and controls with no binding"
+        );
+    }
+
+    /// Whether a message is bound to something, so it shouldn't be passed through
+    fn is_bound(&self, msg: &MidiMsg) -> bool {
+        if self.bypassed {
+            return self.is_bypass_toggle(msg);
+        }
+
+        if self.transport_action(msg).is_some() {
+            return true;
+        }
+
+        let MidiMsg::ChannelVoice { channel, msg } = msg else {
+            return false;
+        };
+
+        match msg {
+            ChannelVoiceMsg::NoteOn { note, .. } | ChannelVoiceMsg::NoteOff { note, .. } => {
+                self.config.notes.get(self.banked_note(*note)).is_some()
+                    && self.note_channel_allowed(*note, *channel as u8 + 1)
+            }
+            ChannelVoiceMsg::ControlChange { control } => {
+                let cc = control.control();
+                self.config.cc.get_dir_config(cc).is_some()
+                    || self.config.sustain.as_ref().is_some_and(|s| s.cc == cc)
+                    || self
+                        .drags
+                        .values()
+                        .any(|(x, y, _)| *x == Some(cc) || *y == Some(cc))
+                    || self
+                        .config
+                        .xy_pads
+                        .values()
+                        .any(|pad| pad.x == cc || pad.y == cc)
+            }
+            ChannelVoiceMsg::PitchBend { .. } if self.config.pitch_bend.is_some() => true,
+            ChannelVoiceMsg::PitchBend { .. } | ChannelVoiceMsg::ChannelPressure { .. } => self
+                .config
+                .mpe
+                .as_ref()
+                .is_some_and(|mpe| mpe.is_member(*channel as u8 + 1)),
+            _ => false,
+        }
+    }
+
+    /// Pass a message midkb doesn't use on to the thru port
+    fn pass_through(&mut self, msg: &MidiMsg) {
+        if let Some(thru) = &mut self.thru {
+            if let Err(e) = thru.send(&msg.to_midi()) {
+                warn!(%e, "Failed to pass MIDI message through");
+            }
+        }
+    }
+
+    pub fn handle_midi_msg(&mut self, msg: MidiMsg) {
+        if self.bypassed && !self.is_bypass_toggle(&msg) {
+            return;
+        }
+
+        // handle ChannelVoice messages and the inner data
+
+        if let MidiMsg::ChannelVoice { channel, msg } = msg {
+            let channel = channel as u8 + 1;
+
+            if self.handle_mpe(channel, msg) {
+                return;
+            }
+
+            if let ChannelVoiceMsg::NoteOn { note, .. } | ChannelVoiceMsg::NoteOff { note, .. } =
+                msg
+            {
+                if !self.note_channel_allowed(note, channel) {
+                    trace!(channel, note, "Note on a filtered out channel, ignoring it");
+                    return;
+                }
+            }
+
+            match msg {
+                ChannelVoiceMsg::NoteOn { note, velocity } => {
+                    // self.device.press(KEY_H);
+                    // A bounced note pressed again before its deferred release lets go first
+                    if self.pending_releases.remove(&note).is_some()
+                        || self.timers.cancel(&Timer::TapRelease(note))
+                        || self.sustained.remove(&note)
+                    {
+                        self.release_note(note);
+                    }
+
+                    // Keyed by the binding, which changes with the bank
+                    let binding = self.banked_note(note);
+                    let cooldown_ms = self
+                        .config
+                        .notes
+                        .get(binding)
+                        .and_then(|binding| binding.cooldown_ms);
+                    if cooling_down(&mut self.note_fired_at, binding, cooldown_ms) {
+                        trace!(?note, "Note pressed again within its cooldown, ignoring it");
+                        return;
+                    }
+
+                    if let Some(action) = self.note_action(note) {
+                        debug!(channel, note, velocity, ?action, "Note on");
+                        self.event = EventVars {
+                            channel: Some(channel),
+                            note: Some(note),
+                            velocity: Some(velocity),
+                            ..Default::default()
+                        };
+                        self.record_event("note_on", channel, note, velocity, || {
+                            format!("{action:?}")
+                        });
+                        let now = Instant::now();
+                        self.note_pressed_at.insert(note, now);
+
+                        let (device, tap_ms, turbo_hz) = self
+                            .config
+                            .notes
+                            .get(self.banked_note(note))
+                            .map_or((None, None, None), |binding| {
+                                (binding.device.clone(), binding.tap_ms, binding.turbo_hz)
+                            });
+                        self.route(device.as_deref());
+                        self.note_on_action(note, &action);
+                        self.route(None);
+
+                        if let Some(tap_ms) = tap_ms {
+                            self.timers.schedule(
+                                now + Duration::from_millis(tap_ms),
+                                Timer::TapRelease(note),
+                            );
+                        }
+
+                        let half_period =
+                            turbo_hz.and_then(|hz| Duration::try_from_secs_f32(0.5 / hz).ok());
+                        if let Some(half_period) = half_period {
+                            self.turbo.insert(note, (half_period, false));
+                            self.timers.schedule(now + half_period, Timer::Turbo(note));
+                        }
+                    }
+
+                    // if let Some(key) = hardcode_notes(note) {
+                    //     let _ = self.device.press(key);
+                    // }
+                }
+                ChannelVoiceMsg::NoteOff { note, velocity: _ } => {
+                    // self.device.release(KEY_H);
+                    if self.timers.is_scheduled(&Timer::TapRelease(note)) {
+                        trace!(?note, "Note released during its tap, holding until tap_ms");
+                        return;
+                    }
+
+                    if self.sustain_down && self.note_pressed_at.contains_key(&note) {
+                        trace!(
+                            ?note,
+                            "Note released while sustained, holding until the pedal lifts"
+                        );
+                        self.sustained.insert(note);
+                        return;
+                    }
+
+                    let min_hold = self
+                        .config
+                        .notes
+                        .get(self.banked_note(note))
+                        .and_then(|binding| binding.min_hold_ms)
+                        .map(Duration::from_millis);
+
+                    let release_at = self
+                        .note_pressed_at
+                        .get(&note)
+                        .zip(min_hold)
+                        .map(|(pressed_at, min_hold)| *pressed_at + min_hold)
+                        .filter(|release_at| *release_at > Instant::now());
+
+                    match release_at {
+                        Some(release_at) => {
+                            trace!(?note, "Note released early, holding until min_hold_ms");
+                            self.pending_releases.insert(note, release_at);
+                        }
+                        None => self.release_note(note),
+                    }
+                }
+
+                ChannelVoiceMsg::ControlChange { control } => {
+                    let control = self.calibrated(control);
+                    if self.handle_sustain(control)
+                        || self.handle_drag(control)
+                        || self.handle_xy_pad(control)
+                    {
+                        return;
+                    }
+
+                    let cc_move = self.handle_cc(control);
+
+                    debug!(
+                        channel,
+                        cc = control.control(),
+                        value = control.value(),
+                        ?cc_move,
+                        "Control change"
+                    );
+
+                    if let Some(cc_config) = self.config.cc.shared_dir_config(control.control()) {
+                        trace!(?cc_config);
+                        self.route(cc_config.device.as_deref());
+
+                        let stepped = matches!(
+                            cc_config.bind_mode,
+                            config::CCBindMode::Keyboard
+                                | config::CCBindMode::Mouse
+                                | config::CCBindMode::Command
+                        );
+
+                        if !stepped || cc_move.is_some() {
+                            let action = || match (stepped, &cc_move) {
+                                (true, Some(cc_move)) => {
+                                    format!("{:?} {:?}", cc_config.bind_mode, cc_move.direction)
+                                }
+                                _ => format!("{:?}", cc_config.bind_mode),
+                            };
+                            self.record_event(
+                                "cc",
+                                channel,
+                                control.control(),
+                                control.value(),
+                                action,
+                            );
+                        }
+
+                        match (&cc_config.bind_mode, cc_move) {
+                            (
+                                config::CCBindMode::Keyboard
+                                | config::CCBindMode::Mouse
+                                | config::CCBindMode::Command,
+                                None,
+                            ) => {
+                                trace!("CC change filtered out");
+                            }
+                            (config::CCBindMode::Command, Some(_)) => {
+                                let vars = EventVars::for_cc(channel, control);
+
+                                if let Some(run) = &cc_config.run {
+                                    run_command(&vars.expand(run));
+                                }
+                            }
+                            (config::CCBindMode::Keyboard, Some(cc_move)) => {
+                                let target = match cc_move.direction {
+                                    CCDirection::CounterClockwise => &cc_config.counter_clockwise,
+                                    CCDirection::Clockwise => &cc_config.clockwise,
+                                };
+
+                                let taps = if cc_config.repeat_acceleration {
+                                    cc_move.repeat_count(cc_config.max_repeat)
+                                } else {
+                                    1
+                                };
+
+                                match target {
+                                    Some(config::CCTarget::Key(key)) => {
+                                        for _ in 0..taps {
+                                            self.tap_key(*key);
+                                        }
+                                    }
+                                    Some(config::CCTarget::Action(action)) => {
+                                        self.event = EventVars::for_cc(channel, control);
+                                        for _ in 0..taps {
+                                            self.fire_action(action);
+                                        }
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            (config::CCBindMode::Mouse, Some(cc_move)) => {
+                                let direction = cc_move.direction;
+                                let speed = mouse_distance(&cc_config, cc_move.delta);
+
+                                let axis = match direction {
+                                    CCDirection::CounterClockwise => &cc_config.counter_clockwise,
+                                    CCDirection::Clockwise => &cc_config.clockwise,
+                                };
+
+                                let amount = match direction {
+                                    CCDirection::CounterClockwise => -speed,
+                                    CCDirection::Clockwise => speed,
+                                };
+
+                                match axis {
+                                    Some(config::CCTarget::Scroll(wheel)) => {
+                                        let units = if cc_config.hi_res {
+                                            cc_move.delta as i32 * HI_RES_WHEEL_STEP
+                                        } else {
+                                            WHEEL_NOTCH
+                                        };
+                                        self.scroll(wheel.motion(amount.signum() * units));
+                                    }
+                                    Some(config::CCTarget::Axis(axis)) => {
+                                        let (dx, dy) = axis.motion(amount);
+
+                                        match cc_config.smoothing {
+                                            Some(factor) => self
+                                                .mouse_smoothing
+                                                .entry(control.control())
+                                                .or_insert_with(|| SmoothedMotion::new(factor))
+                                                .push(dx, dy),
+                                            None => {
+                                                let _ = self.device.move_mouse(dx, dy);
+                                            }
+                                        }
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            (config::CCBindMode::Toggle, _) => {
+                                // Check the current velocity of the control change
+                                // It should either be 0 or 127
+
+                                // todo: probably make the velocity threshold configurable
+
+                                let velocity = control.value();
+                                if velocity == 127
+                                    && cooling_down(
+                                        &mut self.cc_fired_at,
+                                        control.control(),
+                                        cc_config.cooldown_ms,
+                                    )
+                                {
+                                    trace!(
+                                        cc = control.control(),
+                                        "CC switched on again within its cooldown, ignoring it"
+                                    );
+                                    self.route(None);
+                                    return;
+                                }
+
+                                match &cc_config.clockwise {
+                                    Some(config::CCTarget::Key(cw_key)) => {
+                                        if velocity == 127 {
+                                            self.press_key(*cw_key);
+                                        } else if velocity == 0 {
+                                            self.release_key(*cw_key);
+                                        }
+                                    }
+                                    // Other actions can't be held, so they fire once when switched on
+                                    Some(config::CCTarget::Action(action)) if velocity == 127 => {
+                                        self.event = EventVars::for_cc(channel, control);
+                                        self.fire_action(action);
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            (config::CCBindMode::Panic, _) => {
+                                if control.value() > 0 {
+                                    self.release_all();
+                                }
+                            }
+                            (config::CCBindMode::Gamepad, _) => {
+                                if let Some(axis) = &cc_config.gamepad {
+                                    let position = gamepad::position(
+                                        axis,
+                                        control.value() as u16,
+                                        gamepad::CC_MAX,
+                                        config::RestPosition::Bottom,
+                                    );
+                                    let _ = self.device.move_axis(axis.axis.code(), position);
+                                }
+                            }
+                            (config::CCBindMode::Volume, _) => {
+                                let volume = cc_config.volume.clone().unwrap_or_default();
+                                let percent = volume::percent(control.value(), volume.max);
+                                self.change_volume(&volume.target, volume::Change::Set(percent));
+                            }
+                            (config::CCBindMode::Brightness, _) => {
+                                let brightness = cc_config.brightness.clone().unwrap_or_default();
+                                let percent = brightness::percent(
+                                    control.value(),
+                                    brightness.min,
+                                    brightness.max,
+                                );
+                                trace!(percent, "Brightness change");
+                                self.backlight
+                                    .get_or_insert_with(brightness::Backlight::spawn)
+                                    .set(brightness.device.as_deref(), percent);
+                            }
+                            (config::CCBindMode::Zones, _) => {
+                                self.enter_zone(channel, control, &cc_config.zones);
+                            }
+                            (config::CCBindMode::BankUp, _) => {
+                                if control.value() > 0 {
+                                    self.step_bank(1);
+                                }
+                            }
+                            (config::CCBindMode::BankDown, _) => {
+                                if control.value() > 0 {
+                                    self.step_bank(-1);
+                                }
+                            }
+                        }
+
+                        self.route(None);
+                    }
+                }
+
+                ChannelVoiceMsg::PitchBend { bend } => match &self.config.pitch_bend {
+                    Some(config::PitchBendConfig::Gamepad(axis)) => {
+                        let position = gamepad::position(
+                            axis,
+                            bend,
+                            gamepad::PITCH_BEND_MAX,
+                            config::RestPosition::Center,
+                        );
+                        trace!(bend, position, "Pitch bend");
+                        let (code, device) = (axis.axis.code(), axis.device.clone());
+                        self.route(device.as_deref());
+                        let _ = self.device.move_axis(code, position);
+                        self.route(None);
+                    }
+                    Some(config::PitchBendConfig::Keys(keys)) => {
+                        let keys = keys.clone();
+                        self.pitch_bend_keys(channel, bend, &keys);
+                    }
+                    None => {}
+                },
+
+                _ => {}
+            }
+        }
+
+        if let Some((event, number, action)) = self.transport_action(&msg) {
+            let action = action.clone();
+            debug!(event, number, ?action, "Transport");
+            self.event = EventVars::default();
+            self.record_event(event, 0, number, 0, || format!("{action:?}"));
+            self.fire_action(&action);
+        }
+
+        match msg {
+            MidiMsg::ChannelMode {
+                msg: ChannelModeMsg::AllNotesOff,
+                ..
+            } => {
+                trace!("All Notes Off received");
+                self.release_all();
+            }
+            MidiMsg::ChannelMode {
+                msg: ChannelModeMsg::AllSoundOff | ChannelModeMsg::ResetAllControllers,
+                ..
+            }
+            | MidiMsg::SystemRealTime {
+                msg: SystemRealTimeMsg::SystemReset,
+            } => {
+                debug!(?msg, "Controller reset, forgetting its state");
+                self.reset();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Drop for MidiInputHandler {
+    // Runs before the virtual device is dropped, so nothing is left stuck down
+    fn drop(&mut self) {
+        self.release_all();
+    }
+}
+
+fn midi_msg_callback(
+    received: Instant,
+    time: u64,
+    midimsg: &[u8],
+    parser: &mut MidiParser,
+    input: &mut MidiInputHandler,
+) {
+    trace!(?time, "MIDI Message: {:02X?}", midimsg);
+
+    // parse midi message
+
+    let messages = parser.feed(midimsg);
+    for bytes in parser.take_unparsed() {
+        input.record_unparsed(time, &bytes);
+    }
+
+    for msg in messages {
+        trace!(?msg, "Parsed MIDI message");
+        input.received(&msg);
+
+        // Don't let a panic take the worker down, and make sure nothing is left held down
+        let mut bound = false;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            input.router.route(&msg);
+            let msg = input.remap_channel(msg);
+            let msg = input.zero_velocity_note_off(msg);
+
+            bound = input.is_bound(&msg);
+            if !bound {
+                input.pass_through(&msg);
+                input.note_unbound(&msg);
+            }
+
+            input.handle_midi_msg(msg);
+        }));
+
+        if result.is_err() {
+            error!("Panicked while handling MIDI message, releasing all held keys");
+            input.release_all();
+        }
+
+        let latency = received.elapsed();
+        debug!(
+            latency_us = latency.as_micros() as u64,
+            "MIDI message handled"
+        );
+
+        if let (true, Some(stats)) = (bound, &mut input.latency) {
+            stats.record(latency);
+        }
+    }
+}
+
+/// Run the handler's timers, releasing all held keys if that panics instead of leaving them stuck
+fn tick(input: &mut MidiInputHandler) {
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| input.tick()));
+    if result.is_err() {
+        error!("Panicked while running timers, releasing all held keys");
+        input.release_all();
+    }
+}
+
+/// Run midkb with the command line's arguments
+pub fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string()));
+
+    match cli.log_format {
+        LogFormat::Json => subscriber.json().flatten_event(true).init(),
+        // journald timestamps every line itself and doesn't render colors
+        LogFormat::Text if cli.daemon => subscriber.without_time().with_ansi(false).init(),
+        LogFormat::Text => subscriber.init(),
+    }
+
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("{e}");
+            if let Some(hint) = e.hint() {
+                eprintln!("\n{hint}");
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Whether a binding with a `cooldown_ms` fired less than that long ago. If not, it fires now
+fn cooling_down(fired_at: &mut HashMap<u8, Instant>, number: u8, cooldown_ms: Option<u64>) -> bool {
+    let Some(cooldown_ms) = cooldown_ms else {
+        return false;
+    };
+
+    let now = Instant::now();
+    let cooldown = Duration::from_millis(cooldown_ms);
+    if fired_at
+        .get(&number)
+        .is_some_and(|fired_at| now.duration_since(*fired_at) < cooldown)
+    {
+        return true;
+    }
+
+    fired_at.insert(number, now);
+    false
+}
+
+/// Connect to the controller, letting the handler know once it's connected
+fn connect(
+    source: &dyn input::MidiSource,
+    midi_sender: &worker::MidiSender,
+    handler: &Arc<Mutex<MidiInputHandler>>,
+) -> Option<Box<dyn input::Connection>> {
+    let connection = source.connect(midi_sender.new_source())?;
+    lock(handler).connected();
+    Some(connection)
+}
+
+fn run(cli: Cli) -> error::Result<()> {
+    match &cli.command {
+        Some(cli::Command::Schema) => {
+            println!("{}", Config::json_schema());
+            return Ok(());
+        }
+        Some(cli::Command::Map { profile }) => {
+            let config_path = cli.config_path();
+            let mut config = cli.load_config(&config_path)?;
+            if profile.is_some() {
+                config
+                    .switch_profile(profile.as_deref())
+                    .map_err(|message| error::Error::InvalidConfig {
+                        path: config_path,
+                        message,
+                    })?;
+            }
+
+            print!("{}", map::MappingTable::new(&config));
+            return Ok(());
+        }
+        Some(cli::Command::Export {
+            format,
+            profile,
+            template,
+        }) => {
+            let config_path = cli.config_path();
+            let mut config = cli.load_config(&config_path)?;
+            if profile.is_some() {
+                config
+                    .switch_profile(profile.as_deref())
+                    .map_err(|message| error::Error::InvalidConfig {
+                        path: config_path.clone(),
+                        message,
+                    })?;
+            }
+
+            let mut title = format!("midkb: {}", config_path.display());
+            if let Some(profile) = &config.active_profile {
+                title.push_str(&format!(" ({profile})"));
+            }
+            let template = template.as_deref().or(cli.preset.as_deref());
+            print!(
+                "{}",
+                export::export(&map::MappingTable::new(&config), &title, *format, template)
+            );
+            return Ok(());
+        }
+        Some(cli::Command::Monitor) => {
+            let config_path = cli.config_path();
+            let config = match (cli.load_config(&config_path), &cli.device) {
+                (Ok(config), _) => config,
+                // The device is all there is to know, so the config file is optional
+                (Err(error::Error::ReadConfig { .. }), Some(device)) => {
+                    let mut config = Config::default();
+                    config.midi_device = device.clone();
+                    config
+                }
+                (Err(e), _) => return Err(e),
+            };
+
+            return monitor::run(config, &config_path);
+        }
+        Some(cli::Command::Simulate) => {
+            simulate::run(cli.load_config(&cli.config_path())?);
+            return Ok(());
+        }
+        Some(cli::Command::Calibrate) => {
+            let config_path = cli.config_path();
+            return calibrate::run(cli.load_config(&config_path)?, &config_path);
+        }
+        Some(cli::Command::Edit) => {
+            let config_path = cli.config_path();
+            return editor::run(cli.load_config(&config_path)?, &config_path);
+        }
+        None => {}
+    }
+
+    tracing::info!("Starting up");
+    let config_path = cli.config_path();
+    info!(?config_path, "Loading config");
+    let mut config = cli.load_config(&config_path)?;
+    map::MappingTable::report(&config);
+
+    // Let the user pick a port instead of waiting for one that may never show up
+    let interactive = !cli.daemon && std::io::stdin().is_terminal();
+    if interactive
+        && config.backend == Backend::Alsa
+        && !input::source(&InputTarget::from_config(&config)).is_present()
+    {
+        picker::pick_device(&mut config, &config_path);
+    }
+
+    // Signals are taken before any threads start, so none of them gets killed by one
+    let (events_tx, events) = std::sync::mpsc::channel();
+    let signals_tx = events_tx.clone();
+    signals::watch(move |signal| {
+        let event = match signal {
+            signals::Signal::Hangup => {
+                info!("Received SIGHUP");
+                Event::Reload
+            }
+            signal => Event::Signal(signal),
+        };
+        let _ = signals_tx.send(event);
+    })
+    .map_err(error::Error::Signal)?;
+
+    // Only the network services run on tokio, so a build without them doesn't start it
+    #[cfg(feature = "tokio")]
+    let runtime = tokio::runtime::Runtime::new().map_err(error::Error::Runtime)?;
+    #[cfg(feature = "tokio")]
+    let _runtime = runtime.enter();
+
+    let device = VirtualOutput::new(&config.devices).map_err(error::Error::VirtualDevice)?;
+
+    let mut target = InputTarget::from_config(&config);
+    let mut source = input::source(&target);
+    let http = config.http.clone();
+    let web_ui = config.web_ui.clone();
+    let mqtt = config.mqtt.clone();
+    let obs = config.obs.clone();
+    let input_handler = Arc::new(Mutex::new(MidiInputHandler::new(Box::new(device), config)));
+    let midi_sender = worker::spawn(input_handler.clone());
+
+    // Network, Bluetooth and virtual port MIDI come in alongside the local device
+    let extra_sources = input::extra_sources(lock(&input_handler).config(), &input_handler);
+    let network_input = !extra_sources.is_empty();
+    let extra_connections: Vec<_> = extra_sources
+        .iter()
+        .filter_map(|source| source.connect(midi_sender.new_source()))
+        .collect();
+
+    #[cfg(feature = "http")]
+    if let Some(http_config) = http {
+        tokio::spawn(http::listen(http_config, input_handler.clone()));
+    }
+    #[cfg(not(feature = "http"))]
+    if http.is_some() {
+        error!("midkb was built without the HTTP API, rebuild it with `--features http`");
+    }
+
+    // Configs saved from the web UI are reloaded like on SIGHUP
+    #[cfg(feature = "web-ui")]
+    if let Some(web_ui_config) = web_ui {
+        let reload_tx = events_tx.clone();
+        tokio::spawn(web_ui::listen(
+            web_ui_config,
+            input_handler.clone(),
+            config_path.clone(),
+            Arc::new(move || {
+                let _ = reload_tx.send(Event::Reload);
+            }),
+        ));
+    }
+    #[cfg(not(feature = "web-ui"))]
+    if web_ui.is_some() {
+        error!("midkb was built without the web UI, rebuild it with `--features web-ui`");
+    }
+    #[cfg(feature = "mqtt")]
+    if let Some(mqtt_config) = mqtt {
+        tokio::spawn(mqtt::run(mqtt_config, input_handler.clone()));
+    }
+    #[cfg(not(feature = "mqtt"))]
+    if mqtt.is_some() {
+        error!("midkb was built without MQTT support, rebuild it with `--features mqtt`");
+    }
+    #[cfg(feature = "obs")]
+    if let Some(obs_config) = obs {
+        let actions = lock(&input_handler).obs_actions();
+        tokio::spawn(obs::run(obs_config, actions));
+    }
+    #[cfg(not(feature = "obs"))]
+    if obs.is_some() {
+        error!("midkb was built without OBS support, rebuild it with `--features obs`");
+    }
+
+    let mut connection = connect(source.as_ref(), &midi_sender, &input_handler);
+    let sensing_tx = events_tx.clone();
+    lock(&input_handler).on_sensing_lost(move || {
+        let _ = sensing_tx.send(Event::SensingLost);
+    });
+
+    // Plugging the device in later connects to it, and unplugging it releases everything
+    let hotplug_tx = events_tx.clone();
+    if let Err(e) = hotplug::watch(move |event| {
+        let _ = hotplug_tx.send(Event::Hotplug(event));
+    }) {
+        warn!(%e, "Failed to watch for MIDI devices being plugged in");
+
+        if connection.is_none() && !network_input {
+            return Err(error::Error::NoInput);
+        }
+    }
+
+    if connection.is_none() {
+        info!(device = ?target.device, "Waiting for the MIDI device to be plugged in");
+    }
+
+    if cli.measure_latency {
+        lock(&input_handler).latency = Some(LatencyStats::default());
+
+        let report_handler = input_handler.clone();
+        every(LATENCY_REPORT_INTERVAL, move || {
+            let report = lock(&report_handler)
+                .latency
+                .as_mut()
+                .and_then(LatencyStats::report);
+
+            if let Some(report) = report {
+                info!(
+                    events = report.count,
+                    p50 = ?report.p50,
+                    p95 = ?report.p95,
+                    max = ?report.max,
+                    "Latency from MIDI receipt to uinput write"
+                );
+            }
+        });
+    }
+
+    let tick_handler = input_handler.clone();
+    every(TICK_INTERVAL, move || tick(&mut lock(&tick_handler)));
+
+    daemon::notify("READY=1");
+
+    // wait for sigint or sigterm, reloading the config on sighup, toggling bypass on sigusr1
+    // and logging the current state on sigusr2
+    for event in events {
+        match event {
+            Event::Signal(signals::Signal::Interrupt) => {
+                println!("Received SIGINT, exiting...");
+                break;
+            }
+            Event::Signal(signals::Signal::Terminate) => {
+                println!("Received SIGTERM, exiting...");
+                break;
+            }
+            Event::Hotplug(HotplugEvent::PortAdded) if connection.is_none() => {
+                connection = connect(source.as_ref(), &midi_sender, &input_handler);
+            }
+            Event::Hotplug(HotplugEvent::PortRemoved)
+                if connection.is_some() && !source.is_present() =>
+            {
+                info!(device = ?target.device, "MIDI device unplugged, waiting for it to come back");
+                if let Some(connection) = connection.take() {
+                    connection.close();
+                }
+                lock(&input_handler).release_all();
+            }
+            Event::Hotplug(_) | Event::Signal(signals::Signal::Hangup) => {}
+            Event::SensingLost => {
+                // Reconnect if the port is still there, or else wait for it to come back
+                info!(device = ?target.device, "Reconnecting to the MIDI device");
+                if let Some(connection) = connection.take() {
+                    connection.close();
+                }
+                connection = connect(source.as_ref(), &midi_sender, &input_handler);
+                if connection.is_none() {
+                    info!(device = ?target.device, "Waiting for the MIDI device to be plugged in");
+                }
+            }
+            Event::Signal(signals::Signal::User1) => {
+                info!("Received SIGUSR1, toggling bypass");
+                lock(&input_handler).toggle_bypass();
+            }
+            Event::Signal(signals::Signal::User2) => {
+                lock(&input_handler).dump_state();
+            }
+            Event::Reload => {
+                daemon::notify("RELOADING=1");
+                info!(?config_path, "Reloading config");
+
+                match cli.load_config(&config_path) {
+                    Ok(config) => {
+                        map::MappingTable::report(&config);
+                        let new_target = InputTarget::from_config(&config);
+                        lock(&input_handler).reload(config);
+
+                        if new_target != target {
+                            info!(?new_target, "midi_device changed, reconnecting");
+                            if let Some(connection) = connection.take() {
+                                connection.close();
+                            }
+                            target = new_target;
+                            source = input::source(&target);
+                            connection = connect(source.as_ref(), &midi_sender, &input_handler);
+                        }
+                    }
+                    Err(e) => error!(%e, "Failed to reload config, keeping the old one"),
+                }
+
+                daemon::notify("READY=1");
+            }
+        }
+    }
+
+    daemon::notify("STOPPING=1");
+
+    for connection in connection.into_iter().chain(extra_connections) {
+        connection.close();
+    }
+
+    let dropped = midi_sender.dropped();
+    if dropped > 0 {
+        warn!(
+            dropped,
+            "Dropped MIDI messages while the worker was falling behind"
+        );
+    }
+
+    shut_down(&input_handler);
+
+    Ok(())
+}
+
+/// Report what was played unbound and release everything, even if a panic poisoned the handler
+fn shut_down(input_handler: &Mutex<MidiInputHandler>) {
+    let mut input = lock(input_handler);
+    input.report_unbound();
+
+    // Make sure nothing is left held down before the virtual device goes away
+    input.release_all();
+}
+
+/// Lock the handler, carrying on with it if a thread panicked while holding it. Panics while
+/// handling MIDI or ticking release everything, so what's left is safe to keep using
+pub(crate) fn lock(handler: &Mutex<MidiInputHandler>) -> MutexGuard<'_, MidiInputHandler> {
+    handler.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// What the main loop waits for
+enum Event {
+    Signal(signals::Signal),
+    Hotplug(HotplugEvent),
+    /// SIGHUP, or a config saved from the web UI
+    Reload,
+    /// The device stopped sending Active Sensing
+    SensingLost,
+}
+
+/// Call `f` on a thread of its own every `period`
+fn every(period: Duration, mut f: impl FnMut() + Send + 'static) {
+    std::thread::spawn(move || {
+        let mut next = Instant::now() + period;
+        loop {
+            std::thread::sleep(next.saturating_duration_since(Instant::now()));
+            f();
+            next = (next + period).max(Instant::now());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mouse_keyboard_input::{BTN_LEFT, KEY_Q, KEY_RIGHTALT, KEY_Y};
+    use output::{OutputEvent, RecordingOutput};
+    use OutputEvent::{Move, Press, Release};
+
+    /// A handler with the config, and the output recording what it does
+    fn handler(config: &str) -> (MidiInputHandler, RecordingOutput) {
+        let output = RecordingOutput::default();
+        let config = toml::from_str(config).unwrap();
+        (
+            MidiInputHandler::new(Box::new(output.clone()), config),
+            output,
+        )
+    }
+
+    fn send(handler: &mut MidiInputHandler, bytes: &[u8]) {
+        let mut parser = MidiParser::default();
+        midi_msg_callback(Instant::now(), 0, bytes, &mut parser, handler);
+    }
+
+    #[test]
+    fn test_note_stacking() {
+        let (mut handler, output) = handler(
+            r#"
+                [cc]
+                [notes]
+                60 = 32
+                62 = 33
+                64 = [29, 30]
+            "#,
+        );
+
+        send(&mut handler, &[0x90, 60, 100]);
+        send(&mut handler, &[0x90, 62, 100]);
+        send(&mut handler, &[0x80, 60, 0]);
+        assert_eq!(output.take(), [Press(32), Press(33), Release(32)]);
+        assert_eq!(handler.held_keys, HashSet::from([33]));
+
+        // Several keys are pressed in order and released the other way around
+        send(&mut handler, &[0x90, 64, 100]);
+        send(&mut handler, &[0x80, 64, 0]);
+        assert_eq!(
+            output.take(),
+            [Press(29), Press(30), Release(30), Release(29)]
+        );
+
+        send(&mut handler, &[0x80, 62, 0]);
+        assert_eq!(output.take(), [Release(33)]);
+        assert!(handler.held_keys.is_empty());
+    }
+
+    #[test]
+    fn test_note_tap() {
+        let (mut handler, output) = handler(
+            r#"
+                [cc]
+                [notes]
+                60 = { action = 32, tap_ms = 20 }
+            "#,
+        );
+
+        // Letting go early doesn't cut the tap short
+        send(&mut handler, &[0x90, 60, 100]);
+        send(&mut handler, &[0x80, 60, 0]);
+        handler.tick();
+        assert_eq!(output.take(), [Press(32)]);
+
+        // Pressing again during the tap starts a new one
+        send(&mut handler, &[0x90, 60, 100]);
+        assert_eq!(output.take(), [Release(32), Press(32)]);
+
+        // Holding on doesn't make it longer
+        std::thread::sleep(Duration::from_millis(30));
+        handler.tick();
+        assert_eq!(output.take(), [Release(32)]);
+        send(&mut handler, &[0x80, 60, 0]);
+        assert_eq!(output.take(), []);
+        assert!(handler.held_keys.is_empty());
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                60 = { action = 32, cooldown_ms = 40 }
+                [cc.20]
+                bind_mode = "Toggle"
+                clockwise = 33
+                cooldown_ms = 40
+            "#,
+        );
+
+        send(&mut handler, &[0x90, 60, 100, 0x80, 60, 0]);
+        send(&mut handler, &[0x90, 60, 100, 0x80, 60, 0]);
+        send(&mut handler, &[0xB0, 20, 127, 0xB0, 20, 0]);
+        send(&mut handler, &[0xB0, 20, 127, 0xB0, 20, 0]);
+        assert_eq!(
+            output.take(),
+            [Press(32), Release(32), Press(33), Release(33), Release(33)]
+        );
+
+        // Once the cooldown is over, they fire again
+        std::thread::sleep(Duration::from_millis(50));
+        send(&mut handler, &[0x90, 60, 100, 0xB0, 20, 127]);
+        assert_eq!(output.take(), [Press(32), Press(33)]);
+    }
+
+    #[test]
+    fn test_note_channels() {
+        let (mut handler, output) = handler(
+            r#"
+                note_channels = [1, 2]
+                [notes]
+                60 = 30
+                61 = { action = 31, channel = 10 }
+                [cc]
+            "#,
+        );
+
+        // Channels 1, 2 and 3
+        send(&mut handler, &[0x90, 60, 100, 0x80, 60, 0]);
+        send(&mut handler, &[0x91, 60, 100, 0x81, 60, 0]);
+        send(&mut handler, &[0x92, 60, 100, 0x82, 60, 0]);
+        assert_eq!(
+            output.take(),
+            [Press(30), Release(30), Press(30), Release(30)]
+        );
+
+        // The binding's own channel replaces the list
+        send(&mut handler, &[0x90, 61, 100, 0x80, 61, 0]);
+        send(&mut handler, &[0x99, 61, 100, 0x89, 61, 0]);
+        assert_eq!(output.take(), [Press(31), Release(31)]);
+    }
+
+    #[test]
+    fn test_channel_map() {
+        let (mut handler, output) = handler(
+            r#"
+                channel_map = { 10 = 1 }
+                note_channels = [1]
+                [notes]
+                60 = 30
+                [cc]
+            "#,
+        );
+
+        send(&mut handler, &[0x99, 60, 100, 0x89, 60, 0]);
+        send(&mut handler, &[0x91, 60, 100, 0x81, 60, 0]);
+        assert_eq!(output.take(), [Press(30), Release(30)]);
+    }
+
+    #[test]
+    fn test_zero_velocity() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                60 = 30
+                [cc]
+                [profiles.soft]
+                zero_velocity = "press"
+                [profiles.soft.notes]
+                61 = 31
+            "#,
+        );
+
+        // Running status NoteOns, the second one letting go
+        send(&mut handler, &[0x90, 60, 100, 60, 0]);
+        assert_eq!(output.take(), [Press(30), Release(30)]);
+
+        handler.switch_profile(Some("soft")).unwrap();
+        send(&mut handler, &[0x90, 61, 0, 0x80, 61, 0]);
+        assert_eq!(output.take(), [Press(31), Release(31)]);
+    }
+
+    #[test]
+    fn test_controller_reset() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                60 = 30
+                61 = { type = "layer", name = "fx", toggle = true }
+                [cc]
+            "#,
+        );
+
+        // Reset All Controllers, then All Sound Off
+        send(&mut handler, &[0x90, 60, 100, 0x90, 61, 100, 0xB0, 7, 64]);
+        assert_eq!(output.take(), [Press(30)]);
+        assert!(handler.active_layers.contains("fx"));
+        send(&mut handler, &[0xB0, 121, 0]);
+        assert_eq!(output.take(), [Release(30)]);
+        assert!(handler.active_layers.is_empty() && handler.cc_map.is_empty());
+
+        send(&mut handler, &[0x90, 60, 100, 0xB0, 120, 0]);
+        assert_eq!(output.take(), [Press(30), Release(30)]);
+
+        // System Reset
+        send(&mut handler, &[0x90, 60, 100, 0xFF]);
+        assert_eq!(output.take(), [Press(30), Release(30)]);
+    }
+
+    #[test]
+    fn test_active_sensing() {
+        let (mut handler, output) = handler("[notes]\n60 = 30\n[cc]");
+        let (lost_tx, sensing_lost) = std::sync::mpsc::channel();
+        handler.on_sensing_lost(move || lost_tx.send(()).unwrap());
+
+        // Devices that never send it aren't watched
+        send(&mut handler, &[0x90, 60, 100]);
+        assert!(handler.last_sensed.is_none());
+
+        send(&mut handler, &[0xFE]);
+        handler.tick();
+        assert_eq!(output.take(), [Press(30)]);
+        assert!(sensing_lost.try_recv().is_err());
+
+        handler.last_sensed = Some(Instant::now() - ACTIVE_SENSING_TIMEOUT * 2);
+        handler.tick();
+        assert_eq!(output.take(), [Release(30)]);
+        assert!(sensing_lost.try_recv().is_ok());
+        assert!(handler.last_sensed.is_none());
+    }
+
+    #[test]
+    fn test_idle_release() {
+        let (mut handler, output) = handler(
+            r#"
+                idle_release_ms = 1000
+                [notes]
+                60 = 30
+                [cc.20]
+                bind_mode = "Toggle"
+                clockwise = 33
+            "#,
+        );
+
+        send(&mut handler, &[0x90, 60, 100, 0xB0, 20, 127]);
+        handler.tick();
+        assert_eq!(output.take(), [Press(30), Press(33)]);
+
+        handler.last_message = Instant::now() - Duration::from_secs(2);
+        handler.tick();
+        assert_eq!(output.take().len(), 2);
+        assert!(handler.held_keys.is_empty());
+    }
+
+    #[test]
+    fn test_max_hold() {
+        let (mut handler, output) = handler(
+            r#"
+                max_hold_ms = 1000
+                [cc]
+                [notes]
+                60 = 30
+                61 = { action = 31, max_hold_ms = 5000 }
+            "#,
+        );
+
+        send(&mut handler, &[0x90, 60, 100, 0x90, 61, 100]);
+        handler.tick();
+        assert_eq!(output.take(), [Press(30), Press(31)]);
+
+        // A note's own max_hold_ms takes precedence over the global one
+        let two_seconds_ago = Instant::now() - Duration::from_secs(2);
+        handler.note_pressed_at.insert(60, two_seconds_ago);
+        handler.note_pressed_at.insert(61, two_seconds_ago);
+        handler.tick();
+        assert_eq!(output.take(), [Release(30)]);
+
+        // The NoteOff arriving late doesn't release anything again
+        send(&mut handler, &[0x80, 60, 0]);
+        assert_eq!(output.take(), []);
+
+        handler
+            .note_pressed_at
+            .insert(61, Instant::now() - Duration::from_secs(6));
+        handler.tick();
+        assert_eq!(output.take(), [Release(31)]);
+    }
+
+    #[test]
+    fn test_sustain() {
+        let (mut handler, output) = handler(
+            r#"
+                [sustain]
+                [cc]
+                [notes]
+                60 = 30
+                61 = 31
+            "#,
+        );
+
+        send(&mut handler, &[0x90, 60, 100, 0x80, 60, 0]);
+        assert_eq!(output.take(), [Press(30), Release(30)]);
+
+        // While the pedal is down, letting go of a note keeps its key held
+        send(&mut handler, &[0xB0, 64, 127]);
+        send(&mut handler, &[0x90, 60, 100, 0x80, 60, 0]);
+        send(&mut handler, &[0x90, 61, 100]);
+        assert_eq!(output.take(), [Press(30), Press(31)]);
+
+        // Lifting it releases the notes it held, but not ones still down
+        send(&mut handler, &[0xB0, 64, 0]);
+        assert_eq!(output.take(), [Release(30)]);
+        send(&mut handler, &[0x80, 61, 0]);
+        assert_eq!(output.take(), [Release(31)]);
+    }
+
+    #[test]
+    fn test_shut_down_poisoned() {
+        let (mut handler, output) = handler("[cc]\n[notes]\n60 = 30\n");
+        send(&mut handler, &[0x90, 60, 100]);
+        assert_eq!(output.take(), [Press(30)]);
+
+        // A thread panicking while it holds the handler poisons the lock
+        let handler = Arc::new(Mutex::new(handler));
+        let holder = handler.clone();
+        let _ = std::thread::spawn(move || {
+            let _handler = holder.lock().unwrap();
+            panic!("poisoning the handler");
+        })
+        .join();
+        assert!(handler.is_poisoned());
+
+        // Exiting still lets go of everything
+        shut_down(&handler);
+        assert_eq!(output.take(), [Release(30)]);
+    }
+
+    #[test]
+    fn test_report_unbound() {
+        let (mut handler, _) = handler(
+            r#"
+                report_unbound = true
+                [notes]
+                60 = 32
+                [cc]
+            "#,
+        );
+
+        send(&mut handler, &[0x90, 60, 100, 0x80, 60, 0]);
+        send(&mut handler, &[0x90, 61, 100, 0x80, 61, 0, 0x90, 61, 100]);
+        send(&mut handler, &[0xB0, 7, 10, 0xB0, 7, 11]);
+        assert_eq!(
+            handler.unbound.iter().collect::<Vec<_>>(),
+            [(&("cc", 7), &2), (&("note", 61), &2)]
+        );
+    }
+
+    #[test]
+    fn test_note_turbo() {
+        let (mut handler, output) = handler(
+            r#"
+                [cc]
+                [notes]
+                60 = { action = 32, turbo_hz = 1000 }
+                62 = { action = [29, 33], turbo_hz = 1000 }
+            "#,
+        );
+
+        // Faster than the timers run, so the keys flip on every tick
+        send(&mut handler, &[0x90, 60, 100]);
+        std::thread::sleep(TICK_INTERVAL * 2);
+        handler.tick();
+        assert_eq!(output.take(), [Press(32), Release(32)]);
+        std::thread::sleep(TICK_INTERVAL * 2);
+        handler.tick();
+        send(&mut handler, &[0x80, 60, 0]);
+        assert_eq!(output.take(), [Press(32), Release(32)]);
+
+        // Letting go while the keys are up doesn't release them twice
+        send(&mut handler, &[0x90, 62, 100]);
+        std::thread::sleep(TICK_INTERVAL * 2);
+        handler.tick();
+        send(&mut handler, &[0x80, 62, 0]);
+        assert_eq!(
+            output.take(),
+            [Press(29), Press(33), Release(33), Release(29)]
+        );
+
+        std::thread::sleep(TICK_INTERVAL * 2);
+        handler.tick();
+        assert_eq!(output.take(), []);
+        assert!(handler.held_keys.is_empty());
+    }
+
+    #[test]
+    fn test_click_modes() {
+        let (mut handler, output) = handler(
+            r#"
+                [cc]
+                [notes]
+                60 = { type = "click", mode = "double_click" }
+                61 = { type = "click", mode = "hold" }
+                62 = { type = "click", mode = "release" }
+            "#,
+        );
+
+        send(&mut handler, &[0x90, 60, 100]);
+        assert_eq!(
+            output.take(),
+            [
+                Press(BTN_LEFT),
+                Release(BTN_LEFT),
+                Press(BTN_LEFT),
+                Release(BTN_LEFT)
+            ]
+        );
+
+        // The button stays down after the holding pad is let go, until another pad releases it
+        send(&mut handler, &[0x90, 61, 100]);
+        send(&mut handler, &[0x80, 61, 0]);
+        assert_eq!(output.take(), [Press(BTN_LEFT)]);
+        assert_eq!(handler.held_keys, HashSet::from([BTN_LEFT]));
+
+        send(&mut handler, &[0x90, 62, 100]);
+        assert_eq!(output.take(), [Release(BTN_LEFT)]);
+        assert!(handler.held_keys.is_empty());
+    }
+
+    #[test]
+    fn test_mouse_move_velocity() {
+        let (mut handler, output) = handler(
+            r#"
+                [cc]
+                [notes]
+                60 = { type = "mouse_move", dx = 100, dy = -10 }
+                61 = { type = "mouse_move", dx = 100, velocity_curve = 1.0 }
+                62 = { type = "mouse_move", dx = 100, velocity_curve = 2.0 }
+            "#,
+        );
+
+        send(&mut handler, &[0x90, 60, 30]);
+        send(&mut handler, &[0x90, 61, 127]);
+        send(&mut handler, &[0x90, 61, 64]);
+        send(&mut handler, &[0x90, 62, 64]);
+        assert_eq!(
+            output.take(),
+            [Move(100, -10), Move(100, 0), Move(50, 0), Move(25, 0)]
+        );
+    }
+
+    #[test]
+    fn test_mouse_curve() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                [cc.1]
+                bind_mode = "Mouse"
+                mode = "relative"
+                clockwise = "x"
+                [cc.2]
+                bind_mode = "Mouse"
+                mode = "relative"
+                clockwise = "x"
+                curve = "linear"
+                [cc.3]
+                bind_mode = "Mouse"
+                mode = "relative"
+                clockwise = "x"
+                curve = "exponential"
+                [cc.4]
+                bind_mode = "Mouse"
+                mode = "relative"
+                clockwise = "x"
+                curve = "custom"
+                curve_points = [[2, 4], [8, 100]]
+            "#,
+        );
+
+        // One step, then four, on each control
+        for cc in 1..=4 {
+            send(&mut handler, &[0xB0, cc, 65]);
+            send(&mut handler, &[0xB0, cc, 68]);
+        }
+        // Past the last point of the custom curve
+        send(&mut handler, &[0xB0, 4, 84]);
+
+        assert_eq!(
+            output.take(),
+            [
+                Move(10, 0),
+                Move(10, 0),
+                Move(10, 0),
+                Move(40, 0),
+                Move(5, 0),
+                Move(80, 0),
+                Move(2, 0),
+                Move(36, 0),
+                Move(100, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_note_modifier() {
+        let (mut handler, output) = handler(
+            r#"
+                [cc]
+                [notes]
+                48 = { type = "modifier", name = "shift" }
+                60 = { action = 32, with = { shift = 33 } }
+                62 = 34
+            "#,
+        );
+
+        send(&mut handler, &[0x90, 48, 100]);
+        send(&mut handler, &[0x90, 60, 100]);
+        send(&mut handler, &[0x90, 62, 100]);
+        assert_eq!(output.take(), [Press(33), Press(34)]);
+
+        // Letting go of the modifier releases what was pressed under it all the same
+        send(&mut handler, &[0x80, 48, 0]);
+        send(&mut handler, &[0x80, 60, 0]);
+        send(&mut handler, &[0x90, 60, 100]);
+        assert_eq!(output.take(), [Release(33), Press(32)]);
+    }
+
+    #[test]
+    fn test_char_action() {
+        let (mut handler, output) = handler(
+            r#"
+                layout = "de"
+                [cc]
+                [notes]
+                60 = { type = "char", char = "z" }
+                61 = { type = "char", char = "@" }
+            "#,
+        );
+
+        // Z sits where QWERTY has Y, and @ takes AltGr+Q
+        send(&mut handler, &[0x90, 60, 100]);
+        send(&mut handler, &[0x80, 60, 0]);
+        assert_eq!(output.take(), [Press(KEY_Y), Release(KEY_Y)]);
+
+        send(&mut handler, &[0x90, 61, 100]);
+        send(&mut handler, &[0x80, 61, 0]);
+        assert_eq!(
+            output.take(),
+            [
+                Press(KEY_RIGHTALT),
+                Press(KEY_Q),
+                Release(KEY_Q),
+                Release(KEY_RIGHTALT)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_banks() {
+        let (mut handler, output) = handler(
+            r#"
+                [banks]
+                count = 3
+                offset = 16
+                pads = [36, 51]
+                [cc.20]
+                bind_mode = "BankUp"
+                [cc]
+                [notes]
+                0 = { type = "bank_up" }
+                1 = { type = "bank_down" }
+                36 = 30
+                52 = 31
+                68 = 32
+            "#,
+        );
+
+        send(&mut handler, &[0x90, 36, 100]);
+        send(&mut handler, &[0x80, 36, 0]);
+        assert_eq!(output.take(), [Press(30), Release(30)]);
+
+        // A pad held while switching banks still releases what it pressed
+        send(&mut handler, &[0x90, 36, 100]);
+        send(&mut handler, &[0x90, 0, 100]);
+        send(&mut handler, &[0x80, 0, 0]);
+        send(&mut handler, &[0x80, 36, 0]);
+        send(&mut handler, &[0x90, 36, 100]);
+        assert_eq!(output.take(), [Press(30), Release(30), Press(31)]);
+        send(&mut handler, &[0x80, 36, 0]);
+
+        // Stepping stops at the last bank unless `wrap` is set
+        send(&mut handler, &[0xB0, 20, 127]);
+        send(&mut handler, &[0xB0, 20, 0]);
+        send(&mut handler, &[0xB0, 20, 127]);
+        assert_eq!(handler.bank, 2);
+        output.take();
+        send(&mut handler, &[0x90, 36, 100]);
+        send(&mut handler, &[0x90, 1, 100]);
+        send(&mut handler, &[0x90, 37, 100]);
+        assert_eq!(output.take(), [Press(32)]);
+        assert_eq!(handler.bank, 1);
+    }
+
+    #[test]
+    fn test_cc_zones() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                [cc.7]
+                bind_mode = "Zones"
+                zones = ["KEY_1", "KEY_2", "KEY_3", { type = "type", text = "4" }]
+            "#,
+        );
+
+        send(&mut handler, &[0xB0, 7, 10]);
+        send(&mut handler, &[0xB0, 7, 20]);
+        assert_eq!(output.take(), [Press(2)]);
+
+        // Crossing into the next zone swaps the held key
+        send(&mut handler, &[0xB0, 7, 40]);
+        assert_eq!(output.take(), [Release(2), Press(3)]);
+        send(&mut handler, &[0xB0, 7, 80]);
+        assert_eq!(output.take(), [Release(3), Press(4)]);
+
+        send(&mut handler, &[0xB0, 7, 127]);
+        assert_eq!(output.take(), [Release(4), Press(5), Release(5)]);
+        assert!(handler.held_keys.is_empty());
+    }
+
+    #[test]
+    fn test_gamepad_axes() {
+        let (mut handler, output) = handler(
+            r#"
+                [pitch_bend]
+                axis = "x"
+                [notes]
+                [cc.7]
+                bind_mode = "Gamepad"
+                gamepad = { axis = "gas" }
+            "#,
+        );
+
+        send(&mut handler, &[0xB0, 7, 0]);
+        send(&mut handler, &[0xB0, 7, 127]);
+        send(&mut handler, &[0xE0, 0x00, 0x40]);
+        assert_eq!(
+            output.take(),
+            [
+                OutputEvent::Axis(9, gamepad::AXIS_MIN),
+                OutputEvent::Axis(9, gamepad::AXIS_MAX),
+                OutputEvent::Axis(0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pitch_bend_keys() {
+        let (mut handler, output) = handler(
+            r#"
+                [pitch_bend]
+                up = 17
+                down = 31
+                [notes]
+                [cc]
+            "#,
+        );
+
+        // Wobbling around the center does nothing, and springing back short of it releases
+        send(&mut handler, &[0xE0, 0x00, 0x42]);
+        send(&mut handler, &[0xE0, 0x00, 0x45]);
+        send(&mut handler, &[0xE0, 0x00, 0x47]);
+        send(&mut handler, &[0xE0, 0x00, 0x41]);
+        send(&mut handler, &[0xE0, 0x00, 0x3A]);
+        send(&mut handler, &[0xE0, 0x00, 0x45]);
+        assert_eq!(
+            output.take(),
+            [Press(17), Release(17), Press(31), Release(31), Press(17)]
+        );
+    }
+
+    #[test]
+    fn test_pitch_bend_latching() {
+        // Without releasing at the center, the key stays down until bent the other way
+        let (mut handler, output) = handler(
+            r#"
+                [pitch_bend]
+                up = { type = "key", code = "W" }
+                down = 31
+                release_at_center = false
+                [notes]
+                [cc]
+            "#,
+        );
+        send(&mut handler, &[0xE0, 0x00, 0x45]);
+        send(&mut handler, &[0xE0, 0x00, 0x40]);
+        assert_eq!(output.take(), [Press(17)]);
+        send(&mut handler, &[0xE0, 0x00, 0x3A]);
+        assert_eq!(output.take(), [Release(17), Press(31)]);
+    }
+
+    #[test]
+    fn test_pad_colors() {
+        use config::LedColor::Palette;
+
+        let (mut handler, _) = handler(
+            r#"
+                [feedback]
+                port = "no such port"
+                protocol = "launchpad_x"
+                [notes]
+                11 = 30
+                12 = { type = "layer", name = "fx", toggle = true }
+                13 = { when = "fx", then = 31 }
+                [cc]
+            "#,
+        );
+        let feedback = handler.config.feedback.clone().unwrap();
+        let colors = |handler: &MidiInputHandler| {
+            let colors = handler.pad_colors(&feedback);
+            [11, 12, 13, 14].map(|pad| colors.get(&pad).copied())
+        };
+
+        assert_eq!(
+            colors(&handler),
+            [Some(Palette(1)), Some(Palette(1)), Some(Palette(1)), None]
+        );
+
+        send(&mut handler, &[0x90, 11, 100]);
+        send(&mut handler, &[0x90, 12, 100]);
+        send(&mut handler, &[0x80, 12, 0]);
+        assert_eq!(
+            colors(&handler),
+            [Some(Palette(3)), Some(Palette(21)), Some(Palette(45)), None]
+        );
+    }
+
+    #[test]
+    fn test_device_routing() {
+        let (mut handler, output) = handler(
+            r#"
+                [devices.extra.joystick]
+                kind = "gamepad"
+                [notes]
+                60 = { action = 0x130, device = "joystick" }
+                61 = 30
+                [cc.7]
+                bind_mode = "Gamepad"
+                gamepad = { axis = "throttle" }
+                device = "joystick"
+            "#,
+        );
+        let route = |device: Option<&str>| OutputEvent::Route(device.map(str::to_string));
+
+        send(&mut handler, &[0x90, 60, 100]);
+        send(&mut handler, &[0x90, 61, 100]);
+        send(&mut handler, &[0xB0, 7, 0]);
+        assert_eq!(
+            output.take(),
+            [
+                route(Some("joystick")),
+                Press(0x130),
+                route(None),
+                Press(30),
+                route(Some("joystick")),
+                OutputEvent::Axis(6, gamepad::AXIS_MIN),
+                route(None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cc_direction() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                [cc.21]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+                [cc.22]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+                invert = true
+            "#,
+        );
+
+        // The first value only tells where the control is
+        send(&mut handler, &[0xB0, 21, 64]);
+        output.take();
+
+        send(&mut handler, &[0xB0, 21, 63]);
+        send(&mut handler, &[0xB0, 21, 65]);
+        assert_eq!(
+            output.take(),
+            [Press(105), Release(105), Press(106), Release(106)]
+        );
+
+        send(&mut handler, &[0xB0, 22, 64]);
+        output.take();
+        send(&mut handler, &[0xB0, 22, 65]);
+        assert_eq!(output.take(), [Press(105), Release(105)]);
+    }
+
+    #[test]
+    fn test_cc_snapshot() {
+        let (mut handler, output) = handler(
+            r#"
+                [snapshot]
+                [notes]
+                [cc.21]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+            "#,
+        );
+
+        send(&mut handler, &[0xB0, 21, 100]);
+        assert_eq!(output.take(), []);
+        send(&mut handler, &[0xB0, 21, 99]);
+        assert_eq!(output.take(), [Press(105), Release(105)]);
+
+        // Reconnecting takes the control's position again
+        handler.connected();
+        send(&mut handler, &[0xB0, 21, 20]);
+        assert_eq!(output.take(), []);
+    }
+
+    #[test]
+    fn test_soft_takeover() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                [cc.21]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+                soft_takeover = true
+            "#,
+        );
+        let settle = |handler: &mut MidiInputHandler| {
+            handler
+                .cc_last_event
+                .insert(21, Instant::now() - Duration::from_secs(1));
+        };
+
+        send(&mut handler, &[0xB0, 21, 64]);
+        output.take();
+        settle(&mut handler);
+
+        // Far below the stored value, the control is ignored until it comes up past it
+        send(&mut handler, &[0xB0, 21, 20]);
+        send(&mut handler, &[0xB0, 21, 30]);
+        assert_eq!(output.take(), []);
+        send(&mut handler, &[0xB0, 21, 66]);
+        assert_eq!(output.take(), [Press(106), Release(106)]);
+        settle(&mut handler);
+
+        // And far above it, until it comes down past it
+        send(&mut handler, &[0xB0, 21, 110]);
+        send(&mut handler, &[0xB0, 21, 100]);
+        assert_eq!(output.take(), []);
+        send(&mut handler, &[0xB0, 21, 60]);
+        assert_eq!(output.take(), [Press(105), Release(105)]);
+    }
+
+    #[test]
+    fn test_recent_events() {
+        let (mut handler, _output) = handler("[cc]\n[notes]\n60 = 30\n");
+
+        // Nothing is kept until something asks for it
+        send(&mut handler, &[0x90, 60, 100]);
+        assert!(handler.status().recent_events.is_empty());
+
+        handler.keep_recent_events();
+        send(&mut handler, &[0x90, 60, 100]);
+        let events = handler.status().recent_events;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].number, 60);
+    }
+
+    #[test]
+    fn test_cc_min_delta_debounce() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                [cc.21]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+                min_delta = 3
+                [cc.22]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+                debounce_ms = 1000
+            "#,
+        );
+
+        send(&mut handler, &[0xB0, 21, 64]);
+        output.take();
+
+        // Small changes add up until they reach min_delta
+        send(&mut handler, &[0xB0, 21, 65]);
+        send(&mut handler, &[0xB0, 21, 66]);
+        assert_eq!(output.take(), []);
+        send(&mut handler, &[0xB0, 21, 67]);
+        assert_eq!(output.take(), [Press(106), Release(106)]);
+
+        send(&mut handler, &[0xB0, 22, 64]);
+        output.take();
+
+        // Changes within the debounce window are coalesced into the next one after it
+        send(&mut handler, &[0xB0, 22, 63]);
+        assert_eq!(output.take(), []);
+        handler
+            .cc_last_event
+            .insert(22, Instant::now() - Duration::from_secs(2));
+        send(&mut handler, &[0xB0, 22, 62]);
+        assert_eq!(output.take(), [Press(105), Release(105)]);
+    }
+
+    #[test]
+    fn test_cc_repeat_acceleration() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                [cc.21]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+                repeat_acceleration = true
+                max_repeat = 4
+            "#,
+        );
+
+        send(&mut handler, &[0xB0, 21, 64]);
+        output.take();
+
+        // Turned slowly, every move is one tap
+        handler
+            .cc_last_event
+            .insert(21, Instant::now() - Duration::from_secs(1));
+        send(&mut handler, &[0xB0, 21, 65]);
+        assert_eq!(output.take(), [Press(106), Release(106)]);
+
+        // Spun quickly, a move taps as many times as it's fast, up to max_repeat
+        send(&mut handler, &[0xB0, 21, 67]);
+        let taps: Vec<_> = [Press(106), Release(106)]
+            .into_iter()
+            .cycle()
+            .take(8)
+            .collect();
+        assert_eq!(output.take(), taps);
+    }
+
+    #[test]
+    fn test_cc_steps_per_event() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                [cc.21]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+                steps_per_event = 4
+            "#,
+        );
+
+        // The first value counts as a step like any other
+        for value in [64, 65, 66] {
+            send(&mut handler, &[0xB0, 21, value]);
+        }
+        assert_eq!(output.take(), []);
+        send(&mut handler, &[0xB0, 21, 67]);
+        assert_eq!(output.take(), [Press(106), Release(106)]);
+
+        // Turning back starts counting again
+        for value in [66, 65, 64] {
+            send(&mut handler, &[0xB0, 21, value]);
+        }
+        assert_eq!(output.take(), []);
+        send(&mut handler, &[0xB0, 21, 63]);
+        assert_eq!(output.take(), [Press(105), Release(105)]);
+    }
+
+    #[test]
+    fn test_cc_wrap_threshold() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                [cc.21]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+                wrap_threshold = 64
+                [cc.22]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+            "#,
+        );
+
+        send(&mut handler, &[0xB0, 21, 126]);
+        output.take();
+
+        // Going past 127 to 1 is a short turn clockwise, and back again counter-clockwise
+        send(&mut handler, &[0xB0, 21, 1]);
+        assert_eq!(output.take(), [Press(106), Release(106)]);
+        send(&mut handler, &[0xB0, 21, 126]);
+        assert_eq!(output.take(), [Press(105), Release(105)]);
+
+        // Without a threshold it's a long turn the other way
+        send(&mut handler, &[0xB0, 22, 126]);
+        output.take();
+        send(&mut handler, &[0xB0, 22, 1]);
+        assert_eq!(output.take(), [Press(105), Release(105)]);
+    }
+
+    #[test]
+    fn test_cc_pegged_repeat() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                [cc.21]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+                [cc.22]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+                pegged_repeat = true
+            "#,
+        );
+
+        // A repeated value goes nowhere
+        send(&mut handler, &[0xB0, 21, 127]);
+        output.take();
+        send(&mut handler, &[0xB0, 21, 127]);
+        assert_eq!(output.take(), []);
+
+        // Unless the control is pegged at either end and set to repeat there
+        send(&mut handler, &[0xB0, 22, 127]);
+        output.take();
+        send(&mut handler, &[0xB0, 22, 127]);
+        assert_eq!(output.take(), [Press(106), Release(106)]);
+        send(&mut handler, &[0xB0, 22, 0]);
+        send(&mut handler, &[0xB0, 22, 0]);
+        assert_eq!(
+            output.take(),
+            [Press(105), Release(105), Press(105), Release(105)]
+        );
+        send(&mut handler, &[0xB0, 22, 64]);
+        output.take();
+        send(&mut handler, &[0xB0, 22, 64]);
+        assert_eq!(output.take(), []);
+    }
+
+    #[test]
+    fn test_cc_relative() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                [cc.21]
+                bind_mode = "Keyboard"
+                mode = "relative"
+                counter_clockwise = "105"
+                clockwise = "106"
+                [cc.22]
+                bind_mode = "Keyboard"
+                mode = "relative"
+                center = 0
+                counter_clockwise = "105"
+                clockwise = "106"
+            "#,
+        );
+
+        // Every message is a move of its own, even the first and repeated ones
+        send(&mut handler, &[0xB0, 21, 65]);
+        send(&mut handler, &[0xB0, 21, 65]);
+        send(&mut handler, &[0xB0, 21, 64]);
+        send(&mut handler, &[0xB0, 21, 62]);
+        assert_eq!(
+            output.take(),
+            [
+                Press(106),
+                Release(106),
+                Press(106),
+                Release(106),
+                Press(105),
+                Release(105)
+            ]
+        );
+
+        send(&mut handler, &[0xB0, 22, 127]);
+        send(&mut handler, &[0xB0, 22, 1]);
+        assert_eq!(
+            output.take(),
+            [Press(105), Release(105), Press(106), Release(106)]
+        );
+
+        assert_eq!(relative_step(65, 64), 1);
+        assert_eq!(relative_step(61, 64), -3);
+        assert_eq!(relative_step(127, 0), -1);
+        assert_eq!(relative_step(0, 64), -64);
+    }
+
+    #[test]
+    fn test_cc_jitter() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                [cc.21]
+                bind_mode = "Keyboard"
+                counter_clockwise = "105"
+                clockwise = "106"
+                jitter = 2
+            "#,
+        );
+
+        send(&mut handler, &[0xB0, 21, 64]);
+        send(&mut handler, &[0xB0, 21, 65]);
+        output.take();
+
+        // Flapping back and forth by a step or two goes nowhere
+        for value in [63, 64, 63, 65] {
+            send(&mut handler, &[0xB0, 21, value]);
+        }
+        assert_eq!(output.take(), []);
+
+        // Carrying on the same way counts every step, turning back takes more than the jitter
+        send(&mut handler, &[0xB0, 21, 66]);
+        send(&mut handler, &[0xB0, 21, 62]);
+        assert_eq!(
+            output.take(),
+            [Press(106), Release(106), Press(105), Release(105)]
+        );
+    }
+
+    #[test]
+    fn test_cc_toggle() {
+        let (mut handler, output) = handler(
+            r#"
+                [notes]
+                [cc.30]
+                bind_mode = "Toggle"
+                clockwise = "57"
+                [cc.31]
+                bind_mode = "Toggle"
+                clockwise = [{ type = "key", code = "KEY_A" }, 31]
+            "#,
+        );
+
+        send(&mut handler, &[0xB0, 30, 127]);
+        assert_eq!(output.take(), [Press(57)]);
+        send(&mut handler, &[0xB0, 30, 64]);
+        assert_eq!(output.take(), []);
+        send(&mut handler, &[0xB0, 30, 0]);
+        assert_eq!(output.take(), [Release(57)]);
+
+        // Actions other than a single key fire once when switched on
+        send(&mut handler, &[0xB0, 31, 127]);
+        assert_eq!(
+            output.take(),
+            [Press(30), Press(31), Release(31), Release(30)]
+        );
+        send(&mut handler, &[0xB0, 31, 0]);
+        assert_eq!(output.take(), []);
+    }
+}
@@ -0,0 +1,141 @@
+// End-to-end tests through a real ALSA sequencer, with no hardware.
+// Each test creates a virtual output port standing in for the controller, connects to it the way
+// midkb connects to a device, and checks what a recording output backend receives. They need
+// the sequencer (`/dev/snd/seq`), so they only run with `--features loopback-tests`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use midir::MidiOutputConnection;
+use mouse_keyboard_input::{KEY_A, KEY_LEFTSHIFT, KEY_S};
+
+use crate::config::Config;
+use crate::input::{self, Connection, InputTarget};
+use crate::output::{OutputEvent, RecordingOutput};
+use crate::{midi_output, worker, MidiInputHandler};
+use OutputEvent::{Press, Release};
+
+/// How long to wait for events to come through the sequencer
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A virtual controller port, with midkb connected to it
+struct Loopback {
+    controller: MidiOutputConnection,
+    connection: Option<Box<dyn Connection>>,
+    output: RecordingOutput,
+    handler: Arc<Mutex<MidiInputHandler>>,
+}
+
+impl Loopback {
+    fn new(config: &str) -> Self {
+        // Tests run in parallel, so every controller gets a port name of its own
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "midkb loopback {}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let controller =
+            midi_output::open_virtual(&name).expect("the ALSA sequencer should be available");
+
+        let mut config: Config = toml::from_str(config).unwrap();
+        config.midi_device = name;
+
+        let output = RecordingOutput::default();
+        let target = InputTarget::from_config(&config);
+        let handler = Arc::new(Mutex::new(MidiInputHandler::new(
+            Box::new(output.clone()),
+            config,
+        )));
+        let connection = input::connect(&target, worker::spawn(handler.clone()))
+            .expect("the virtual controller should be found like a device");
+
+        Self {
+            controller,
+            connection: Some(connection),
+            output,
+            handler,
+        }
+    }
+
+    fn send(&mut self, bytes: &[u8]) {
+        self.controller.send(bytes).unwrap();
+    }
+
+    /// The events recorded once `count` of them came through, or whatever did by the timeout
+    fn wait_for(&self, count: usize) -> Vec<OutputEvent> {
+        let deadline = Instant::now() + TIMEOUT;
+        let mut events = Vec::new();
+        while events.len() < count && Instant::now() < deadline {
+            events.extend(self.output.take());
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        events
+    }
+}
+
+impl Drop for Loopback {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            connection.close();
+        }
+    }
+}
+
+#[test]
+fn test_loopback_notes() {
+    let mut loopback = Loopback::new(
+        r#"
+            [notes]
+            60 = 30
+            62 = [42, 31]
+            [cc]
+        "#,
+    );
+
+    loopback.send(&[0x90, 60, 100]);
+    loopback.send(&[0x80, 60, 0]);
+    assert_eq!(loopback.wait_for(2), [Press(KEY_A), Release(KEY_A)]);
+
+    // A velocity 0 NoteOn lets go, like a NoteOff
+    loopback.send(&[0x90, 62, 100]);
+    loopback.send(&[0x90, 62, 0]);
+    assert_eq!(
+        loopback.wait_for(4),
+        [
+            Press(KEY_LEFTSHIFT),
+            Press(KEY_S),
+            Release(KEY_S),
+            Release(KEY_LEFTSHIFT)
+        ]
+    );
+    assert!(loopback.handler.lock().unwrap().held_keys.is_empty());
+}
+
+#[test]
+fn test_loopback_cc() {
+    let mut loopback = Loopback::new(
+        r#"
+            [notes]
+            [cc.20]
+            bind_mode = "Toggle"
+            clockwise = 30
+        "#,
+    );
+
+    loopback.send(&[0xB0, 20, 127]);
+    loopback.send(&[0xB0, 20, 0]);
+    assert_eq!(loopback.wait_for(2), [Press(KEY_A), Release(KEY_A)]);
+}
+
+#[test]
+fn test_loopback_reset() {
+    let mut loopback = Loopback::new("[notes]\n60 = 30\n[cc]");
+
+    // Reset All Controllers lets go of a note whose NoteOff never comes
+    loopback.send(&[0x90, 60, 100]);
+    loopback.send(&[0xB0, 121, 0]);
+    assert_eq!(loopback.wait_for(2), [Press(KEY_A), Release(KEY_A)]);
+}
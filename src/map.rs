@@ -0,0 +1,661 @@
+// `midkb map`: the loaded bindings as a table, to check a config or print as a cheat sheet.
+// Also points out keys bound more than once, bindings reached from more than one place, and
+// bindings that can never do anything. The same problems are logged as a config loads.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use tracing::{info, warn};
+
+use crate::config::{
+    Action, BankConfig, CCBindMode, CCTarget, ClickMode, Condition, ConditionKind, Config,
+    GamepadAxisConfig, MouseAxis, NoteConfig, PitchBendConfig, ScrollAxis, VolumeTarget,
+};
+
+/// One bound note, control or message
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct Row {
+    /// What triggers it, like `note 60` or `cc 21`
+    pub source: String,
+    pub channel: String,
+    pub action: String,
+    /// Names of the keys and buttons it presses
+    pub keys: Vec<String>,
+}
+
+/// The bindings of a config, with the problems found in them
+#[derive(serde::Serialize, Debug, Default)]
+pub struct MappingTable {
+    pub rows: Vec<Row>,
+    /// Keys pressed by more than one binding
+    pub conflicts: Vec<String>,
+    /// Notes and controls claimed twice, or bindings reached from more than one pad
+    pub overlaps: Vec<String>,
+    /// Bindings that can never be triggered
+    pub unused: Vec<String>,
+}
+
+impl MappingTable {
+    pub fn new(config: &Config) -> Self {
+        let mut table = Self::default();
+
+        let notes = numbered(&config.notes.notes, "note", &mut table.unused);
+        // A modifier may only be bound in some profiles, which the base bindings can still opt into
+        let mut modifiers = BTreeSet::new();
+        let profiles = config.profiles.values().map(|profile| &profile.notes.notes);
+        for bindings in std::iter::once(&config.notes.notes).chain(profiles) {
+            for binding in bindings.values() {
+                modifier_names(&binding.action, &mut modifiers);
+            }
+        }
+
+        for (&note, binding) in &notes {
+            table.push(format!("note {note}"), &binding.action);
+            table.on_device(&binding.device);
+            table.on_channels(binding.channel, &config.note_channels);
+
+            let with: BTreeMap<_, _> = binding.with.iter().collect();
+            for (modifier, action) in with {
+                if !modifiers.contains(modifier.as_str()) {
+                    table.unused.push(format!(
+                        "note {note} + {modifier}: no note is bound to modifier `{modifier}`"
+                    ));
+                    continue;
+                }
+
+                table.push(format!("note {note} + {modifier}"), action);
+                table.on_device(&binding.device);
+                table.on_channels(binding.channel, &config.note_channels);
+            }
+        }
+
+        if let Some(banks) = &config.banks {
+            table.find_bank_overlaps(banks, &notes);
+        }
+
+        // Controls claimed by the sustain pedal and XY pads never reach their [cc] binding
+        let mut claimed = BTreeMap::new();
+        let mut claims = Vec::new();
+        if let Some(sustain) = &config.sustain {
+            claims.push((sustain.cc, "the sustain pedal".to_string()));
+        }
+        let xy_pads: BTreeMap<_, _> = config.xy_pads.iter().collect();
+        for (name, pad) in xy_pads {
+            claims.push((pad.x, format!("XY pad `{name}`")));
+            claims.push((pad.y, format!("XY pad `{name}`")));
+        }
+        for (cc, owner) in claims {
+            if let Some(other) = claimed.get(&cc) {
+                table
+                    .overlaps
+                    .push(format!("cc {cc} is used by both {other} and {owner}"));
+                continue;
+            }
+            claimed.insert(cc, owner);
+        }
+
+        let controls = numbered(&config.cc.cc, "cc", &mut table.unused);
+        for (cc, dir_config) in controls {
+            if let Some(owner) = claimed.get(&cc) {
+                table
+                    .unused
+                    .push(format!("cc {cc} is taken over by {owner}"));
+                continue;
+            }
+
+            let targets = [&dir_config.counter_clockwise, &dir_config.clockwise];
+            let action = match dir_config.bind_mode {
+                CCBindMode::Keyboard | CCBindMode::Mouse => {
+                    if targets.iter().all(|target| target.is_none()) {
+                        table
+                            .unused
+                            .push(format!("cc {cc} has no `clockwise` or `counter_clockwise`"));
+                        continue;
+                    }
+
+                    let [counter_clockwise, clockwise] = targets.map(|target| {
+                        target
+                            .as_ref()
+                            .map_or_else(|| "nothing".to_string(), describe_target)
+                    });
+                    // Mouse mode moves both ways along the same axis
+                    if counter_clockwise == clockwise {
+                        format!("turn: {clockwise}")
+                    } else {
+                        format!("turn: {counter_clockwise} / {clockwise}")
+                    }
+                }
+                CCBindMode::Toggle => match &dir_config.clockwise {
+                    Some(CCTarget::Action(action)) => format!("when on: {}", describe(action)),
+                    Some(target) => format!("hold {}", describe_target(target)),
+                    None => {
+                        table.unused.push(format!("cc {cc} has no `clockwise` key"));
+                        continue;
+                    }
+                },
+                CCBindMode::Gamepad => match &dir_config.gamepad {
+                    Some(gamepad) => describe_axis(gamepad),
+                    None => {
+                        table.unused.push(format!("cc {cc} has no `gamepad` axis"));
+                        continue;
+                    }
+                },
+                CCBindMode::Zones => {
+                    if dir_config.zones.is_empty() {
+                        table.unused.push(format!("cc {cc} has no `zones`"));
+                        continue;
+                    }
+
+                    let zones: Vec<_> = dir_config.zones.iter().map(describe_target).collect();
+                    format!("zones: {}", zones.join(" / "))
+                }
+                CCBindMode::Volume => {
+                    let volume = dir_config.volume.clone().unwrap_or_default();
+                    format!(
+                        "volume of {}, up to {}%",
+                        describe_volume(&volume.target),
+                        volume.max
+                    )
+                }
+                CCBindMode::Brightness => {
+                    let brightness = dir_config.brightness.clone().unwrap_or_default();
+                    let device = brightness.device.as_deref().unwrap_or("the screen");
+                    format!(
+                        "brightness of {device}, {}% to {}%",
+                        brightness.min, brightness.max
+                    )
+                }
+                CCBindMode::Panic => "release everything".to_string(),
+                CCBindMode::BankUp => "next bank".to_string(),
+                CCBindMode::BankDown => "previous bank".to_string(),
+                CCBindMode::Command => {
+                    format!("run `{}`", dir_config.run.as_deref().unwrap_or_default())
+                }
+            };
+
+            let mut keys = Vec::new();
+            let zones = dir_config.zones.iter();
+            for target in targets.into_iter().flatten().chain(zones) {
+                match target {
+                    CCTarget::Key(code) => keys.push(*code),
+                    CCTarget::Action(action) => action_keys(action, &mut keys),
+                    _ => {}
+                }
+            }
+            let keys = keys.into_iter().map(key_name).collect();
+            table.rows.push(Row {
+                source: format!("cc {cc}"),
+                channel: "any".to_string(),
+                action,
+                keys,
+            });
+            table.on_device(&dir_config.device);
+        }
+
+        for (cc, owner) in claimed {
+            table.rows.push(Row {
+                source: format!("cc {cc}"),
+                channel: "any".to_string(),
+                action: owner,
+                keys: Vec::new(),
+            });
+        }
+
+        match &config.pitch_bend {
+            Some(PitchBendConfig::Gamepad(axis)) => {
+                table.rows.push(Row {
+                    source: "pitch bend".to_string(),
+                    channel: "any".to_string(),
+                    action: describe_axis(axis),
+                    keys: Vec::new(),
+                });
+                table.on_device(&axis.device);
+            }
+            Some(PitchBendConfig::Keys(keys)) => {
+                let actions = [("pitch bend up", &keys.up), ("pitch bend down", &keys.down)];
+                for (source, action) in actions {
+                    if let Some(action) = action {
+                        table.push(source.to_string(), action);
+                        table.on_device(&keys.device);
+                    }
+                }
+            }
+            None => {}
+        }
+
+        let transport = &config.transport;
+        let messages = [
+            ("start", &transport.start),
+            ("stop", &transport.stop),
+            ("continue", &transport.resume),
+        ];
+        for (name, action) in messages {
+            if let Some(action) = action {
+                table.push(name.to_string(), action);
+            }
+        }
+        let songs = numbered(&transport.songs, "song", &mut table.unused);
+        for (song, action) in songs {
+            table.push(format!("song {song}"), action);
+        }
+        for command in [
+            "play",
+            "stop",
+            "pause",
+            "record",
+            "record_exit",
+            "fast_forward",
+            "rewind",
+            "locate",
+        ] {
+            if let Some(action) = transport.mmc.get(command) {
+                table.push(format!("mmc {command}"), action);
+            }
+        }
+
+        table.find_conflicts();
+        table
+    }
+
+    fn push(&mut self, source: String, action: &Action) {
+        let mut keys = Vec::new();
+        action_keys(action, &mut keys);
+
+        self.rows.push(Row {
+            source,
+            channel: "any".to_string(),
+            action: describe(action),
+            keys: keys.into_iter().map(key_name).collect(),
+        });
+    }
+
+    /// Log the problems with a config as it loads
+    pub fn report(config: &Config) {
+        let table = Self::new(config);
+        for overlap in &table.overlaps {
+            warn!(overlap, "Binding overlaps another");
+        }
+        for unused in &table.unused {
+            warn!(unused, "Binding can never do anything");
+        }
+        // Pressing a key from two places is often on purpose
+        for conflict in &table.conflicts {
+            info!(conflict, "Key is bound more than once");
+        }
+    }
+
+    /// Point out bindings that pads reach in more than one bank, or both as a banked pad and
+    /// as a note of their own
+    fn find_bank_overlaps(&mut self, banks: &BankConfig, notes: &BTreeMap<u8, &NoteConfig>) {
+        // Without `pads`, every note is banked and the controller only has so many
+        let Some([first, last]) = banks.pads else {
+            return;
+        };
+        if !banks.profiles.is_empty() || banks.offset == 0 || banks.len() < 2 {
+            return;
+        }
+
+        let pads = first as i32..=last as i32;
+        let width = pads.clone().count();
+        if (banks.offset as usize) < width {
+            self.overlaps.push(format!(
+                "banks move the {width} banked pads {} notes at a time, so neighbouring banks \
+                 share bindings",
+                banks.offset
+            ));
+        }
+
+        // Notes outside the banked pads always trigger their own binding
+        for &note in notes.keys().filter(|note| !pads.contains(&(**note as i32))) {
+            for bank in 1..banks.len() {
+                let pad = note as i32 - bank as i32 * banks.offset as i32;
+                if pads.contains(&pad) {
+                    self.overlaps.push(format!(
+                        "note {note} is its own pad's binding, and pad {pad}'s in bank {}",
+                        bank + 1
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Note the extra device the last row's events go to
+    fn on_device(&mut self, device: &Option<String>) {
+        if let (Some(row), Some(device)) = (self.rows.last_mut(), device) {
+            row.action = format!("{} on {device}", row.action);
+        }
+    }
+
+    /// Note the channels the last row's note is played on, if it's not any of them
+    fn on_channels(&mut self, channel: Option<u8>, note_channels: &[u8]) {
+        let channels = match channel {
+            Some(channel) => channel.to_string(),
+            None if note_channels.is_empty() => return,
+            None => note_channels
+                .iter()
+                .map(u8::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+        };
+        if let Some(row) = self.rows.last_mut() {
+            row.channel = channels;
+        }
+    }
+
+    fn find_conflicts(&mut self) {
+        let mut bound_by: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for row in &self.rows {
+            for key in &row.keys {
+                let sources = bound_by.entry(key).or_default();
+                if !sources.contains(&row.source.as_str()) {
+                    sources.push(&row.source);
+                }
+            }
+        }
+
+        self.conflicts = bound_by
+            .into_iter()
+            .filter(|(_, sources)| sources.len() > 1)
+            .map(|(key, sources)| format!("{key} is bound by {}", sources.join(", ")))
+            .collect();
+    }
+}
+
+impl fmt::Display for MappingTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = ["NOTE/CC", "CHANNEL", "ACTION", "KEYS"];
+        let cells: Vec<[String; 4]> = self
+            .rows
+            .iter()
+            .map(|row| {
+                [
+                    row.source.clone(),
+                    row.channel.clone(),
+                    row.action.clone(),
+                    row.keys.join(" + "),
+                ]
+            })
+            .collect();
+
+        let mut widths = header.map(str::len);
+        for row in &cells {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let header = header.map(str::to_string);
+        for row in std::iter::once(&header).chain(&cells) {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(f, "{}", line.trim_end())?;
+        }
+
+        let sections = [
+            ("Conflicts", &self.conflicts),
+            ("Overlaps", &self.overlaps),
+            ("Unused", &self.unused),
+        ];
+        for (title, problems) in sections {
+            if !problems.is_empty() {
+                writeln!(f, "\n{title}:")?;
+                for problem in problems {
+                    writeln!(f, "  {problem}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Bindings keyed by a MIDI number, in order, reporting the keys that aren't one
+fn numbered<'a, T>(
+    bindings: &'a std::collections::HashMap<String, T>,
+    kind: &str,
+    unused: &mut Vec<String>,
+) -> BTreeMap<u8, &'a T> {
+    let mut numbered = BTreeMap::new();
+
+    for (key, binding) in bindings {
+        match key.trim().parse::<u8>() {
+            Ok(number) if number < 128 => {
+                numbered.insert(number, binding);
+            }
+            _ => unused.push(format!("{kind} `{key}` isn't a number from 0 to 127")),
+        }
+    }
+
+    numbered
+}
+
+/// The modifiers an action holds, which notes opt into with `with`
+fn modifier_names<'a>(action: &'a Action, names: &mut BTreeSet<&'a str>) {
+    match action {
+        Action::Modifier { name } => {
+            names.insert(name);
+        }
+        Action::Multi(actions) => {
+            for action in actions {
+                modifier_names(action, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The key and button codes an action presses
+fn action_keys(action: &Action, keys: &mut Vec<u16>) {
+    match action {
+        Action::Key(code) | Action::Sticky { code } => keys.push(*code),
+        Action::Drag { button, .. } | Action::Click { button, .. } => keys.push(button.code()),
+        Action::Multi(actions) => {
+            for action in actions {
+                action_keys(action, keys);
+            }
+        }
+        Action::When { then, .. } => action_keys(then, keys),
+        _ => {}
+    }
+}
+
+/// A short description of what an action does
+pub fn describe(action: &Action) -> String {
+    match action {
+        Action::Key(code) => format!("press {}", key_name(*code)),
+        Action::Sticky { code } => format!("sticky {}", key_name(*code)),
+        Action::Panic => "release everything".to_string(),
+        Action::Bypass => "toggle bypass".to_string(),
+        Action::Type { text } => format!("type {text:?}"),
+        Action::Char { char } => format!("key for {char:?}"),
+        Action::Unicode { text } => format!("type {text:?} as unicode"),
+        Action::Command { run } => format!("run `{run}`"),
+        Action::Compositor { command } => format!("compositor `{command}`"),
+        Action::Notify { text } => format!("notify {text:?}"),
+        Action::Volume { target } => format!("mute/unmute {}", describe_volume(target)),
+        Action::ObsScene { scene } => format!("OBS scene {scene:?}"),
+        Action::ObsSource {
+            source,
+            scene: Some(scene),
+        } => format!("show/hide {source:?} in OBS scene {scene:?}"),
+        Action::ObsSource {
+            source,
+            scene: None,
+        } => format!("show/hide {source:?} in OBS"),
+        Action::ObsRecord { command } => format!("{command:?} OBS recording").to_lowercase(),
+        Action::Layer { name, toggle: true } => format!("toggle layer `{name}`"),
+        Action::Layer { name, .. } => format!("hold layer `{name}`"),
+        Action::Modifier { name } => format!("hold modifier `{name}`"),
+        Action::BankUp => "next bank".to_string(),
+        Action::BankDown => "previous bank".to_string(),
+        Action::Drag { button, .. } => format!("drag with the {button:?} button"),
+        Action::MouseMove {
+            dx,
+            dy,
+            velocity_curve: None,
+        } => format!("move the mouse by {dx}, {dy}"),
+        Action::MouseMove { dx, dy, .. } => {
+            format!("move the mouse by up to {dx}, {dy}, farther the harder it's hit")
+        }
+        Action::Click {
+            button,
+            count,
+            mode,
+        } => {
+            let times = match count {
+                1 => String::new(),
+                count => format!(" {count} times"),
+            };
+            match mode {
+                ClickMode::Click => format!("click {button:?}{times}"),
+                ClickMode::DoubleClick => format!("double click {button:?}{times}"),
+                ClickMode::Hold => format!("hold {button:?} until released"),
+                ClickMode::Release => format!("release {button:?}"),
+            }
+        }
+        Action::Multi(actions) => actions.iter().map(describe).collect::<Vec<_>>().join(", "),
+        Action::When { when, then } => {
+            format!("if {}: {}", describe_condition(when), describe(then))
+        }
+    }
+}
+
+fn describe_condition(condition: &Condition) -> String {
+    let kind = match &condition.kind {
+        ConditionKind::Layer(layer) => format!("layer `{layer}`"),
+        ConditionKind::Key(code) => format!("{} held", key_name(*code)),
+    };
+
+    match condition.negate {
+        true => format!("not {kind}"),
+        false => kind,
+    }
+}
+
+fn describe_volume(target: &VolumeTarget) -> String {
+    match (&target.app, &target.sink) {
+        (Some(app), _) => format!("app {app:?}"),
+        (None, Some(sink)) => format!("sink {sink:?}"),
+        (None, None) => "the default sink".to_string(),
+    }
+}
+
+fn describe_axis(config: &GamepadAxisConfig) -> String {
+    format!("gamepad axis {:?}", config.axis).to_lowercase()
+}
+
+fn describe_target(target: &CCTarget) -> String {
+    match target {
+        CCTarget::Key(code) => key_name(*code),
+        CCTarget::Action(action) => describe(action),
+        CCTarget::Axis(MouseAxis::X) => "mouse x".to_string(),
+        CCTarget::Axis(MouseAxis::NegX) => "mouse -x".to_string(),
+        CCTarget::Axis(MouseAxis::Y) => "mouse y".to_string(),
+        CCTarget::Axis(MouseAxis::NegY) => "mouse -y".to_string(),
+        CCTarget::Scroll(ScrollAxis::Wheel) => "wheel".to_string(),
+        CCTarget::Scroll(ScrollAxis::NegWheel) => "-wheel".to_string(),
+        CCTarget::Scroll(ScrollAxis::HWheel) => "hwheel".to_string(),
+        CCTarget::Scroll(ScrollAxis::NegHWheel) => "-hwheel".to_string(),
+    }
+}
+
+/// A key's name without the `KEY_` prefix, or its code if it has no name
+pub fn key_name(code: u16) -> String {
+    match crate::keycodes::name(code) {
+        Some(name) => name.strip_prefix("KEY_").unwrap_or(name).to_string(),
+        None => code.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mapping_table() {
+        let config = r#"
+            [sustain]
+            [notes]
+            60 = 30
+            61 = { type = "sticky", code = 42 }
+            62 = [30, 31]
+            200 = 32
+            [cc.21]
+            bind_mode = "Keyboard"
+            counter_clockwise = "KEY_VOLUMEDOWN"
+            clockwise = "115"
+            [cc.22]
+            bind_mode = "Mouse"
+            [cc.64]
+            bind_mode = "Toggle"
+            clockwise = "57"
+        "#;
+        let config: Config = toml::from_str(config).unwrap();
+        let table = MappingTable::new(&config);
+
+        assert_eq!(
+            table.rows[0],
+            Row {
+                source: "note 60".to_string(),
+                channel: "any".to_string(),
+                action: "press A".to_string(),
+                keys: vec!["A".to_string()],
+            }
+        );
+        assert_eq!(table.rows[1].action, "sticky LEFTSHIFT");
+        assert_eq!(table.rows[2].keys, ["A", "S"]);
+        assert_eq!(table.rows[3].action, "turn: VOLUMEDOWN / VOLUMEUP");
+        assert_eq!(table.rows[4].action, "the sustain pedal");
+        assert_eq!(table.conflicts, ["A is bound by note 60, note 62"]);
+        assert_eq!(table.unused.len(), 3);
+
+        let printed = table.to_string();
+        assert!(printed.starts_with("NOTE/CC  CHANNEL  ACTION"));
+        assert!(printed.contains("\n  cc 22 has no `clockwise` or `counter_clockwise`"));
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let config = r#"
+            [sustain]
+            cc = 1
+            [xy_pads.nub]
+            x = 1
+            y = 2
+            [banks]
+            count = 3
+            offset = 4
+            pads = [36, 43]
+            [notes]
+            44 = { type = "bank_up" }
+            47 = { type = "modifier", name = "shift" }
+            60 = { action = 30, with = { shift = 31, fn = 32 } }
+            [cc]
+        "#;
+        let config: Config = toml::from_str(config).unwrap();
+        let table = MappingTable::new(&config);
+
+        assert_eq!(
+            table.overlaps,
+            [
+                "banks move the 8 banked pads 4 notes at a time, so neighbouring banks share \
+                 bindings",
+                "note 44 is its own pad's binding, and pad 40's in bank 2",
+                "note 44 is its own pad's binding, and pad 36's in bank 3",
+                "note 47 is its own pad's binding, and pad 43's in bank 2",
+                "note 47 is its own pad's binding, and pad 39's in bank 3",
+                "cc 1 is used by both the sustain pedal and XY pad `nub`",
+            ]
+        );
+        assert_eq!(
+            table.unused,
+            ["note 60 + fn: no note is bound to modifier `fn`"]
+        );
+        assert!(table.rows.iter().any(|row| row.source == "note 60 + shift"));
+        assert!(table.to_string().contains("\nOverlaps:\n"));
+    }
+}
@@ -0,0 +1,42 @@
+// MIDI output ports, for passing messages on to other MIDI software
+
+use midir::os::unix::VirtualOutput;
+use midir::{MidiOutput, MidiOutputConnection};
+use tracing::{error, info};
+
+/// Connect to the first output port whose name contains `port`
+pub fn connect(port: &str) -> Option<MidiOutputConnection> {
+    let output = MidiOutput::new("midkb")
+        .inspect_err(|e| error!(%e, "Failed to open the MIDI sequencer for output"))
+        .ok()?;
+
+    let Some(out_port) = output
+        .ports()
+        .into_iter()
+        .find(|p| output.port_name(p).is_ok_and(|name| name.contains(port)))
+    else {
+        error!(?port, "No output port found");
+        return None;
+    };
+
+    info!(?port, "Connecting to MIDI output port");
+
+    output
+        .connect(&out_port, "midkb-route")
+        .inspect_err(|e| error!(%e, ?port, "Failed to connect to MIDI output port"))
+        .ok()
+}
+
+/// Create a virtual output port other MIDI clients can connect to
+pub fn open_virtual(name: &str) -> Option<MidiOutputConnection> {
+    let output = MidiOutput::new("midkb")
+        .inspect_err(|e| error!(%e, "Failed to open the MIDI sequencer for output"))
+        .ok()?;
+
+    info!(?name, "Creating virtual MIDI output port");
+
+    output
+        .create_virtual(name)
+        .inspect_err(|e| error!(%e, ?name, "Failed to create virtual MIDI output port"))
+        .ok()
+}
@@ -0,0 +1,256 @@
+// Config format versions, and upgrading older configs to the current one as they're loaded,
+// so existing configs keep working when the format changes.
+//
+//   1  the original format, without a `version`: notes bound to bare keycodes, and CC directions
+//      written as strings holding a keycode, key name, mouse axis or wheel
+//   2  typed action tables like `{ type = "key", code = "KEY_A" }`, shared by notes and CCs.
+//      The older forms are still read as shorthand, but upgrades write them out in full
+
+use toml::{Table, Value};
+
+/// The config format this version of midkb writes and understands
+pub const CURRENT_VERSION: i64 = 2;
+
+/// Upgrade a config file's table to the current version, returning a line for every change
+pub fn migrate(table: &mut Table) -> Result<Vec<String>, String> {
+    let version = match table.get("version") {
+        None => 1,
+        Some(Value::Integer(version)) if *version >= 1 => *version,
+        Some(_) => return Err("`version` must be a positive number".to_string()),
+    };
+
+    // Newer configs are refused once parsed, after every file has been read
+    if version >= CURRENT_VERSION {
+        return Ok(Vec::new());
+    }
+
+    let mut changes = Vec::new();
+    if version < 2 {
+        typed_actions(table, &mut changes);
+    }
+
+    table.insert("version".to_string(), Value::Integer(CURRENT_VERSION));
+    Ok(changes)
+}
+
+/// Version 2: write out bare keycodes and CC strings as typed action tables
+fn typed_actions(table: &mut Table, changes: &mut Vec<String>) {
+    bindings(table, "", changes);
+
+    if let Some(Value::Table(profiles)) = table.get_mut("profiles") {
+        for (name, profile) in profiles {
+            if let Value::Table(profile) = profile {
+                bindings(profile, &format!("profiles.{name}."), changes);
+            }
+        }
+    }
+
+    if let Some(Value::Table(transport)) = table.get_mut("transport") {
+        for (name, value) in transport {
+            match (name.as_str(), value) {
+                ("songs" | "mmc", Value::Table(actions)) => {
+                    for (key, action) in actions {
+                        upgrade_action(action, &format!("transport.{name}.{key}"), changes);
+                    }
+                }
+                (_, action) => upgrade_action(action, &format!("transport.{name}"), changes),
+            }
+        }
+    }
+}
+
+/// Upgrade the `notes` and `cc` tables of a config or profile
+fn bindings(table: &mut Table, prefix: &str, changes: &mut Vec<String>) {
+    if let Some(Value::Table(notes)) = table.get_mut("notes") {
+        for (note, binding) in notes {
+            let path = format!("{prefix}notes.{note}");
+
+            match binding {
+                // `{ action = ..., min_hold_ms = ..., with = { ... } }`
+                Value::Table(options) if options.contains_key("action") => {
+                    for (key, value) in options {
+                        match (key.as_str(), value) {
+                            ("action", action) => {
+                                upgrade_action(action, &format!("{path}.action"), changes)
+                            }
+                            ("with", Value::Table(with)) => {
+                                for (modifier, action) in with {
+                                    let path = format!("{path}.with.{modifier}");
+                                    upgrade_action(action, &path, changes);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                action => upgrade_action(action, &path, changes),
+            }
+        }
+    }
+
+    if let Some(Value::Table(controls)) = table.get_mut("cc") {
+        for (cc, control) in controls {
+            let Value::Table(control) = control else {
+                continue;
+            };
+
+            for direction in ["counter_clockwise", "clockwise"] {
+                if let Some(target) = control.get_mut(direction) {
+                    upgrade_target(target, &format!("{prefix}cc.{cc}.{direction}"), changes);
+                }
+            }
+        }
+    }
+}
+
+/// Upgrade a note action: bare keycodes become key tables, also inside lists and `when`
+fn upgrade_action(action: &mut Value, path: &str, changes: &mut Vec<String>) {
+    match action {
+        Value::Integer(code) => {
+            let upgraded = tagged("key", [("code", Value::Integer(*code))]);
+            replace(action, upgraded, path, changes);
+        }
+        Value::Array(actions) => {
+            for (index, action) in actions.iter_mut().enumerate() {
+                upgrade_action(action, &format!("{path}[{index}]"), changes);
+            }
+        }
+        Value::Table(table) if table.contains_key("when") => {
+            if let Some(then) = table.get_mut("then") {
+                upgrade_action(then, &format!("{path}.then"), changes);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Upgrade a CC direction: strings become key, `mouse_move` or `scroll` tables
+fn upgrade_target(target: &mut Value, path: &str, changes: &mut Vec<String>) {
+    let Value::String(s) = target else {
+        upgrade_action(target, path, changes);
+        return;
+    };
+
+    let trimmed = s.trim();
+    let upgraded = match trimmed {
+        "x" | "-x" | "y" | "-y" => tagged("mouse_move", [("axis", Value::from(trimmed))]),
+        "wheel" | "-wheel" | "hwheel" | "-hwheel" => {
+            tagged("scroll", [("axis", Value::from(trimmed))])
+        }
+        _ => match trimmed.parse::<i64>() {
+            Ok(code) => tagged("key", [("code", Value::Integer(code))]),
+            Err(_) if crate::keycodes::parse(trimmed).is_some() => {
+                tagged("key", [("code", Value::from(trimmed))])
+            }
+            // Left for parsing to report
+            Err(_) => return,
+        },
+    };
+
+    replace(target, upgraded, path, changes);
+}
+
+fn tagged<const N: usize>(kind: &str, fields: [(&str, Value); N]) -> Value {
+    let mut table = Table::new();
+    table.insert("type".to_string(), Value::from(kind));
+    for (key, value) in fields {
+        table.insert(key.to_string(), value);
+    }
+    Value::Table(table)
+}
+
+fn replace(value: &mut Value, upgraded: Value, path: &str, changes: &mut Vec<String>) {
+    changes.push(format!("{path}: {value} is now {}", inline(&upgraded)));
+    *value = upgraded;
+}
+
+/// An action table the way it's written in configs, with its `type` first
+fn inline(value: &Value) -> String {
+    let Value::Table(table) = value else {
+        return value.to_string();
+    };
+
+    let fields = table
+        .get("type")
+        .map(|kind| ("type", kind))
+        .into_iter()
+        .chain(
+            table
+                .iter()
+                .filter(|(key, _)| *key != "type")
+                .map(|(key, value)| (key.as_str(), value)),
+        )
+        .map(|(key, value)| format!("{key} = {value}"))
+        .collect::<Vec<_>>();
+
+    format!("{{ {} }}", fields.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_typed_actions() {
+        let mut table: Table = toml::from_str(
+            r#"
+                [notes]
+                60 = 32
+                61 = [29, { type = "command", run = "true" }]
+                62 = { action = 30, min_hold_ms = 20, with = { shift = 31 } }
+                63 = { type = "sticky", code = 42 }
+                [cc.1]
+                bind_mode = "Keyboard"
+                counter_clockwise = "60"
+                clockwise = "KEY_VOLUMEUP"
+                [cc.2]
+                bind_mode = "Mouse"
+                counter_clockwise = "-x"
+                clockwise = "wheel"
+                [profiles.editing.notes]
+                60 = 33
+                [transport]
+                start = 57
+            "#,
+        )
+        .unwrap();
+
+        let changes = migrate(&mut table).unwrap();
+        assert_eq!(changes.len(), 10);
+        assert!(changes.contains(&"notes.60: 32 is now { type = \"key\", code = 32 }".to_string()));
+        assert!(changes.contains(
+            &"cc.2.counter_clockwise: \"-x\" is now { type = \"mouse_move\", axis = \"-x\" }"
+                .to_string()
+        ));
+        assert_eq!(table["version"].as_integer(), Some(CURRENT_VERSION));
+
+        let expected: Table = toml::from_str(
+            r#"
+                version = 2
+                [notes]
+                60 = { type = "key", code = 32 }
+                61 = [{ type = "key", code = 29 }, { type = "command", run = "true" }]
+                62 = { action = { type = "key", code = 30 }, min_hold_ms = 20, with = { shift = { type = "key", code = 31 } } }
+                63 = { type = "sticky", code = 42 }
+                [cc.1]
+                bind_mode = "Keyboard"
+                counter_clockwise = { type = "key", code = 60 }
+                clockwise = { type = "key", code = "KEY_VOLUMEUP" }
+                [cc.2]
+                bind_mode = "Mouse"
+                counter_clockwise = { type = "mouse_move", axis = "-x" }
+                clockwise = { type = "scroll", axis = "wheel" }
+                [profiles.editing.notes]
+                60 = { type = "key", code = 33 }
+                [transport]
+                start = { type = "key", code = 57 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(table, expected);
+
+        // Current configs are left alone
+        assert_eq!(migrate(&mut table).unwrap(), Vec::<String>::new());
+        assert!(migrate(&mut toml::from_str("version = \"2\"").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+// MIDI Machine Control (MMC) decoding.
+// The transport sections of several Korg and M-Audio controllers send MMC SysEx for
+// play, stop and record instead of notes or CCs. midi-msg can write MMC commands but not
+// parse them, so the commands midkb binds are decoded here.
+
+use midi_msg::{
+    DeviceID, MachineControlCommandMsg, MidiMsg, SystemExclusiveMsg, UniversalRealTimeMsg,
+};
+
+/// Sub-ID of MMC commands within universal real-time SysEx
+const MMC_COMMAND: u8 = 0x06;
+
+/// Command byte of MMC Locate, kept raw since it carries a time code
+const LOCATE: u8 = 0x44;
+
+/// Decode an MMC command SysEx, `F0 7F <device> 06 <command> ... F7`
+pub fn decode(bytes: &[u8]) -> Option<MidiMsg> {
+    let [0xF0, 0x7F, device, MMC_COMMAND, data @ .., 0xF7] = bytes else {
+        return None;
+    };
+
+    let command = match data.first()? {
+        0x01 => MachineControlCommandMsg::Stop,
+        0x02 => MachineControlCommandMsg::Play,
+        0x03 => MachineControlCommandMsg::DeferredPlay,
+        0x04 => MachineControlCommandMsg::FastForward,
+        0x05 => MachineControlCommandMsg::Rewind,
+        0x06 => MachineControlCommandMsg::RecordStrobe,
+        0x07 => MachineControlCommandMsg::RecordExit,
+        0x08 => MachineControlCommandMsg::RecordPause,
+        0x09 => MachineControlCommandMsg::Pause,
+        _ => MachineControlCommandMsg::Unimplemented(data.to_vec()),
+    };
+
+    let device = match device {
+        0x7F => DeviceID::AllCall,
+        id => DeviceID::Device(*id),
+    };
+
+    Some(MidiMsg::SystemExclusive {
+        msg: SystemExclusiveMsg::UniversalRealTime {
+            device,
+            msg: UniversalRealTimeMsg::MachineControlCommand(command),
+        },
+    })
+}
+
+/// The name an MMC command is bound by in `[transport.mmc]`
+pub fn command_name(msg: &MidiMsg) -> Option<&'static str> {
+    let MidiMsg::SystemExclusive {
+        msg:
+            SystemExclusiveMsg::UniversalRealTime {
+                msg: UniversalRealTimeMsg::MachineControlCommand(command),
+                ..
+            },
+    } = msg
+    else {
+        return None;
+    };
+
+    match command {
+        MachineControlCommandMsg::Stop => Some("stop"),
+        MachineControlCommandMsg::Play | MachineControlCommandMsg::DeferredPlay => Some("play"),
+        MachineControlCommandMsg::FastForward => Some("fast_forward"),
+        MachineControlCommandMsg::Rewind => Some("rewind"),
+        MachineControlCommandMsg::RecordStrobe => Some("record"),
+        MachineControlCommandMsg::RecordExit => Some("record_exit"),
+        MachineControlCommandMsg::Pause | MachineControlCommandMsg::RecordPause => Some("pause"),
+        MachineControlCommandMsg::Unimplemented(data) if data.first() == Some(&LOCATE) => {
+            Some("locate")
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_mmc() {
+        let play = decode(&[0xF0, 0x7F, 0x7F, 0x06, 0x02, 0xF7]).unwrap();
+        assert_eq!(command_name(&play), Some("play"));
+        assert_eq!(play.to_midi(), vec![0xF0, 0x7F, 0x7F, 0x06, 0x02, 0xF7]);
+
+        let locate = [
+            0xF0, 0x7F, 0x01, 0x06, 0x44, 0x06, 0x01, 0x21, 0x00, 0x00, 0x00, 0x00, 0xF7,
+        ];
+        let msg = decode(&locate).unwrap();
+        assert_eq!(command_name(&msg), Some("locate"));
+        assert_eq!(msg.to_midi(), locate.to_vec());
+
+        // Other universal real-time messages aren't MMC
+        assert_eq!(
+            decode(&[0xF0, 0x7F, 0x7F, 0x04, 0x01, 0x00, 0x40, 0xF7]),
+            None
+        );
+    }
+}
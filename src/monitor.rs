@@ -0,0 +1,193 @@
+// `midkb monitor`: print every message the device sends, without mapping anything,
+// to find out which notes and controls to bind when writing a config.
+
+use std::io::IsTerminal;
+use std::path::Path;
+
+use midi_msg::{ChannelModeMsg, ChannelVoiceMsg, MidiMsg, SystemCommonMsg, SystemRealTimeMsg};
+
+use crate::config::{Backend, Config};
+use crate::error::{Error, Result};
+use crate::input::{self, InputTarget};
+use crate::signals::Signal;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// A note's name and octave, with middle C (60) as C4
+pub fn note_name(note: u8) -> String {
+    let octave = note as i32 / 12 - 1;
+    format!("{}{octave}", NOTE_NAMES[note as usize % 12])
+}
+
+/// The usual name of a controller number, for the ones most controllers agree on
+pub fn cc_name(cc: u8) -> Option<&'static str> {
+    let name = match cc {
+        0 => "bank select",
+        1 => "mod wheel",
+        2 => "breath",
+        4 => "foot pedal",
+        5 => "portamento time",
+        6 => "data entry",
+        7 => "volume",
+        8 => "balance",
+        10 => "pan",
+        11 => "expression",
+        64 => "sustain",
+        65 => "portamento",
+        66 => "sostenuto",
+        67 => "soft pedal",
+        71 => "resonance",
+        72 => "release",
+        73 => "attack",
+        74 => "cutoff",
+        91 => "reverb",
+        93 => "chorus",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// A line describing a message, or `None` for clock and active sensing messages,
+/// which some devices send several times a second
+pub fn describe(msg: &MidiMsg) -> Option<String> {
+    let line = match msg {
+        MidiMsg::ChannelVoice { channel, msg } => {
+            let channel = *channel as u8 + 1;
+            let event = match *msg {
+                ChannelVoiceMsg::NoteOn { note, velocity } => {
+                    format!(
+                        "note on   {:<4} ({note:>3})  velocity {velocity}",
+                        note_name(note)
+                    )
+                }
+                ChannelVoiceMsg::NoteOff { note, velocity } => {
+                    format!(
+                        "note off  {:<4} ({note:>3})  velocity {velocity}",
+                        note_name(note)
+                    )
+                }
+                ChannelVoiceMsg::ControlChange { control } => {
+                    let cc = control.control();
+                    match cc_name(cc) {
+                        Some(name) => format!("cc {cc:<3} ({name})  value {}", control.value()),
+                        None => format!("cc {cc:<3}  value {}", control.value()),
+                    }
+                }
+                ChannelVoiceMsg::PolyPressure { note, pressure } => {
+                    format!("pressure  {:<4} ({note:>3})  {pressure}", note_name(note))
+                }
+                ChannelVoiceMsg::ChannelPressure { pressure } => {
+                    format!("channel pressure  {pressure}")
+                }
+                ChannelVoiceMsg::ProgramChange { program } => format!("program change  {program}"),
+                ChannelVoiceMsg::PitchBend { bend } => {
+                    format!("pitch bend  {:+}", bend as i32 - 8192)
+                }
+                ref msg => format!("{msg:?}"),
+            };
+            format!("ch {channel:<2}  {event}")
+        }
+        MidiMsg::ChannelMode { channel, msg } => {
+            let event = match msg {
+                ChannelModeMsg::AllSoundOff => "all sound off (cc 120)".to_string(),
+                ChannelModeMsg::ResetAllControllers => "reset all controllers (cc 121)".to_string(),
+                ChannelModeMsg::AllNotesOff => "all notes off (cc 123)".to_string(),
+                msg => format!("{msg:?}"),
+            };
+            format!("ch {:<2}  {event}", *channel as u8 + 1)
+        }
+        MidiMsg::SystemRealTime {
+            msg: SystemRealTimeMsg::TimingClock | SystemRealTimeMsg::ActiveSensing,
+        } => return None,
+        MidiMsg::SystemRealTime { msg } => format!("{msg:?}").to_lowercase(),
+        MidiMsg::SystemCommon {
+            msg: SystemCommonMsg::SongSelect(song),
+        } => format!("song select  {song}"),
+        msg => match crate::mmc::command_name(msg) {
+            Some(command) => format!("mmc {command}"),
+            None => format!("{msg:?}"),
+        },
+    };
+
+    Some(line)
+}
+
+/// Print the device's messages until Ctrl+C is pressed
+pub fn run(mut config: Config, config_path: &Path) -> Result<()> {
+    if std::io::stdin().is_terminal()
+        && config.backend == Backend::Alsa
+        && !input::source(&InputTarget::from_config(&config)).is_present()
+    {
+        crate::picker::pick_device(&mut config, config_path);
+    }
+
+    let (stop_tx, stop) = std::sync::mpsc::channel();
+    crate::signals::watch(move |signal| {
+        if matches!(signal, Signal::Interrupt | Signal::Terminate) {
+            let _ = stop_tx.send(());
+        }
+    })
+    .map_err(Error::Signal)?;
+
+    let sender = crate::worker::spawn_with(|_, _, bytes, parser| {
+        for msg in parser.feed(bytes) {
+            if let Some(line) = describe(&msg) {
+                println!("{line}");
+            }
+        }
+        for bytes in parser.take_unparsed() {
+            println!("unparsed  {bytes:02X?}");
+        }
+    });
+
+    let connection =
+        input::connect(&InputTarget::from_config(&config), sender).ok_or(Error::NoInput)?;
+    println!("Showing MIDI messages, press Ctrl+C to stop");
+
+    let _ = stop.recv();
+    connection.close();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midi_msg::{Channel, ControlChange};
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(note_name(60), "C4");
+        assert_eq!(note_name(61), "C#4");
+        assert_eq!(note_name(0), "C-1");
+
+        let note_on = MidiMsg::ChannelVoice {
+            channel: Channel::Ch10,
+            msg: ChannelVoiceMsg::NoteOn {
+                note: 36,
+                velocity: 100,
+            },
+        };
+        assert_eq!(
+            describe(&note_on).unwrap(),
+            "ch 10  note on   C2   ( 36)  velocity 100"
+        );
+
+        let cc = MidiMsg::ChannelVoice {
+            channel: Channel::Ch1,
+            msg: ChannelVoiceMsg::ControlChange {
+                control: ControlChange::CC {
+                    control: 64,
+                    value: 127,
+                },
+            },
+        };
+        assert_eq!(describe(&cc).unwrap(), "ch 1   cc 64  (sustain)  value 127");
+
+        let clock = MidiMsg::SystemRealTime {
+            msg: SystemRealTimeMsg::TimingClock,
+        };
+        assert_eq!(describe(&clock), None);
+    }
+}
@@ -0,0 +1,89 @@
+// MIDI Polyphonic Expression (MPE) tracking.
+// MPE controllers play every note on its own "member" channel, so channel-wide
+// messages like pitch bend and pressure on that channel only apply to that note.
+// This keeps track of which note each member channel is playing, and turns the
+// channel's expression messages into per-note changes.
+
+use std::collections::HashMap;
+
+/// Center of the 14-bit pitch bend range
+pub const PITCH_BEND_CENTER: u16 = 8192;
+
+#[derive(Debug, Default)]
+pub struct MpeState {
+    // Member channel (1-16) to the note currently held on it
+    channel_notes: HashMap<u8, u8>,
+
+    // Last expression values seen on each member channel, to compute deltas from
+    last_bend: HashMap<u8, u16>,
+    last_pressure: HashMap<u8, u8>,
+}
+
+impl MpeState {
+    pub fn note_on(&mut self, channel: u8, note: u8) {
+        self.channel_notes.insert(channel, note);
+        // Each new note starts from a centered bend and no pressure
+        self.last_bend.insert(channel, PITCH_BEND_CENTER);
+        self.last_pressure.insert(channel, 0);
+    }
+
+    pub fn note_off(&mut self, channel: u8) {
+        self.channel_notes.remove(&channel);
+        self.last_bend.remove(&channel);
+        self.last_pressure.remove(&channel);
+    }
+
+    /// Record a pitch bend on a member channel, returning the note it applies to
+    /// and how far the bend moved since the last message, outside of the dead zone
+    pub fn pitch_bend(&mut self, channel: u8, bend: u16, dead_zone: u16) -> Option<(u8, i32)> {
+        let note = *self.channel_notes.get(&channel)?;
+        let last = self
+            .last_bend
+            .insert(channel, bend)
+            .unwrap_or(PITCH_BEND_CENTER);
+        let outside = |bend: u16| {
+            let offset = bend as i32 - PITCH_BEND_CENTER as i32;
+            offset.signum() * (offset.abs() - dead_zone as i32).max(0)
+        };
+        Some((note, outside(bend) - outside(last)))
+    }
+
+    /// Record a pressure change on a member channel, returning the note it applies to
+    /// and how far the pressure moved since the last message
+    pub fn pressure(&mut self, channel: u8, pressure: u8) -> Option<(u8, i32)> {
+        let note = *self.channel_notes.get(&channel)?;
+        let last = self.last_pressure.insert(channel, pressure).unwrap_or(0);
+        Some((note, pressure as i32 - last as i32))
+    }
+
+    pub fn clear(&mut self) {
+        self.channel_notes.clear();
+        self.last_bend.clear();
+        self.last_pressure.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_note_expression() {
+        let mut state = MpeState::default();
+
+        state.note_on(2, 60);
+        state.note_on(3, 64);
+
+        assert_eq!(state.pitch_bend(2, 8292, 0), Some((60, 100)));
+        assert_eq!(state.pitch_bend(3, 8092, 0), Some((64, -100)));
+        assert_eq!(state.pressure(3, 40), Some((64, 40)));
+
+        state.note_off(2);
+        assert_eq!(state.pitch_bend(2, 9000, 0), None);
+
+        // Only the part of the bend past the dead zone counts
+        assert_eq!(state.pitch_bend(3, 8142, 100), Some((64, 0)));
+        assert_eq!(state.pitch_bend(3, 8442, 100), Some((64, 150)));
+        assert_eq!(state.pitch_bend(3, 7992, 100), Some((64, -250)));
+    }
+}
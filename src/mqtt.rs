@@ -0,0 +1,139 @@
+// MQTT integration, for home automation like Home Assistant.
+// Every triggered action is published as JSON to `<topic>/events`, the current status is kept
+// retained on `<topic>/status`, and plain text commands are read from `<topic>/command`:
+//
+//   pause            stop turning MIDI into input, like the bypass toggle
+//   resume           start again
+//   profile <name>   switch to a profile
+//   profile          go back to plain `[notes]` and `[cc]`
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use tracing::{debug, info, warn};
+
+use crate::config::MqttConfig;
+use crate::{lock, MidiInputHandler};
+
+/// How long to wait before reconnecting after the broker goes away
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// A command read from the command topic
+#[derive(Debug, PartialEq)]
+enum Command {
+    Pause,
+    Resume,
+    Profile(Option<String>),
+}
+
+impl Command {
+    fn parse(payload: &str) -> Option<Self> {
+        let mut words = payload.split_whitespace();
+
+        let command = match words.next()? {
+            "pause" => Self::Pause,
+            "resume" => Self::Resume,
+            "profile" => Self::Profile(words.next().map(str::to_string)),
+            _ => return None,
+        };
+
+        words.next().is_none().then_some(command)
+    }
+}
+
+/// Stay connected to the broker until midkb exits
+pub async fn run(config: MqttConfig, handler: Arc<Mutex<MidiInputHandler>>) {
+    let mut options = MqttOptions::new(&config.client_id, &config.broker, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+    }
+
+    let (client, mut eventloop) = AsyncClient::new(options, 64);
+    let mut events = {
+        let mut handler = lock(&handler);
+        handler.keep_recent_events();
+        handler.subscribe_events()
+    };
+
+    let events_topic = format!("{}/events", config.topic);
+    let status_topic = format!("{}/status", config.topic);
+    let command_topic = format!("{}/command", config.topic);
+
+    let publish_status = |client: &AsyncClient| {
+        let status =
+            serde_json::to_vec(&lock(&handler).status()).expect("statuses always serialize");
+        if let Err(e) = client.try_publish(&status_topic, QoS::AtLeastOnce, true, status) {
+            debug!(%e, "Failed to publish midkb status to MQTT");
+        }
+    };
+
+    loop {
+        tokio::select! {
+            event = eventloop.poll() => match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    info!(broker = config.broker, port = config.port, "Connected to MQTT broker");
+                    if let Err(e) = client.try_subscribe(&command_topic, QoS::AtLeastOnce) {
+                        warn!(%e, "Failed to subscribe to the MQTT command topic");
+                    }
+                    publish_status(&client);
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) if publish.topic == command_topic => {
+                    let payload = String::from_utf8_lossy(&publish.payload);
+
+                    let Some(command) = Command::parse(&payload) else {
+                        warn!(%payload, "Ignoring unknown MQTT command");
+                        continue;
+                    };
+
+                    debug!(?command, "MQTT command");
+                    let result = {
+                        let mut handler = lock(&handler);
+                        match command {
+                            Command::Pause | Command::Resume => {
+                                handler.set_bypass(command == Command::Pause);
+                                Ok(())
+                            }
+                            Command::Profile(name) => handler.switch_profile(name.as_deref()),
+                        }
+                    };
+
+                    if let Err(e) = result {
+                        warn!(%e, "MQTT command failed");
+                    }
+                    publish_status(&client);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!(%e, "MQTT connection failed, retrying");
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            },
+            Some(entry) = events.recv() => {
+                let entry = serde_json::to_vec(&entry).expect("history entries always serialize");
+                if let Err(e) = client.try_publish(&events_topic, QoS::AtMostOnce, false, entry) {
+                    debug!(%e, "Failed to publish action to MQTT");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(Command::parse("pause"), Some(Command::Pause));
+        assert_eq!(Command::parse(" resume\n"), Some(Command::Resume));
+        assert_eq!(
+            Command::parse("profile photoshop"),
+            Some(Command::Profile(Some("photoshop".to_string())))
+        );
+        assert_eq!(Command::parse("profile"), Some(Command::Profile(None)));
+        assert_eq!(Command::parse("profile a b"), None);
+        assert_eq!(Command::parse("reboot"), None);
+    }
+}
@@ -0,0 +1,48 @@
+// Desktop notifications confirming that a pad press registered, for switching profiles, layers
+// and banks, which change nothing on screen by themselves, and for `notify` actions.
+// They're shown with `notify-send`, and hinted to replace each other like an OSD instead of
+// piling up in the notification center.
+
+use std::process::Command;
+
+use tracing::{debug, warn};
+
+/// Show a notification for `timeout_ms`, without waiting for the notification daemon
+pub fn show(text: &str, timeout_ms: u32) {
+    debug!(text, "Showing notification");
+
+    match Command::new("notify-send")
+        .args(args(text, timeout_ms))
+        .spawn()
+    {
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => warn!(%e, "Failed to run notify-send, is libnotify installed?"),
+    }
+}
+
+fn args(text: &str, timeout_ms: u32) -> Vec<String> {
+    vec![
+        "--app-name=midkb".to_string(),
+        format!("--expire-time={timeout_ms}"),
+        // Replaces the last notification on GNOME, KDE, dunst and mako
+        "--hint=string:x-canonical-private-synchronous:midkb".to_string(),
+        // Keeps it out of the notification history
+        "--hint=boolean:transient:true".to_string(),
+        "--".to_string(),
+        text.to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        let args = args("-Profile: drums", 1500);
+        assert_eq!(args[1], "--expire-time=1500");
+        assert_eq!(args[args.len() - 2..], ["--", "-Profile: drums"]);
+    }
+}
@@ -0,0 +1,312 @@
+// OBS Studio control through obs-websocket (version 5, built into OBS 28 and later),
+// so pads can switch scenes, show and hide sources and start recording without going
+// through hotkeys that the focused app would see too.
+// The handler queues `obs_*` actions, and a task sends them over one connection to OBS,
+// reconnecting whenever OBS is closed and opened again.
+
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{debug, info, warn};
+
+use crate::config::{Action, ObsConfig, ObsRecordCommand};
+
+/// How long to wait before reconnecting after OBS goes away
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// The obs-websocket RPC version spoken here
+const RPC_VERSION: u64 = 1;
+
+// Message opcodes
+const OP_HELLO: u64 = 0;
+const OP_IDENTIFY: u64 = 1;
+const OP_IDENTIFIED: u64 = 2;
+const OP_REQUEST: u64 = 6;
+const OP_REQUEST_RESPONSE: u64 = 7;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Debug, thiserror::Error)]
+enum ObsError {
+    #[error("{0}")]
+    Socket(#[from] tungstenite::Error),
+
+    #[error("OBS closed the connection")]
+    Closed,
+
+    #[error("unexpected message from OBS: {0}")]
+    Protocol(String),
+
+    #[error("{request} failed: {comment}")]
+    Request {
+        request: &'static str,
+        comment: String,
+    },
+}
+
+/// Stay connected to OBS until midkb exits, performing the actions queued by the handler
+pub async fn run(config: ObsConfig, mut actions: UnboundedReceiver<Action>) {
+    let url = format!("ws://{}:{}", config.host, config.port);
+
+    loop {
+        match connect(&url, config.password.as_deref()).await {
+            Ok(mut socket) => {
+                info!(url, "Connected to OBS");
+                match serve(&mut socket, &mut actions).await {
+                    Ok(()) => return,
+                    Err(e) => warn!(%e, "Lost the connection to OBS, reconnecting"),
+                }
+            }
+            Err(e) => debug!(%e, url, "Failed to connect to OBS, retrying"),
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+
+        // Pads hit while OBS was away would all fire at once on reconnecting
+        while let Ok(action) = actions.try_recv() {
+            warn!(?action, "OBS isn't connected, dropping action");
+        }
+    }
+}
+
+/// Open the connection and log in
+async fn connect(url: &str, password: Option<&str>) -> Result<Socket, ObsError> {
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await?;
+
+    let hello = receive(&mut socket).await?;
+    if hello["op"] != OP_HELLO {
+        return Err(ObsError::Protocol(hello.to_string()));
+    }
+
+    let identify = identify(&hello, password)?;
+    send(&mut socket, OP_IDENTIFY, identify).await?;
+
+    // A wrong password gets the connection closed instead of an answer
+    let identified = receive(&mut socket).await?;
+    if identified["op"] != OP_IDENTIFIED {
+        return Err(ObsError::Protocol(identified.to_string()));
+    }
+
+    Ok(socket)
+}
+
+/// The Identify message answering `hello`, logging in if OBS asks for a password
+fn identify(hello: &Value, password: Option<&str>) -> Result<Value, ObsError> {
+    let mut identify = json!({ "rpcVersion": RPC_VERSION, "eventSubscriptions": 0 });
+    if let Some(auth) = hello["d"]["authentication"].as_object() {
+        let salt = auth.get("salt").and_then(Value::as_str);
+        let challenge = auth.get("challenge").and_then(Value::as_str);
+        let (Some(salt), Some(challenge)) = (salt, challenge) else {
+            return Err(ObsError::Protocol(hello.to_string()));
+        };
+
+        identify["authentication"] =
+            authentication(password.unwrap_or_default(), salt, challenge).into();
+    }
+
+    Ok(identify)
+}
+
+/// Perform actions as they come in, until the handler goes away or the connection drops
+async fn serve(
+    socket: &mut Socket,
+    actions: &mut UnboundedReceiver<Action>,
+) -> Result<(), ObsError> {
+    let mut next_id = 0;
+
+    loop {
+        tokio::select! {
+            action = actions.recv() => {
+                let Some(action) = action else {
+                    return Ok(());
+                };
+
+                debug!(?action, "Sending action to OBS");
+                match perform(socket, &mut next_id, &action).await {
+                    Err(e @ ObsError::Request { .. }) => warn!(%e, ?action, "OBS action failed"),
+                    result => result?,
+                }
+            }
+            // Nothing is subscribed to, but reading keeps pings answered and notices OBS closing
+            message = socket.next() => match message {
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+                None => return Err(ObsError::Closed),
+            },
+        }
+    }
+}
+
+/// Send the requests for an action
+async fn perform(socket: &mut Socket, next_id: &mut u64, action: &Action) -> Result<(), ObsError> {
+    match action {
+        Action::ObsScene { scene } => {
+            request(
+                socket,
+                next_id,
+                "SetCurrentProgramScene",
+                json!({ "sceneName": scene }),
+            )
+            .await?;
+        }
+        Action::ObsSource { source, scene } => {
+            let scene = match scene {
+                Some(scene) => scene.clone(),
+                None => {
+                    let current =
+                        request(socket, next_id, "GetCurrentProgramScene", json!({})).await?;
+                    let name = current["currentProgramSceneName"].as_str();
+                    name.ok_or_else(|| ObsError::Protocol(current.to_string()))?
+                        .to_string()
+                }
+            };
+
+            let item = request(
+                socket,
+                next_id,
+                "GetSceneItemId",
+                json!({ "sceneName": scene, "sourceName": source }),
+            )
+            .await?;
+            let item = item["sceneItemId"].clone();
+
+            let enabled = request(
+                socket,
+                next_id,
+                "GetSceneItemEnabled",
+                json!({ "sceneName": scene, "sceneItemId": item }),
+            )
+            .await?;
+            let enabled = enabled["sceneItemEnabled"].as_bool().unwrap_or_default();
+
+            request(
+                socket,
+                next_id,
+                "SetSceneItemEnabled",
+                json!({ "sceneName": scene, "sceneItemId": item, "sceneItemEnabled": !enabled }),
+            )
+            .await?;
+        }
+        Action::ObsRecord { command } => {
+            request(socket, next_id, record_request(*command), json!({})).await?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn record_request(command: ObsRecordCommand) -> &'static str {
+    match command {
+        ObsRecordCommand::Toggle => "ToggleRecord",
+        ObsRecordCommand::Start => "StartRecord",
+        ObsRecordCommand::Stop => "StopRecord",
+    }
+}
+
+/// Send a request and wait for its response, returning the response's data
+async fn request(
+    socket: &mut Socket,
+    next_id: &mut u64,
+    request_type: &'static str,
+    data: Value,
+) -> Result<Value, ObsError> {
+    *next_id += 1;
+    let id = next_id.to_string();
+
+    let request = json!({ "requestType": request_type, "requestId": id, "requestData": data });
+    send(socket, OP_REQUEST, request).await?;
+
+    loop {
+        let response = receive(socket).await?;
+        if response["op"] != OP_REQUEST_RESPONSE || response["d"]["requestId"] != id {
+            continue;
+        }
+
+        let status = &response["d"]["requestStatus"];
+        if status["result"] != true {
+            return Err(ObsError::Request {
+                request: request_type,
+                comment: status["comment"]
+                    .as_str()
+                    .map_or_else(|| status.to_string(), str::to_string),
+            });
+        }
+
+        return Ok(response["d"]["responseData"].clone());
+    }
+}
+
+async fn send(socket: &mut Socket, op: u64, data: Value) -> Result<(), ObsError> {
+    let message = json!({ "op": op, "d": data }).to_string();
+    socket.send(Message::text(message)).await?;
+    Ok(())
+}
+
+/// The next text message from OBS, parsed
+async fn receive(socket: &mut Socket) -> Result<Value, ObsError> {
+    while let Some(message) = socket.next().await {
+        match message? {
+            Message::Text(text) => {
+                return serde_json::from_str(&text)
+                    .map_err(|_| ObsError::Protocol(text.to_string()))
+            }
+            Message::Close(_) => break,
+            _ => continue,
+        }
+    }
+
+    Err(ObsError::Closed)
+}
+
+/// The login string for a password: the base64 SHA-256 of the password and salt,
+/// hashed again with the challenge
+fn authentication(password: &str, salt: &str, challenge: &str) -> String {
+    let hash = |text: String| data_encoding::BASE64.encode(&Sha256::digest(text.as_bytes()));
+
+    let secret = hash(format!("{password}{salt}"));
+    hash(format!("{secret}{challenge}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authentication() {
+        // The example from the obs-websocket protocol docs
+        assert_eq!(
+            authentication(
+                "supersecretpassword",
+                "lM1GncleQOaCu9lT1yeUZhFYnqhsLLP1G5lAGo3ixaI=",
+                "+IxH4CnCiqpX1rM9scsNynZzbOe4KhDeYcTNS3PDaeY="
+            ),
+            "1Ct943GAT+6YQUUX47Ia/ncufilbe6+oD6lY+5kaCu4="
+        );
+    }
+
+    #[test]
+    fn test_identify() {
+        let hello = json!({ "op": 0, "d": { "rpcVersion": 1 } });
+        let message = identify(&hello, None).unwrap();
+        assert_eq!(message["authentication"], Value::Null);
+
+        let hello =
+            json!({ "op": 0, "d": { "authentication": { "salt": "a", "challenge": "b" } } });
+        let message = identify(&hello, Some("password")).unwrap();
+        assert_eq!(
+            message["authentication"],
+            authentication("password", "a", "b")
+        );
+
+        // A malformed hello is an error, not a panic
+        let hello = json!({ "op": 0, "d": { "authentication": { "challenge": "b" } } });
+        assert!(matches!(identify(&hello, None), Err(ObsError::Protocol(_))));
+    }
+}
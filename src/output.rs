@@ -0,0 +1,474 @@
+// The virtual devices midkb injects events through.
+// Keys go to a virtual keyboard, and mouse buttons, movement and scrolling to a separate
+// virtual mouse, since some desktops and games treat a device that's both oddly.
+// Gamepad buttons and axes go to a virtual gamepad, created the first time one is used.
+// Bindings can pick one of the extra devices in `[devices.extra]` instead, which gets the events
+// of its kind while the binding is handled, and the releases of whatever it pressed.
+// `midkb simulate` prints the events instead, through the same `Output` interface.
+
+use std::collections::HashMap;
+use std::io;
+use std::time::Duration;
+
+use evdev::uinput::VirtualDevice;
+use evdev::{
+    AbsInfo, AbsoluteAxisCode, AttributeSet, EventType, InputEvent, KeyCode, RelativeAxisCode,
+    UinputAbsSetup,
+};
+use tracing::info;
+
+use crate::config::{DeviceKind, DevicesConfig};
+
+/// Mouse buttons, BTN_LEFT to BTN_TASK, which go to the mouse instead of the keyboard
+const MOUSE_BUTTONS: std::ops::RangeInclusive<u16> = 0x110..=0x117;
+
+/// Gamepad buttons, BTN_SOUTH to BTN_THUMBR, which go to the gamepad
+const GAMEPAD_BUTTONS: std::ops::RangeInclusive<u16> = 0x130..=0x13e;
+
+/// Axes of the gamepad, ABS_X to ABS_BRAKE
+const GAMEPAD_AXES: std::ops::RangeInclusive<u16> = 0x00..=0x0a;
+
+/// Wheel units in one notch of a scroll wheel, as used by the high-resolution wheel events
+pub const WHEEL_NOTCH: i32 = 120;
+
+/// How long a click holds the button down, some apps ignore clicks with no time in between
+const CLICK_HOLD: Duration = Duration::from_millis(5);
+
+/// Where the handler's key presses, mouse movement and scrolling go
+pub trait Output: Send {
+    /// Press a key or mouse button
+    fn press(&mut self, code: u16) -> io::Result<()>;
+
+    /// Release a key or mouse button
+    fn release(&mut self, code: u16) -> io::Result<()>;
+
+    /// Press and release a key or mouse button
+    fn click(&mut self, code: u16) -> io::Result<()> {
+        self.press(code)?;
+        self.release(code)
+    }
+
+    /// Move the cursor, with positive `y` going up
+    fn move_mouse(&mut self, x: i32, y: i32) -> io::Result<()>;
+
+    /// Scroll horizontally by `value` high-resolution units, positive to the right
+    fn scroll_x(&mut self, value: i32) -> io::Result<()>;
+
+    /// Scroll vertically by `value` high-resolution units, positive going up
+    fn scroll_y(&mut self, value: i32) -> io::Result<()>;
+
+    /// Move a gamepad axis to `value`, from `gamepad::AXIS_MIN` to `gamepad::AXIS_MAX`
+    fn move_axis(&mut self, axis: u16, value: i32) -> io::Result<()>;
+
+    /// Send the following events to one of the extra devices, or back to the usual ones
+    fn route(&mut self, _device: Option<&str>) {}
+}
+
+pub struct VirtualOutput {
+    keyboard: VirtualDevice,
+    mouse: VirtualDevice,
+    gamepad: Option<VirtualDevice>,
+    gamepad_name: String,
+    extra: HashMap<String, (DeviceKind, VirtualDevice)>,
+
+    // The extra device bindings are being handled for, and the one each key went down on
+    route: Option<String>,
+    pressed_on: HashMap<u16, String>,
+
+    // High-resolution scrolling not adding up to a whole notch yet, horizontal and vertical
+    wheel_remainder: (i32, i32),
+}
+
+impl VirtualOutput {
+    /// Create the virtual keyboard and mouse, and the extra devices
+    pub fn new(config: &DevicesConfig) -> io::Result<Self> {
+        let mut extra = HashMap::new();
+        for (name, device) in &config.extra {
+            let device_name = device.name.as_deref().unwrap_or(name);
+            info!(name = device_name, kind = ?device.kind, "Creating extra virtual device");
+
+            let built = match device.kind {
+                DeviceKind::Keyboard => keyboard(device_name)?,
+                DeviceKind::Mouse => mouse(device_name)?,
+                DeviceKind::Gamepad => gamepad(device_name)?,
+            };
+            extra.insert(name.clone(), (device.kind, built));
+        }
+
+        Ok(Self {
+            keyboard: keyboard(&config.keyboard_name)?,
+            mouse: mouse(&config.mouse_name)?,
+            gamepad: None,
+            gamepad_name: config.gamepad_name.clone(),
+            extra,
+            route: None,
+            pressed_on: HashMap::new(),
+            wheel_remainder: (0, 0),
+        })
+    }
+
+    /// The virtual gamepad, created the first time it's needed so there's no gamepad around
+    /// for games to pick up unless the config uses one
+    fn gamepad(&mut self) -> io::Result<&mut VirtualDevice> {
+        if self.gamepad.is_none() {
+            info!(name = self.gamepad_name, "Creating virtual gamepad");
+            self.gamepad = Some(gamepad(&self.gamepad_name)?);
+        }
+
+        Ok(self.gamepad.as_mut().expect("the gamepad was just created"))
+    }
+
+    /// The extra device events of a kind are routed to, if any
+    fn routed(&mut self, kind: DeviceKind) -> Option<&mut VirtualDevice> {
+        let (device_kind, device) = self.extra.get_mut(self.route.as_deref()?)?;
+        (*device_kind == kind).then_some(device)
+    }
+
+    /// The mouse, or the extra mouse events are routed to
+    fn mouse(&mut self) -> &mut VirtualDevice {
+        match self
+            .route
+            .as_deref()
+            .and_then(|name| self.extra.get_mut(name))
+        {
+            Some((DeviceKind::Mouse, device)) => device,
+            _ => &mut self.mouse,
+        }
+    }
+
+    fn key_event(&mut self, code: u16, value: i32) -> io::Result<()> {
+        let event = [InputEvent::new(EventType::KEY.0, code, value)];
+
+        // Keys go back up on the device they went down on, wherever events are routed now
+        let extra = if value == 0 {
+            self.pressed_on.remove(&code)
+        } else {
+            let routed = self.route.clone().filter(
+                |name| matches!(self.extra.get(name), Some((kind, _)) if *kind == key_kind(code)),
+            );
+            if let Some(name) = &routed {
+                self.pressed_on.insert(code, name.clone());
+            }
+            routed
+        };
+        if let Some((_, device)) = extra.and_then(|name| self.extra.get_mut(&name)) {
+            return device.emit(&event);
+        }
+
+        let device = match key_kind(code) {
+            DeviceKind::Mouse => &mut self.mouse,
+            DeviceKind::Gamepad => self.gamepad()?,
+            DeviceKind::Keyboard => &mut self.keyboard,
+        };
+
+        device.emit(&event)
+    }
+}
+
+/// Which device a key or button belongs on
+fn key_kind(code: u16) -> DeviceKind {
+    if MOUSE_BUTTONS.contains(&code) {
+        DeviceKind::Mouse
+    } else if GAMEPAD_BUTTONS.contains(&code) {
+        DeviceKind::Gamepad
+    } else {
+        DeviceKind::Keyboard
+    }
+}
+
+/// A virtual keyboard with every regular key, and the extended media and function keys
+/// after the button ranges
+fn keyboard(name: &str) -> io::Result<VirtualDevice> {
+    let mut keys = AttributeSet::<KeyCode>::new();
+    for code in (1..0x100).chain(0x160..0x2c0) {
+        keys.insert(KeyCode::new(code));
+    }
+
+    VirtualDevice::builder()?
+        .name(name)
+        .with_keys(&keys)?
+        .build()
+}
+
+/// A virtual mouse with its buttons, movement and both scroll wheels
+fn mouse(name: &str) -> io::Result<VirtualDevice> {
+    let mut buttons = AttributeSet::<KeyCode>::new();
+    for code in MOUSE_BUTTONS {
+        buttons.insert(KeyCode::new(code));
+    }
+
+    let mut axes = AttributeSet::<RelativeAxisCode>::new();
+    for axis in [
+        RelativeAxisCode::REL_X,
+        RelativeAxisCode::REL_Y,
+        RelativeAxisCode::REL_WHEEL,
+        RelativeAxisCode::REL_HWHEEL,
+        RelativeAxisCode::REL_WHEEL_HI_RES,
+        RelativeAxisCode::REL_HWHEEL_HI_RES,
+    ] {
+        axes.insert(axis);
+    }
+
+    VirtualDevice::builder()?
+        .name(name)
+        .with_keys(&buttons)?
+        .with_relative_axes(&axes)?
+        .build()
+}
+
+/// A virtual gamepad with its buttons and axes
+fn gamepad(name: &str) -> io::Result<VirtualDevice> {
+    let mut buttons = AttributeSet::<KeyCode>::new();
+    for code in GAMEPAD_BUTTONS {
+        buttons.insert(KeyCode::new(code));
+    }
+
+    let mut builder = VirtualDevice::builder()?.name(name).with_keys(&buttons)?;
+    for axis in GAMEPAD_AXES {
+        let info = AbsInfo::new(
+            0,
+            crate::gamepad::AXIS_MIN,
+            crate::gamepad::AXIS_MAX,
+            0,
+            0,
+            0,
+        );
+        builder = builder.with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisCode(axis), info))?;
+    }
+
+    builder.build()
+}
+
+impl Output for VirtualOutput {
+    fn press(&mut self, code: u16) -> io::Result<()> {
+        self.key_event(code, 1)
+    }
+
+    fn release(&mut self, code: u16) -> io::Result<()> {
+        self.key_event(code, 0)
+    }
+
+    fn click(&mut self, code: u16) -> io::Result<()> {
+        self.press(code)?;
+        std::thread::sleep(CLICK_HOLD);
+        self.release(code)
+    }
+
+    fn move_mouse(&mut self, x: i32, y: i32) -> io::Result<()> {
+        self.mouse().emit(&[
+            InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_X.0, x),
+            InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_Y.0, -y),
+        ])
+    }
+
+    fn scroll_x(&mut self, value: i32) -> io::Result<()> {
+        let events = wheel_events(
+            RelativeAxisCode::REL_HWHEEL_HI_RES,
+            RelativeAxisCode::REL_HWHEEL,
+            value,
+            &mut self.wheel_remainder.0,
+        );
+        self.mouse().emit(&events)
+    }
+
+    fn scroll_y(&mut self, value: i32) -> io::Result<()> {
+        let events = wheel_events(
+            RelativeAxisCode::REL_WHEEL_HI_RES,
+            RelativeAxisCode::REL_WHEEL,
+            value,
+            &mut self.wheel_remainder.1,
+        );
+        self.mouse().emit(&events)
+    }
+
+    fn move_axis(&mut self, axis: u16, value: i32) -> io::Result<()> {
+        let event = [InputEvent::new(EventType::ABSOLUTE.0, axis, value)];
+        match self.routed(DeviceKind::Gamepad) {
+            Some(device) => device.emit(&event),
+            None => self.gamepad()?.emit(&event),
+        }
+    }
+
+    fn route(&mut self, device: Option<&str>) {
+        self.route = device.map(str::to_string);
+    }
+}
+
+/// Prints every event instead of injecting it, for trying out a config
+#[derive(Default)]
+pub struct DryRunOutput {
+    route: Option<String>,
+}
+
+impl DryRunOutput {
+    /// Print an event, with the extra device it's routed to
+    fn print(&self, event: std::fmt::Arguments) {
+        match &self.route {
+            Some(device) => println!("{event} on {device}"),
+            None => println!("{event}"),
+        }
+    }
+}
+
+impl Output for DryRunOutput {
+    fn press(&mut self, code: u16) -> io::Result<()> {
+        self.print(format_args!("press {}", key_name(code)));
+        Ok(())
+    }
+
+    fn release(&mut self, code: u16) -> io::Result<()> {
+        self.print(format_args!("release {}", key_name(code)));
+        Ok(())
+    }
+
+    fn move_mouse(&mut self, x: i32, y: i32) -> io::Result<()> {
+        self.print(format_args!("move {x} {y}"));
+        Ok(())
+    }
+
+    fn scroll_x(&mut self, value: i32) -> io::Result<()> {
+        self.print(format_args!("scroll_x {value}"));
+        Ok(())
+    }
+
+    fn scroll_y(&mut self, value: i32) -> io::Result<()> {
+        self.print(format_args!("scroll_y {value}"));
+        Ok(())
+    }
+
+    fn move_axis(&mut self, axis: u16, value: i32) -> io::Result<()> {
+        self.print(format_args!("axis {:?} {value}", AbsoluteAxisCode(axis)));
+        Ok(())
+    }
+
+    fn route(&mut self, device: Option<&str>) {
+        self.route = device.map(str::to_string);
+    }
+}
+
+/// An event sent to a `RecordingOutput`
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputEvent {
+    Press(u16),
+    Release(u16),
+    Move(i32, i32),
+    ScrollX(i32),
+    ScrollY(i32),
+    Axis(u16, i32),
+    /// Events after this go to an extra device, or back to the usual ones
+    Route(Option<String>),
+}
+
+/// Keeps every event instead of injecting it, for tests to check what the handler did.
+/// Clones share the same events, so a test can keep one and give the handler another
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct RecordingOutput {
+    events: std::sync::Arc<std::sync::Mutex<Vec<OutputEvent>>>,
+}
+
+#[cfg(test)]
+impl RecordingOutput {
+    /// The events recorded since the last call
+    pub fn take(&self) -> Vec<OutputEvent> {
+        std::mem::take(&mut self.events.lock().unwrap())
+    }
+
+    fn record(&mut self, event: OutputEvent) -> io::Result<()> {
+        self.events.lock().unwrap().push(event);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Output for RecordingOutput {
+    fn press(&mut self, code: u16) -> io::Result<()> {
+        self.record(OutputEvent::Press(code))
+    }
+
+    fn release(&mut self, code: u16) -> io::Result<()> {
+        self.record(OutputEvent::Release(code))
+    }
+
+    fn move_mouse(&mut self, x: i32, y: i32) -> io::Result<()> {
+        self.record(OutputEvent::Move(x, y))
+    }
+
+    fn scroll_x(&mut self, value: i32) -> io::Result<()> {
+        self.record(OutputEvent::ScrollX(value))
+    }
+
+    fn scroll_y(&mut self, value: i32) -> io::Result<()> {
+        self.record(OutputEvent::ScrollY(value))
+    }
+
+    fn move_axis(&mut self, axis: u16, value: i32) -> io::Result<()> {
+        self.record(OutputEvent::Axis(axis, value))
+    }
+
+    fn route(&mut self, device: Option<&str>) {
+        let _ = self.record(OutputEvent::Route(device.map(str::to_string)));
+    }
+}
+
+fn key_name(code: u16) -> String {
+    crate::keycodes::name(code).map_or_else(|| code.to_string(), str::to_string)
+}
+
+/// The high-resolution wheel event for a scroll, plus a regular wheel event whenever the
+/// scrolling adds up to whole notches, for apps that only understand those
+fn wheel_events(
+    hi_res: RelativeAxisCode,
+    notch: RelativeAxisCode,
+    value: i32,
+    remainder: &mut i32,
+) -> Vec<InputEvent> {
+    let mut events = vec![InputEvent::new(EventType::RELATIVE.0, hi_res.0, value)];
+
+    *remainder += value;
+    let notches = *remainder / WHEEL_NOTCH;
+    *remainder -= notches * WHEEL_NOTCH;
+
+    if notches != 0 {
+        events.push(InputEvent::new(EventType::RELATIVE.0, notch.0, notches));
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wheel_events_add_up_to_notches() {
+        let mut remainder = 0;
+
+        let events = wheel_events(
+            RelativeAxisCode::REL_WHEEL_HI_RES,
+            RelativeAxisCode::REL_WHEEL,
+            90,
+            &mut remainder,
+        );
+        assert_eq!(events.len(), 1);
+
+        let events = wheel_events(
+            RelativeAxisCode::REL_WHEEL_HI_RES,
+            RelativeAxisCode::REL_WHEEL,
+            60,
+            &mut remainder,
+        );
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].value(), 1);
+        assert_eq!(remainder, 30);
+
+        let events = wheel_events(
+            RelativeAxisCode::REL_WHEEL_HI_RES,
+            RelativeAxisCode::REL_WHEEL,
+            -270,
+            &mut remainder,
+        );
+        assert_eq!(events[1].value(), -2);
+    }
+}
@@ -0,0 +1,157 @@
+// Stateful MIDI byte stream parser.
+// Drivers don't promise one complete message per callback: devices using running status
+// omit the status byte on repeated messages, and a message can be split over two buffers.
+// This keeps the running status and any partial message between callbacks, and hands
+// complete messages to midi-msg one at a time.
+
+use midi_msg::MidiMsg;
+use tracing::{trace, warn};
+
+#[derive(Debug, Default)]
+pub struct MidiParser {
+    // Status byte of the last channel message, reused for data bytes without one
+    running_status: Option<u8>,
+
+    // Bytes of the message currently being received
+    pending: Vec<u8>,
+
+    // Whether `pending` is an unfinished SysEx message
+    in_sysex: bool,
+
+    // Complete messages midi-msg couldn't parse, until they're taken
+    unparsed: Vec<Vec<u8>>,
+}
+
+/// How many data bytes follow a status byte
+pub fn data_len(status: u8) -> usize {
+    match status {
+        0x80..=0xBF | 0xE0..=0xEF => 2,
+        0xC0..=0xDF => 1,
+        0xF1 | 0xF3 => 1,
+        0xF2 => 2,
+        _ => 0,
+    }
+}
+
+impl MidiParser {
+    /// Feed raw bytes from the driver, returning every message they complete
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<MidiMsg> {
+        let mut messages = Vec::new();
+
+        for &byte in bytes {
+            match byte {
+                // Real-time messages can show up anywhere, even in the middle of another message
+                0xF8..=0xFF => Self::emit(&[byte], &mut messages, &mut self.unparsed),
+                0xF0 => {
+                    self.pending = vec![byte];
+                    self.in_sysex = true;
+                    self.running_status = None;
+                }
+                0xF7 => {
+                    if self.in_sysex {
+                        self.pending.push(byte);
+                        Self::emit(&self.pending, &mut messages, &mut self.unparsed);
+                    }
+                    self.pending.clear();
+                    self.in_sysex = false;
+                }
+                0x80..=0xF6 => {
+                    if !self.pending.is_empty() {
+                        trace!(pending = ?self.pending, "Dropping truncated MIDI message");
+                    }
+
+                    self.pending = vec![byte];
+                    self.in_sysex = false;
+                    // System common messages cancel running status
+                    self.running_status = (byte < 0xF0).then_some(byte);
+                }
+                _ if self.in_sysex => self.pending.push(byte),
+                _ => {
+                    if self.pending.is_empty() {
+                        match self.running_status {
+                            Some(status) => self.pending.push(status),
+                            None => {
+                                trace!(?byte, "Dropping data byte without a status");
+                                continue;
+                            }
+                        }
+                    }
+                    self.pending.push(byte);
+                }
+            }
+
+            if !self.in_sysex
+                && !self.pending.is_empty()
+                && self.pending.len() == 1 + data_len(self.pending[0])
+            {
+                Self::emit(&self.pending, &mut messages, &mut self.unparsed);
+                self.pending.clear();
+            }
+        }
+
+        messages
+    }
+
+    /// Take the raw bytes of the messages that failed to parse since the last call
+    pub fn take_unparsed(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.unparsed)
+    }
+
+    fn emit(bytes: &[u8], messages: &mut Vec<MidiMsg>, unparsed: &mut Vec<Vec<u8>>) {
+        if let Some(msg) = crate::mmc::decode(bytes) {
+            messages.push(msg);
+            return;
+        }
+
+        match MidiMsg::from_midi(bytes) {
+            Ok((msg, _)) => messages.push(msg),
+            Err(e) => {
+                warn!(?e, "Failed to parse MIDI message: {:02X?}", bytes);
+                unparsed.push(bytes.to_vec());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midi_msg::{Channel, ChannelVoiceMsg};
+
+    fn note_on(note: u8, velocity: u8) -> MidiMsg {
+        MidiMsg::ChannelVoice {
+            channel: Channel::Ch1,
+            msg: ChannelVoiceMsg::NoteOn { note, velocity },
+        }
+    }
+
+    #[test]
+    fn test_running_status_across_buffers() {
+        let mut parser = MidiParser::default();
+
+        assert_eq!(
+            parser.feed(&[0x90, 60, 100, 62, 100, 64]),
+            vec![note_on(60, 100), note_on(62, 100)]
+        );
+        // The rest of the third note arrives with a clock tick in the middle
+        assert_eq!(
+            parser.feed(&[0xF8, 100]),
+            vec![
+                MidiMsg::SystemRealTime {
+                    msg: midi_msg::SystemRealTimeMsg::TimingClock
+                },
+                note_on(64, 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unparsed() {
+        let mut parser = MidiParser::default();
+
+        // 0xF4 is an undefined system common message
+        assert_eq!(parser.feed(&[0xF4, 0x90, 60, 100]), vec![note_on(60, 100)]);
+        assert_eq!(parser.take_unparsed(), vec![vec![0xF4]]);
+        assert!(parser.take_unparsed().is_empty());
+    }
+}
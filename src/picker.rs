@@ -0,0 +1,103 @@
+// Interactive MIDI device picker, for when `midi_device` doesn't match any port.
+// Instead of giving up, list the ports, let the user pick one, and offer to save
+// the choice back into the config file.
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use tracing::{error, info};
+
+use crate::config::{Config, DeviceMatch};
+use crate::input;
+
+pub fn prompt(question: &str) -> Option<String> {
+    print!("{question}");
+    std::io::stdout().flush().ok()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer).ok()?;
+    Some(answer.trim().to_string())
+}
+
+/// Ask which port to use, updating the config with the choice
+pub fn pick_device(config: &mut Config, config_path: &Path) {
+    let names = input::port_names();
+
+    if names.is_empty() {
+        return;
+    }
+
+    println!("No MIDI port matches {:?}, pick one:", config.midi_device);
+    for (i, name) in names.iter().enumerate() {
+        println!("  {i}: {name}");
+    }
+
+    let Some(name) = prompt("Port number: ")
+        .and_then(|answer| answer.parse::<usize>().ok())
+        .and_then(|index| names.get(index))
+    else {
+        return;
+    };
+
+    info!(?name, "Picked MIDI device");
+    config.midi_device = name.clone();
+    config.device_match = DeviceMatch::Exact;
+
+    let save = prompt(&format!("Save it to {}? [y/N] ", config_path.display()))
+        .is_some_and(|answer| answer.eq_ignore_ascii_case("y"));
+
+    if save {
+        let result = std::fs::read_to_string(config_path)
+            .and_then(|text| std::fs::write(config_path, set_midi_device(&text, name)));
+
+        if let Err(e) = result {
+            error!(%e, ?config_path, "Failed to save the picked device");
+        }
+    }
+}
+
+/// Replace the `midi_device` line of a config file, keeping everything else as is
+fn set_midi_device(config: &str, name: &str) -> String {
+    let line = format!("midi_device = {}", toml::Value::String(name.to_string()));
+
+    let is_midi_device = |l: &&str| {
+        l.strip_prefix("midi_device")
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    };
+
+    if !config.lines().any(|l| is_midi_device(&l)) {
+        // Top level keys have to come before any table
+        return format!("{line}\n{config}");
+    }
+
+    let mut out: String = config
+        .lines()
+        .map(|l| if is_midi_device(&l) { line.as_str() } else { l })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if config.ends_with('\n') {
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_midi_device() {
+        let config = "# the device\nmidi_device = \"28:0\"\n\n[cc]\n";
+        assert_eq!(
+            set_midi_device(config, "Launchkey \"MK3\""),
+            "# the device\nmidi_device = 'Launchkey \"MK3\"'\n\n[cc]\n"
+        );
+
+        assert_eq!(
+            set_midi_device("[cc]\n", "28:0"),
+            "midi_device = \"28:0\"\n[cc]\n"
+        );
+    }
+}
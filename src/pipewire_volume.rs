@@ -0,0 +1,417 @@
+// Volume changes through PipeWire's own API, over one connection kept open while midkb runs,
+// instead of starting `pactl` for every change.
+// Sinks and app streams are tracked from the registry, and a change sets the Props param of
+// each matching node, like `pw-cli set-param` does. The default sink comes from the session
+// manager's `default` metadata. If PipeWire restarts, the connection is opened again and the
+// changes made in the meantime are applied then.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::rc::Rc;
+use std::time::Duration;
+
+use pipewire as pw;
+use pw::channel::{Receiver, Sender};
+use pw::context::ContextRc;
+use pw::core::{CoreRc, PW_ID_CORE};
+use pw::main_loop::MainLoopRc;
+use pw::metadata::{Metadata, MetadataListener};
+use pw::node::{Node, NodeListener};
+use pw::registry::{GlobalObject, RegistryRc};
+use pw::spa::param::ParamType;
+use pw::spa::pod::deserialize::PodDeserializer;
+use pw::spa::pod::serialize::PodSerializer;
+use pw::spa::pod::{Object, Pod, Property, Value, ValueArray};
+use pw::spa::sys::{SPA_PROP_channelVolumes as PROP_CHANNEL_VOLUMES, SPA_PROP_mute as PROP_MUTE};
+use pw::spa::utils::dict::DictRef;
+use pw::spa::utils::result::AsyncSeq;
+use pw::spa::utils::SpaTypes;
+use pw::types::ObjectType;
+use tracing::{debug, warn};
+
+use crate::config::VolumeTarget;
+use crate::volume::Change;
+
+/// How long to wait before connecting again after PipeWire goes away
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+type Changes = (VolumeTarget, Change);
+
+/// Start the thread applying changes through PipeWire, failing if PipeWire isn't running
+pub fn spawn() -> Result<Sender<Changes>, pw::Error> {
+    let (changes, mut receiver) = pw::channel::channel();
+    let (connected_tx, connected) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        pw::init();
+
+        let mut connected_tx = Some(connected_tx);
+        loop {
+            match Connection::open() {
+                Ok(connection) => {
+                    if let Some(tx) = connected_tx.take() {
+                        let _ = tx.send(Ok(()));
+                    }
+
+                    receiver = connection.run(receiver);
+                    warn!("Lost the connection to PipeWire, reconnecting");
+                }
+                Err(e) => match connected_tx.take() {
+                    // Not running at all, so let the mixer use pactl instead
+                    Some(tx) => {
+                        let _ = tx.send(Err(e));
+                        return;
+                    }
+                    None => debug!(%e, "Failed to reconnect to PipeWire"),
+                },
+            }
+
+            std::thread::sleep(RECONNECT_DELAY);
+        }
+    });
+
+    connected
+        .recv()
+        .unwrap_or(Err(pw::Error::CreationFailed))
+        .map(|()| changes)
+}
+
+/// The connection to PipeWire and everything tracked through it.
+/// Listeners come before what they listen to, so they're dropped first
+struct Connection {
+    _core_listener: pw::core::Listener,
+    _registry_listener: pw::registry::Listener,
+    state: Rc<RefCell<State>>,
+    _registry: RegistryRc,
+    core: CoreRc,
+    _context: ContextRc,
+    main_loop: MainLoopRc,
+    /// The sync that the main loop is running until PipeWire answers
+    pending: Rc<Cell<Option<AsyncSeq>>>,
+}
+
+impl Connection {
+    fn open() -> Result<Self, pw::Error> {
+        let main_loop = MainLoopRc::new(None)?;
+        let context = ContextRc::new(&main_loop, None)?;
+        let core = context.connect_rc(None)?;
+        let registry = core.get_registry_rc()?;
+
+        let state = Rc::new(RefCell::new(State::default()));
+        let pending = Rc::new(Cell::new(None));
+
+        let done_loop = main_loop.downgrade();
+        let error_loop = main_loop.downgrade();
+        let done_pending = pending.clone();
+        let core_listener = core
+            .add_listener_local()
+            .done(move |id, seq| {
+                if id == PW_ID_CORE && done_pending.get() == Some(seq) {
+                    done_pending.set(None);
+                    if let Some(main_loop) = done_loop.upgrade() {
+                        main_loop.quit();
+                    }
+                }
+            })
+            .error(move |id, _, _, message| {
+                // An error on the core itself means the connection is gone
+                if id == PW_ID_CORE {
+                    debug!(error = message, "PipeWire connection error");
+                    if let Some(main_loop) = error_loop.upgrade() {
+                        main_loop.quit();
+                    }
+                }
+            })
+            .register();
+
+        let global_registry = registry.downgrade();
+        let global_state = state.clone();
+        let remove_state = state.clone();
+        let registry_listener = registry
+            .add_listener_local()
+            .global(move |global| {
+                if let Some(registry) = global_registry.upgrade() {
+                    track(&global_state, &registry, global);
+                }
+            })
+            .global_remove(move |id| remove_state.borrow_mut().forget(id))
+            .register();
+
+        Ok(Self {
+            _core_listener: core_listener,
+            _registry_listener: registry_listener,
+            state,
+            _registry: registry,
+            core,
+            _context: context,
+            main_loop,
+            pending,
+        })
+    }
+
+    /// Apply changes until the connection drops, handing the receiver back for the next one
+    fn run(self, changes: Receiver<Changes>) -> Receiver<Changes> {
+        // Once for the sinks and streams to show up, once more for the properties of the
+        // nodes bound then, so changes already waiting find what they're for
+        if !(self.roundtrip() && self.roundtrip()) {
+            return changes;
+        }
+
+        let state = self.state.clone();
+        let attached = changes.attach(self.main_loop.loop_(), move |(target, change)| {
+            state.borrow_mut().apply(&target, change);
+        });
+        self.main_loop.run();
+
+        attached.deattach()
+    }
+
+    /// Wait for PipeWire to handle everything sent so far, false if the connection dropped
+    fn roundtrip(&self) -> bool {
+        match self.core.sync(0) {
+            Ok(seq) => self.pending.set(Some(seq)),
+            Err(e) => {
+                debug!(%e, "Failed to sync with PipeWire");
+                return false;
+            }
+        }
+
+        self.main_loop.run();
+        self.pending.take().is_none()
+    }
+}
+
+/// The sinks and app streams there are, and which sink is the default
+#[derive(Default)]
+struct State {
+    nodes: HashMap<u32, Volume>,
+    /// The `default` metadata holding the default sink, with its id
+    metadata: Option<(u32, MetadataListener, Metadata)>,
+    default_sink: Option<String>,
+}
+
+impl State {
+    fn forget(&mut self, id: u32) {
+        self.nodes.remove(&id);
+        if self
+            .metadata
+            .as_ref()
+            .is_some_and(|(meta_id, ..)| *meta_id == id)
+        {
+            self.metadata = None;
+            self.default_sink = None;
+        }
+    }
+
+    fn apply(&mut self, target: &VolumeTarget, change: Change) {
+        let default_sink = self.default_sink.as_deref();
+        let mut found = false;
+
+        for volume in self.nodes.values_mut() {
+            if volume.is(target, default_sink) {
+                debug!(name = ?volume.name, ?change, "Changing volume");
+                volume.change(change);
+                found = true;
+            }
+        }
+
+        if !found {
+            match &target.app {
+                Some(app) => debug!(app, "App isn't playing anything, not changing its volume"),
+                None => warn!(sink = ?target.sink, "No such sink, not changing its volume"),
+            }
+        }
+    }
+}
+
+/// Start tracking a sink, an app stream or the default metadata as it shows up
+fn track(state: &Rc<RefCell<State>>, registry: &RegistryRc, global: &GlobalObject<&DictRef>) {
+    let Some(props) = global.props else {
+        return;
+    };
+    let id = global.id;
+
+    match global.type_ {
+        ObjectType::Node => {
+            let sink = match props.get("media.class") {
+                Some("Audio/Sink") => true,
+                Some("Stream/Output/Audio") => false,
+                _ => return,
+            };
+            let node: Node = match registry.bind(global) {
+                Ok(node) => node,
+                Err(e) => {
+                    debug!(%e, id, "Failed to bind PipeWire node");
+                    return;
+                }
+            };
+
+            // Streams only have all their properties in the node's info
+            let info_state = Rc::downgrade(state);
+            let param_state = Rc::downgrade(state);
+            let listener = node
+                .add_listener_local()
+                .info(move |info| {
+                    let (Some(state), Some(props)) = (info_state.upgrade(), info.props()) else {
+                        return;
+                    };
+                    let mut state = state.borrow_mut();
+                    if let Some(volume) = state.nodes.get_mut(&id) {
+                        volume.update(props);
+                    }
+                })
+                .param(move |_, param_type, _, _, param| {
+                    let (Some(state), Some(param)) = (param_state.upgrade(), param) else {
+                        return;
+                    };
+                    if param_type != ParamType::Props {
+                        return;
+                    }
+                    let mut state = state.borrow_mut();
+                    if let Some(volume) = state.nodes.get_mut(&id) {
+                        volume.update_props(param);
+                    }
+                })
+                .register();
+            node.subscribe_params(&[ParamType::Props]);
+
+            let mut volume = Volume {
+                _listener: listener,
+                node,
+                sink,
+                name: None,
+                app: None,
+                binary: None,
+                channels: 2,
+                muted: false,
+            };
+            volume.update(props);
+            state.borrow_mut().nodes.insert(id, volume);
+        }
+        ObjectType::Metadata if props.get("metadata.name") == Some("default") => {
+            let metadata: Metadata = match registry.bind(global) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    debug!(%e, id, "Failed to bind PipeWire metadata");
+                    return;
+                }
+            };
+
+            let property_state = Rc::downgrade(state);
+            let listener = metadata
+                .add_listener_local()
+                .property(move |subject, key, _, value| {
+                    if subject == PW_ID_CORE && matches!(key, Some("default.audio.sink") | None) {
+                        if let Some(state) = property_state.upgrade() {
+                            state.borrow_mut().default_sink = value.and_then(sink_name);
+                        }
+                    }
+                    0
+                })
+                .register();
+
+            state.borrow_mut().metadata = Some((id, listener, metadata));
+        }
+        _ => {}
+    }
+}
+
+/// The sink name in a `default.audio.sink` value, like `{ "name": "alsa_output.pci-0000_00_1f.3" }`
+fn sink_name(value: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(value).ok()?;
+    Some(value["name"].as_str()?.to_string())
+}
+
+/// A sink or app stream whose volume can be changed
+struct Volume {
+    _listener: NodeListener,
+    node: Node,
+    sink: bool,
+    name: Option<String>,
+    app: Option<String>,
+    binary: Option<String>,
+    /// How many channel volumes to set, stereo until the node says otherwise
+    channels: usize,
+    muted: bool,
+}
+
+impl Volume {
+    /// Whether `target` means this node, going by the app's name or binary without minding the
+    /// case like with `pactl`
+    fn is(&self, target: &VolumeTarget, default_sink: Option<&str>) -> bool {
+        match &target.app {
+            Some(app) => {
+                !self.sink
+                    && [&self.app, &self.binary]
+                        .into_iter()
+                        .flatten()
+                        .any(|name| name.eq_ignore_ascii_case(app))
+            }
+            None => {
+                let sink = target.sink.as_deref().or(default_sink);
+                self.sink && sink.is_some() && self.name.as_deref() == sink
+            }
+        }
+    }
+
+    fn update(&mut self, props: &DictRef) {
+        for (field, key) in [
+            (&mut self.name, "node.name"),
+            (&mut self.app, "application.name"),
+            (&mut self.binary, "application.process.binary"),
+        ] {
+            if let Some(value) = props.get(key) {
+                *field = Some(value.to_string());
+            }
+        }
+    }
+
+    /// Keep up with the node's mute and channels, which a mute toggle and a volume need
+    fn update_props(&mut self, param: &Pod) {
+        let Ok((_, Value::Object(object))) =
+            PodDeserializer::deserialize_any_from(param.as_bytes())
+        else {
+            return;
+        };
+
+        for property in object.properties {
+            match (property.key, property.value) {
+                (PROP_MUTE, Value::Bool(muted)) => self.muted = muted,
+                (PROP_CHANNEL_VOLUMES, Value::ValueArray(ValueArray::Float(volumes)))
+                    if !volumes.is_empty() =>
+                {
+                    self.channels = volumes.len();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn change(&mut self, change: Change) {
+        let property = match change {
+            Change::Set(percent) => {
+                // Percentages are on the cubic scale PulseAudio and `pactl` use
+                let volume = (percent as f32 / 100.0).powi(3);
+                let volumes = ValueArray::Float(vec![volume; self.channels]);
+                Property::new(PROP_CHANNEL_VOLUMES, Value::ValueArray(volumes))
+            }
+            Change::ToggleMute => {
+                self.muted = !self.muted;
+                Property::new(PROP_MUTE, Value::Bool(self.muted))
+            }
+        };
+        let props = Value::Object(Object {
+            type_: SpaTypes::ObjectParamProps.as_raw(),
+            id: ParamType::Props.as_raw(),
+            properties: vec![property],
+        });
+
+        let Ok((bytes, _)) = PodSerializer::serialize(Cursor::new(Vec::new()), &props) else {
+            return;
+        };
+        if let Some(param) = Pod::from_bytes(bytes.get_ref()) {
+            self.node.set_param(ParamType::Props, 0, param);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+// Configs for common controllers, built into the binary and picked with `--preset`.
+// The user's config file is merged on top, so it only needs to list what it changes.
+
+/// The bundled presets, by name
+pub const PRESETS: &[(&str, &str)] = &[
+    ("apc-mini", include_str!("../presets/apc-mini.toml")),
+    (
+        "launchpad-mini",
+        include_str!("../presets/launchpad-mini.toml"),
+    ),
+    ("mpd218", include_str!("../presets/mpd218.toml")),
+    ("nanokontrol2", include_str!("../presets/nanokontrol2.toml")),
+];
+
+/// The config text of a bundled preset
+pub fn get(name: &str) -> Option<&'static str> {
+    PRESETS
+        .iter()
+        .find(|(preset, _)| *preset == name)
+        .map(|(_, config)| *config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Action, Config};
+
+    #[test]
+    fn test_presets_load() {
+        for (name, _) in PRESETS {
+            Config::load_preset(name, None).unwrap();
+        }
+        assert!(Config::load_preset("missing", None).is_err());
+    }
+
+    #[test]
+    fn test_preset_overrides() {
+        let dir = std::env::temp_dir().join(format!("midkb-preset-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+                midi_device = "28:0"
+                [notes]
+                36 = 57
+                [cc.16]
+                bind_mode = "Mouse"
+                clockwise = "x"
+            "#,
+        )
+        .unwrap();
+
+        let config = Config::load_preset("mpd218", Some(&path)).unwrap();
+        assert_eq!(config.midi_device, "28:0");
+        assert_eq!(config.notes.get_action(36), Some(&Action::Key(57)));
+        assert_eq!(config.notes.get_action(37), Some(&Action::Key(45)));
+        assert!(config.cc.get_dir_config(3).is_some());
+        assert!(config.cc.get_dir_config(16).is_some());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+// Replaying a file of MIDI as if it came from a controller, with `backend = "replay"`.
+// The file is written like the input of `midkb simulate`, one command per line, with `wait`
+// letting time pass between them, so a recorded or hand-written performance can be played
+// through the real handler and virtual devices without the hardware.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::time::Instant;
+
+use tracing::{error, info, warn};
+
+use crate::input::{Connection, MidiSource};
+use crate::simulate::Command;
+use crate::worker::MidiSender;
+
+/// A file of `midkb simulate` commands, played once from the start every time it's connected
+pub struct Replay {
+    path: PathBuf,
+}
+
+impl Replay {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: PathBuf::from(path),
+        }
+    }
+}
+
+/// Stops the replay once closed
+struct ReplayConnection(Sender<()>);
+
+impl Connection for ReplayConnection {
+    fn close(self: Box<Self>) {
+        let _ = self.0.send(());
+    }
+}
+
+impl MidiSource for Replay {
+    fn connect(&self, sender: MidiSender) -> Option<Box<dyn Connection>> {
+        let text = std::fs::read_to_string(&self.path)
+            .inspect_err(|e| error!(%e, path = ?self.path, "Failed to read the file to replay"))
+            .ok()?;
+
+        let mut commands = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            match Command::parse(line) {
+                Ok(Some(command)) => commands.push(command),
+                Ok(None) => {}
+                Err(e) => warn!(%e, line = number + 1, "Skipping a line that can't be replayed"),
+            }
+        }
+
+        info!(path = ?self.path, "Replaying MIDI");
+
+        let (stop_tx, stop) = channel();
+        std::thread::Builder::new()
+            .name("midkb-replay".to_string())
+            .spawn(move || play(commands, sender, stop))
+            .inspect_err(|e| error!(%e, "Failed to start replaying"))
+            .ok()?;
+
+        Some(Box::new(ReplayConnection(stop_tx)))
+    }
+
+    fn is_present(&self) -> bool {
+        self.path.exists()
+    }
+}
+
+fn play(commands: Vec<Command>, sender: MidiSender, stop: Receiver<()>) {
+    let started = Instant::now();
+
+    for command in commands {
+        match command {
+            Command::Midi(bytes) => {
+                if !matches!(stop.try_recv(), Err(TryRecvError::Empty)) {
+                    return;
+                }
+                sender.send(started.elapsed().as_micros() as u64, &bytes);
+            }
+            Command::Wait(duration) => match stop.recv_timeout(duration) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return,
+            },
+        }
+    }
+
+    info!("Finished replaying MIDI");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker;
+
+    #[test]
+    fn test_replay() {
+        let path = std::env::temp_dir().join(format!("midkb-replay-{}.txt", std::process::id()));
+        std::fs::write(&path, "noteon 60\nwait 20\nnope\ncc 1 64 2\nnoteoff 60\n").unwrap();
+
+        let (tx, rx) = channel();
+        let sender = worker::spawn_with(move |_, _, bytes, _| {
+            let _ = tx.send(bytes.to_vec());
+        });
+
+        let replay = Replay::new(path.to_str().unwrap());
+        assert!(replay.is_present());
+        let connection = replay.connect(sender).unwrap();
+
+        let received: Vec<Vec<u8>> = rx.iter().take(3).collect();
+        assert_eq!(
+            received,
+            [vec![0x90, 60, 100], vec![0xB1, 1, 64], vec![0x80, 60, 0]]
+        );
+        connection.close();
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(!replay.is_present());
+        assert!(replay
+            .connect(worker::spawn_with(|_, _, _, _| {}))
+            .is_none());
+    }
+}
@@ -0,0 +1,187 @@
+// Reading the HTTP/1.1 requests the HTTP API and the web UI answer.
+// Clients get a few seconds to send the whole request, and the request line and headers can
+// only take up so much, so a client can neither hold a connection open forever nor grow its
+// buffers without end.
+
+use std::io;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader, Take};
+use tokio::net::TcpStream;
+
+/// The most the request line and headers can take up together
+const MAX_HEAD: u64 = 16 * 1024;
+
+/// How long a client has to send its whole request
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Header names, lowercased, with their values
+    headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The value of a header, named in lowercase
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReadError {
+    /// The connection failed, so there's no one to answer
+    #[error(transparent)]
+    Io(io::Error),
+    /// The client didn't send the whole request in time
+    #[error("the request took too long to arrive")]
+    TimedOut,
+    /// The head or the body was larger than allowed, which can still be answered
+    #[error("the request is too large")]
+    TooLarge,
+}
+
+/// Read a request whose body can be up to `max_body` bytes
+pub async fn read(stream: &mut TcpStream, max_body: usize) -> Result<Request, ReadError> {
+    tokio::time::timeout(TIMEOUT, read_request(stream, max_body))
+        .await
+        .map_err(|_| ReadError::TimedOut)?
+}
+
+async fn read_request(stream: &mut TcpStream, max_body: usize) -> Result<Request, ReadError> {
+    let mut head = BufReader::new(stream).take(MAX_HEAD);
+
+    let mut request_line = String::new();
+    read_line(&mut head, &mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        read_line(&mut head, &mut line).await?;
+        if line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    };
+
+    let content_length: usize = request
+        .header("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    if content_length > max_body {
+        return Err(ReadError::TooLarge);
+    }
+
+    // Part of the body may already be buffered, so it's read through the same reader
+    request.body = vec![0; content_length];
+    head.into_inner()
+        .read_exact(&mut request.body)
+        .await
+        .map_err(ReadError::Io)?;
+
+    Ok(request)
+}
+
+/// A path segment with its `%XX` escapes turned back into the bytes they stand for, if they're
+/// all well formed and make up UTF-8
+pub fn percent_decode(segment: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(segment.len());
+    let mut rest = segment.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        rest = after;
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+
+        // `from_str_radix` would also take a sign
+        let hex = rest
+            .get(..2)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+        bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+        rest = &rest[2..];
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Read a line of the head, which has to end before the head's limit is reached
+async fn read_line(
+    head: &mut Take<BufReader<&mut TcpStream>>,
+    line: &mut String,
+) -> Result<(), ReadError> {
+    head.read_line(line).await.map_err(ReadError::Io)?;
+    match line.ends_with('\n') || head.limit() > 0 {
+        true => Ok(()),
+        false => Err(ReadError::TooLarge),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    /// Send `bytes` over a local connection and read them as a request
+    async fn read_sent(bytes: Vec<u8>, max_body: usize) -> Result<Request, ReadError> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        // Keep the client open while the request is read
+        tokio::spawn(async move {
+            let _ = client.write_all(&bytes).await;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+        read(&mut stream, max_body).await
+    }
+
+    #[tokio::test]
+    async fn test_read() {
+        let request = read_sent(
+            b"PUT /config HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello".to_vec(),
+            5,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            (request.method.as_str(), request.path.as_str()),
+            ("PUT", "/config")
+        );
+        assert_eq!(request.header("host"), Some("localhost"));
+        assert_eq!(request.body, b"hello");
+
+        let body_too_large = b"PUT / HTTP/1.1\r\nContent-Length: 6\r\n\r\nhello!".to_vec();
+        assert!(matches!(
+            read_sent(body_too_large, 5).await,
+            Err(ReadError::TooLarge)
+        ));
+
+        let mut head_too_large = b"GET / HTTP/1.1\r\nX-Padding: ".to_vec();
+        head_too_large.extend(vec![b'a'; MAX_HEAD as usize]);
+        assert!(matches!(
+            read_sent(head_too_large, 0).await,
+            Err(ReadError::TooLarge)
+        ));
+    }
+}
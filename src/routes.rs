@@ -0,0 +1,179 @@
+// MIDI routing.
+// Each `[routes.<name>]` entry forwards incoming messages to another MIDI port,
+// optionally filtering them by channel and remapping channels, notes and CC numbers
+// on the way, so midkb can double as a small MIDI router.
+
+use std::collections::HashMap;
+
+use midi_msg::{Channel, ChannelVoiceMsg, ControlChange, MidiMsg};
+use midir::MidiOutputConnection;
+use tracing::warn;
+
+use crate::config::RouteConfig;
+use crate::midi_output;
+
+/// Apply a route's filter and transformations to a message,
+/// returning `None` if the route doesn't forward it
+pub fn transform(route: &RouteConfig, msg: &MidiMsg) -> Option<MidiMsg> {
+    let (channel, msg) = match msg {
+        MidiMsg::ChannelVoice { channel, msg } => (*channel, *msg),
+        MidiMsg::ChannelMode { channel, msg } => {
+            if !route.accepts(*channel as u8 + 1) {
+                return None;
+            }
+
+            return Some(MidiMsg::ChannelMode {
+                channel: route.remap(*channel),
+                msg: *msg,
+            });
+        }
+        // System messages aren't tied to a channel
+        other => return Some(other.clone()),
+    };
+
+    if !route.accepts(channel as u8 + 1) {
+        return None;
+    }
+
+    let transpose = |note: u8| {
+        let note = note as i16 + route.transpose as i16;
+        (0..=127).contains(&note).then_some(note as u8)
+    };
+
+    let msg = match msg {
+        ChannelVoiceMsg::NoteOn { note, velocity } => ChannelVoiceMsg::NoteOn {
+            note: transpose(note)?,
+            velocity,
+        },
+        ChannelVoiceMsg::NoteOff { note, velocity } => ChannelVoiceMsg::NoteOff {
+            note: transpose(note)?,
+            velocity,
+        },
+        ChannelVoiceMsg::PolyPressure { note, pressure } => ChannelVoiceMsg::PolyPressure {
+            note: transpose(note)?,
+            pressure,
+        },
+        ChannelVoiceMsg::ControlChange { control } => {
+            match route.cc.get(&control.control().to_string()) {
+                Some(&renumbered) => ChannelVoiceMsg::ControlChange {
+                    control: ControlChange::CC {
+                        control: renumbered,
+                        value: control.value(),
+                    },
+                },
+                None => msg,
+            }
+        }
+        msg => msg,
+    };
+
+    Some(MidiMsg::ChannelVoice {
+        channel: route.remap(channel),
+        msg,
+    })
+}
+
+impl RouteConfig {
+    /// Whether messages on this channel (1-16) go through the route
+    fn accepts(&self, channel: u8) -> bool {
+        self.channels.is_empty() || self.channels.contains(&channel)
+    }
+
+    fn remap(&self, channel: Channel) -> Channel {
+        match self.channel {
+            Some(to) => Channel::from_u8(to.clamp(1, 16) - 1),
+            None => channel,
+        }
+    }
+}
+
+/// The open output ports of every configured route
+#[derive(Default)]
+pub struct Router {
+    outputs: Vec<(RouteConfig, MidiOutputConnection)>,
+}
+
+impl Router {
+    pub fn new(routes: &HashMap<String, RouteConfig>) -> Self {
+        let outputs = routes
+            .values()
+            .filter_map(|route| {
+                midi_output::connect(&route.port).map(|output| (route.clone(), output))
+            })
+            .collect();
+
+        Self { outputs }
+    }
+
+    /// Forward a message through every route that accepts it
+    pub fn route(&mut self, msg: &MidiMsg) {
+        for (route, output) in &mut self.outputs {
+            let Some(msg) = transform(route, msg) else {
+                continue;
+            };
+
+            if let Err(e) = output.send(&msg.to_midi()) {
+                warn!(%e, port = ?route.port, "Failed to route MIDI message");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(channel: Channel, note: u8) -> MidiMsg {
+        MidiMsg::ChannelVoice {
+            channel,
+            msg: ChannelVoiceMsg::NoteOn {
+                note,
+                velocity: 100,
+            },
+        }
+    }
+
+    #[test]
+    fn test_route_transform() {
+        let route: RouteConfig = toml::from_str(
+            r#"
+            port = "FLUID"
+            channels = [1]
+            channel = 10
+            transpose = 12
+            cc = { 1 = 74 }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            transform(&route, &note_on(Channel::Ch1, 60)),
+            Some(note_on(Channel::Ch10, 72))
+        );
+        // Filtered out by channel, and transposed out of range
+        assert_eq!(transform(&route, &note_on(Channel::Ch2, 60)), None);
+        assert_eq!(transform(&route, &note_on(Channel::Ch1, 120)), None);
+
+        let cc = MidiMsg::ChannelVoice {
+            channel: Channel::Ch1,
+            msg: ChannelVoiceMsg::ControlChange {
+                control: ControlChange::CC {
+                    control: 1,
+                    value: 64,
+                },
+            },
+        };
+        assert_eq!(
+            transform(&route, &cc),
+            Some(MidiMsg::ChannelVoice {
+                channel: Channel::Ch10,
+                msg: ChannelVoiceMsg::ControlChange {
+                    control: ControlChange::CC {
+                        control: 74,
+                        value: 64,
+                    },
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,262 @@
+// RTP-MIDI (AppleMIDI) network input.
+// macOS, iOS and rtpMIDI on Windows send MIDI over UDP as RTP packets, after setting up
+// a session with the AppleMIDI protocol on a control port and the data port right after it.
+// This accepts every invitation, answers clock syncs so the peer keeps the session alive,
+// and queues the MIDI commands in each RTP packet for the worker like a local device.
+
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use tokio::net::UdpSocket;
+use tracing::{debug, info, trace, warn};
+
+use crate::config::RtpMidiConfig;
+use crate::input::{Connection, MidiSource};
+use crate::parser::data_len;
+use crate::worker::MidiSender;
+
+/// Every AppleMIDI session packet starts with this signature
+const SIGNATURE: [u8; 2] = [0xFF, 0xFF];
+const PROTOCOL_VERSION: u32 = 2;
+/// Fixed size of an RTP header without contributing sources
+const RTP_HEADER_LEN: usize = 12;
+
+struct Session {
+    ssrc: u32,
+    name: String,
+    started: Instant,
+}
+
+impl Session {
+    /// Clock in the 100µs units AppleMIDI timestamps use
+    fn now(&self) -> u64 {
+        (self.started.elapsed().as_micros() / 100) as u64
+    }
+
+    /// Answer a session packet, returning whether the packet was one
+    async fn handle(&self, packet: &[u8], socket: &UdpSocket, from: SocketAddr) -> bool {
+        if packet.len() < 4 || packet[..2] != SIGNATURE {
+            return false;
+        }
+
+        match &packet[2..4] {
+            b"IN" if packet.len() >= 16 => {
+                let peer = packet[16..].split(|b| *b == 0).next().unwrap_or_default();
+                info!(?from, peer = %String::from_utf8_lossy(peer), "Accepting RTP-MIDI session");
+
+                let mut reply = Vec::with_capacity(16 + self.name.len() + 1);
+                reply.extend(SIGNATURE);
+                reply.extend(b"OK");
+                reply.extend(PROTOCOL_VERSION.to_be_bytes());
+                // Initiator token, echoed back
+                reply.extend(&packet[8..12]);
+                reply.extend(self.ssrc.to_be_bytes());
+                reply.extend(self.name.as_bytes());
+                reply.push(0);
+
+                self.send(socket, &reply, from).await;
+            }
+            b"CK" if packet.len() >= 36 => {
+                // The initiator sends count 0, we answer with count 1 and our time,
+                // and it finishes with count 2
+                if packet[8] == 0 {
+                    let mut reply = packet[..36].to_vec();
+                    reply[4..8].copy_from_slice(&self.ssrc.to_be_bytes());
+                    reply[8] = 1;
+                    reply[20..28].copy_from_slice(&self.now().to_be_bytes());
+                    self.send(socket, &reply, from).await;
+                }
+            }
+            b"BY" => info!(?from, "RTP-MIDI session ended"),
+            command => trace!(?command, "Ignoring AppleMIDI command"),
+        }
+
+        true
+    }
+
+    async fn send(&self, socket: &UdpSocket, packet: &[u8], to: SocketAddr) {
+        if let Err(e) = socket.send_to(packet, to).await {
+            warn!(%e, ?to, "Failed to answer RTP-MIDI peer");
+        }
+    }
+}
+
+/// Extract the MIDI command list of an RTP-MIDI packet, and whether
+/// its first command has a delta time (the Z flag)
+fn midi_list(packet: &[u8]) -> Option<(&[u8], bool)> {
+    let csrc_count = (*packet.first()? & 0x0F) as usize;
+    let section = packet.get(RTP_HEADER_LEN + csrc_count * 4..)?;
+
+    let flags = *section.first()?;
+    let (len, header_len) = if flags & 0x80 != 0 {
+        // B flag, 12 bit length
+        (
+            (((flags & 0x0F) as usize) << 8) | *section.get(1)? as usize,
+            2,
+        )
+    } else {
+        ((flags & 0x0F) as usize, 1)
+    };
+
+    let list = section.get(header_len..header_len + len)?;
+    Some((list, flags & 0x20 != 0))
+}
+
+/// Strip the delta times out of a MIDI command list, leaving the raw MIDI bytes.
+/// `first_has_delta` is the Z flag, set when the first command also has a delta time
+fn strip_delta_times(list: &[u8], first_has_delta: bool) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(list.len());
+    let mut running_status = None;
+    let mut i = 0;
+    let mut first = true;
+
+    while i < list.len() {
+        if !first || first_has_delta {
+            // Delta times are 1-4 bytes, all but the last with the high bit set
+            while i < list.len() && list[i] & 0x80 != 0 {
+                i += 1;
+            }
+            i += 1;
+        }
+        first = false;
+
+        let Some(&status) = list.get(i) else {
+            break;
+        };
+
+        let len = match status {
+            0xF0 => list[i..]
+                .iter()
+                .position(|b| *b == 0xF7)
+                .map_or(list.len() - i, |end| end + 1),
+            0x80..=0xFF => {
+                if status < 0xF0 {
+                    running_status = Some(status);
+                } else if status < 0xF8 {
+                    running_status = None;
+                }
+                1 + data_len(status)
+            }
+            _ => match running_status {
+                Some(status) => data_len(status),
+                None => break,
+            },
+        };
+
+        let end = (i + len).min(list.len());
+        bytes.extend(&list[i..end]);
+        i = end;
+    }
+
+    bytes
+}
+
+impl MidiSource for RtpMidiConfig {
+    fn connect(&self, sender: MidiSender) -> Option<Box<dyn Connection>> {
+        Some(Box::new(tokio::spawn(listen(self.clone(), sender))))
+    }
+}
+
+/// Listen for RTP-MIDI sessions, queueing their MIDI for the worker
+async fn listen(config: RtpMidiConfig, sender: MidiSender) {
+    let control_addr = config.listen;
+    let mut data_addr = control_addr;
+    // The data port is always the one after the control port
+    let Some(data_port) = control_addr.port().checked_add(1) else {
+        warn!(
+            ?control_addr,
+            "Can't listen for RTP-MIDI on port 65535, the next port is needed for data"
+        );
+        return;
+    };
+    data_addr.set_port(data_port);
+
+    let (control, data) = match (
+        UdpSocket::bind(control_addr).await,
+        UdpSocket::bind(data_addr).await,
+    ) {
+        (Ok(control), Ok(data)) => (control, data),
+        (Err(e), _) | (_, Err(e)) => {
+            warn!(%e, ?control_addr, "Failed to listen for RTP-MIDI");
+            return;
+        }
+    };
+
+    info!(?control_addr, ?data_addr, "Listening for RTP-MIDI sessions");
+
+    let session = Session {
+        ssrc: std::process::id()
+            ^ std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |time| time.subsec_nanos()),
+        name: config.name,
+        started: Instant::now(),
+    };
+
+    let mut control_buf = [0u8; 1024];
+    let mut data_buf = [0u8; 1024];
+
+    loop {
+        tokio::select! {
+            received = control.recv_from(&mut control_buf) => {
+                let (len, from) = match received {
+                    Ok(received) => received,
+                    Err(e) => {
+                        warn!(%e, "Failed to receive on the RTP-MIDI control port");
+                        continue;
+                    }
+                };
+
+                session.handle(&control_buf[..len], &control, from).await;
+            }
+            received = data.recv_from(&mut data_buf) => {
+                let (len, from) = match received {
+                    Ok(received) => received,
+                    Err(e) => {
+                        warn!(%e, "Failed to receive on the RTP-MIDI data port");
+                        continue;
+                    }
+                };
+                let packet = &data_buf[..len];
+
+                if session.handle(packet, &data, from).await {
+                    continue;
+                }
+
+                let Some((list, first_has_delta)) = midi_list(packet) else {
+                    debug!(?from, "Dropping malformed RTP-MIDI packet");
+                    continue;
+                };
+
+                let bytes = strip_delta_times(list, first_has_delta);
+
+                sender.send(session.now(), &bytes);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_midi_list() {
+        let mut packet = vec![0x80, 0x61, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1];
+        // Two note ons, the second one using running status after a delta time
+        packet.extend([0x06, 0x90, 60, 100, 0x00, 62, 100]);
+
+        let (list, first_has_delta) = midi_list(&packet).unwrap();
+        assert!(!first_has_delta);
+        assert_eq!(
+            strip_delta_times(list, first_has_delta),
+            vec![0x90, 60, 100, 62, 100]
+        );
+
+        // With the Z flag, the first command has a delta time too
+        assert_eq!(
+            strip_delta_times(&[0x81, 0x00, 0xB0, 1, 64], true),
+            vec![0xB0, 1, 64]
+        );
+    }
+}
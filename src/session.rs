@@ -0,0 +1,155 @@
+// Running midkb from a frontend other than the command line, like the desktop GUI in midkb-gui.
+// A session creates the virtual devices and runs the worker and the handler's tick like `midkb`
+// does, but the MIDI port is picked and changed while it runs instead of found through
+// `midi_device`, and every message is passed on to the frontend to show as it comes in.
+
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use midi_msg::{ChannelVoiceMsg, MidiMsg};
+use tracing::info;
+
+use crate::config::{Backend, Config, DeviceMatch};
+use crate::config_edit::Control;
+use crate::error::{Error, Result};
+use crate::input::{self, Connection, InputTarget};
+use crate::output::VirtualOutput;
+use crate::parser::MidiParser;
+use crate::worker::{self, MidiSender};
+use crate::{lock, midi_msg_callback, MidiInputHandler, TICK_INTERVAL};
+
+/// A message from the controller, for a live view
+#[derive(Debug, Clone)]
+pub struct MidiEvent {
+    /// The message as `midkb monitor` prints it
+    pub line: String,
+    /// The pad pressed or control turned, if it's one that can be bound
+    pub control: Option<Control>,
+}
+
+impl MidiEvent {
+    fn new(msg: &MidiMsg) -> Option<Self> {
+        let line = crate::monitor::describe(msg)?;
+        let control = match *msg {
+            MidiMsg::ChannelVoice {
+                msg: ChannelVoiceMsg::NoteOn { note, velocity },
+                ..
+            } if velocity > 0 => Some(Control::Note(note)),
+            MidiMsg::ChannelVoice {
+                msg: ChannelVoiceMsg::ControlChange { control },
+                ..
+            } => Some(Control::Cc(control.control())),
+            _ => None,
+        };
+        Some(Self { line, control })
+    }
+}
+
+pub struct Session {
+    handler: Arc<Mutex<MidiInputHandler>>,
+    sender: MidiSender,
+    /// The port connected to, by name
+    connection: Option<(String, Box<dyn Connection>)>,
+    events: Receiver<MidiEvent>,
+}
+
+impl Session {
+    /// Create the virtual devices and start handling MIDI with the config, not yet connected
+    /// to any port
+    pub fn start(config: Config) -> Result<Self> {
+        let device = VirtualOutput::new(&config.devices).map_err(Error::VirtualDevice)?;
+        let mut handler = MidiInputHandler::new(Box::new(device), config);
+        handler.keep_recent_events();
+        let handler = Arc::new(Mutex::new(handler));
+
+        let (events_tx, events) = channel();
+        let worker_handler = handler.clone();
+        // Only one port is connected at a time, so one parser can follow its running status
+        let mut monitor = MidiParser::default();
+        let sender = worker::spawn_with(move |received, time, bytes, parser| {
+            for msg in monitor.feed(bytes) {
+                if let Some(event) = MidiEvent::new(&msg) {
+                    let _ = events_tx.send(event);
+                }
+            }
+            monitor.take_unparsed();
+
+            midi_msg_callback(received, time, bytes, parser, &mut lock(&worker_handler));
+        });
+
+        // The worker keeps the handler alive until the session's sender is dropped
+        let tick_handler = Arc::downgrade(&handler);
+        std::thread::Builder::new()
+            .name("midkb-tick".to_string())
+            .spawn(move || {
+                while let Some(handler) = tick_handler.upgrade() {
+                    crate::tick(&mut lock(&handler));
+                    drop(handler);
+                    std::thread::sleep(TICK_INTERVAL);
+                }
+            })
+            .expect("failed to spawn the tick thread");
+
+        Ok(Self {
+            handler,
+            sender,
+            connection: None,
+            events,
+        })
+    }
+
+    /// Names of the MIDI input ports that can be connected to
+    pub fn ports() -> Vec<String> {
+        input::port_names()
+    }
+
+    /// Connect to the port with this name, instead of the one connected to before.
+    /// Returns whether the port could be opened
+    pub fn connect(&mut self, port: &str) -> bool {
+        self.disconnect();
+
+        let target = InputTarget {
+            backend: Backend::Alsa,
+            device: port.to_string(),
+            device_match: DeviceMatch::Exact,
+        };
+        let Some(connection) = input::connect(&target, self.sender.new_source()) else {
+            return false;
+        };
+
+        self.handler().connected();
+        self.connection = Some((port.to_string(), connection));
+        true
+    }
+
+    /// Close the connection to the port, letting go of everything held
+    pub fn disconnect(&mut self) {
+        if let Some((port, connection)) = self.connection.take() {
+            info!(?port, "Disconnecting from MIDI device");
+            connection.close();
+            self.handler().release_all();
+        }
+    }
+
+    /// The name of the port connected to
+    pub fn port(&self) -> Option<&str> {
+        self.connection.as_ref().map(|(port, _)| port.as_str())
+    }
+
+    pub fn handler(&self) -> MutexGuard<'_, MidiInputHandler> {
+        lock(&self.handler)
+    }
+
+    /// The messages received since the last call
+    pub fn events(&self) -> impl Iterator<Item = MidiEvent> + '_ {
+        self.events.try_iter()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.disconnect();
+        // Make sure nothing is left held down before the virtual devices go away
+        self.handler().release_all();
+    }
+}
@@ -0,0 +1,74 @@
+// Signal handling without an async runtime.
+// The signals midkb reacts to are blocked, so they don't kill it or interrupt whatever thread
+// they land on, and read one at a time from a signalfd on a thread of their own instead.
+
+use nix::sys::signal::{SigSet, Signal as NixSignal};
+use nix::sys::signalfd::SignalFd;
+use tracing::{debug, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+    /// SIGINT, from Ctrl+C
+    Interrupt,
+    /// SIGTERM
+    Terminate,
+    /// SIGHUP, asking for the config to be reloaded
+    Hangup,
+    /// SIGUSR1, toggling bypass
+    User1,
+    /// SIGUSR2, logging the current state
+    User2,
+}
+
+impl Signal {
+    fn from_number(number: i32) -> Option<Self> {
+        match NixSignal::try_from(number).ok()? {
+            NixSignal::SIGINT => Some(Self::Interrupt),
+            NixSignal::SIGTERM => Some(Self::Terminate),
+            NixSignal::SIGHUP => Some(Self::Hangup),
+            NixSignal::SIGUSR1 => Some(Self::User1),
+            NixSignal::SIGUSR2 => Some(Self::User2),
+            _ => None,
+        }
+    }
+}
+
+/// Pass every signal midkb handles to `on_signal` from a background thread.
+/// Threads started before this still get the signals, so it has to come before any of them
+pub fn watch<F>(mut on_signal: F) -> std::io::Result<()>
+where
+    F: FnMut(Signal) + Send + 'static,
+{
+    let mut mask = SigSet::empty();
+    for signal in [
+        NixSignal::SIGINT,
+        NixSignal::SIGTERM,
+        NixSignal::SIGHUP,
+        NixSignal::SIGUSR1,
+        NixSignal::SIGUSR2,
+    ] {
+        mask.add(signal);
+    }
+    mask.thread_block()?;
+    let signals = SignalFd::new(&mask)?;
+
+    std::thread::Builder::new()
+        .name("midkb-signals".to_string())
+        .spawn(move || loop {
+            let info = match signals.read_signal() {
+                Ok(Some(info)) => info,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!(%e, "Stopped listening for signals");
+                    return;
+                }
+            };
+
+            if let Some(signal) = Signal::from_number(info.ssi_signo as i32) {
+                debug!(?signal, "Received signal");
+                on_signal(signal);
+            }
+        })?;
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+// `midkb simulate`: feed made-up MIDI events through the real handler, printing the
+// key presses and mouse movement they cause instead of injecting them.
+// Reads one command per line from stdin, so configs can be tried out interactively
+// or checked from a script:
+//
+//   noteon <note> [velocity] [channel]
+//   noteoff <note> [channel]
+//   cc <controller> <value> [channel]
+//   start, stop, continue
+//   wait <milliseconds>    let time pass, for hold timers and smoothing
+
+use std::io::BufRead;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::output::DryRunOutput;
+use crate::parser::MidiParser;
+use crate::{midi_msg_callback, MidiInputHandler, TICK_INTERVAL};
+
+/// A line of input
+#[derive(Debug, PartialEq)]
+pub(crate) enum Command {
+    /// Raw MIDI bytes to handle
+    Midi(Vec<u8>),
+    Wait(Duration),
+}
+
+impl Command {
+    pub(crate) fn parse(line: &str) -> Result<Option<Self>, String> {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(None);
+        };
+
+        let args = words
+            .map(|word| {
+                word.parse::<u32>()
+                    .map_err(|_| format!("`{word}` isn't a number"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let arg = |index: usize, name: &str, default: Option<u32>, max: u32| {
+            let value = args
+                .get(index)
+                .copied()
+                .or(default)
+                .ok_or_else(|| format!("`{command}` needs a {name}"))?;
+            match value <= max {
+                true => Ok(value as u8),
+                false => Err(format!("the {name} can't be more than {max}")),
+            }
+        };
+        let status = |kind: u8, channel: u8| kind | channel.saturating_sub(1);
+
+        let command = match command {
+            "noteon" => Self::Midi(vec![
+                status(0x90, arg(2, "channel", Some(1), 16)?),
+                arg(0, "note", None, 127)?,
+                arg(1, "velocity", Some(100), 127)?,
+            ]),
+            "noteoff" => Self::Midi(vec![
+                status(0x80, arg(1, "channel", Some(1), 16)?),
+                arg(0, "note", None, 127)?,
+                0,
+            ]),
+            "cc" => Self::Midi(vec![
+                status(0xB0, arg(2, "channel", Some(1), 16)?),
+                arg(0, "controller", None, 127)?,
+                arg(1, "value", None, 127)?,
+            ]),
+            "start" => Self::Midi(vec![0xFA]),
+            "continue" => Self::Midi(vec![0xFB]),
+            "stop" => Self::Midi(vec![0xFC]),
+            "wait" => {
+                let ms = args
+                    .first()
+                    .ok_or("`wait` needs a number of milliseconds")?;
+                Self::Wait(Duration::from_millis(*ms as u64))
+            }
+            _ => return Err(format!("unknown command `{command}`")),
+        };
+
+        Ok(Some(command))
+    }
+}
+
+/// Run commands from stdin through a handler with the config, until stdin closes
+pub fn run(config: Config) {
+    let mut handler = MidiInputHandler::new(Box::new(DryRunOutput::default()), config);
+    let mut parser = MidiParser::default();
+
+    for (number, line) in std::io::stdin().lock().lines().enumerate() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        match Command::parse(&line) {
+            Ok(Some(Command::Midi(bytes))) => {
+                midi_msg_callback(Instant::now(), 0, &bytes, &mut parser, &mut handler);
+            }
+            Ok(Some(Command::Wait(duration))) => {
+                let until = Instant::now() + duration;
+                while Instant::now() < until {
+                    std::thread::sleep(TICK_INTERVAL);
+                    handler.tick();
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("line {}: {e}", number + 1),
+        }
+
+        handler.tick();
+    }
+
+    handler.release_all();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            Command::parse("noteon 60 100"),
+            Ok(Some(Command::Midi(vec![0x90, 60, 100])))
+        );
+        assert_eq!(
+            Command::parse("noteon 60"),
+            Ok(Some(Command::Midi(vec![0x90, 60, 100])))
+        );
+        assert_eq!(
+            Command::parse("noteoff 60 10"),
+            Ok(Some(Command::Midi(vec![0x89, 60, 0])))
+        );
+        assert_eq!(
+            Command::parse("cc 1 64 # mod wheel"),
+            Ok(Some(Command::Midi(vec![0xB0, 1, 64])))
+        );
+        assert_eq!(
+            Command::parse("wait 50"),
+            Ok(Some(Command::Wait(Duration::from_millis(50))))
+        );
+        assert_eq!(Command::parse("  # comment"), Ok(None));
+        assert!(Command::parse("cc 1").is_err());
+        assert!(Command::parse("noteon 128").is_err());
+        assert!(Command::parse("noteon C4").is_err());
+        assert!(Command::parse("jump").is_err());
+    }
+}
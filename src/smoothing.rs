@@ -0,0 +1,73 @@
+// Exponential smoothing for CC-driven mouse movement.
+// Instead of jumping the cursor by the whole step when a CC moves, the movement
+// is accumulated and drained a fraction at a time on every tick, so the cursor
+// glides towards where it should end up.
+
+/// Mouse movement accumulated from a smoothed CC, waiting to be drained
+#[derive(Debug, Default)]
+pub struct SmoothedMotion {
+    dx: f32,
+    dy: f32,
+    /// How much of the pending movement is held back each tick, 0.0 to 1.0.
+    /// 0.0 moves everything immediately, values close to 1.0 glide very slowly
+    factor: f32,
+}
+
+impl SmoothedMotion {
+    pub fn new(factor: f32) -> Self {
+        Self {
+            factor: factor.clamp(0.0, 0.99),
+            ..Default::default()
+        }
+    }
+
+    /// Queue up movement to be drained over the next ticks
+    pub fn push(&mut self, dx: i32, dy: i32) {
+        self.dx += dx as f32;
+        self.dy += dy as f32;
+    }
+
+    /// Take this tick's share of the pending movement
+    pub fn drain(&mut self) -> (i32, i32) {
+        (
+            Self::step(&mut self.dx, self.factor),
+            Self::step(&mut self.dy, self.factor),
+        )
+    }
+
+    fn step(pending: &mut f32, factor: f32) -> i32 {
+        let mut out = *pending * (1.0 - factor);
+
+        // Always make at least one pixel of progress so the tail doesn't linger forever
+        if out.abs() < 1.0 && pending.abs() >= 1.0 {
+            out = pending.signum();
+        }
+
+        let out = out.trunc();
+        *pending -= out;
+        out as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smoothed_motion_drains_fully() {
+        let mut motion = SmoothedMotion::new(0.5);
+        motion.push(10, -10);
+
+        let (dx, dy) = motion.drain();
+        assert_eq!((dx, dy), (5, -5));
+
+        let mut total = (dx, dy);
+        for _ in 0..20 {
+            let (dx, dy) = motion.drain();
+            total.0 += dx;
+            total.1 += dy;
+        }
+
+        assert_eq!(total, (10, -10));
+    }
+}
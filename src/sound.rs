@@ -0,0 +1,105 @@
+// Audible feedback for controllers without LEDs: a short click when a binding fires, and a
+// rising or falling pair of tones when something is toggled on or off.
+// The tones are generated and played on a thread holding the audio stream, since the stream
+// can't be moved between threads like the handler is.
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+use rodio::source::{SineWave, Source};
+use rodio::{OutputStream, OutputStreamBuilder};
+use tracing::{debug, warn};
+
+/// How long to wait for more sounds before playing, so a toggle fired right after its click
+/// replaces the click instead of overlapping it
+const COALESCE: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Sound {
+    Click,
+    On,
+    Off,
+}
+
+/// Queues sounds for the thread playing them
+pub struct Speaker {
+    sounds: Sender<(Sound, f32)>,
+}
+
+impl Speaker {
+    /// Start the thread playing sounds
+    pub fn spawn() -> Self {
+        let (sounds, receiver) = channel();
+        std::thread::spawn(move || play_sounds(receiver));
+        Self { sounds }
+    }
+
+    /// Play a sound at a volume from 0 to 1
+    pub fn play(&self, sound: Sound, volume: f32) {
+        let _ = self.sounds.send((sound, volume));
+    }
+}
+
+/// The tones making up a sound, as frequencies in Hz and lengths in milliseconds
+fn tones(sound: Sound) -> &'static [(f32, u64)] {
+    match sound {
+        Sound::Click => &[(2000.0, 12)],
+        Sound::On => &[(660.0, 50), (990.0, 70)],
+        Sound::Off => &[(990.0, 50), (660.0, 70)],
+    }
+}
+
+fn play_sounds(receiver: Receiver<(Sound, f32)>) {
+    let mut stream: Option<OutputStream> = None;
+    let mut warned = false;
+
+    while let Ok(first) = receiver.recv() {
+        std::thread::sleep(COALESCE);
+        // Toggles win over clicks
+        let (sound, volume) = std::iter::once(first)
+            .chain(receiver.try_iter())
+            .max_by_key(|(sound, _)| *sound)
+            .expect("at least one sound");
+
+        if stream.is_none() {
+            match OutputStreamBuilder::open_default_stream() {
+                Ok(mut opened) => {
+                    opened.log_on_drop(false);
+                    stream = Some(opened);
+                }
+                Err(e) if !warned => {
+                    warn!(%e, "Failed to open the audio output for sound feedback");
+                    warned = true;
+                }
+                Err(e) => debug!(%e, "Still can't open the audio output"),
+            }
+        }
+        let Some(stream) = &stream else {
+            continue;
+        };
+
+        let source = rodio::source::from_iter(tones(sound).iter().map(|&(frequency, ms)| {
+            SineWave::new(frequency).take_duration(Duration::from_millis(ms))
+        }));
+        stream.mixer().add(source.amplify(volume.clamp(0.0, 1.0)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tones() {
+        let frequencies = |sound| tones(sound).iter().map(|tone| tone.0).collect::<Vec<_>>();
+
+        // Off goes down through the tones on goes up through
+        let mut off = frequencies(Sound::Off);
+        off.reverse();
+        assert_eq!(off, frequencies(Sound::On));
+        assert!(off[0] < off[1]);
+
+        // Toggles take priority over the click they come with
+        assert!(Sound::Click < Sound::On && Sound::Click < Sound::Off);
+    }
+}
@@ -0,0 +1,66 @@
+// Placeholders in `command` actions, filled in from the event that triggered them,
+// so e.g. one binding can set the system volume to wherever a fader is.
+
+use midi_msg::ControlChange;
+
+/// Values of the triggering event. Ones that don't apply to it, like `note` for a CC, are empty
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct EventVars {
+    pub channel: Option<u8>,
+    pub note: Option<u8>,
+    pub velocity: Option<u8>,
+    pub cc: Option<u8>,
+    pub value: Option<u8>,
+}
+
+impl EventVars {
+    /// The values of a control change on a channel, counting from 1
+    pub fn for_cc(channel: u8, control: ControlChange) -> Self {
+        Self {
+            channel: Some(channel),
+            cc: Some(control.control()),
+            value: Some(control.value()),
+            ..Default::default()
+        }
+    }
+
+    /// Replace `{channel}`, `{note}`, `{velocity}`, `{cc}` and `{value}` in a command
+    pub fn expand(&self, template: &str) -> String {
+        let vars = [
+            ("{channel}", self.channel),
+            ("{note}", self.note),
+            ("{velocity}", self.velocity),
+            ("{cc}", self.cc),
+            ("{value}", self.value),
+        ];
+
+        vars.iter()
+            .fold(template.to_string(), |command, (placeholder, value)| {
+                command.replace(
+                    placeholder,
+                    &value.map(|v| v.to_string()).unwrap_or_default(),
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_placeholders() {
+        let vars = EventVars {
+            channel: Some(1),
+            cc: Some(7),
+            value: Some(100),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            vars.expand("pactl set-sink-volume @DEFAULT_SINK@ $(({value} * 100 / 127))%"),
+            "pactl set-sink-volume @DEFAULT_SINK@ $((100 * 100 / 127))%"
+        );
+        assert_eq!(vars.expand("echo {cc}:{note}:{channel}"), "echo 7::1");
+    }
+}
@@ -0,0 +1,135 @@
+// A hashed timer wheel for things the handler has to do later, like releasing a tapped note.
+// Time is cut into ticks of the handler's tick interval, and each timer goes in the slot for
+// the tick it's due on, modulo the number of slots, so scheduling is a push and each tick only
+// looks at the timers in the slots it passed. Timers further out than one turn of the wheel
+// stay in their slot until the turn they're due on.
+
+use std::time::{Duration, Instant};
+
+/// How many slots the wheel has, a turn covering this many ticks
+const SLOTS: usize = 64;
+
+#[derive(Debug)]
+pub struct TimerWheel<T> {
+    /// When tick 0 started
+    start: Instant,
+    /// How long a tick is
+    resolution: Duration,
+    /// The last tick whose timers fired
+    current: u64,
+    /// Timers with the tick they're due on, in the slot for that tick
+    slots: Vec<Vec<(u64, T)>>,
+}
+
+impl<T: PartialEq> TimerWheel<T> {
+    pub fn new(resolution: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            resolution,
+            current: 0,
+            slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+        }
+    }
+
+    /// The tick an instant falls in, counting partial ticks as whole ones when rounding `up`
+    fn tick_of(&self, at: Instant, up: bool) -> u64 {
+        let elapsed = at.saturating_duration_since(self.start).as_nanos();
+        let resolution = self.resolution.as_nanos().max(1);
+        let tick = if up {
+            elapsed.div_ceil(resolution)
+        } else {
+            elapsed / resolution
+        };
+        tick as u64
+    }
+
+    /// Fire `item` on the first tick at or after `at`
+    pub fn schedule(&mut self, at: Instant, item: T) {
+        // Never on a tick that already fired, or it would wait a whole turn
+        let tick = self.tick_of(at, true).max(self.current + 1);
+        self.slots[tick as usize % SLOTS].push((tick, item));
+    }
+
+    /// Remove the timers for `item`, returning whether there were any
+    pub fn cancel(&mut self, item: &T) -> bool {
+        let mut cancelled = false;
+        for slot in &mut self.slots {
+            slot.retain(|(_, scheduled)| {
+                let matches = scheduled == item;
+                cancelled |= matches;
+                !matches
+            });
+        }
+        cancelled
+    }
+
+    /// Whether a timer for `item` is waiting to fire
+    pub fn is_scheduled(&self, item: &T) -> bool {
+        self.slots
+            .iter()
+            .flatten()
+            .any(|(_, scheduled)| scheduled == item)
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(Vec::clear);
+    }
+
+    /// Take the timers due by `now`, in the order their ticks came up
+    pub fn expire(&mut self, now: Instant) -> Vec<T> {
+        let target = self.tick_of(now, false);
+        let mut due = Vec::new();
+
+        // Once a whole turn has passed, every slot has been visited
+        let passed = target.saturating_sub(self.current).min(SLOTS as u64);
+        for tick in target + 1 - passed..=target {
+            let slot = &mut self.slots[tick as usize % SLOTS];
+            let mut i = 0;
+            while i < slot.len() {
+                if slot[i].0 <= target {
+                    due.push(slot.remove(i).1);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+
+        self.current = self.current.max(target);
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_wheel() {
+        let tick = Duration::from_millis(8);
+        let mut wheel = TimerWheel::new(tick);
+        let start = wheel.start;
+
+        wheel.schedule(start + Duration::from_millis(20), 1);
+        wheel.schedule(start + Duration::from_millis(10), 2);
+        // More than a turn of the wheel away
+        wheel.schedule(start + tick * (SLOTS as u32 + 3), 3);
+        wheel.schedule(start + Duration::from_millis(30), 4);
+
+        assert_eq!(
+            wheel.expire(start + Duration::from_millis(15)),
+            [] as [i32; 0]
+        );
+        assert_eq!(wheel.expire(start + Duration::from_millis(16)), [2]);
+        assert!(wheel.cancel(&4));
+        assert!(!wheel.is_scheduled(&4));
+        assert_eq!(wheel.expire(start + Duration::from_millis(40)), [1]);
+
+        // Its slot came up on tick 3, but it's due a turn later
+        assert!(wheel.is_scheduled(&3));
+        assert_eq!(wheel.expire(start + tick * (SLOTS as u32 * 3)), [3]);
+
+        // Timers scheduled in the past fire on the next tick
+        wheel.schedule(start, 5);
+        assert_eq!(wheel.expire(start + tick * (SLOTS as u32 * 3 + 1)), [5]);
+    }
+}
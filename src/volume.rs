@@ -0,0 +1,213 @@
+// Setting sink and app volumes from faders, and muting them from pads.
+// Built with the `pipewire` feature, changes go through one PipeWire connection kept open (see
+// `pipewire_volume`). Otherwise, or when PipeWire isn't running, they go through `pactl`, which
+// talks to PulseAudio and to PipeWire's PulseAudio server alike, so midkb builds without either's
+// headers. The cost is a runtime dependency: volume bindings need `pactl` on the PATH
+// (pulseaudio-utils), and log a warning and do nothing without it.
+// Changes for `pactl` go to a thread that only applies the latest volume for each target, so
+// a fader swept quickly doesn't leave a queue of commands behind it and the volume follows
+// without lag.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use tracing::{debug, warn};
+
+use crate::config::VolumeTarget;
+
+/// Something to do to a sink or app's volume
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    /// Set the volume, in percent
+    Set(u16),
+    ToggleMute,
+}
+
+/// Queues volume changes for the thread applying them
+pub struct Mixer {
+    changes: Changes,
+}
+
+enum Changes {
+    Pactl(Sender<(VolumeTarget, Change)>),
+    #[cfg(feature = "pipewire")]
+    PipeWire(pipewire::channel::Sender<(VolumeTarget, Change)>),
+}
+
+impl Mixer {
+    /// Start the thread applying changes, through PipeWire if built with it and it's running
+    pub fn spawn() -> Self {
+        #[cfg(feature = "pipewire")]
+        match crate::pipewire_volume::spawn() {
+            Ok(changes) => {
+                return Self {
+                    changes: Changes::PipeWire(changes),
+                }
+            }
+            Err(e) => warn!(%e, "Failed to connect to PipeWire, changing volumes through pactl"),
+        }
+
+        let (changes, receiver) = channel();
+        std::thread::spawn(move || apply_changes(receiver));
+        Self {
+            changes: Changes::Pactl(changes),
+        }
+    }
+
+    pub fn change(&self, target: &VolumeTarget, change: Change) {
+        let change = (target.clone(), change);
+        match &self.changes {
+            Changes::Pactl(changes) => {
+                let _ = changes.send(change);
+            }
+            #[cfg(feature = "pipewire")]
+            Changes::PipeWire(changes) => {
+                let _ = changes.send(change);
+            }
+        }
+    }
+}
+
+/// Where a control's value puts the volume, in percent
+pub fn percent(value: u8, max: u16) -> u16 {
+    ((value as u32 * max as u32 + 63) / 127) as u16
+}
+
+fn apply_changes(receiver: Receiver<(VolumeTarget, Change)>) {
+    while let Ok(first) = receiver.recv() {
+        // Only the last volume set for each target matters, mutes all count
+        let mut volumes = HashMap::new();
+        let mut mutes = Vec::new();
+        for (target, change) in std::iter::once(first).chain(receiver.try_iter()) {
+            match change {
+                Change::Set(percent) => {
+                    volumes.insert(target, percent);
+                }
+                Change::ToggleMute => mutes.push(target),
+            }
+        }
+
+        let changes = volumes
+            .into_iter()
+            .map(|(target, percent)| (target, Change::Set(percent)))
+            .chain(mutes.into_iter().map(|target| (target, Change::ToggleMute)));
+        for (target, change) in changes {
+            apply(&target, change);
+        }
+    }
+}
+
+fn apply(target: &VolumeTarget, change: Change) {
+    let value = match change {
+        Change::Set(percent) => format!("{percent}%"),
+        Change::ToggleMute => "toggle".to_string(),
+    };
+    let command = match change {
+        Change::Set(_) => "volume",
+        Change::ToggleMute => "mute",
+    };
+
+    let objects = match &target.app {
+        Some(app) => {
+            let streams = match pactl(&["list", "sink-inputs"]) {
+                Some(listing) => app_streams(&listing, app),
+                None => return,
+            };
+            if streams.is_empty() {
+                debug!(app, "App isn't playing anything, not changing its volume");
+            }
+
+            streams
+                .into_iter()
+                .map(|stream| (format!("set-sink-input-{command}"), stream.to_string()))
+                .collect()
+        }
+        None => {
+            let sink = target.sink.as_deref().unwrap_or("@DEFAULT_SINK@");
+            vec![(format!("set-sink-{command}"), sink.to_string())]
+        }
+    };
+
+    for (subcommand, object) in objects {
+        debug!(subcommand, object, value, "Changing volume");
+        pactl(&[&subcommand, &object, &value]);
+    }
+}
+
+/// Run `pactl`, returning what it printed if it worked
+fn pactl(args: &[&str]) -> Option<String> {
+    match Command::new("pactl").args(args).output() {
+        Ok(output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        }
+        Ok(output) => {
+            let error = String::from_utf8_lossy(&output.stderr);
+            warn!(?args, error = %error.trim(), "pactl failed");
+            None
+        }
+        Err(e) => {
+            warn!(%e, "Failed to run pactl, is it installed?");
+            None
+        }
+    }
+}
+
+/// The sink inputs, the streams apps play sound through, belonging to an app in the output of
+/// `pactl list sink-inputs`, going by their name or binary without minding the case
+fn app_streams(listing: &str, app: &str) -> Vec<u32> {
+    let mut streams = Vec::new();
+    let mut current = None;
+
+    for line in listing.lines() {
+        let line = line.trim();
+        if let Some(index) = line.strip_prefix("Sink Input #") {
+            current = index.parse().ok();
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(" = ") else {
+            continue;
+        };
+        let matches = matches!(key, "application.name" | "application.process.binary")
+            && value.trim_matches('"').eq_ignore_ascii_case(app);
+
+        if let Some(stream) = current.filter(|_| matches) {
+            if !streams.contains(&stream) {
+                streams.push(stream);
+            }
+        }
+    }
+
+    streams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_app_streams() {
+        let listing = r#"
+Sink Input #41
+	Driver: PipeWire
+	Properties:
+		application.name = "Firefox"
+		application.process.binary = "firefox"
+Sink Input #57
+	Properties:
+		application.name = "spotify"
+Sink Input #60
+	Properties:
+		application.name = "Firefox"
+"#;
+
+        assert_eq!(app_streams(listing, "firefox"), [41, 60]);
+        assert_eq!(app_streams(listing, "Spotify"), [57]);
+        assert_eq!(app_streams(listing, "mpv"), Vec::<u32>::new());
+
+        assert_eq!(percent(0, 100), 0);
+        assert_eq!(percent(64, 100), 50);
+        assert_eq!(percent(127, 150), 150);
+    }
+}
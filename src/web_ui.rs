@@ -0,0 +1,260 @@
+// Web UI for configuring midkb from a browser on the same machine.
+// An embedded page shows the last triggered actions as they happen, the current bindings, and
+// an editor for the config file. Saved configs are checked before they're written, and
+// reloaded like on SIGHUP.
+//
+//   GET /            the page
+//   GET /status      the status, as served by the HTTP API
+//   GET /bindings    the bindings, as `midkb map` lists them
+//   GET /config      the config file
+//   PUT /config      replace the config file and reload it
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use crate::config::{Config, WebUiConfig};
+use crate::map::MappingTable;
+use crate::request::{self, ReadError};
+use crate::MidiInputHandler;
+
+const PAGE: &str = include_str!("web_ui.html");
+
+/// The largest config file the editor can save
+const MAX_BODY: usize = 1024 * 1024;
+
+/// What to serve the page and endpoints with
+#[derive(Clone)]
+struct Server {
+    handler: Arc<Mutex<MidiInputHandler>>,
+    config_path: PathBuf,
+    /// Asks for the config file to be reloaded
+    reload: Reload,
+}
+
+/// Asks for the config file to be reloaded, like SIGHUP does
+pub type Reload = Arc<dyn Fn() + Send + Sync>;
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: String) -> Self {
+        Self {
+            status: "200 OK",
+            content_type,
+            body,
+        }
+    }
+
+    fn json(value: &impl serde::Serialize) -> Self {
+        let body = serde_json::to_string(value).expect("responses always serialize");
+        Self::ok("application/json", body)
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::json!({ "error": message }).to_string(),
+        }
+    }
+}
+
+/// Serve the web UI until midkb exits
+pub async fn listen(
+    config: WebUiConfig,
+    handler: Arc<Mutex<MidiInputHandler>>,
+    config_path: PathBuf,
+    reload: Reload,
+) {
+    // Anyone who can save a config can run commands through it
+    if !config.listen.ip().is_loopback() {
+        warn!(listen = ?config.listen, "The web UI can only be served on a loopback address");
+        return;
+    }
+
+    let listener = match TcpListener::bind(config.listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!(%e, listen = ?config.listen, "Failed to listen for web UI requests");
+            return;
+        }
+    };
+
+    info!("Serving the web UI on http://{}", config.listen);
+    handler
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .keep_recent_events();
+
+    let server = Server {
+        handler,
+        config_path,
+        reload,
+    };
+    loop {
+        match listener.accept().await {
+            Ok((stream, from)) => {
+                tokio::spawn(serve(stream, from, server.clone()));
+            }
+            Err(e) => warn!(%e, "Failed to accept web UI client"),
+        }
+    }
+}
+
+async fn serve(mut stream: TcpStream, from: SocketAddr, server: Server) {
+    let response = match request::read(&mut stream, MAX_BODY).await {
+        Ok(request) => {
+            debug!(
+                method = request.method,
+                path = request.path,
+                ?from,
+                "Web UI request"
+            );
+            let local_host = request.header("host").is_some_and(is_local_host);
+            match local_host {
+                true => {
+                    let body = String::from_utf8_lossy(&request.body);
+                    respond(&server, &request.method, &request.path, &body)
+                }
+                // Pages from other sites could reach a local server through DNS rebinding
+                false => Response::error("403 Forbidden", "the web UI only answers to localhost"),
+            }
+        }
+        Err(ReadError::TooLarge) => {
+            Response::error("413 Payload Too Large", "the config file is too large")
+        }
+        Err(e) => {
+            debug!(%e, ?from, "Failed to read web UI request");
+            return;
+        }
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+
+    let result = match stream.write_all(head.as_bytes()).await {
+        Ok(()) => stream.write_all(response.body.as_bytes()).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        debug!(%e, ?from, "Failed to send web UI response");
+    }
+}
+
+/// Whether a Host header names this machine
+fn is_local_host(host: &str) -> bool {
+    // Drop the port, keeping IPv6 addresses in their brackets whole
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+
+    name.eq_ignore_ascii_case("localhost")
+        || name
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+fn respond(server: &Server, method: &str, path: &str, body: &str) -> Response {
+    let handler = || {
+        server
+            .handler
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    };
+
+    match (method, path) {
+        ("GET", "/") => Response::ok("text/html; charset=utf-8", PAGE.to_string()),
+        ("GET", "/status") => Response::json(&handler().status()),
+        ("GET", "/bindings") => Response::json(&MappingTable::new(handler().config())),
+        ("GET", "/config") => match std::fs::read_to_string(&server.config_path) {
+            Ok(text) => Response::ok("text/plain; charset=utf-8", text),
+            Err(e) => Response::error("500 Internal Server Error", &e.to_string()),
+        },
+        ("PUT", "/config") => match save_config(&server.config_path, body) {
+            Ok(()) => {
+                info!(config_path = ?server.config_path, "Saved the config from the web UI");
+                (server.reload)();
+                Response::ok("application/json", "{}".to_string())
+            }
+            Err(message) => Response::error("400 Bad Request", &message),
+        },
+        _ => Response::error("404 Not Found", "no such page"),
+    }
+}
+
+/// Replace the config file, leaving the old one in place if the new one doesn't load
+fn save_config(path: &Path, text: &str) -> Result<(), String> {
+    // Catch syntax errors before touching the file
+    toml::from_str::<toml::Table>(text).map_err(|e| e.to_string())?;
+
+    // Written next to the config file and renamed over it, so a crash halfway can't truncate it.
+    // Included files are found relative to the config file, so it's loaded from there too
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".new");
+    let new_path = path.with_file_name(name);
+    std::fs::write(&new_path, text).map_err(|e| e.to_string())?;
+
+    let result = match Config::load(&new_path) {
+        Ok(_) => std::fs::rename(&new_path, path).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if result.is_err() {
+        let _ = std::fs::remove_file(&new_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_local_host() {
+        assert!(is_local_host("localhost:8767"));
+        assert!(is_local_host("127.0.0.1:8767"));
+        assert!(is_local_host("[::1]:8767"));
+        assert!(is_local_host("localhost"));
+        assert!(!is_local_host("evil.example:8767"));
+        assert!(!is_local_host("192.168.1.2:8767"));
+    }
+
+    #[test]
+    fn test_save_config() {
+        let path = std::env::temp_dir().join(format!("midkb-web-ui-{}.toml", std::process::id()));
+        std::fs::write(&path, "[cc]\n[notes]\n60 = 30\n").unwrap();
+
+        assert!(save_config(&path, "[cc]\n[notes]\n60 = 48\n").is_ok());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[cc]\n[notes]\n60 = 48\n"
+        );
+
+        // Neither broken TOML nor an invalid config replaces the file
+        assert!(save_config(&path, "[notes\n").is_err());
+        assert!(save_config(&path, "[cc]\n[notes]\n60 = { type = \"nope\" }\n").is_err());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[cc]\n[notes]\n60 = 48\n"
+        );
+        let mut new_name = path.file_name().unwrap().to_os_string();
+        new_name.push(".new");
+        assert!(!path.with_file_name(new_name).exists());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
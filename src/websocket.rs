@@ -0,0 +1,81 @@
+// WebSocket MIDI input.
+// Browser control surfaces can forward Web MIDI API events here, either as binary
+// messages holding the raw MIDI bytes or as text messages with a JSON array of them
+// (`JSON.stringify(Array.from(event.data))`), so a phone or tablet can drive midkb.
+
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use futures_util::StreamExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+use crate::config::WebSocketConfig;
+use crate::input::{Connection, MidiSource};
+use crate::worker::MidiSender;
+
+impl MidiSource for WebSocketConfig {
+    fn connect(&self, sender: MidiSender) -> Option<Box<dyn Connection>> {
+        Some(Box::new(tokio::spawn(listen(self.clone(), sender))))
+    }
+}
+
+/// Listen for WebSocket clients, queueing the MIDI they send for the worker
+async fn listen(config: WebSocketConfig, sender: MidiSender) {
+    let listener = match TcpListener::bind(config.listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!(%e, listen = ?config.listen, "Failed to listen for WebSocket MIDI");
+            return;
+        }
+    };
+
+    info!(listen = ?config.listen, "Listening for WebSocket MIDI clients");
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, from)) => {
+                tokio::spawn(serve(stream, from, sender.new_source()));
+            }
+            Err(e) => warn!(%e, "Failed to accept WebSocket client"),
+        }
+    }
+}
+
+async fn serve(stream: TcpStream, from: SocketAddr, sender: MidiSender) {
+    let mut socket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            debug!(%e, ?from, "WebSocket handshake failed");
+            return;
+        }
+    };
+
+    info!(?from, "WebSocket MIDI client connected");
+
+    let started = Instant::now();
+
+    while let Some(message) = socket.next().await {
+        let bytes = match message {
+            Ok(Message::Binary(bytes)) => bytes.to_vec(),
+            Ok(Message::Text(text)) => match serde_json::from_str::<Vec<u8>>(&text) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    debug!(%e, ?from, "Ignoring WebSocket message that isn't a MIDI byte array");
+                    continue;
+                }
+            },
+            Ok(Message::Close(_)) => break,
+            Ok(_) => continue,
+            Err(e) => {
+                debug!(%e, ?from, "WebSocket connection error");
+                break;
+            }
+        };
+
+        sender.send(started.elapsed().as_micros() as u64, &bytes);
+    }
+
+    info!(?from, "WebSocket MIDI client disconnected");
+}
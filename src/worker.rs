@@ -0,0 +1,173 @@
+// MIDI processing worker.
+// Input callbacks (the MIDI driver's thread, JACK's process thread, network tasks)
+// only timestamp the raw bytes and queue them here. A dedicated thread parses them
+// and runs the bound actions, so a slow action never holds up the driver or
+// delays the events queued behind it. If the worker falls too far behind, new
+// packets are dropped rather than blocking the callback.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+
+use tracing::{trace, warn};
+
+use crate::parser::MidiParser;
+use crate::{midi_msg_callback, MidiInputHandler};
+
+/// How many raw MIDI packets can wait for the worker before new ones are dropped
+const QUEUE_SIZE: usize = 1024;
+
+enum WorkerMsg {
+    Midi {
+        source: usize,
+        received: Instant,
+        time: u64,
+        bytes: Vec<u8>,
+    },
+    /// The source went away, so its parser state can be dropped
+    Closed { source: usize },
+}
+
+/// Queues raw MIDI bytes from one input for the worker.
+/// Each source keeps its own running status, so every input gets its own sender
+pub struct MidiSender {
+    tx: SyncSender<WorkerMsg>,
+    source: usize,
+    next_source: Arc<AtomicUsize>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl MidiSender {
+    /// A sender for another input, with its own parser state
+    pub fn new_source(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            source: self.next_source.fetch_add(1, Ordering::Relaxed),
+            next_source: self.next_source.clone(),
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    /// Queue raw bytes received at `time`, in the input's own clock.
+    /// Never blocks: if the queue is full the bytes are dropped and counted
+    pub fn send(&self, time: u64, bytes: &[u8]) {
+        let msg = WorkerMsg::Midi {
+            source: self.source,
+            received: Instant::now(),
+            time,
+            bytes: bytes.to_vec(),
+        };
+
+        match self.tx.try_send(msg) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                // Warn on 1, 2, 4, 8, ... drops so a flood doesn't flood the log too
+                if dropped.is_power_of_two() {
+                    warn!(
+                        dropped,
+                        "MIDI worker is falling behind, dropping MIDI messages"
+                    );
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                warn!("MIDI worker has stopped, dropping MIDI message");
+            }
+        }
+    }
+
+    /// How many messages from any input were dropped because the queue was full
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for MidiSender {
+    fn drop(&mut self) {
+        let _ = self.tx.send(WorkerMsg::Closed {
+            source: self.source,
+        });
+    }
+}
+
+/// Start the worker thread, returning the sender for the first input
+pub fn spawn(handler: Arc<Mutex<MidiInputHandler>>) -> MidiSender {
+    spawn_with(move |received, time, bytes, parser| {
+        // A panic is caught while handling, so don't let a poisoned lock cause one
+        let mut handler = handler.lock().unwrap_or_else(PoisonError::into_inner);
+        midi_msg_callback(received, time, bytes, parser, &mut handler);
+    })
+}
+
+/// Start a worker thread passing every packet to `callback` instead of the handler,
+/// along with the parser keeping its source's running status
+pub fn spawn_with<F>(callback: F) -> MidiSender
+where
+    F: FnMut(Instant, u64, &[u8], &mut MidiParser) + Send + 'static,
+{
+    let (tx, rx) = sync_channel(QUEUE_SIZE);
+
+    std::thread::Builder::new()
+        .name("midkb-worker".to_string())
+        .spawn(move || run(rx, callback))
+        .expect("failed to spawn the MIDI worker thread");
+
+    MidiSender {
+        tx,
+        source: 0,
+        next_source: Arc::new(AtomicUsize::new(1)),
+        dropped: Arc::new(AtomicUsize::new(0)),
+    }
+}
+
+fn run(rx: Receiver<WorkerMsg>, mut callback: impl FnMut(Instant, u64, &[u8], &mut MidiParser)) {
+    let mut parsers: HashMap<usize, MidiParser> = HashMap::new();
+
+    for msg in rx {
+        match msg {
+            WorkerMsg::Midi {
+                source,
+                received,
+                time,
+                bytes,
+            } => {
+                let parser = parsers.entry(source).or_default();
+                callback(received, time, &bytes, parser);
+            }
+            WorkerMsg::Closed { source } => {
+                trace!(?source, "MIDI source closed");
+                parsers.remove(&source);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_full_queue_drops() {
+        let (started, wait_started) = channel();
+        let (release, wait_release) = channel::<()>();
+        let sender = spawn_with(move |_, _, bytes, _| {
+            // Hold the worker up on the first message until the queue behind it is full
+            if bytes == [0xF8] {
+                let _ = started.send(());
+                let _ = wait_release.recv();
+            }
+        });
+
+        sender.send(0, &[0xF8]);
+        wait_started.recv().unwrap();
+        for _ in 0..QUEUE_SIZE + 10 {
+            sender.send(0, &[0xFE]);
+        }
+        assert_eq!(sender.dropped(), 10);
+
+        release.send(()).unwrap();
+    }
+}
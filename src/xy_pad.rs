@@ -0,0 +1,102 @@
+// Rate-controlled mouse movement from a pair of CCs acting as a joystick.
+// An XY pad or joystick nub reports an absolute position on each axis, centered at 64.
+// Holding it off center keeps the cursor moving in that direction every tick, faster the
+// further it's pushed, so diagonal movement comes from both axes being deflected at once.
+
+use crate::config::XyPadConfig;
+
+/// Center value of an axis at rest
+const CENTER: i32 = 64;
+
+/// Pixels moved per tick at full deflection and a speed of 1.0
+const FULL_SPEED: f32 = 8.0;
+
+/// Where a pad currently is, and the sub-pixel movement not emitted yet
+#[derive(Debug)]
+pub struct XyPadState {
+    x: u8,
+    y: u8,
+    remainder: (f32, f32),
+}
+
+impl Default for XyPadState {
+    fn default() -> Self {
+        Self {
+            x: CENTER as u8,
+            y: CENTER as u8,
+            remainder: (0.0, 0.0),
+        }
+    }
+}
+
+impl XyPadState {
+    /// Record a CC value if it belongs to one of the pad's axes.
+    /// Returns false if the CC isn't part of the pad
+    pub fn update(&mut self, config: &XyPadConfig, cc: u8, value: u8) -> bool {
+        if cc == config.x {
+            self.x = value;
+        } else if cc == config.y {
+            self.y = value;
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    /// This tick's mouse movement for the pad's current position
+    pub fn motion(&mut self, config: &XyPadConfig) -> (i32, i32) {
+        let speed = |value: u8, invert: bool| {
+            let offset = value as i32 - CENTER;
+            let dead_zone = config.dead_zone as i32;
+
+            if offset.abs() <= dead_zone {
+                return 0.0;
+            }
+
+            // Start from zero at the edge of the dead zone, so the cursor doesn't jump as it's left
+            let range = (CENTER - dead_zone).max(1) as f32;
+            let amount = (offset.abs() - dead_zone) as f32 / range * offset.signum() as f32;
+            let amount = if invert { -amount } else { amount };
+
+            amount * FULL_SPEED * config.speed
+        };
+
+        let dx = self.remainder.0 + speed(self.x, config.invert_x);
+        let dy = self.remainder.1 + speed(self.y, config.invert_y);
+
+        self.remainder = (dx.fract(), dy.fract());
+        (dx.trunc() as i32, dy.trunc() as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pad() -> XyPadConfig {
+        toml::from_str("x = 1\ny = 2\ndead_zone = 4").unwrap()
+    }
+
+    #[test]
+    fn test_xy_pad_motion() {
+        let config = pad();
+        let mut state = XyPadState::default();
+
+        // Resting near the center stays inside the dead zone
+        state.update(&config, 1, 66);
+        assert_eq!(state.motion(&config), (0, 0));
+
+        // Full deflection on both axes moves diagonally at full speed
+        assert!(state.update(&config, 1, 127));
+        assert!(state.update(&config, 2, 0));
+        assert!(!state.update(&config, 3, 0));
+        assert_eq!(state.motion(&config), (7, -8));
+
+        let config = XyPadConfig {
+            invert_y: true,
+            ..pad()
+        };
+        assert_eq!(state.motion(&config).1, 8);
+    }
+}